@@ -0,0 +1,228 @@
+use crate::document::Document;
+use crate::formula;
+use crate::stats::Summary;
+
+use std::collections::{HashMap, HashSet};
+use unicode_width::UnicodeWidthStr;
+
+//a figure worked out from the values down a column
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    Sum,
+    Mean,
+    Count,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "sum" => Some(Self::Sum),
+            "mean" | "avg" | "average" => Some(Self::Mean),
+            "count" => Some(Self::Count),
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sum => "sum",
+            Self::Mean => "mean",
+            Self::Count => "count",
+            Self::Min => "min",
+            Self::Max => "max",
+        }
+    }
+
+    //the figure for `summary`; blank when it needs numbers and the column has none, or not only numbers
+    fn of(self, summary: &Summary) -> String {
+        let numbers = summary.numbers.as_ref();
+        let value = match self {
+            Self::Count => return summary.count.to_string(),
+            Self::Sum => numbers.map(|numbers| numbers.sum),
+            Self::Mean => numbers.map(|numbers| numbers.mean),
+            Self::Min => numbers.map(|numbers| numbers.min),
+            Self::Max => numbers.map(|numbers| numbers.max),
+        };
+        value.map(formula::format_number).unwrap_or_default()
+    }
+}
+
+//a figure for each column of a document, shown in a footer under the table. The figures are only
+//worked out again when the table has changed since they last were
+#[derive(Debug, Default)]
+pub struct Totals {
+    //the figure chosen for each column; the rest show a sum when they hold numbers and a count when not
+    chosen: HashMap<usize, Aggregate>,
+    //the document revision, and whether the first row was left out, when `figures` was worked out
+    worked_out: Option<(u64, bool)>,
+    figures: Vec<(Aggregate, String)>,
+}
+
+impl Totals {
+    pub fn choose(&mut self, x: usize, aggregate: Aggregate) {
+        self.chosen.insert(x, aggregate);
+        self.worked_out = None;
+    }
+
+    //what column `x` shows, and its value
+    pub fn figure(&self, x: usize) -> Option<&(Aggregate, String)> {
+        self.figures.get(x.checked_sub(1)?)
+    }
+
+    //works the figures out again if the document has changed. `header` leaves the first row,
+    //which names the columns, out of them
+    pub fn refresh(&mut self, document: &Document, header: bool) {
+        if self.worked_out == Some((document.revision(), header)) {
+            return;
+        }
+        let first = if header { 2 } else { 1 };
+        self.figures = (1..document.table.num_cols() + 1)
+            .map(|x| {
+                let values: Vec<String> = (first..document.table.num_rows() + 1)
+                    .map(|y| document.value(x, y).unwrap_or_default())
+                    .collect();
+                let summary = Summary::of(values.iter().map(String::as_str));
+                let aggregate = self.chosen.get(&x).copied().unwrap_or(match summary.numbers {
+                    Some(_) => Aggregate::Sum,
+                    None => Aggregate::Count,
+                });
+                (aggregate, aggregate.of(&summary))
+            })
+            .collect();
+        self.worked_out = Some((document.revision(), header));
+    }
+}
+
+//how many blank cells, empty or only spaces, each column has below the header, for the quality
+//view. The counts are only worked out again when the table has changed since they last were
+#[derive(Debug, Default)]
+pub struct Blanks {
+    worked_out: Option<(u64, bool)>,
+    counts: Vec<usize>,
+}
+
+impl Blanks {
+    pub fn count(&self, x: usize) -> usize {
+        x.checked_sub(1).and_then(|i| self.counts.get(i)).copied().unwrap_or(0)
+    }
+
+    pub fn refresh(&mut self, document: &Document, header: bool) {
+        if self.worked_out == Some((document.revision(), header)) {
+            return;
+        }
+        let first = if header { 2 } else { 1 };
+        self.counts = (1..document.table.num_cols() + 1)
+            .map(|x| {
+                (first..document.table.num_rows() + 1)
+                    .filter(|&y| document.value(x, y).unwrap_or_default().trim().is_empty())
+                    .count()
+            })
+            .collect();
+        self.worked_out = Some((document.revision(), header));
+    }
+}
+
+//each distinct value of a column with how many rows hold it, most common first: a quick answer to
+//"how many rows per category". Can also work out a figure from another column over each value's rows
+#[derive(Debug, Default)]
+pub struct Frequencies {
+    pub rows: usize,
+    //the value, how many rows hold it and, with an aggregate, its figure
+    pub groups: Vec<(String, usize, String)>,
+}
+
+impl Frequencies {
+    //`rows` pairs a value with the value in the other column of its row, which is only looked at
+    //with an `aggregate`. Blank values make a group of their own
+    pub fn of(rows: &[(String, String)], aggregate: Option<Aggregate>) -> Self {
+        let mut order: Vec<&str> = Vec::new();
+        let mut others: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (value, other) in rows {
+            let value = value.trim();
+            others.entry(value).or_insert_with(|| {
+                order.push(value);
+                Vec::new()
+            }).push(other);
+        }
+        let mut groups: Vec<(String, usize, String)> = order.into_iter()
+            .map(|value| {
+                let others = &others[value];
+                let figure = aggregate.map(|aggregate| aggregate.of(&Summary::of(others.iter().copied()))).unwrap_or_default();
+                (value.to_string(), others.len(), figure)
+            })
+            .collect();
+        //a stable sort, so values as common as each other stay in the order they first turn up
+        groups.sort_by_key(|group| std::cmp::Reverse(group.1));
+        Self { rows: rows.len(), groups }
+    }
+
+    //one line per value under a line naming the columns, for showing in a panel. `figure` names
+    //the figure's column, like "sum of price"
+    pub fn lines(&self, figure: Option<&str>) -> Vec<String> {
+        let value_width = self.groups.iter()
+            .map(|(value, ..)| value.width().max(BLANK.len()))
+            .chain([5])
+            .max()
+            .unwrap_or(0);
+        let count_width = self.rows.to_string().len().max(5);
+        let mut heading = format!("{} {:>count_width$} {:>6}", pad("value", value_width), "count", "%");
+        if let Some(figure) = figure {
+            heading.push_str(&format!("  {}", figure));
+        }
+        let mut lines = vec![heading];
+        lines.extend(self.groups.iter().map(|(value, count, figure_value)| {
+            let share = *count as f64 * 100.0 / self.rows.max(1) as f64;
+            let value = if value.is_empty() { BLANK } else { value };
+            let mut line = format!("{} {:>count_width$} {:>5.1}%", pad(value, value_width), count, share);
+            if figure.is_some() {
+                line.push_str(&format!("  {}", figure_value));
+            }
+            line
+        }));
+        lines
+    }
+}
+
+//a cross tabulation: a row for each value of one column and a column for each value of another,
+//with a figure worked out where they meet from a third column over the rows holding both. `rows`
+//holds each row's three values, and the result starts with a row naming the columns, `corner` over
+//the first. Values keep the order they first turn up in, and blank ones make a group of their own
+pub fn pivot(rows: &[(String, String, String)], aggregate: Aggregate, corner: &str) -> Vec<Vec<String>> {
+    let mut row_keys: Vec<&str> = Vec::new();
+    let mut column_keys: Vec<&str> = Vec::new();
+    let (mut seen_rows, mut seen_columns) = (HashSet::new(), HashSet::new());
+    let mut cells: HashMap<(&str, &str), Vec<&str>> = HashMap::new();
+    for (row, column, value) in rows {
+        let (row, column) = (row.trim(), column.trim());
+        if seen_rows.insert(row) {
+            row_keys.push(row);
+        }
+        if seen_columns.insert(column) {
+            column_keys.push(column);
+        }
+        cells.entry((row, column)).or_default().push(value);
+    }
+    let name = |key: &str| if key.is_empty() { BLANK.to_string() } else { key.to_string() };
+    let mut table = vec![std::iter::once(corner.to_string()).chain(column_keys.iter().map(|key| name(key))).collect()];
+    for &row in &row_keys {
+        let figures = column_keys.iter().map(|&column| {
+            cells.get(&(row, column))
+                .map(|values| aggregate.of(&Summary::of(values.iter().copied())))
+                .unwrap_or_default()
+        });
+        table.push(std::iter::once(name(row)).chain(figures).collect());
+    }
+    table
+}
+
+//stands in for blank cells in a frequency table
+const BLANK: &str = "(blank)";
+
+//`text` with spaces after it to fill `width` terminal columns
+fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.width())))
+}
@@ -0,0 +1,249 @@
+use crate::compression::Compression;
+use crate::diff;
+use crate::document;
+use crate::formula;
+use crate::stats::Summary;
+use crate::table::{self, Align, Table};
+use crate::workbook;
+use crate::Document;
+
+use std::io;
+use std::path::Path;
+use termion::color;
+use unicode_width::UnicodeWidthStr;
+
+const USAGE: &str = "usage: clicsv convert INPUT OUTPUT | clicsv stats FILE [--col COLUMN] | clicsv get FILE CELL | clicsv set FILE CELL VALUE | clicsv diff OLD NEW | clicsv print FILE [--width N] [--cols COLUMNS] [--rows N]";
+
+//runs a subcommand and returns its exit code, or None when the arguments don't name one and the editor should start
+pub fn run(args: &[String]) -> Option<i32> {
+    let command = args.get(1)?;
+    let rest = &args[2..];
+    //a file that happens to be called "stats" still opens in the editor
+    if rest.is_empty() && Path::new(command).exists() {
+        return None;
+    }
+    let result = match command.as_str() {
+        "convert" => convert(rest),
+        "stats" => stats(rest),
+        "get" => get(rest),
+        "set" => set(rest),
+        "diff" => compare(rest),
+        "print" => print_table(rest),
+        _ => return None,
+    };
+    match result {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("Err: {}", e);
+            Some(1)
+        }
+    }
+}
+
+fn open(file_name: &str) -> Result<Document, String> {
+    Document::open(file_name).map_err(|e| format!("unable to open {}: {}", file_name, e))
+}
+
+fn save(document: &mut Document) -> Result<(), String> {
+    let file_name = document.file_name.clone().unwrap_or_default();
+    document.save().map_err(|e| format!("unable to write {}: {}", file_name, e))
+}
+
+//a cell reference like B3, as a position in the table
+fn cell(document: &Document, reference: &str) -> Result<(usize, usize), String> {
+    let at = formula::parse_reference(reference).ok_or_else(|| format!("not a cell reference: {}", reference))?;
+    if document.table.get(at.x, at.y).is_none() {
+        return Err(format!("{} is outside the table", reference));
+    }
+    Ok((at.x, at.y))
+}
+
+//a column letter, or with a header row, the column's name
+fn column(document: &Document, name: &str) -> Result<usize, String> {
+    let ncols = document.table.num_cols();
+    formula::column_index(name)
+        .filter(|&x| x <= ncols)
+        .or_else(|| (1..ncols+1).find(|&x| document.dialect.header && document.value(x, 1).as_deref() == Some(name)))
+        .ok_or_else(|| format!("no column {}", name))
+}
+
+//the value of `--NAME VALUE` or `--NAME=VALUE` when `arg` is that option, taking the value from
+//the arguments that follow in the first case
+fn option_value(arg: &str, name: &str, rest: &mut std::slice::Iter<String>) -> Result<Option<String>, String> {
+    if let Some(value) = arg.strip_prefix(name).and_then(|value| value.strip_prefix('=')) {
+        return Ok(Some(value.to_string()));
+    }
+    if arg == name {
+        return rest.next().cloned().map(Some).ok_or_else(|| format!("{} needs a value", name));
+    }
+    Ok(None)
+}
+
+fn count(name: &str, value: &str) -> Result<usize, String> {
+    value.parse().map_err(|_| format!("{} needs a number, not {}", name, value))
+}
+
+//writes INPUT in the format OUTPUT's extension asks for
+fn convert(args: &[String]) -> Result<(), String> {
+    let [input, output] = args else { return Err(String::from(USAGE)) };
+    let mut document = open(input)?;
+    if !workbook::is_workbook(output) {
+        document.dialect.delimiter = document::extension_delimiter(output).unwrap_or(',');
+        document.dialect.compression = Compression::from_extension(output);
+    }
+    document.file_name = Some(output.clone());
+    save(&mut document)
+}
+
+//prints the statistics of one column, or of every column
+fn stats(args: &[String]) -> Result<(), String> {
+    let mut file_name = None;
+    let mut column = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if let Some(value) = option_value(arg, "--col", &mut iter)? {
+            column = Some(value);
+        } else if file_name.is_none() {
+            file_name = Some(arg);
+        } else {
+            return Err(String::from(USAGE));
+        }
+    }
+    let document = open(file_name.ok_or(USAGE)?)?;
+    let header = document.dialect.header;
+    let name = |x: usize| {
+        let letter = formula::column_name(x);
+        match document.table.get(x, 1) {
+            Some(cell) if header => format!("{} ({})", letter, cell.value()),
+            _ => letter,
+        }
+    };
+    //formulas count with their results
+    let print = |x: usize| {
+        let first = if header { 2 } else { 1 };
+        let values: Vec<String> = (first..document.table.num_rows()+1)
+            .filter_map(|y| document.value(x, y))
+            .collect();
+        println!("{}", name(x));
+        for line in Summary::of(values.iter().map(String::as_str)).lines() {
+            println!("  {}", line);
+        }
+    };
+    match column {
+        Some(column) => print(self::column(&document, &column)?),
+        None => (1..document.table.num_cols()+1).for_each(print),
+    }
+    Ok(())
+}
+
+//prints what a cell shows, with formulas evaluated
+fn get(args: &[String]) -> Result<(), String> {
+    let [file_name, reference] = args else { return Err(String::from(USAGE)) };
+    let document = open(file_name)?;
+    let (x, y) = cell(&document, reference)?;
+    println!("{}", document.value(x, y).unwrap_or_default());
+    Ok(())
+}
+
+//changes one cell and saves the file in place
+fn set(args: &[String]) -> Result<(), String> {
+    let [file_name, reference, value] = args else { return Err(String::from(USAGE)) };
+    let mut document = open(file_name)?;
+    let (x, y) = cell(&document, reference)?;
+    document.apply(vec![(x, y, value.to_string())]);
+    save(&mut document)
+}
+
+//prints the rows that differ between two files: `-` for rows only in OLD, `+` for rows only in NEW
+//and `~` for rows that changed, with each changed cell's old and new contents
+fn compare(args: &[String]) -> Result<(), String> {
+    let [old, new] = args else { return Err(String::from(USAGE)) };
+    let (a, b) = (open(old)?.rows(), open(new)?.rows());
+    let difference = diff::diff(&a, &b);
+    let colored = termion::is_tty(&io::stdout());
+    let paint = |color: &dyn color::Color, text: String| match colored {
+        true => format!("{}{}{}", color::Fg(color), text, color::Fg(color::Reset)),
+        false => text,
+    };
+    for line in difference.describe(&a, &b) {
+        let color: &dyn color::Color = match line.chars().next() {
+            Some('-') => &color::Red,
+            Some('+') => &color::Green,
+            _ => &color::Yellow,
+        };
+        println!("{}", paint(color, line));
+    }
+    Ok(())
+}
+
+//prints the table in a box, each column as wide as its widest value and numbers on the right, the
+//way the editor lays it out. `--width N` fits it in N terminal columns by cutting the widest
+//columns down (to the terminal's width by default when printing to one), `--cols A,C,price` picks
+//the columns and `--rows N` stops after N rows below the header
+fn print_table(args: &[String]) -> Result<(), String> {
+    let mut file_name = None;
+    let mut max_width = if termion::is_tty(&io::stdout()) { termion::terminal_size().ok().map(|(width, _)| width as usize) } else { None };
+    let mut columns = None;
+    let mut limit = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if let Some(value) = option_value(arg, "--width", &mut iter)? {
+            max_width = Some(count("--width", &value)?);
+        } else if let Some(value) = option_value(arg, "--cols", &mut iter)? {
+            columns = Some(value);
+        } else if let Some(value) = option_value(arg, "--rows", &mut iter)? {
+            limit = Some(count("--rows", &value)?);
+        } else if file_name.is_none() {
+            file_name = Some(arg);
+        } else {
+            return Err(String::from(USAGE));
+        }
+    }
+    let document = open(file_name.ok_or(USAGE)?)?;
+    let xs: Vec<usize> = match columns {
+        Some(names) => names.split(',').map(|name| column(&document, name.trim())).collect::<Result<_, _>>()?,
+        None => (1..document.table.num_cols()+1).collect(),
+    };
+    let nrows = document.table.num_rows();
+    let header = document.dialect.header && nrows > 1;
+    let above = if header { 1 } else { 0 };
+    let last = limit.map_or(nrows, |limit| (above + limit).min(nrows));
+    //formulas show their results, and line breaks would break the box
+    let rows: Vec<Vec<String>> = (1..last+1)
+        .map(|y| xs.iter().map(|&x| document.value(x, y).unwrap_or_default().replace("\r\n", "↵").replace('\n', "↵")).collect())
+        .collect();
+    //the table's widths take in the space drawn after each cell
+    let layout = Table::from_rows(rows.clone());
+    let mut widths: Vec<usize> = (1..xs.len()+1).map(|x| layout.column_width(x).saturating_sub(1)).collect();
+    //each column is drawn as "│ " and its contents and a space, with a "│" closing the row
+    if let Some(max_width) = max_width {
+        while widths.iter().sum::<usize>() + 3 * widths.len() + 1 > max_width {
+            match widths.iter_mut().max() {
+                Some(widest) if *widest > 1 => *widest -= 1,
+                _ => break,
+            }
+        }
+    }
+    let rule = |left: &str, middle: &str, right: &str| {
+        let parts: Vec<String> = widths.iter().map(|&width| "─".repeat(width + 2)).collect();
+        format!("{}{}{}", left, parts.join(middle), right)
+    };
+    println!("{}", rule("┌", "┬", "┐"));
+    for (i, row) in rows.iter().enumerate() {
+        let cells: Vec<String> = row.iter().zip(&widths).map(|(value, &width)| {
+            let contents = table::fit_width(value, width);
+            let align = if table::infer_type(value).is_number() { Align::Right } else { Align::Left };
+            let (before, after) = align.padding(width.saturating_sub(contents.width()));
+            format!("{}{}{}", " ".repeat(before), contents, " ".repeat(after))
+        }).collect();
+        println!("│ {} │", cells.join(" │ "));
+        if header && i == 0 {
+            println!("{}", rule("├", "┼", "┤"));
+        }
+    }
+    println!("{}", rule("└", "┴", "┘"));
+    if last < nrows {
+        println!("({} more rows)", nrows - last);
+    }
+    Ok(())
+}
@@ -0,0 +1,290 @@
+//everything the editor can be asked to do from the keyboard
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Command {
+    Quit,
+    Save,
+    SaveAs,
+    Edit,
+    EditWith(char),
+    Help,
+    Palette,
+    NextFile,
+    PreviousFile,
+    Prompt,
+    Stats,
+    Frequencies,
+    Copy,
+    Cut,
+    YankRows,
+    CutRows,
+    PutRows,
+    PutRowsAbove,
+    DuplicateRows,
+    FillDown,
+    Paste,
+    Delete,
+    Undo,
+    Redo,
+    Find,
+    Goto,
+    FuzzyFind,
+    PickValues,
+    Filter,
+    FindNext,
+    FindPrevious,
+    ClearSearch,
+    NextDifference,
+    PreviousDifference,
+    ClearDiff,
+    Replace,
+    Sort,
+    SortBy,
+    InsertRowAbove,
+    InsertRowBelow,
+    InsertColLeft,
+    InsertColRight,
+    DeleteRow,
+    DeleteCol,
+    MoveColLeft,
+    MoveColRight,
+    RenameCol,
+    EditNote,
+    CellHistory,
+    ValidateColumn,
+    HighlightDuplicates,
+    FilterDuplicates,
+    DeleteDuplicates,
+    Transpose,
+    AlignColumn,
+    NumberFormat,
+    HideColumn,
+    ShowColumns,
+    LockRow,
+    LockColumn,
+    Highlights,
+    TakeSnapshot,
+    RestoreSnapshot,
+    ExportSelection,
+    Join,
+    Append,
+    TransformCells,
+    SplitColumn,
+    MergeColumns,
+    ViewCell,
+    EditInEditor,
+    Pivot,
+    Describe,
+    RunScript,
+    //a script from the config's [scripts], by its place there
+    Script(usize),
+    NextViolation,
+    FreezeHeader,
+    WidenColumn,
+    NarrowColumn,
+    ToggleWrap,
+    ToggleCrosshair,
+    ToggleTotals,
+    ToggleBlanks,
+    NextBlank,
+    BlankReport,
+    ToggleHeaderLabels,
+    NextSheet,
+    PreviousSheet,
+    PickSheet,
+    ToggleFormulas,
+    RecordMacro,
+    ReplayMacro,
+    //grow the selection one cell at a time while the key is held
+    GrowSelectionLeft,
+    GrowSelectionRight,
+    GrowSelectionUp,
+    GrowSelectionDown,
+    //to the edge of the run of filled cells, or the next one, as Ctrl and an arrow do in spreadsheets
+    JumpUp,
+    JumpDown,
+    JumpLeft,
+    JumpRight,
+    //select from the current cell to the edge of the table
+    SelectColLeft,
+    SelectColRight,
+    SelectRowUp,
+    SelectRowDown,
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    //a left click at a terminal column and row (1-based)
+    Click(u16, u16),
+    Nothing,
+}
+
+//every command by the name it goes by in the config file's [keys] table, with what it does for
+//the command palette
+const COMMANDS: &[(&str, Command, &str)] = &[
+    ("quit", Command::Quit, "Quit"),
+    ("save", Command::Save, "Save the file"),
+    ("save_as", Command::SaveAs, "Save under another name; the extension picks the format"),
+    ("edit", Command::Edit, "Edit the current cell"),
+    ("help", Command::Help, "Show the keybindings"),
+    ("command_palette", Command::Palette, "List every command"),
+    ("prompt", Command::Prompt, "Run a typed command like :impute or :diff"),
+    ("stats", Command::Stats, "Statistics for the selection or column"),
+    ("frequencies", Command::Frequencies, "Count each distinct value of the column"),
+    ("copy", Command::Copy, "Copy the selection"),
+    ("cut", Command::Cut, "Cut the selection"),
+    ("yank_rows", Command::YankRows, "Copy whole rows"),
+    ("cut_rows", Command::CutRows, "Copy whole rows and delete them"),
+    ("put_rows", Command::PutRows, "Put copied rows below the current one"),
+    ("put_rows_above", Command::PutRowsAbove, "Put copied rows above the current one"),
+    ("duplicate_rows", Command::DuplicateRows, "Duplicate the current or selected rows"),
+    ("fill_down", Command::FillDown, "Fill the selection down, continuing series"),
+    ("paste", Command::Paste, "Paste"),
+    ("delete", Command::Delete, "Clear the selected cells"),
+    ("undo", Command::Undo, "Undo"),
+    ("redo", Command::Redo, "Redo"),
+    ("find", Command::Find, "Find text"),
+    ("goto", Command::Goto, "Go to a cell like B12"),
+    ("fuzzy_find", Command::FuzzyFind, "Fuzzy find in the current column"),
+    ("filter", Command::Filter, "Show only the rows passing a test"),
+    ("pick_values", Command::PickValues, "Tick which of the column's values to show rows for"),
+    ("find_next", Command::FindNext, "Next search match"),
+    ("find_previous", Command::FindPrevious, "Previous search match"),
+    ("clear_search", Command::ClearSearch, "Clear the search"),
+    ("next_difference", Command::NextDifference, "Next difference from the :diff file"),
+    ("previous_difference", Command::PreviousDifference, "Previous difference from the :diff file"),
+    ("clear_diff", Command::ClearDiff, "Stop comparing with the :diff file"),
+    ("replace", Command::Replace, "Find and replace"),
+    ("sort", Command::Sort, "Sort rows by the current column"),
+    ("sort_by", Command::SortBy, "Sort rows by several columns, like C desc, A"),
+    ("insert_row_above", Command::InsertRowAbove, "Insert a row above"),
+    ("insert_row_below", Command::InsertRowBelow, "Insert a row below"),
+    ("insert_col_left", Command::InsertColLeft, "Insert a column left"),
+    ("insert_col_right", Command::InsertColRight, "Insert a column right"),
+    ("delete_row", Command::DeleteRow, "Delete the current row"),
+    ("delete_col", Command::DeleteCol, "Delete the current column"),
+    ("move_col_left", Command::MoveColLeft, "Move the column left"),
+    ("move_col_right", Command::MoveColRight, "Move the column right"),
+    ("rename_col", Command::RenameCol, "Rename the column"),
+    ("edit_note", Command::EditNote, "Attach a note to the cell"),
+    ("cell_history", Command::CellHistory, "Show the cell's earlier values and put one back"),
+    ("validate_column", Command::ValidateColumn, "Set what the column's values must look like"),
+    ("next_violation", Command::NextViolation, "Jump to the next cell that breaks its column's rule"),
+    ("highlight_duplicates", Command::HighlightDuplicates, "Highlight rows that repeat another row"),
+    ("filter_duplicates", Command::FilterDuplicates, "Show only rows that repeat another row"),
+    ("delete_duplicates", Command::DeleteDuplicates, "Delete rows that repeat an earlier row"),
+    ("transpose", Command::Transpose, "Swap rows and columns"),
+    ("align_column", Command::AlignColumn, "Put the column's contents left, centered or right"),
+    ("number_format", Command::NumberFormat, "Show the column's numbers with fixed decimals, thousands separators, as percentages or in scientific notation"),
+    ("hide_column", Command::HideColumn, "Take the column off the screen; it is still saved"),
+    ("show_columns", Command::ShowColumns, "Show every hidden column again"),
+    ("lock_row", Command::LockRow, "Protect the current or selected rows from edits, or unprotect them"),
+    ("lock_column", Command::LockColumn, "Protect the column from edits, or unprotect it"),
+    ("highlights", Command::Highlights, "Color cells that pass a test, like D < 0 -> red"),
+    ("take_snapshot", Command::TakeSnapshot, "Remember the table as it is now, to go back to later"),
+    ("restore_snapshot", Command::RestoreSnapshot, "Put the table back the way it was at a snapshot"),
+    ("export_selection", Command::ExportSelection, "Write the selected cells to a new file or the clipboard"),
+    ("join", Command::Join, "Add another file's columns, matching rows on a key column"),
+    ("append", Command::Append, "Add another file's rows under these"),
+    ("transform_cells", Command::TransformCells, "Rewrite the column or selection with a regex, trim, upper..."),
+    ("view_cell", Command::ViewCell, "Show all of a long cell in a popup, wrapped"),
+    ("edit_in_editor", Command::EditInEditor, "Edit the cell in $EDITOR"),
+    ("pivot", Command::Pivot, "Cross-tabulate two columns into a new file, with a sum, mean or count"),
+    ("describe", Command::Describe, "Describe every column (type, count, blanks, range, top values) in a new file"),
+    ("split_column", Command::SplitColumn, "Split the column into several on a separator or fixed widths"),
+    ("merge_columns", Command::MergeColumns, "Join the selected columns, or this one and the next, into one"),
+    ("run_script", Command::RunScript, "Run a Rhai script that reads and changes the table"),
+    ("freeze_header", Command::FreezeHeader, "Freeze the first row"),
+    ("widen_column", Command::WidenColumn, "Widen the column"),
+    ("narrow_column", Command::NarrowColumn, "Narrow the column"),
+    ("toggle_wrap", Command::ToggleWrap, "Wrap long cells"),
+    ("toggle_crosshair", Command::ToggleCrosshair, "Shade the current row and column"),
+    ("toggle_totals", Command::ToggleTotals, "Show column totals under the table"),
+    ("toggle_blanks", Command::ToggleBlanks, "Show how many blank cells each column has beside its letter"),
+    ("next_blank", Command::NextBlank, "Go to the next blank cell"),
+    ("blank_report", Command::BlankReport, "Count the blank cells in each column"),
+    ("toggle_header_labels", Command::ToggleHeaderLabels, "Name columns after the first row"),
+    ("next_sheet", Command::NextSheet, "Next sheet"),
+    ("previous_sheet", Command::PreviousSheet, "Previous sheet"),
+    ("pick_sheet", Command::PickSheet, "Pick a sheet from a list"),
+    ("next_file", Command::NextFile, "Switch to the next open file"),
+    ("previous_file", Command::PreviousFile, "Switch to the previous open file"),
+    ("toggle_formulas", Command::ToggleFormulas, "Show formulas instead of their values"),
+    ("record_macro", Command::RecordMacro, "Start or stop recording a macro"),
+    ("replay_macro", Command::ReplayMacro, "Replay the recorded macro"),
+    ("jump_up", Command::JumpUp, "Jump up to the edge of the data"),
+    ("jump_down", Command::JumpDown, "Jump down to the edge of the data"),
+    ("jump_left", Command::JumpLeft, "Jump left to the edge of the data"),
+    ("jump_right", Command::JumpRight, "Jump right to the edge of the data"),
+    ("grow_selection_left", Command::GrowSelectionLeft, "Grow the selection left"),
+    ("grow_selection_right", Command::GrowSelectionRight, "Grow the selection right"),
+    ("grow_selection_up", Command::GrowSelectionUp, "Grow the selection up"),
+    ("grow_selection_down", Command::GrowSelectionDown, "Grow the selection down"),
+    ("select_col_left", Command::SelectColLeft, "Select to the first column"),
+    ("select_col_right", Command::SelectColRight, "Select to the last column"),
+    ("select_row_up", Command::SelectRowUp, "Select to the first row"),
+    ("select_row_down", Command::SelectRowDown, "Select to the last row"),
+    ("move_up", Command::MoveUp, "Move up"),
+    ("move_down", Command::MoveDown, "Move down"),
+    ("move_left", Command::MoveLeft, "Move left"),
+    ("move_right", Command::MoveRight, "Move right"),
+    ("page_up", Command::PageUp, "Page up"),
+    ("page_down", Command::PageDown, "Page down"),
+    ("home", Command::Home, "First column"),
+    ("end", Command::End, "Last column"),
+    ("nothing", Command::Nothing, "Do nothing"),
+];
+
+impl Command {
+    pub fn from_name(name: &str) -> Option<Self> {
+        COMMANDS.iter().find(|(n, _, _)| *n == name).map(|&(_, command, _)| command)
+    }
+
+    //whether the command changes the table or writes the file, which a read-only file doesn't allow.
+    //sorting counts as a way of looking at the rows, and save as writes somewhere else, so they're left out
+    pub fn changes_file(self) -> bool {
+        matches!(self,
+            Command::Save
+            | Command::Edit
+            | Command::EditWith(_)
+            | Command::Cut
+            | Command::CutRows
+            | Command::PutRows
+            | Command::PutRowsAbove
+            | Command::DuplicateRows
+            | Command::FillDown
+            | Command::Paste
+            | Command::Delete
+            | Command::Replace
+            | Command::InsertRowAbove
+            | Command::InsertRowBelow
+            | Command::InsertColLeft
+            | Command::InsertColRight
+            | Command::DeleteRow
+            | Command::DeleteCol
+            | Command::MoveColLeft
+            | Command::MoveColRight
+            | Command::RenameCol
+            | Command::EditNote
+            | Command::CellHistory
+            | Command::DeleteDuplicates
+            | Command::Transpose
+            | Command::Join
+            | Command::Append
+            | Command::TransformCells
+            | Command::EditInEditor
+            | Command::SplitColumn
+            | Command::MergeColumns
+            | Command::RunScript
+            | Command::RestoreSnapshot
+            | Command::Script(_))
+    }
+
+    //the commands worth offering in the palette, as (name, command, description)
+    pub fn all() -> impl Iterator<Item = &'static (&'static str, Command, &'static str)> {
+        COMMANDS.iter().filter(|(_, command, _)| !matches!(command, Command::Nothing | Command::Palette))
+    }
+}
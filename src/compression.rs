@@ -0,0 +1,116 @@
+use std::io::Error;
+
+//how a delimited text file's bytes are packed on disk. Files are unpacked on open and packed the
+//same way again on save
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    //`.gz` or `.zst` on the end of a file name like data.csv.gz
+    pub fn from_extension(filename: &str) -> Self {
+        if filename.ends_with(".gz") {
+            Self::Gzip
+        } else if filename.ends_with(".zst") || filename.ends_with(".zstd") {
+            Self::Zstd
+        } else {
+            Self::None
+        }
+    }
+
+    //the magic number at the start of the bytes, for files whose name doesn't say
+    pub fn sniff(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            Self::Gzip
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Self::Zstd
+        } else {
+            Self::None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+//the file name without a compression extension, so data.tsv.gz still reads as tab separated
+pub fn inner_name(filename: &str) -> &str {
+    [".gz", ".zst", ".zstd"].iter()
+        .find_map(|extension| filename.strip_suffix(extension))
+        .unwrap_or(filename)
+}
+
+pub fn decompress(bytes: Vec<u8>, compression: Compression) -> Result<Vec<u8>, Error> {
+    match compression {
+        Compression::None => Ok(bytes),
+        Compression::Gzip => gunzip(&bytes),
+        Compression::Zstd => unzstd(&bytes),
+    }
+}
+
+pub fn compress(bytes: Vec<u8>, compression: Compression) -> Result<Vec<u8>, Error> {
+    match compression {
+        Compression::None => Ok(bytes),
+        Compression::Gzip => gzip(&bytes),
+        Compression::Zstd => zstd(&bytes),
+    }
+}
+
+//every member of a gzip file, as `cat a.gz b.gz` makes one
+#[cfg(feature = "gzip")]
+fn gunzip(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    use std::io::Read;
+
+    let mut unpacked = Vec::new();
+    flate2::read::MultiGzDecoder::new(bytes).read_to_end(&mut unpacked)?;
+    Ok(unpacked)
+}
+
+#[cfg(feature = "gzip")]
+fn gzip(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip(_bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    Err(Error::new(std::io::ErrorKind::Unsupported, "clicsv was built without gzip support"))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gzip(_bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    Err(Error::new(std::io::ErrorKind::Unsupported, "clicsv was built without gzip support"))
+}
+
+#[cfg(feature = "zstd")]
+fn unzstd(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    zstd::stream::decode_all(bytes)
+}
+
+#[cfg(feature = "zstd")]
+fn zstd(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    //the level the zstd tool uses when given none
+    zstd::stream::encode_all(bytes, 3)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn unzstd(_bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    Err(Error::new(std::io::ErrorKind::Unsupported, "clicsv was built without zstd support"))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd(_bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    Err(Error::new(std::io::ErrorKind::Unsupported, "clicsv was built without zstd support"))
+}
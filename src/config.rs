@@ -0,0 +1,320 @@
+use crate::command::Command;
+use crate::editor::parse_delimiter;
+use crate::theme::{self, Depth, Shade};
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::env;
+use std::path::PathBuf;
+use termion::color::Rgb;
+use termion::event::Key;
+
+//settings from ~/.config/clicsv/config.toml; anything left out keeps its default
+pub struct Config {
+    //keys mapped to a command, checked before the built in bindings
+    pub keys: HashMap<Key, Command>,
+    pub colors: Colors,
+    //used for new files; opened files still have theirs detected
+    pub delimiter: Option<char>,
+    pub mouse: bool,
+    pub wrap: bool,
+    //shade the current row and column
+    pub crosshair: bool,
+    pub freeze_header: bool,
+    //vim-style keys: letters move and act instead of typing into the cell
+    pub vim: bool,
+    //the row numbers down the left; hiding them leaves more room for the table
+    pub gutter: bool,
+    //the number the first row below the header gets, 0 or 1
+    pub number_from: usize,
+    //before saving a file committed to git, show how it differs from the last commit and ask
+    pub git_preview: bool,
+    //saving keeps the file's previous version as file.csv.bak
+    pub backup: bool,
+    //how many colours to draw with; None works it out from the terminal
+    pub depth: Option<Depth>,
+    //script files bound to keys in [scripts], which `keys` maps to Command::Script(index)
+    pub scripts: Vec<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            keys: HashMap::new(),
+            colors: Colors::default(),
+            delimiter: None,
+            mouse: false,
+            wrap: false,
+            crosshair: false,
+            freeze_header: false,
+            vim: false,
+            gutter: true,
+            number_from: 1,
+            git_preview: true,
+            backup: false,
+            depth: None,
+            scripts: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Colors {
+    pub status_fg: Shade,
+    pub status_bg: Shade,
+    pub match_fg: Shade,
+    pub match_bg: Shade,
+    pub added_bg: Shade,
+    pub changed_bg: Shade,
+    pub removed_fg: Shade,
+    pub note_fg: Shade,
+    pub invalid_fg: Shade,
+    pub duplicate_bg: Shade,
+    pub locked_fg: Shade,
+    //the current cell, set apart from the rest of a selection
+    pub cursor_fg: Shade,
+    pub cursor_bg: Shade,
+    //the rest of the current row and column, while Alt+Shift+H shades them
+    pub crosshair_bg: Shade,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        theme::light()
+    }
+}
+
+impl Config {
+    //$XDG_CONFIG_HOME/clicsv/config.toml, falling back to ~/.config
+    pub fn path() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(base.join("clicsv").join("config.toml"))
+    }
+
+    //a missing config file gives the defaults; a broken one an error to show in the status bar
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = Self::path() else { return Ok(Self::default()) };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let mut config = Self::parse(&text).map_err(|e| format!("Err: {}: {}", path.display(), e))?;
+                //script paths are taken from the config's directory unless they start from / or ~
+                let directory = path.parent().map(PathBuf::from).unwrap_or_default();
+                let home = env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+                for script in &mut config.scripts {
+                    *script = match script.strip_prefix("~") {
+                        Ok(rest) => home.join(rest),
+                        Err(_) => directory.join(&script),
+                    };
+                }
+                Ok(config)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Err: {}: {}", path.display(), e)),
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let table = text.parse::<toml::Table>().map_err(|e| e.message().to_string())?;
+        let mut config = Self::default();
+        //the theme comes first, for [colors] to change
+        if let Some(value) = table.get("theme") {
+            let name = value.as_str().ok_or_else(|| invalid("theme"))?;
+            config.colors = theme::named(name).ok_or_else(|| format!("no theme {}; use {}", name, theme::NAMES.join(", ")))?;
+        }
+        for (name, value) in &table {
+            match name.as_str() {
+                "theme" => (),
+                "color_depth" => {
+                    let depth = value.as_str().ok_or_else(|| invalid(name))?;
+                    config.depth = match depth {
+                        "auto" => None,
+                        depth => Some(Depth::parse(depth).ok_or_else(|| String::from("color_depth must be auto, truecolor, 256 or 16"))?),
+                    };
+                }
+                "delimiter" => {
+                    let delimiter = value.as_str().and_then(parse_delimiter);
+                    config.delimiter = Some(delimiter.ok_or_else(|| invalid(name))?);
+                }
+                "mouse" => config.mouse = value.as_bool().ok_or_else(|| invalid(name))?,
+                "wrap" => config.wrap = value.as_bool().ok_or_else(|| invalid(name))?,
+                "crosshair" => config.crosshair = value.as_bool().ok_or_else(|| invalid(name))?,
+                "freeze_header" => config.freeze_header = value.as_bool().ok_or_else(|| invalid(name))?,
+                "vim" => config.vim = value.as_bool().ok_or_else(|| invalid(name))?,
+                "gutter" => config.gutter = value.as_bool().ok_or_else(|| invalid(name))?,
+                "git_preview" => config.git_preview = value.as_bool().ok_or_else(|| invalid(name))?,
+                "backup" => config.backup = value.as_bool().ok_or_else(|| invalid(name))?,
+                "number_from" => {
+                    config.number_from = match value.as_integer() {
+                        Some(0) => 0,
+                        Some(1) => 1,
+                        _ => return Err(String::from("number_from must be 0 or 1")),
+                    };
+                }
+                "colors" => {
+                    let colors = value.as_table().ok_or_else(|| invalid(name))?;
+                    for (name, value) in colors {
+                        let color = Shade(parse_color(value).ok_or_else(|| invalid(name))?);
+                        match name.as_str() {
+                            "status_fg" => config.colors.status_fg = color,
+                            "status_bg" => config.colors.status_bg = color,
+                            "match_fg" => config.colors.match_fg = color,
+                            "match_bg" => config.colors.match_bg = color,
+                            "added_bg" => config.colors.added_bg = color,
+                            "changed_bg" => config.colors.changed_bg = color,
+                            "removed_fg" => config.colors.removed_fg = color,
+                            "note_fg" => config.colors.note_fg = color,
+                            "invalid_fg" => config.colors.invalid_fg = color,
+                            "duplicate_bg" => config.colors.duplicate_bg = color,
+                            "locked_fg" => config.colors.locked_fg = color,
+                            "cursor_fg" => config.colors.cursor_fg = color,
+                            "cursor_bg" => config.colors.cursor_bg = color,
+                            "crosshair_bg" => config.colors.crosshair_bg = color,
+                            _ => return Err(format!("unknown color {}", name)),
+                        }
+                    }
+                }
+                "keys" => {
+                    let keys = value.as_table().ok_or_else(|| invalid(name))?;
+                    for (key, command) in keys {
+                        let parsed = parse_key(key).ok_or_else(|| format!("unknown key {}", key))?;
+                        let command = command.as_str().and_then(Command::from_name)
+                            .ok_or_else(|| format!("unknown command {} for {}", command.as_str().unwrap_or_default(), key))?;
+                        config.keys.insert(parsed, command);
+                    }
+                }
+                "scripts" => {
+                    let scripts = value.as_table().ok_or_else(|| invalid(name))?;
+                    for (key, file) in scripts {
+                        let parsed = parse_key(key).ok_or_else(|| format!("unknown key {}", key))?;
+                        let file = file.as_str().ok_or_else(|| format!("expected a file name for {}", key))?;
+                        config.keys.insert(parsed, Command::Script(config.scripts.len()));
+                        config.scripts.push(PathBuf::from(file));
+                    }
+                }
+                _ => return Err(format!("unknown setting {}", name)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn invalid(name: &str) -> String {
+    format!("invalid value for {}", name)
+}
+
+//either "#rrggbb" or [r, g, b]
+fn parse_color(value: &toml::Value) -> Option<Rgb> {
+    if let Some(hex) = value.as_str() {
+        let hex = hex.strip_prefix('#')?;
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    let channels: Vec<u8> = value.as_array()?.iter()
+        .map(|channel| channel.as_integer().and_then(|c| u8::try_from(c).ok()))
+        .collect::<Option<_>>()?;
+    match channels[..] {
+        [r, g, b] => Some(Rgb(r, g, b)),
+        _ => None,
+    }
+}
+
+//a key as written in the config: a character, `ctrl-x`, `alt-x`, or a name like `pagedown` or `shift-up`
+fn parse_key(name: &str) -> Option<Key> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(Key::Char(c));
+    }
+    let single = |rest: &str| {
+        let mut chars = rest.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+    if let Some(rest) = name.strip_prefix("ctrl-") {
+        if let Some(c) = single(rest) {
+            return Some(Key::Ctrl(c.to_ascii_lowercase()));
+        }
+    }
+    if let Some(rest) = name.strip_prefix("alt-") {
+        if let Some(c) = single(rest) {
+            return Some(Key::Alt(c));
+        }
+    }
+    let key = match name.to_ascii_lowercase().as_str() {
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "shift-up" => Key::ShiftUp,
+        "shift-down" => Key::ShiftDown,
+        "shift-left" => Key::ShiftLeft,
+        "shift-right" => Key::ShiftRight,
+        "ctrl-up" => Key::CtrlUp,
+        "ctrl-down" => Key::CtrlDown,
+        "ctrl-left" => Key::CtrlLeft,
+        "ctrl-right" => Key::CtrlRight,
+        "alt-up" => Key::AltUp,
+        "alt-left" => Key::AltLeft,
+        "alt-right" => Key::AltRight,
+        "alt-down" => Key::AltDown,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "delete" => Key::Delete,
+        "backspace" => Key::Backspace,
+        "insert" => Key::Insert,
+        "esc" => Key::Esc,
+        "enter" => Key::Char('\n'),
+        "tab" => Key::Char('\t'),
+        "space" => Key::Char(' '),
+        function => {
+            let number = function.strip_prefix('f')?.parse::<u8>().ok()?;
+            Key::F(number)
+        }
+    };
+    Some(key)
+}
+
+//how the help text writes a key, e.g. Ctrl-s, Alt-Left or PageDown
+pub fn key_name(key: Key) -> Option<String> {
+    let name = match key {
+        Key::Char('\n') => "Return",
+        Key::Char(c) => return Some(c.to_string()),
+        Key::Ctrl(c) => return Some(format!("Ctrl-{}", c)),
+        Key::Alt(c) => return Some(format!("Alt-{}", c)),
+        Key::F(number) => return Some(format!("F{}", number)),
+        Key::Null => "Ctrl-`",
+        Key::Up => "Up",
+        Key::Down => "Down",
+        Key::Left => "Left",
+        Key::Right => "Right",
+        Key::ShiftUp => "Shift-Up",
+        Key::ShiftDown => "Shift-Down",
+        Key::ShiftLeft => "Shift-Left",
+        Key::ShiftRight => "Shift-Right",
+        Key::CtrlUp => "Ctrl-Up",
+        Key::CtrlDown => "Ctrl-Down",
+        Key::CtrlLeft => "Ctrl-Left",
+        Key::CtrlRight => "Ctrl-Right",
+        Key::AltLeft => "Alt-Left",
+        Key::AltRight => "Alt-Right",
+        Key::AltUp => "Alt-Up",
+        Key::AltDown => "Alt-Down",
+        Key::Home => "Home",
+        Key::End => "End",
+        Key::PageUp => "PageUp",
+        Key::PageDown => "PageDown",
+        Key::Delete => "Delete",
+        Key::Esc => "Esc",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
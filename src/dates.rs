@@ -0,0 +1,117 @@
+//a calendar date with an optional time of day, as read from ISO 8601 text
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DateTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub time: Option<(u32, u32, u32)>,
+}
+
+pub const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+
+pub const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+impl DateTime {
+    //YYYY-MM-DD, followed by nothing or a time like T12:30, " 12:30:00" or T12:30:00.250Z
+    pub fn parse_iso(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let number = |range: std::ops::Range<usize>| {
+            let digits = value.get(range)?;
+            digits.bytes().all(|b| b.is_ascii_digit()).then(|| digits.parse::<u32>().ok())?
+        };
+        let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+        if value.get(4..5) != Some("-") || value.get(7..8) != Some("-") || !(1..=12).contains(&month) || day < 1 || day > days_in_month(year as i32, month) {
+            return None;
+        }
+        let time = match value.get(10..11) {
+            None => None,
+            Some("T") | Some(" ") => {
+                let (hour, minute) = (number(11..13)?, number(14..16)?);
+                if value.get(13..14) != Some(":") || hour > 23 || minute > 59 {
+                    return None;
+                }
+                let second = match value.get(16..17) {
+                    Some(":") => number(17..19).filter(|&second| second <= 60)?,
+                    _ => 0,
+                };
+                Some((hour, minute, second))
+            }
+            Some(_) => return None,
+        };
+        Some(Self { year: year as i32, month, day, time })
+    }
+
+    //days since 1970-01-01, by Howard Hinnant's days_from_civil
+    pub fn days(&self) -> i64 {
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let month = i64::from(self.month);
+        let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(self.day) - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    //the date `days` after 1970-01-01, by Howard Hinnant's civil_from_days
+    pub fn from_days(days: i64) -> Self {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+        Self { year: year as i32, month, day, time: None }
+    }
+
+    //writes the date out following a strftime-like pattern: %Y %y %m %d %e %H %M %S %b %B %a %A %%
+    pub fn format(&self, pattern: &str) -> String {
+        let (hour, minute, second) = self.time.unwrap_or((0, 0, 0));
+        let month = MONTHS[self.month as usize - 1];
+        //1970-01-01 was a Thursday
+        let weekday = WEEKDAYS[(self.days() + 3).rem_euclid(7) as usize];
+        let mut text = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => text.push_str(&format!("{:04}", self.year)),
+                Some('y') => text.push_str(&format!("{:02}", self.year.rem_euclid(100))),
+                Some('m') => text.push_str(&format!("{:02}", self.month)),
+                Some('d') => text.push_str(&format!("{:02}", self.day)),
+                Some('e') => text.push_str(&self.day.to_string()),
+                Some('H') => text.push_str(&format!("{:02}", hour)),
+                Some('M') => text.push_str(&format!("{:02}", minute)),
+                Some('S') => text.push_str(&format!("{:02}", second)),
+                Some('b') => text.push_str(&month[..3]),
+                Some('B') => text.push_str(month),
+                Some('a') => text.push_str(&weekday[..3]),
+                Some('A') => text.push_str(weekday),
+                Some('%') => text.push('%'),
+                Some(other) => {
+                    text.push('%');
+                    text.push(other);
+                }
+                None => text.push('%'),
+            }
+        }
+        text
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
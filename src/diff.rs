@@ -0,0 +1,205 @@
+use crate::formula;
+
+use std::collections::HashMap;
+
+//above this many row pairs left to line up, rows are paired by position instead
+const ALIGN_LIMIT: usize = 4_000_000;
+
+//how a cell of the newer table differs from the older one
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added,
+    //holds what the cell used to be
+    Changed(String),
+}
+
+//a row of each table lined up with the other: 1-based rows of the older (a) and newer (b) table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Line {
+    Same(usize, usize),
+    Changed(usize, usize),
+    Added(usize),
+    Removed(usize),
+}
+
+//the differences between two tables, seen from the newer one
+#[derive(Debug, Default, Clone)]
+pub struct Diff {
+    pub lines: Vec<Line>,
+    //keyed by (x, y) in b
+    cells: HashMap<(usize, usize), Change>,
+    //how many rows of a were removed just above row y of b, keyed by y (one past the end for the last rows)
+    removed: HashMap<usize, usize>,
+}
+
+impl Diff {
+    pub fn change(&self, x: usize, y: usize) -> Option<&Change> {
+        self.cells.get(&(x, y))
+    }
+
+    pub fn removed_above(&self, y: usize) -> usize {
+        self.removed.get(&y).copied().unwrap_or(0)
+    }
+
+    //how many rows were changed, added or removed
+    pub fn count(&self) -> usize {
+        self.lines.iter().filter(|line| !matches!(line, Line::Same(..))).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+
+    //a line for each row that differs: `- ` and the row for rows only in `a`, `+ ` for rows only in
+    //`b`, and `~ ` for rows that changed, with each changed cell's old and new contents
+    pub fn describe(&self, a: &[Vec<String>], b: &[Vec<String>]) -> Vec<String> {
+        self.lines.iter().filter_map(|line| match *line {
+            Line::Same(..) => None,
+            Line::Removed(y) => Some(format!("- {}: {}", y, a[y - 1].join(", "))),
+            Line::Added(y) => Some(format!("+ {}: {}", y, b[y - 1].join(", "))),
+            Line::Changed(from, y) => {
+                let cells: Vec<String> = (1..b[y - 1].len() + 1)
+                    .filter_map(|x| match self.change(x, y)? {
+                        Change::Changed(before) => Some(format!("{} {:?} -> {:?}", formula::column_name(x), before, b[y - 1][x - 1])),
+                        Change::Added => Some(format!("{} {:?}", formula::column_name(x), b[y - 1][x - 1])),
+                    })
+                    .collect();
+                //columns only the old table has
+                let dropped = a[from - 1].len().saturating_sub(b[y - 1].len());
+                let note = if dropped > 0 { format!(" ({} columns fewer)", dropped) } else { String::new() };
+                Some(format!("~ {} -> {}: {}{}", from, y, cells.join(", "), note))
+            }
+        }).collect()
+    }
+
+    //where each difference shows up in b, top to bottom: the first changed cell of a changed row,
+    //the start of an added row, and the row below removed ones
+    pub fn positions(&self, rows: usize) -> Vec<(usize, usize)> {
+        let mut positions: Vec<(usize, usize)> = Vec::new();
+        let mut next = 1;
+        for line in &self.lines {
+            let at = match *line {
+                Line::Same(_, y) => {
+                    next = y + 1;
+                    continue;
+                }
+                Line::Changed(_, y) => {
+                    next = y + 1;
+                    let x = self.cells.keys().filter(|&&(_, row)| row == y).map(|&(x, _)| x).min();
+                    (x.unwrap_or(1), y)
+                }
+                Line::Added(y) => {
+                    next = y + 1;
+                    (1, y)
+                }
+                Line::Removed(_) => (1, next.min(rows.max(1))),
+            };
+            if positions.last() != Some(&at) {
+                positions.push(at);
+            }
+        }
+        positions
+    }
+}
+
+//lines up the rows of `a` and `b` like a line diff, then compares the cells of rows that replaced
+//one another. Columns are compared by position
+pub fn diff(a: &[Vec<String>], b: &[Vec<String>]) -> Diff {
+    let mut lines = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    //rows removed and added between two unchanged ones are taken as changed, pair by pair
+    let flush = |lines: &mut Vec<Line>, removed: &mut Vec<usize>, added: &mut Vec<usize>| {
+        let paired = removed.len().min(added.len());
+        lines.extend(removed.iter().zip(added.iter()).map(|(&a, &b)| Line::Changed(a, b)));
+        lines.extend(removed.drain(..).skip(paired).map(Line::Removed));
+        lines.extend(added.drain(..).skip(paired).map(Line::Added));
+    };
+    for pair in align(a, b) {
+        match pair {
+            (Some(i), Some(j)) => {
+                flush(&mut lines, &mut removed, &mut added);
+                lines.push(Line::Same(i + 1, j + 1));
+            }
+            (Some(i), None) => removed.push(i + 1),
+            (None, Some(j)) => added.push(j + 1),
+            (None, None) => (),
+        }
+    }
+    flush(&mut lines, &mut removed, &mut added);
+
+    let mut result = Diff { lines, ..Diff::default() };
+    let mut next = 1;
+    for line in &result.lines {
+        match *line {
+            Line::Same(_, y) => next = y + 1,
+            Line::Changed(i, y) => {
+                next = y + 1;
+                let (old, new) = (&a[i - 1], &b[y - 1]);
+                for x in 1..new.len() + 1 {
+                    let before = old.get(x - 1).map(String::as_str).unwrap_or_default();
+                    if before == new[x - 1] {
+                        continue;
+                    }
+                    let change = if x > old.len() { Change::Added } else { Change::Changed(before.to_string()) };
+                    result.cells.insert((x, y), change);
+                }
+            }
+            Line::Added(y) => {
+                next = y + 1;
+                for x in 1..b[y - 1].len() + 1 {
+                    result.cells.insert((x, y), Change::Added);
+                }
+            }
+            Line::Removed(_) => *result.removed.entry(next).or_insert(0) += 1,
+        }
+    }
+    result
+}
+
+//pairs each row of `a` with an equal row of `b` where the longest common run of rows allows,
+//as 0-based indices; unpaired rows come with None on the other side
+fn align(a: &[Vec<String>], b: &[Vec<String>]) -> Vec<(Option<usize>, Option<usize>)> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let (n, m) = (a.len() - prefix - suffix, b.len() - prefix - suffix);
+    let mut pairs: Vec<(Option<usize>, Option<usize>)> = (0..prefix).map(|i| (Some(i), Some(i))).collect();
+    if n * m > ALIGN_LIMIT {
+        pairs.extend((0..n.max(m)).flat_map(|i| {
+            let (i, j) = ((i < n).then_some(prefix + i), (i < m).then_some(prefix + i));
+            //unequal rows are one removed and one added, which diff pairs up again as changed
+            match (i, j) {
+                (Some(i), Some(j)) if a[i] != b[j] => vec![(Some(i), None), (None, Some(j))],
+                pair => vec![pair],
+            }
+        }));
+    } else {
+        //lengths of the longest common runs of what follows each pair of rows
+        let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[i * (m + 1) + j] = if a[prefix + i] == b[prefix + j] {
+                    lengths[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && a[prefix + i] == b[prefix + j] {
+                pairs.push((Some(prefix + i), Some(prefix + j)));
+                i += 1;
+                j += 1;
+            } else if j < m && (i == n || lengths[i * (m + 1) + j + 1] >= lengths[(i + 1) * (m + 1) + j]) {
+                pairs.push((None, Some(prefix + j)));
+                j += 1;
+            } else {
+                pairs.push((Some(prefix + i), None));
+                i += 1;
+            }
+        }
+    }
+    pairs.extend((0..suffix).map(|k| (Some(a.len() - suffix + k), Some(b.len() - suffix + k))));
+    pairs
+}
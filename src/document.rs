@@ -1,54 +1,547 @@
-extern crate termion;
+use crate::compression::{self, Compression};
+use crate::dates::DateTime;
+use crate::encoding;
+use crate::fetch;
+use crate::formula::{self, Formulas};
+use crate::json;
+use crate::latex;
+use crate::notes::{self, Note};
+use crate::series;
+use crate::sqlite::{self, Snapshot};
 use crate::table;
+use crate::transform::Split;
+use crate::workbook::{self, Sheet};
+use crate::validation::Rule;
 use crate::Position;
 
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::io::{Error, Write};
-use table::Table;
-use table::Cell;
-use termion::event::Key;
+use std::io::{Error, ErrorKind};
+use std::time::Instant;
+use table::{Progress, Table};
+use table::{Cell, CellType, Collation, SortKey};
 
+//unsaved edits are autosaved next to the file, under its name with this added
+const SWAP_SUFFIX: &str = ".clicsv-swap";
 
-pub struct Action{
-    pub key: Key,
-    pub cells_affected: Vec<Cell>
+//a single cell's contents before and after an edit
+#[derive(Clone, Debug)]
+pub struct CellChange{
+    pub x: usize,
+    pub y: usize,
+    pub before: String,
+    pub after: String,
+}
+
+//one undoable edit
+#[derive(Clone, Debug)]
+pub enum Action{
+    //new contents for any number of cells
+    Edit(Vec<CellChange>),
+    InsertRow(usize),
+    InsertCol(usize),
+    //the removed row/column along with its contents, so it can be put back
+    DeleteRow(usize, Vec<String>),
+    DeleteCol(usize, Vec<String>),
+    //two columns trading places; doing it again puts them back
+    SwapCols(usize, usize),
+    //rows and columns trading places; doing it again puts them back
+    Transpose,
+    //the contents of the whole table before and after, like going back to a checkpoint
+    Replace(Vec<Vec<String>>, Vec<Vec<String>>),
+    //several actions undone and redone as one, e.g. a paste that grew the table
+    Group(Vec<Action>),
+}
+
+//what `Document::join` does with rows that have no match in the other table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Join{
+    //they are deleted
+    Inner,
+    //they are kept, blank in the new columns
+    Left,
+}
+
+//what a sheet held at some point in the session, to go back to later. Cheaper than undoing a
+//bulk change step by step, and kept whatever is undone
+pub struct Checkpoint{
+    pub name: String,
+    pub taken: Instant,
+    sheet: usize,
+    rows: Vec<Vec<String>>,
+}
+
+impl Checkpoint{
+    pub fn size(&self) -> (usize, usize){
+        (self.rows.len(), self.rows.first().map_or(0, Vec::len))
+    }
+}
+
+//a block of copied cells, each kept by its offset from the block's top left corner
+#[derive(Clone, Debug, Default)]
+pub struct Region{
+    pub width: usize,
+    pub height: usize,
+    cells: Vec<(usize, usize, String)>,
+}
+
+impl Region{
+    pub fn is_empty(&self) -> bool{
+        self.cells.is_empty()
+    }
+}
+
+//a sheet of an open workbook with its own undo history. The open sheet's table and
+//history live in the Document itself, leaving placeholders here
+struct StoredSheet{
+    name: String,
+    table: Table,
+    undo_stack: Vec<Action>,
+    redo_stack: Vec<Action>,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum LineEnding{
+    Lf,
+    CrLf,
+}
+
+//how the open file was encoded and laid out, so it can be written back the same way
+#[derive(PartialEq, Clone, Debug)]
+pub struct Dialect{
+    pub encoding: String,
+    pub bom: bool,
+    pub line_ending: LineEnding,
+    pub delimiter: char,
+    pub quote: char,
+    //whether the first row looked like column names
+    pub header: bool,
+    pub compression: Compression,
+}
+
+impl Default for Dialect{
+    fn default() -> Self{
+        Self{
+            encoding: String::from("UTF-8"),
+            bom: false,
+            line_ending: LineEnding::Lf,
+            delimiter: ',',
+            quote: '"',
+            header: true,
+            compression: Compression::None,
+        }
+    }
+}
+
+impl Dialect{
+    //compact description for the status bar, e.g. "UTF-8 LF ,"
+    pub fn indicator(&self) -> String{
+        let encoding = if self.bom { format!("{} BOM", self.encoding) } else { self.encoding.clone() };
+        let line_ending = match self.line_ending{
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+        };
+        let delimiter = match self.delimiter{
+            '\t' => String::from("TAB"),
+            ' ' => String::from("SPACE"),
+            c => c.to_string(),
+        };
+        match self.compression{
+            Compression::None => format!("{} {} {}", encoding, line_ending, delimiter),
+            compression => format!("{} {} {} {}", encoding, line_ending, delimiter, compression.name()),
+        }
+    }
+
+    //what was guessed about a file on open, e.g. `Detected ; delimiter, " quotes, header row`
+    pub fn detected(&self) -> String{
+        let delimiter = match self.delimiter{
+            '\t' => String::from("TAB"),
+            ' ' => String::from("SPACE"),
+            c => c.to_string(),
+        };
+        let header = if self.header { "header row" } else { "no header row" };
+        //anything but UTF-8 is worth pointing out, since it is saved the same way
+        let encoding = match self.encoding.as_str(){
+            "UTF-8" => String::new(),
+            encoding => format!(", {} text (:encoding utf-8 to convert)", encoding),
+        };
+        format!("Detected {} delimiter, {} quotes, {}{}", delimiter, self.quote, header, encoding)
+    }
+
+    pub fn line_ending(&self) -> &'static str{
+        match self.line_ending{
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
 }
 
 pub struct Document{
     pub file_name:Option<String>,
+    //the URL an unnamed document was downloaded from
+    pub source: Option<String>,
     pub table: Table,
     saved: bool,
-    pub last_action: Action
+    pub read_only: bool,
+    pub dialect: Dialect,
+    undo_stack: Vec<Action>,
+    redo_stack: Vec<Action>,
+    //positions (x, y) of the highlighted cells, kept in sync with each cell's `highlighted` flag
+    highlighted: HashSet<(usize, usize)>,
+    //cells matching the last search, in row-major order
+    matches: Vec<(usize, usize)>,
+    formulas: Formulas,
+    //every sheet of a workbook (or table of a database), and which one is in `table`; empty for text files
+    sheets: Vec<StoredSheet>,
+    sheet: usize,
+    //what the database's tables held when last read or saved
+    snapshot: Option<Snapshot>,
+    //counts changes to the table, so anything worked out from it knows when to work it out again
+    revision: u64,
+    //lets edits through to locked cells for the rest of the session
+    pub override_locks: bool,
+    //locked cells left alone since the last take_refused
+    refused: usize,
+    checkpoints: Vec<Checkpoint>,
+    //saving keeps the file's previous version as file.csv.bak
+    pub backup: bool,
 }
 
 impl Default for Document{
     fn default() -> Self{
     
-        let mut table = Table::from(String::from(" "));
+        let mut table = Table::from_rows(vec![vec![String::new()]]);
         table.cell_count = 0;
+        Self::new(None, table)
+    }
+}
+
+impl Document{ 
+    fn new(file_name: Option<String>, table: Table) -> Self{
+        let mut formulas = Formulas::default();
+        formulas.rebuild(&table);
         Self{
-            file_name: None,
-            table: table,
+            file_name,
+            table,
+            source: None,
             saved: false,
-            last_action: Action{key: Key::Null,cells_affected: Vec::new()}
+            read_only: false,
+            dialect: Dialect::default(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            highlighted: HashSet::new(),
+            matches: Vec::new(),
+            formulas,
+            sheets: Vec::new(),
+            sheet: 0,
+            snapshot: None,
+            revision: 0,
+            override_locks: false,
+            refused: 0,
+            checkpoints: Vec::new(),
+            backup: false,
         }
     }
-}
 
-impl Document{ 
     pub fn open(filename: &str) -> Result<Self, std::io::Error> {
-        let contents = fs::read_to_string(filename)?;
-        let table = Table::from(contents);
+        Self::open_with_delimiter(filename, None)
+    }
+
+    //opens a delimited text file; without an explicit delimiter it is taken from the
+    //extension (.tsv, .psv) or guessed from the first lines
+    pub fn open_with_delimiter(filename: &str, delimiter: Option<char>) -> Result<Self, std::io::Error> {
+        Self::open_with_progress(filename, delimiter, &Progress::default())
+    }
+
+    //like open_with_delimiter, counting rows as they are read so another thread can show how far it
+    //has got. A cancelled load stops with ErrorKind::Interrupted
+    pub fn open_with_progress(filename: &str, delimiter: Option<char>, progress: &Progress) -> Result<Self, std::io::Error> {
+        let mut document = Self::read_file(filename, delimiter, progress)?;
+        //a notes file that can't be read leaves the cells without notes rather than the file unopened
+        document.attach_notes(notes::read(filename).unwrap_or_default());
+        Ok(document)
+    }
+
+    fn read_file(filename: &str, delimiter: Option<char>, progress: &Progress) -> Result<Self, std::io::Error> {
+        if fetch::is_url(filename){
+            return Self::download(filename, delimiter, progress);
+        }
+        if filename.ends_with(".parquet"){
+            return Self::open_parquet(filename);
+        }
+        if workbook::is_workbook(filename){
+            return Ok(Self::from_sheets(filename, workbook::read_sheets_with(filename, progress)?));
+        }
+        if json::is_any_json(filename){
+            return Self::open_json(filename);
+        }
+        if sqlite::is_sqlite(filename){
+            return Self::open_database(filename);
+        }
+        let delimiter = delimiter.or_else(|| extension_delimiter(filename));
+        let bytes = fs::read(filename)?;
+        let compression = match Compression::from_extension(filename){
+            Compression::None => Compression::sniff(&bytes),
+            compression => compression,
+        };
+        let mut document = Self::from_text(&compression::decompress(bytes, compression)?, delimiter, progress)?;
+        document.dialect.compression = compression;
+        document.file_name = Some(filename.to_string());
+        document.read_only = is_read_only(filename);
+        Ok(document)
+
+    }
+
+    //delimited text from the web, read-only and without a file name until it is saved as one
+    fn download(url: &str, delimiter: Option<char>, progress: &Progress) -> Result<Self, std::io::Error> {
+        let name = fetch::file_name(url);
+        let bytes = fetch::download(url, progress)?;
+        let compression = match Compression::from_extension(name){
+            Compression::None => Compression::sniff(&bytes),
+            compression => compression,
+        };
+        let delimiter = delimiter.or_else(|| extension_delimiter(name));
+        let mut document = Self::from_text(&compression::decompress(bytes, compression)?, delimiter, progress)?;
+        document.source = Some(url.to_string());
+        document.read_only = true;
+        Ok(document)
+    }
+
+    //puts each note on its sheet's table; notes without a sheet go on the open one
+    fn attach_notes(&mut self, notes: Vec<Note>){
+        for note in notes{
+            let index = note.sheet.as_ref().and_then(|name| self.sheets.iter().position(|sheet| &sheet.name == name));
+            let table = match index{
+                Some(i) if i != self.sheet => &mut self.sheets[i].table,
+                _ => &mut self.table,
+            };
+            table.notes.insert((note.x, note.y), note.text);
+        }
+    }
+
+    //every sheet's notes, in order of sheet, row and column
+    fn all_notes(&self) -> Vec<Note>{
+        let tables: Vec<(Option<String>, &Table)> = match self.sheets.len(){
+            0 => vec![(None, &self.table)],
+            _ => self.sheets.iter().enumerate()
+                .map(|(i, sheet)| (Some(sheet.name.clone()), if i == self.sheet { &self.table } else { &sheet.table }))
+                .collect(),
+        };
+        let mut all = Vec::new();
+        for (sheet, table) in tables{
+            let mut notes: Vec<Note> = table.notes.iter()
+                .map(|(&(x, y), text)| Note{sheet: sheet.clone(), x, y, text: text.clone()})
+                .collect();
+            notes.sort_by_key(|note| (note.y, note.x));
+            all.extend(notes);
+        }
+        all
+    }
 
-        Ok(Self{
-            file_name: Some(filename.to_string()),
-            table: table,
-            saved: true,
-            last_action: Action{key: Key::Null,cells_affected: Vec::new()}
-        })
+    pub fn note(&self, x: usize, y: usize) -> Option<&str>{
+        self.table.notes.get(&(x, y)).map(String::as_str)
+    }
 
+    //what the cell held before each of its edits this session, oldest first
+    pub fn cell_history(&self, x: usize, y: usize) -> &[String]{
+        self.table.history.get(&(x, y)).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    //attaches a note to a cell, or with empty text takes it away
+    pub fn set_note(&mut self, x: usize, y: usize, text: &str){
+        let changed = if text.is_empty(){
+            self.table.notes.remove(&(x, y)).is_some()
+        } else {
+            self.table.notes.insert((x, y), text.to_string()).as_deref() != Some(text)
+        };
+        if changed{
+            self.saved = false;
+        }
+    }
+
+    //sets what the values in column `x` have to look like, or with None lets them be anything
+    pub fn set_rule(&mut self, x: usize, rule: Option<Rule>){
+        match rule{
+            Some(rule) => self.table.rules.insert(x, rule),
+            None => self.table.rules.remove(&x),
+        };
+    }
+
+    //whether a cell's value breaks the rule set for its column
+    pub fn breaks_rule(&self, x: usize, y: usize) -> bool{
+        match self.table.rules.get(&x){
+            Some(rule) => !rule.allows(&self.value(x, y).unwrap_or_default()),
+            None => false,
+        }
+    }
+
+    //every cell from row `first` down that breaks its column's rule, as (x, y) in reading order
+    pub fn rule_violations(&self, first: usize) -> Vec<(usize, usize)>{
+        if self.table.rules.is_empty(){
+            return Vec::new();
+        }
+        (first..self.table.num_rows()+1)
+            .flat_map(|y| (1..self.table.num_cols()+1).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.breaks_rule(x, y))
+            .collect()
+    }
+
+    //an unnamed document holding delimited text, e.g. read from stdin. Its encoding, delimiter and
+    //quotes are worked out like a file's, and gzip or zstd text is unpacked first. It is written back
+    //as plain text
+    pub fn from_bytes(bytes: &[u8], delimiter: Option<char>) -> Result<Self, std::io::Error> {
+        let compression = Compression::sniff(bytes);
+        Self::from_text(&compression::decompress(bytes.to_vec(), compression)?, delimiter, &Progress::default())
+    }
+
+    fn from_text(bytes: &[u8], delimiter: Option<char>, progress: &Progress) -> Result<Self, std::io::Error> {
+        let decoded = encoding::decode(bytes);
+        let contents = decoded.text;
+        let mut dialect = Dialect{encoding: decoded.encoding, bom: decoded.bom, ..Dialect::default()};
+        if contents.contains("\r\n"){
+            dialect.line_ending = LineEnding::CrLf;
+        }
+        dialect.quote = table::sniff_quote(&contents);
+        dialect.delimiter = delimiter.unwrap_or_else(|| table::sniff_delimiter(&contents, dialect.quote));
+        let rows = table::parse_csv_with(&contents, dialect.delimiter, dialect.quote, progress);
+        if progress.is_cancelled(){
+            return Err(Error::new(ErrorKind::Interrupted, "loading was cancelled"));
+        }
+        dialect.header = table::sniff_header(&rows);
+        let mut document = Self::new(None, Table::from_rows(rows));
+        document.saved = true;
+        document.dialect = dialect;
+        Ok(document)
+    }
+
+    //a database's tables open like a workbook's sheets, starting with the first table
+    fn open_database(filename: &str) -> Result<Self, std::io::Error> {
+        let (sheets, snapshot) = sqlite::read(filename)?;
+        let mut document = Self::from_sheets(filename, sheets);
+        document.snapshot = Some(snapshot);
+        document.dialect.header = true;
+        Ok(document)
+    }
+
+    //workbooks open on their first sheet, with every other sheet kept in memory to switch to
+    fn from_sheets(filename: &str, sheets: Vec<Sheet>) -> Self {
+        let mut sheets: Vec<StoredSheet> = sheets.into_iter()
+            .map(|sheet| StoredSheet{
+                name: sheet.name,
+                table: Table::from_rows(sheet.rows),
+                undo_stack: Vec::new(),
+                redo_stack: Vec::new(),
+            })
+            .collect();
+        let table = match sheets.first_mut(){
+            Some(first) => std::mem::take(&mut first.table),
+            None => Table::new(),
+        };
+        let mut document = Self::new(Some(filename.to_string()), table);
+        document.saved = true;
+        document.read_only = is_read_only(filename);
+        document.sheets = sheets;
+        document
     }
     
+    //objects become rows under a header row of their keys
+    fn open_json(filename: &str) -> Result<Self, std::io::Error> {
+        let table = Table::from_rows(json::read_rows(filename)?);
+        let mut document = Self::new(Some(filename.to_string()), table);
+        document.saved = true;
+        document.read_only = is_read_only(filename);
+        document.dialect.header = true;
+        Ok(document)
+    }
+
+    //parquet files are only ever read; saving writes a csv file next to them
+    #[cfg(feature = "parquet")]
+    fn open_parquet(filename: &str) -> Result<Self, std::io::Error> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::Field;
+
+        let to_io = |e: parquet::errors::ParquetError| Error::new(ErrorKind::InvalidData, e.to_string());
+        let reader = SerializedFileReader::new(fs::File::open(filename)?).map_err(to_io)?;
+        let schema = reader.metadata().file_metadata().schema_descr_ptr();
+        let mut header = Vec::new();
+        for field in schema.root_schema().get_fields(){
+            if field.is_group(){
+                return Err(Error::new(ErrorKind::InvalidData, format!("Unsupported nested parquet column: {}", field.name())));
+            }
+            header.push(field.name().to_string());
+        }
+        let mut rows = vec![header];
+        //a file written in several row groups has its rows spread over all of them
+        for i in 0..reader.num_row_groups(){
+            let row_group = reader.get_row_group(i).map_err(to_io)?;
+            for row in row_group.get_row_iter(None).map_err(to_io)?{
+                let row = row.map_err(to_io)?;
+                let values = row.get_column_iter().map(|(_, field)| match field{
+                    Field::Null => String::new(),
+                    Field::Str(s) => s.clone(),
+                    field => field.to_string(),
+                }).collect();
+                rows.push(values);
+            }
+        }
+        let csv_name = format!("{}.csv", filename.trim_end_matches(".parquet"));
+        Ok(Self::new(Some(csv_name), Table::from_rows(rows)))
+    }
+
+    #[cfg(not(feature = "parquet"))]
+    fn open_parquet(_filename: &str) -> Result<Self, std::io::Error> {
+        Err(Error::new(ErrorKind::Unsupported, "clicsv was built without parquet support"))
+    }
+
+    //the name of the open sheet, for workbooks
+    pub fn sheet_name(&self) -> Option<&str>{
+        self.sheets.get(self.sheet).map(|sheet| sheet.name.as_str())
+    }
+
+    pub fn sheet_count(&self) -> usize{
+        self.sheets.len()
+    }
+
+    pub fn sheet_names(&self) -> Vec<String>{
+        self.sheets.iter().map(|sheet| sheet.name.clone()).collect()
+    }
+
+    //whether the sheets are the tables of a database
+    pub fn is_database(&self) -> bool{
+        self.snapshot.is_some()
+    }
+
+    pub fn sheet_index(&self) -> usize{
+        self.sheet
+    }
+
+    //finds a sheet by name, or by its 1-based number
+    pub fn find_sheet(&self, name: &str) -> Option<usize>{
+        self.sheets.iter().position(|sheet| sheet.name == name)
+            .or_else(|| name.parse::<usize>().ok().filter(|&n| n >= 1 && n <= self.sheets.len()).map(|n| n - 1))
+    }
+
+    //puts the open sheet away and opens another one in its place, keeping each sheet's undo history
+    pub fn switch_sheet(&mut self, index: usize) -> bool{
+        if index >= self.sheets.len() || index == self.sheet{
+            return false;
+        }
+        self.before_reshape();
+        let (max_col_width, max_cell_width) = (self.table.max_col_width, self.table.max_cell_width);
+        for i in [self.sheet, index]{
+            let sheet = &mut self.sheets[i];
+            std::mem::swap(&mut self.table, &mut sheet.table);
+            std::mem::swap(&mut self.undo_stack, &mut sheet.undo_stack);
+            std::mem::swap(&mut self.redo_stack, &mut sheet.redo_stack);
+        }
+        self.table.max_col_width = max_col_width;
+        self.table.max_cell_width = max_cell_width;
+        self.sheet = index;
+        self.after_reshape();
+        self.revision += 1;
+        true
+    }
+
     pub fn is_empty(&self)-> bool {
         self.table.cell_count == 0
     }
@@ -57,18 +550,17 @@ impl Document{
         self.saved
     }
 
+    //goes up whenever the table changes
+    pub fn revision(&self) -> u64{
+        self.revision
+    }
+
     pub fn len(&self) -> usize {
         self.table.cell_count
     }
 
     pub fn get_row(&self,index:usize) -> Vec<&Cell> {
-        let mut row = Vec::new();
-        for cell in &self.table.cells{
-            if cell.y_loc == index{
-                row.push(cell);
-            }
-        }
-        row
+        self.table.row(index)
     }
 
     pub fn insert_newrow(&mut self, at: &Position) {
@@ -81,167 +573,1323 @@ impl Document{
             }
             self.saved = false;
         }
-        else{
-            return;
-        }
     }
 
     pub fn insert_newcol(&mut self, at: &Position){
         if at.x == self.table.num_cols() + 1{
             for i in 1..self.table.num_rows() + 1 {
-                let mut cell = Cell::from(" ");
+                let mut cell = Cell::from("");
                 cell.x_loc = at.x;
                 cell.y_loc = i;
                 self.table.add(cell);
             }
             self.saved = false;
         }
+    }
+
+    //highlights only the cell at the given position
+    pub fn highlight(&mut self, at: &Position){
+        self.clear_highlights();
+        self.multi_highlight(at);
+    }
+
+    //adds the cell at the given position to the highlighted cells
+    pub fn multi_highlight(&mut self, at: & Position){
+        if let Some(cell) = self.table.get_mut(at.x, at.y){
+            cell.highlight();
+            self.highlighted.insert((at.x, at.y));
+        }
+    }
+
+    pub fn clear_highlights(&mut self){
+        for (x, y) in self.highlighted.drain(){
+            if let Some(cell) = self.table.get_mut(x, y){
+                cell.unhighlight();
+            }
+        }
+    }
+
+    //highlighted positions in row-major order
+    pub fn highlighted_positions(&self) -> Vec<(usize, usize)>{
+        let mut positions: Vec<(usize, usize)> = self.highlighted.iter().copied().collect();
+        positions.sort_by_key(|&(x, y)| (y, x));
+        positions
+    }
+
+    //finds every cell containing `query`, returning how many matched
+    pub fn find(&mut self, query: &str) -> usize{
+        self.matches = if query.is_empty(){
+            Vec::new()
+        }
         else{
-            return;
+            self.table.cells.iter()
+                .filter(|c| c.contents.contains(query))
+                .map(|c| (c.x_loc, c.y_loc))
+                .collect()
+        };
+        self.matches.sort_by_key(|&(x, y)| (y, x));
+        self.matches.len()
+    }
+
+    pub fn clear_matches(&mut self){
+        self.matches.clear();
+    }
+
+    pub fn matches(&self) -> &[(usize, usize)]{
+        &self.matches
+    }
+
+    pub fn is_match(&self, x: usize, y: usize) -> bool{
+        self.matches.binary_search_by_key(&(y, x), |&(mx, my)| (my, mx)).is_ok()
+    }
+
+    //the match after (or before) the given position, wrapping around the table
+    pub fn next_match(&self, at: &Position, forward: bool) -> Option<Position>{
+        let here = (at.y, at.x);
+        let found = if forward{
+            self.matches.iter().find(|&&(x, y)| (y, x) > here).or_else(|| self.matches.first())
         }
+        else{
+            self.matches.iter().rev().find(|&&(x, y)| (y, x) < here).or_else(|| self.matches.last())
+        };
+        found.map(|&(x, y)| Position{x, y})
     }
 
-    pub fn highlight(&mut self, at: &Position){
-        let cells = self.table.cells.clone();
-        self.table.cells = Vec::new();
-        for mut cell in cells{
-            if cell.x_loc == at.x && cell.y_loc == at.y{
-                cell.highlight();
+    //substitutes `replacement` for `query` in the given cells as one undoable action
+    pub fn replace(&mut self, query: &str, replacement: &str, positions: &[(usize, usize)]) -> usize{
+        let edits = positions.iter()
+            .filter_map(|&(x, y)| self.table.get(x, y).map(|c| (x, y, c.contents.replace(query, replacement))))
+            .collect();
+        self.apply(edits)
+    }
+
+    //sorts the rows by a column as one undoable action, returning whether the column sorted numerically
+    pub fn sort_by_column(&mut self, x: usize, ascending: bool, header: bool) -> bool{
+        self.sort(&[SortKey{x, ascending}], header) == [Collation::Number]
+    }
+
+    //sorts the rows by several columns as one undoable action, returning how each was compared
+    pub fn sort(&mut self, keys: &[SortKey], header: bool) -> Vec<Collation>{
+        //highlights are tracked by position, so drop them rather than let them travel with the rows
+        self.clear_highlights();
+        let before: Vec<(usize, usize, String)> = self.table.cells.iter()
+            .map(|c| (c.x_loc, c.y_loc, c.contents.clone()))
+            .collect();
+        let collations = self.table.sort(keys, header);
+        self.after_reshape();
+        let changes: Vec<CellChange> = before.into_iter()
+            .filter_map(|(x, y, before)| {
+                let after = self.table.get(x, y)?.contents.clone();
+                (after != before).then_some(CellChange{x, y, before, after})
+            })
+            .collect();
+        if !changes.is_empty(){
+            self.remember(&changes);
+            self.record(Action::Edit(changes));
+        }
+        collations
+    }
+
+    //looks each row's value in column `key` up in column `other_key` of `other`, and appends the
+    //rest of the first row found there as new columns, like a VLOOKUP for every column at once. With
+    //`header`, both first rows name their columns: the new columns are named after `other`'s and
+    //the first row is never deleted. One undo takes it all back. Returns how many rows found a match
+    pub fn join(&mut self, other: &[Vec<String>], key: usize, other_key: usize, kind: Join, header: bool) -> usize{
+        let first = if header { 2 } else { 1 };
+        let width = other.iter().map(Vec::len).max().unwrap_or(0);
+        let columns: Vec<usize> = (0..width).filter(|&i| i + 1 != other_key).collect();
+        let mut lookup: HashMap<&str, &Vec<String>> = HashMap::new();
+        for row in other.iter().skip(first - 1){
+            if let Some(value) = row.get(other_key - 1){
+                lookup.entry(value.trim()).or_insert(row);
             }
-            else{
-                cell.unhighlight();
+        }
+        let keys: Vec<String> = (1..self.table.num_rows()+1)
+            .map(|y| self.value(key, y).unwrap_or_default())
+            .collect();
+        let start = self.table.num_cols() + 1;
+        self.before_reshape();
+        let mut actions: Vec<Action> = (start..start + columns.len()).map(|x| {
+            self.table.insert_col(x);
+            Action::InsertCol(x)
+        }).collect();
+        let mut changes = Vec::new();
+        let mut unmatched = Vec::new();
+        for (y, value) in (1..).zip(&keys){
+            let found = if y < first { other.first() } else { lookup.get(value.trim()).copied() };
+            let Some(row) = found else {
+                if y >= first{
+                    unmatched.push(y);
+                }
+                continue;
+            };
+            for (x, &i) in (start..).zip(&columns){
+                let contents = row.get(i).cloned().unwrap_or_default();
+                if let Some(before) = self.table.set_contents(x, y, &contents){
+                    changes.push(CellChange{x, y, before, after: contents});
+                }
+            }
+        }
+        self.remember(&changes);
+        actions.push(Action::Edit(changes));
+        if kind == Join::Inner{
+            //from the bottom up, so the rows still to go keep their places
+            for &y in unmatched.iter().rev(){
+                //the last row stays, as with delete_row
+                if self.table.num_rows() <= 1{
+                    break;
+                }
+                if self.refuse(0, y){
+                    continue;
+                }
+                actions.push(Action::DeleteRow(y, self.table.remove_row(y)));
             }
-            self.table.cells.push(cell);
         }
+        self.after_reshape();
+        self.record(Action::Group(actions));
+        keys.len() + 1 - first - unmatched.len()
     }
 
-    pub fn multi_highlight(&mut self, at: & Position){
-        let cells = self.table.cells.clone();
-        self.table.cells = Vec::new();
-        for mut cell in cells{
-            if cell.x_loc == at.x && cell.y_loc == at.y{
-                cell.highlight();
+    //cuts each value of column `x` into pieces that take its place, in as many columns as the most
+    //pieces any value has. With a header the columns are named after it, numbered from 1. Returns
+    //how many columns it became, or None when the column holds locked cells
+    pub fn split_column(&mut self, x: usize, split: &Split, header: bool) -> Option<usize>{
+        let first = if header { 2 } else { 1 };
+        let rows = self.table.num_rows();
+        if (1..rows+1).any(|y| self.refuse(x, y)){
+            return None;
+        }
+        let pieces: Vec<Vec<String>> = (first..rows+1)
+            .map(|y| split.pieces(&self.value(x, y).unwrap_or_default()))
+            .collect();
+        let count = pieces.iter().map(Vec::len).max().unwrap_or(1);
+        if count <= 1{
+            return Some(1);
+        }
+        self.before_reshape();
+        let mut actions: Vec<Action> = (x + 1..x + count).map(|x| {
+            self.table.insert_col(x);
+            Action::InsertCol(x)
+        }).collect();
+        let mut changes = Vec::new();
+        let name = if header { self.value(x, 1).unwrap_or_default() } else { String::new() };
+        let names = (1..count + 1).map(|i| format!("{} {}", name.trim(), i).trim_start().to_string()).collect();
+        let header_row = header.then_some(names);
+        for (y, row) in (1..).zip(header_row.iter().chain(&pieces)){
+            for (x, contents) in (x..).zip(row){
+                if let Some(before) = self.table.set_contents(x, y, contents){
+                    if before != *contents{
+                        changes.push(CellChange{x, y, before, after: contents.clone()});
+                    }
+                }
             }
-            self.table.cells.push(cell);
         }
+        self.remember(&changes);
+        actions.push(Action::Edit(changes));
+        self.after_reshape();
+        self.record(Action::Group(actions));
+        Some(count)
     }
 
-    pub fn copy(&mut self) -> Result<Vec<Cell>,Error> {
-        let mut cells = Vec::new();
-        for cell in &self.table.cells{
-            if cell.highlighted{
-                cells.push(cell.clone());
+    //joins the non-empty values of columns `from` to `to` with `separator` into column `from`, and
+    //removes the others. False when there is only one column or any of them holds locked cells
+    pub fn merge_columns(&mut self, from: usize, to: usize, separator: &str) -> bool{
+        let rows = self.table.num_rows();
+        if from >= to || to > self.table.num_cols() || (from..to+1).any(|x| (1..rows+1).any(|y| self.refuse(x, y))){
+            return false;
+        }
+        let merged: Vec<String> = (1..rows+1)
+            .map(|y| {
+                let values: Vec<String> = (from..to+1)
+                    .filter_map(|x| self.value(x, y))
+                    .filter(|value| !value.trim().is_empty())
+                    .collect();
+                values.join(separator)
+            })
+            .collect();
+        self.before_reshape();
+        let mut changes = Vec::new();
+        for (y, contents) in (1..).zip(merged){
+            if let Some(before) = self.table.set_contents(from, y, &contents){
+                if before != contents{
+                    changes.push(CellChange{x: from, y, before, after: contents});
+                }
             }
         }
-        Ok(cells)
+        self.remember(&changes);
+        let mut actions = vec![Action::Edit(changes)];
+        //from the right, so the columns still to go keep their places
+        for x in (from + 1..to + 1).rev(){
+            actions.push(Action::DeleteCol(x, self.table.remove_col(x)));
+        }
+        self.after_reshape();
+        self.record(Action::Group(actions));
+        true
     }
 
-    pub fn get_highlight_cells(&self) -> Vec<Cell>{
-        let mut cells = Vec::new();
-        for c in &self.table.cells{
-            if c.highlighted{
-                cells.push(c.clone());
+    //a new unnamed document in the same dialect holding `rows`, the first of which names the columns,
+    //like a summary worked out from this one
+    pub fn derived(&self, rows: Vec<Vec<String>>) -> Document{
+        let mut document = Self::new(None, Table::from_rows(rows));
+        document.dialect = self.dialect.clone();
+        document.dialect.header = true;
+        document
+    }
+
+    //a new unnamed document, in the same dialect and with the same column alignments and number
+    //formats, holding the value of every cell
+    pub fn values(&self) -> Document{
+        let mut document = Self::new(None, Table::from_rows(self.value_rows()));
+        document.dialect = self.dialect.clone();
+        document.table.alignments = self.table.alignments.clone();
+        document.table.number_formats = self.table.number_formats.clone();
+        document
+    }
+
+    //a new unnamed document, in the same dialect, holding the values of the selected cells (formulas
+    //give their results). With `header`, the first row's cells for the selected columns go on top
+    //when the selection starts below it. None when nothing is selected
+    pub fn selection(&self, header: bool) -> Option<Document>{
+        let positions = self.highlighted_positions();
+        let left = positions.iter().map(|p| p.0).min()?;
+        let top = positions.iter().map(|p| p.1).min()?;
+        let right = positions.iter().map(|p| p.0).max().unwrap_or(left);
+        let bottom = positions.iter().map(|p| p.1).max().unwrap_or(top);
+        let mut rows = vec![vec![String::new(); right - left + 1]; bottom - top + 1];
+        for (x, y) in positions{
+            rows[y - top][x - left] = self.value(x, y).unwrap_or_default();
+        }
+        if header && top > 1{
+            rows.insert(0, (left..right+1).map(|x| self.value(x, 1).unwrap_or_default()).collect());
+        }
+        let mut document = Self::new(None, Table::from_rows(rows));
+        document.dialect = self.dialect.clone();
+        document.table.alignments = (left..right+1)
+            .filter_map(|x| Some((x - left + 1, *self.table.alignments.get(&x)?)))
+            .collect();
+        document.table.number_formats = (left..right+1)
+            .filter_map(|x| Some((x - left + 1, *self.table.number_formats.get(&x)?)))
+            .collect();
+        Some(document)
+    }
+
+    //the highlighted cells as a block; cells between them that aren't highlighted are left out of it
+    pub fn copy(&self) -> Region{
+        let positions = self.highlighted_positions();
+        let (Some(left), Some(top)) = (positions.iter().map(|p| p.0).min(), positions.iter().map(|p| p.1).min()) else{
+            return Region::default();
+        };
+        let right = positions.iter().map(|p| p.0).max().unwrap_or(left);
+        let bottom = positions.iter().map(|p| p.1).max().unwrap_or(top);
+        let cells = positions.into_iter()
+            .filter_map(|(x, y)| Some((x - left, y - top, self.table.get(x, y)?.contents.clone())))
+            .collect();
+        Region{width: right - left + 1, height: bottom - top + 1, cells}
+    }
+
+    //the rows the selection touches, or just row `y` when nothing is selected
+    pub fn selected_rows(&self, y: usize) -> (usize, usize){
+        let positions = self.highlighted_positions();
+        match (positions.iter().map(|p| p.1).min(), positions.iter().map(|p| p.1).max()){
+            (Some(top), Some(bottom)) => (top, bottom),
+            _ => (y, y),
+        }
+    }
+
+    //copies rows `top` through `bottom` whole, to be pasted at the start of a row
+    pub fn copy_rows(&self, top: usize, bottom: usize) -> Region{
+        let width = self.table.num_cols();
+        let cells = (top..bottom+1)
+            .flat_map(|y| (1..width+1).map(move |x| (x, y)))
+            .filter_map(|(x, y)| Some((x - 1, y - top, self.table.get(x, y)?.contents.clone())))
+            .collect();
+        Region{width, height: bottom - top + 1, cells}
+    }
+
+    //puts copies of rows `top` through `bottom` straight after them, moving later rows down.
+    //One undo takes them all away
+    pub fn duplicate_rows(&mut self, top: usize, bottom: usize) -> usize{
+        if top == 0 || top > bottom || bottom > self.table.num_rows(){
+            return 0;
+        }
+        let rows: Vec<Vec<String>> = (top..bottom+1)
+            .map(|y| self.table.row(y).iter().map(|c| c.contents.clone()).collect())
+            .collect();
+        self.before_reshape();
+        let mut actions = Vec::new();
+        let mut changes = Vec::new();
+        for (i, row) in rows.iter().enumerate(){
+            let y = bottom + 1 + i;
+            self.table.insert_row(y);
+            actions.push(Action::InsertRow(y));
+            for (x, contents) in row.iter().enumerate(){
+                if let Some(before) = self.table.set_contents(x + 1, y, contents){
+                    changes.push(CellChange{x: x + 1, y, before, after: contents.clone()});
+                }
             }
         }
-        return cells;
+        self.after_reshape();
+        self.remember(&changes);
+        actions.push(Action::Edit(changes));
+        self.record(Action::Group(actions));
+        rows.len()
     }
 
-    pub fn undo(&mut self){
-        if self.last_action.key == Key::Null{
-            return;
+    //every row from `first` down that is the same cell for cell as another, mapped to the first row
+    //of its set of copies (which maps to itself)
+    pub fn repeated_rows(&self, first: usize) -> BTreeMap<usize, usize>{
+        let mut seen: HashMap<Vec<&str>, usize> = HashMap::new();
+        let mut repeated = BTreeMap::new();
+        for y in first.max(1)..self.table.num_rows()+1{
+            let row: Vec<&str> = self.table.row(y).iter().map(|c| c.value()).collect();
+            let original = *seen.entry(row).or_insert(y);
+            if original != y{
+                repeated.insert(original, original);
+                repeated.insert(y, original);
+            }
         }
-        for cell in self.last_action.cells_affected.clone(){
-            let pos = Position{x: cell.x_loc,y: cell.y_loc};
-            self.insert(pos, &cell.contents);
+        repeated
+    }
+
+    //deletes every row from `first` down that repeats an earlier one, keeping the first of each set
+    //of copies. One undo brings them all back
+    pub fn remove_repeated_rows(&mut self, first: usize) -> usize{
+        let copies: Vec<usize> = self.repeated_rows(first).into_iter()
+            .filter(|&(y, original)| y != original && !self.refuse(0, y))
+            .map(|(y, _)| y)
+            .collect();
+        if copies.is_empty(){
+            return 0;
         }
+        self.before_reshape();
+        //from the bottom up, so the rows still to go keep their places
+        let actions = copies.iter().rev().map(|&y| Action::DeleteRow(y, self.table.remove_row(y))).collect();
+        self.after_reshape();
+        self.record(Action::Group(actions));
+        copies.len()
+    }
 
+    //puts copied rows in as new rows straight after row `y`, starting from column A. One undo takes
+    //them all away
+    pub fn put_rows(&mut self, y: usize, region: &Region) -> usize{
+        if region.is_empty() || y > self.table.num_rows(){
+            return 0;
+        }
+        self.before_reshape();
+        let mut actions: Vec<Action> = (y + 1..y + region.height + 1).map(|row| {
+            self.table.insert_row(row);
+            Action::InsertRow(row)
+        }).collect();
+        let mut changes = Vec::new();
+        for (dx, dy, contents) in &region.cells{
+            let (x, row) = (1 + dx, y + 1 + dy);
+            if let Some(before) = self.table.set_contents(x, row, contents){
+                changes.push(CellChange{x, y: row, before, after: contents.clone()});
+            }
+        }
+        self.after_reshape();
+        self.remember(&changes);
+        actions.push(Action::Edit(changes));
+        self.record(Action::Group(actions));
+        region.height
     }
 
-    pub fn paste(&mut self,at:&Position, cells: &Vec<Cell>) -> Result<(),Error> {
-        self.saved = false;
-        self.last_action.cells_affected = Vec::new();
-        let mut x = at.x;
-        let mut y = at.y;
-        let mut prev_x = cells.first().unwrap().x_loc;
-        let mut prev_y = cells.first().unwrap().y_loc;
-        if x == 0{
-            x = 1;
-        }
-        if y == 0{
-            y = 1;
-        }
-        for cell in cells{         
-            if cell.x_loc > prev_x{
-                x +=1;
-            }
-            else if cell.y_loc > prev_y{
-                y += 1;
-            }
-            let mut c = cell.clone();
-            c.contents = self.table.get_content_from(Position {x, y});
-            c.x_loc = x;
-            c.y_loc = y;
-            self.last_action.cells_affected.push(c);
-            self.insert(Position {x,y},&cell.contents);
-            prev_x = cell.x_loc;
-            prev_y = cell.y_loc;
+    //rewrites the ISO 8601 dates in the selection (or column `x` when at most one cell is selected)
+    //following a pattern like %d/%m/%Y
+    pub fn format_dates(&mut self, pattern: &str, x: usize) -> usize{
+        let positions = if self.highlighted.len() > 1{
+            self.highlighted_positions()
         }
+        else{
+            (1..self.table.num_rows()+1).map(|y| (x, y)).collect()
+        };
+        let edits = positions.into_iter()
+            .filter_map(|(x, y)| {
+                let date = DateTime::parse_iso(&self.table.get(x, y)?.contents)?;
+                Some((x, y, date.format(pattern)))
+            })
+            .collect();
+        self.apply(edits)
+    }
 
-        Ok(())
+    //fills blank cells in the selection (or the whole table when at most one cell is selected) with a value
+    pub fn impute(&mut self, value: &str) -> usize{
+        let positions = if self.highlighted.len() > 1{
+            self.highlighted_positions()
+        }
+        else{
+            let mut positions: Vec<(usize, usize)> = self.table.cells.iter().map(|c| (c.x_loc, c.y_loc)).collect();
+            positions.sort_by_key(|&(x, y)| (y, x));
+            positions
+        };
+        let edits = positions.into_iter()
+            .filter(|&(x, y)| self.table.get(x, y).is_some_and(|c| c.contents.trim().is_empty()))
+            .map(|(x, y)| (x, y, value.to_string()))
+            .collect();
+        self.apply(edits)
     }
 
-    pub fn insert(&mut self,at:Position,line: &str) {
-        self.saved =false;
-        let cells = self.table.cells.clone();
-        self.table.cells = Vec::new();
-        
-        for c in cells{
-            if c.x_loc == at.x && c.y_loc == at.y{
-                let mut cell = Cell::from(line);
-                cell.x_loc = at.x;
-                cell.y_loc = at.y;
-                self.table.cells.push(cell);
+    //fills each column of the selection down from its top cell: with copies of it, or when the top
+    //two cells start a series (1, 2 or Jan, Feb), with the rest of the series
+    pub fn fill_down(&mut self) -> usize{
+        let mut columns: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for (x, y) in self.highlighted_positions(){
+            columns.entry(x).or_default().push(y);
+        }
+        let mut edits = Vec::new();
+        for (x, mut rows) in columns{
+            rows.sort_unstable();
+            let contents = |y: usize| self.table.get(x, y).map(|cell| cell.contents.clone()).unwrap_or_default();
+            let top = contents(rows[0]);
+            let series = rows.get(1).and_then(|&second| series::extend(&top, &contents(second), rows.len() - 2));
+            match series{
+                Some(values) => edits.extend(rows[2..].iter().zip(values).map(|(&y, value)| (x, y, value))),
+                None => edits.extend(rows[1..].iter().map(|&y| (x, y, top.clone()))),
             }
-            else{
-                self.table.cells.push(c);
+        }
+        self.apply(edits)
+    }
+
+    //writes the number in each cell of a formatted column into it the way the column shows it, e.g.
+    //1234.5 as 1,234.50, as one undoable edit, leaving out the header row when there is one.
+    //Formulas and text are left alone. Returns how many cells changed
+    pub fn bake_number_formats(&mut self, header: bool) -> usize{
+        let first = if header { 2 } else { 1 };
+        let edits = self.table.number_formats.iter()
+            .flat_map(|(&x, format)| (first..self.table.num_rows()+1).map(move |y| (x, y, format)))
+            .filter_map(|(x, y, format)| {
+                let contents = &self.table.get(x, y)?.contents;
+                let formatted = format.apply(contents).filter(|_| !formula::is_formula(contents))?;
+                Some((x, y, formatted))
+            })
+            .collect();
+        self.apply(edits)
+    }
+
+    //sets the contents of several cells as a single undoable action, returning how many cells changed.
+    //Locked cells are left alone
+    pub fn apply(&mut self, edits: Vec<(usize, usize, String)>) -> usize{
+        let mut changes = Vec::new();
+        for (x, y, contents) in edits{
+            if self.refuse(x, y){
+                continue;
+            }
+            if let Some(before) = self.table.set_contents(x, y, &contents){
+                if before != contents{
+                    self.formulas.update(&self.table, x, y);
+                    changes.push(CellChange{x, y, before, after: contents});
+                }
             }
         }
+        let changed = changes.len();
+        if changed > 0{
+            self.remember(&changes);
+            self.record(Action::Edit(changes));
+        }
+        changed
     }
 
-    pub fn delete(&mut self){
-        let cells = self.table.cells.clone();
-        self.table.cells = Vec::new();
+    //adds what each changed cell held before to its history, for the Alt-b popup. Called with an
+    //edit's changes while they still name the cells they were made to, before any row or column goes
+    fn remember(&mut self, changes: &[CellChange]){
+        for change in changes{
+            let history = self.table.history.entry((change.x, change.y)).or_default();
+            if change.before != change.after && history.last() != Some(&change.before){
+                history.push(change.before.clone());
+            }
+        }
+    }
+
+    //whether the cell at (x, y) is locked against changes, counting it as refused when it is
+    pub fn refuse(&mut self, x: usize, y: usize) -> bool{
+        let locked = self.is_locked(x, y);
+        if locked{
+            self.refused += 1;
+        }
+        locked
+    }
+
+    pub fn is_locked(&self, x: usize, y: usize) -> bool{
+        !self.override_locks && self.table.is_locked(x, y)
+    }
+
+    //how many changes to locked cells were refused since it was last asked
+    pub fn take_refused(&mut self) -> usize{
+        std::mem::take(&mut self.refused)
+    }
+
+    fn record(&mut self, action: Action){
         self.saved = false;
-        for mut c in cells{
-            if c.highlighted{
-                c.edit_content(String::from(" "));
+        self.revision += 1;
+        self.undo_stack.push(action);
+        self.redo_stack.clear();
+    }
+
+    //adds a blank row at `y`, moving that row and everything below it down
+    pub fn insert_row(&mut self, y: usize){
+        self.before_reshape();
+        self.table.insert_row(y);
+        self.after_reshape();
+        self.record(Action::InsertRow(y));
+    }
+
+    //adds a blank column at `x`, moving that column and everything right of it along
+    pub fn insert_col(&mut self, x: usize){
+        self.before_reshape();
+        self.table.insert_col(x);
+        self.after_reshape();
+        self.record(Action::InsertCol(x));
+    }
+
+    //removes row `y` entirely; the last remaining row and locked rows are never removed
+    pub fn delete_row(&mut self, y: usize) -> bool{
+        if self.table.num_rows() <= 1 || y == 0 || y > self.table.num_rows() || self.refuse(0, y){
+            return false;
+        }
+        self.before_reshape();
+        let contents = self.table.remove_row(y);
+        self.after_reshape();
+        self.record(Action::DeleteRow(y, contents));
+        true
+    }
+
+    //takes rows `top` through `bottom` out entirely, leaving locked rows and always at least one row,
+    //and gives back the rows that went as whole rows to paste. One undo brings them all back
+    pub fn cut_rows(&mut self, top: usize, bottom: usize) -> Region{
+        let width = self.table.num_cols();
+        if top == 0 || top > bottom || bottom > self.table.num_rows(){
+            return Region::default();
+        }
+        self.before_reshape();
+        let mut actions = Vec::new();
+        //from the bottom up, so the rows still to go keep their places
+        for y in (top..bottom+1).rev(){
+            if self.table.num_rows() <= 1{
+                break;
+            }
+            if self.refuse(0, y){
+                continue;
+            }
+            actions.push(Action::DeleteRow(y, self.table.remove_row(y)));
+        }
+        self.after_reshape();
+        let cells = actions.iter().rev().enumerate()
+            .flat_map(|(i, action)| match action {
+                Action::DeleteRow(_, row) => row.iter().enumerate().map(|(x, contents)| (x, i, contents.clone())).collect(),
+                _ => Vec::new(),
+            })
+            .collect();
+        let height = actions.len();
+        if height > 0{
+            self.record(Action::Group(actions));
+        }
+        Region{width, height, cells}
+    }
+
+    //removes column `x` entirely; the last remaining column and locked columns are never removed
+    pub fn delete_col(&mut self, x: usize) -> bool{
+        if self.table.num_cols() <= 1 || x == 0 || x > self.table.num_cols() || self.refuse(x, 0){
+            return false;
+        }
+        self.before_reshape();
+        let contents = self.table.remove_col(x);
+        self.after_reshape();
+        self.record(Action::DeleteCol(x, contents));
+        true
+    }
+
+    //swaps rows and columns, so the first row becomes column A
+    pub fn transpose(&mut self){
+        self.before_reshape();
+        self.table.transpose();
+        self.after_reshape();
+        self.record(Action::Transpose);
+    }
+
+    //moves column `x` one place left or right, returning false when it is already at that edge
+    pub fn move_col(&mut self, x: usize, right: bool) -> bool{
+        let to = if right { x + 1 } else { x.wrapping_sub(1) };
+        if x == 0 || x > self.table.num_cols() || to == 0 || to > self.table.num_cols(){
+            return false;
+        }
+        self.before_reshape();
+        self.table.swap_cols(x, to);
+        self.after_reshape();
+        self.record(Action::SwapCols(x, to));
+        true
+    }
+
+    //highlights and search matches are kept by position, which stop meaning anything once rows/columns move
+    fn before_reshape(&mut self){
+        self.clear_highlights();
+        self.clear_matches();
+    }
+
+    //formula references are not rewritten when rows/columns move, but what they point at has changed
+    fn after_reshape(&mut self){
+        self.formulas.rebuild(&self.table);
+    }
+
+    //the text a cell shows: its contents, or for a formula its result. None when (x, y) is outside the table
+    pub fn value(&self, x: usize, y: usize) -> Option<String>{
+        let cell = self.table.get(x, y)?;
+        if formula::is_formula(&cell.contents){
+            return Some(self.formula_display(x, y));
+        }
+        Some(cell.value().to_string())
+    }
+
+    //the type of what a cell shows, which for a formula is the type of its result
+    pub fn cell_type(&self, x: usize, y: usize) -> CellType{
+        match self.table.get(x, y){
+            Some(cell) if formula::is_formula(&cell.contents) => table::infer_type(&self.formula_display(x, y)),
+            Some(cell) => cell.kind,
+            None => CellType::Empty,
+        }
+    }
+
+    //what a formula cell shows: its computed value or error
+    pub fn formula_display(&self, x: usize, y: usize) -> String{
+        match self.formulas.value(x, y){
+            Some(Ok(value)) => formula::format_number(*value),
+            Some(Err(e)) => e.clone(),
+            None => String::new(),
+        }
+    }
+
+    //reverts the most recent action, returning false when there is nothing to undo
+    pub fn undo(&mut self) -> bool{
+        match self.undo_stack.pop(){
+            Some(action) => {
+                self.revert(&action);
+                self.redo_stack.push(action);
+                self.saved = false;
+                self.revision += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    //remembers what the open sheet holds now under `name`, in place of any checkpoint already called that
+    pub fn checkpoint(&mut self, name: &str){
+        let sheet = self.sheet;
+        self.checkpoints.retain(|checkpoint| !(checkpoint.sheet == sheet && checkpoint.name == name));
+        self.checkpoints.push(Checkpoint{ name: name.to_string(), taken: Instant::now(), sheet, rows: self.rows() });
+    }
+
+    //the open sheet's checkpoints, oldest first
+    pub fn checkpoints(&self) -> Vec<&Checkpoint>{
+        self.checkpoints.iter().filter(|checkpoint| checkpoint.sheet == self.sheet).collect()
+    }
+
+    //puts back what the open sheet held at the checkpoint called `name`, as one undoable change.
+    //Locked cells go back too. False when there is no such checkpoint
+    pub fn restore_checkpoint(&mut self, name: &str) -> bool{
+        let Some(rows) = self.checkpoints().into_iter().find(|checkpoint| checkpoint.name == name).map(|checkpoint| checkpoint.rows.clone()) else {
+            return false;
+        };
+        let before = self.rows();
+        self.before_reshape();
+        self.table.replace_contents(rows.clone());
+        self.after_reshape();
+        self.record(Action::Replace(before, rows));
+        true
+    }
+
+    //adds `rows` under the last row as one undoable change. The table grows new columns for rows
+    //wider than it, and shorter rows are filled out with blanks
+    pub fn append_rows(&mut self, rows: Vec<Vec<String>>){
+        let before = self.rows();
+        let width = rows.iter().map(Vec::len).chain([self.table.num_cols()]).max().unwrap_or(0);
+        let mut after = before.clone();
+        after.extend(rows);
+        for row in &mut after{
+            row.resize(width, String::new());
+        }
+        self.before_reshape();
+        self.table.replace_contents(after.clone());
+        self.after_reshape();
+        self.record(Action::Replace(before, after));
+    }
+
+    //re-applies the most recently undone action, returning false when there is nothing to redo
+    pub fn redo(&mut self) -> bool{
+        match self.redo_stack.pop(){
+            Some(action) => {
+                self.replay(&action);
+                self.undo_stack.push(action);
+                self.saved = false;
+                self.revision += 1;
+                true
             }
-            self.table.cells.push(c);
+            None => false,
+        }
+    }
+
+    fn revert(&mut self, action: &Action){
+        match action{
+            Action::Edit(changes) => {
+                for change in changes.iter().rev(){
+                    self.table.set_contents(change.x, change.y, &change.before);
+                    self.formulas.update(&self.table, change.x, change.y);
+                }
+            }
+            Action::InsertRow(y) => {
+                self.before_reshape();
+                self.table.remove_row(*y);
+            }
+            Action::InsertCol(x) => {
+                self.before_reshape();
+                self.table.remove_col(*x);
+            }
+            Action::DeleteRow(y, contents) => {
+                self.before_reshape();
+                self.table.insert_row(*y);
+                for (x, value) in contents.iter().enumerate(){
+                    self.table.set_contents(x + 1, *y, value);
+                }
+            }
+            Action::DeleteCol(x, contents) => {
+                self.before_reshape();
+                self.table.insert_col(*x);
+                for (y, value) in contents.iter().enumerate(){
+                    self.table.set_contents(*x, y + 1, value);
+                }
+            }
+            Action::SwapCols(a, b) => {
+                self.before_reshape();
+                self.table.swap_cols(*a, *b);
+            }
+            Action::Transpose => {
+                self.before_reshape();
+                self.table.transpose();
+            }
+            Action::Replace(before, _) => {
+                self.before_reshape();
+                self.table.replace_contents(before.clone());
+            }
+            Action::Group(actions) => {
+                for action in actions.iter().rev(){
+                    self.revert(action);
+                }
+            }
+        }
+        if !matches!(action, Action::Edit(_) | Action::Group(_)){
+            self.after_reshape();
+        }
+    }
+
+    fn replay(&mut self, action: &Action){
+        match action{
+            Action::Edit(changes) => {
+                for change in changes{
+                    self.table.set_contents(change.x, change.y, &change.after);
+                    self.formulas.update(&self.table, change.x, change.y);
+                }
+            }
+            Action::InsertRow(y) => {
+                self.before_reshape();
+                self.table.insert_row(*y);
+            }
+            Action::InsertCol(x) => {
+                self.before_reshape();
+                self.table.insert_col(*x);
+            }
+            Action::DeleteRow(y, _) => {
+                self.before_reshape();
+                self.table.remove_row(*y);
+            }
+            Action::DeleteCol(x, _) => {
+                self.before_reshape();
+                self.table.remove_col(*x);
+            }
+            Action::SwapCols(a, b) => {
+                self.before_reshape();
+                self.table.swap_cols(*a, *b);
+            }
+            Action::Transpose => {
+                self.before_reshape();
+                self.table.transpose();
+            }
+            Action::Replace(_, after) => {
+                self.before_reshape();
+                self.table.replace_contents(after.clone());
+            }
+            Action::Group(actions) => {
+                for action in actions{
+                    self.replay(action);
+                }
+            }
+        }
+        if !matches!(action, Action::Edit(_) | Action::Group(_)){
+            self.after_reshape();
+        }
+    }
+
+    //puts a copied block down with its top left corner at `at`, adding rows and columns
+    //when it runs off the edge. One undo takes the whole paste back
+    pub fn paste(&mut self, at: &Position, region: &Region){
+        let (x, y) = (at.x.max(1), at.y.max(1));
+        let mut actions = Vec::new();
+        if x + region.width - 1 > self.table.num_cols() || y + region.height - 1 > self.table.num_rows(){
+            self.before_reshape();
+            while self.table.num_cols() < x + region.width - 1{
+                let col = self.table.num_cols() + 1;
+                self.table.insert_col(col);
+                actions.push(Action::InsertCol(col));
+            }
+            while self.table.num_rows() < y + region.height - 1{
+                let row = self.table.num_rows() + 1;
+                self.table.insert_row(row);
+                actions.push(Action::InsertRow(row));
+            }
+            self.after_reshape();
+        }
+        let mut changes = Vec::new();
+        for (dx, dy, contents) in &region.cells{
+            let (x, y) = (x + dx, y + dy);
+            if self.refuse(x, y){
+                continue;
+            }
+            if let Some(before) = self.table.set_contents(x, y, contents){
+                if &before != contents{
+                    self.formulas.update(&self.table, x, y);
+                    changes.push(CellChange{x, y, before, after: contents.clone()});
+                }
+            }
+        }
+        if !changes.is_empty(){
+            self.remember(&changes);
+            actions.push(Action::Edit(changes));
+        }
+        match actions.len(){
+            0 => (),
+            1 => self.record(actions.remove(0)),
+            _ => self.record(Action::Group(actions)),
         }
     }
 
+    pub fn insert(&mut self,at:Position,line: &str) {
+        self.apply(vec![(at.x, at.y, line.to_string())]);
+    }
+
+    pub fn delete(&mut self){
+        let edits = self.highlighted_positions().into_iter()
+            .map(|(x, y)| (x, y, String::new()))
+            .collect();
+        self.apply(edits);
+    }
+
+    pub fn rows(&self) -> Vec<Vec<String>>{
+        table_rows(&self.table)
+    }
+
+    //like rows, but with formulas replaced by their results
+    pub fn value_rows(&self) -> Vec<Vec<String>>{
+        (1..self.table.num_rows()+1).map(|y| {
+            (1..self.table.num_cols()+1).map(|x| self.value(x, y).unwrap_or_default()).collect()
+        }).collect()
+    }
+
+    //writes the document in the format its file name's extension asks for. Databases are changed in
+    //a transaction; every other format is written beside the file and then swapped in for it, so a
+    //crash part way through leaves the old version whole
     pub fn save(&mut self) -> Result<(),Error>{
-        if let Some(file_name) = &self.file_name{
-            let mut file = fs::File::create(file_name)?;
-            let n_rows = self.table.num_rows();
-            let mut line = String::new();
-
-            for i in 1..n_rows+1{
-                for cell in &self.table.cells{
-                    if i == cell.y_loc{
-                        line.push_str(&cell.contents);
-                        line.pop();
-                        line.push_str(",");
-                    }
+        if let Some(file_name) = self.file_name.clone(){
+            let result = if sqlite::is_sqlite(&file_name){
+                self.write_database(&file_name)
+            }
+            else{
+                replace_safely(&file_name, self.backup, |path| self.write_file(path))
+            };
+            //notes go in a file of their own, whatever the format
+            let result = result.and_then(|_| notes::write(&file_name, &self.all_notes()));
+            if let Err(e) = result{
+                if e.kind() == ErrorKind::PermissionDenied{
+                    self.read_only = true;
                 }
-                line.pop();
-                file.write_all(line.as_bytes())?;
-                file.write_all(b"\n")?;
-                line.clear();
+                return Err(e);
             }
             self.saved = true;
+            self.remove_swap();
+        }
+        Ok(())
+    }
+
+    //writes every format but a database's to `path`, in the format its extension asks for
+    fn write_file(&self, path: &str) -> Result<(),Error>{
+        if workbook::is_xlsx(path){
+            workbook::write_xlsx(path, &self.workbook_sheets())
+        }
+        else if workbook::is_ods(path){
+            workbook::write_ods(path, &self.workbook_sheets())
+        }
+        else if json::is_any_json(path){
+            json::write_rows(path, &self.value_rows())
+        }
+        else if latex::is_latex(path){
+            let alignments: Vec<_> = (1..self.table.num_cols()+1).map(|x| self.table.alignments.get(&x).copied()).collect();
+            latex::write(path, &self.value_rows(), self.dialect.header, &alignments)
+        }
+        else{
+            self.write_delimited(path)
+        }
+    }
+
+    //where unsaved edits are kept in case the editor dies, e.g. data.csv.clicsv-swap.
+    //only delimited text files get one; workbooks and unnamed documents don't
+    pub fn swap_path(&self) -> Option<String>{
+        let file_name = self.file_name.as_ref()?;
+        if workbook::is_workbook(file_name) || sqlite::is_sqlite(file_name) || file_name.ends_with(".parquet"){
+            return None;
+        }
+        Some(format!("{}{}", file_name, SWAP_SUFFIX))
+    }
+
+    pub fn has_swap(&self) -> bool{
+        self.swap_path().is_some_and(|path| fs::metadata(path).is_ok())
+    }
+
+    pub fn write_swap(&self) -> Result<(),Error>{
+        match self.swap_path(){
+            //in UTF-8, so nothing typed can fail to fit
+            Some(path) => self.write_csv(&path, "UTF-8", false),
+            None => Ok(()),
+        }
+    }
+
+    pub fn remove_swap(&self){
+        if let Some(path) = self.swap_path(){
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    //replaces the table with the one in the swap file, leaving the document unsaved
+    pub fn recover_swap(&mut self) -> Result<(),Error>{
+        let path = self.swap_path().ok_or_else(|| Error::new(ErrorKind::NotFound, "no swap file"))?;
+        let contents = encoding::decode(&fs::read(path)?).text;
+        let rows = table::parse_csv(&contents, self.dialect.delimiter, self.dialect.quote);
+        self.before_reshape();
+        let mut table = Table::from_rows(rows);
+        table.max_col_width = self.table.max_col_width;
+        table.max_cell_width = self.table.max_cell_width;
+        table.notes = std::mem::take(&mut self.table.notes);
+        self.table = table;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.after_reshape();
+        self.saved = false;
+        self.revision += 1;
+        Ok(())
+    }
+
+    fn write_csv(&self, file_name: &str, encoding: &str, bom: bool) -> Result<(),Error>{
+        //encoded before the file is touched, so text that won't fit leaves it as it was
+        let bytes = self.encode_csv(encoding, bom)?;
+        fs::write(file_name, bytes)
+    }
+
+    //the file's own delimited text, packed again if it was compressed
+    fn write_delimited(&self, file_name: &str) -> Result<(),Error>{
+        let bytes = self.encode_csv(&self.dialect.encoding, self.dialect.bom)?;
+        fs::write(file_name, compression::compress(bytes, self.dialect.compression)?)
+    }
+
+    //the table as delimited text in the file's dialect, as saving would write it
+    pub fn csv_bytes(&self) -> Result<Vec<u8>,Error>{
+        self.encode_csv(&self.dialect.encoding, self.dialect.bom)
+    }
+
+    //for when the document has been written somewhere other than its file
+    pub fn mark_saved(&mut self){
+        self.saved = true;
+        self.remove_swap();
+    }
+
+    fn encode_csv(&self, encoding: &str, bom: bool) -> Result<Vec<u8>,Error>{
+        let delimiter = self.dialect.delimiter;
+        let quote = self.dialect.quote;
+        let mut text = String::new();
+        for row in self.rows(){
+            let fields: Vec<String> = row.iter().map(|value| table::quote_field(value, delimiter, quote)).collect();
+            //a lone empty field is quoted, or the row would be a blank line that readers skip
+            if fields.len() == 1 && fields[0].is_empty(){
+                text.push_str(&[quote, quote].iter().collect::<String>());
+            }
+            text.push_str(&fields.join(&delimiter.to_string()));
+            text.push_str(self.dialect.line_ending());
+        }
+        encoding::encode(&text, encoding, bom)
+    }
+
+    //changes the encoding delimited text is saved in, e.g. to utf-8. Returns the encoding's
+    //proper name, or None when there is no such encoding
+    pub fn set_encoding(&mut self, label: &str) -> Option<String>{
+        let name = encoding::find(label)?.to_string();
+        //UTF-16 is always written with a byte order mark, so it can be told apart when read
+        self.dialect.bom = name.starts_with("UTF-16") || (self.dialect.bom && name == self.dialect.encoding);
+        if name != self.dialect.encoding{
+            self.dialect.encoding = name.clone();
+            self.saved = false;
         }
+        Some(name)
+    }
+
+    //saves the changed rows, then fills in the rowids the database gave new ones
+    fn write_database(&mut self, file_name: &str) -> Result<(),Error>{
+        let sheets = self.workbook_sheets();
+        let snapshot = self.snapshot.as_mut()
+            .ok_or_else(|| Error::new(ErrorKind::Unsupported, "only databases opened in clicsv can be saved back to"))?;
+        for (index, y, rowid) in sqlite::write(file_name, sheets, snapshot)?{
+            let table = if index == self.sheet { &mut self.table } else { &mut self.sheets[index].table };
+            table.set_contents(1, y + 1, &rowid.to_string());
+        }
+        self.formulas.rebuild(&self.table);
         Ok(())
     }
 
+    //the sheets to write to a workbook, with the open one holding the current table
+    fn workbook_sheets(&self) -> Vec<Sheet>{
+        if self.sheets.is_empty(){
+            return vec![Sheet{name: String::from("Sheet1"), rows: self.rows(), notes: table_notes(&self.table)}];
+        }
+        self.sheets.iter().enumerate().map(|(i, sheet)| {
+            let table = if i == self.sheet { &self.table } else { &sheet.table };
+            Sheet{name: sheet.name.clone(), rows: table_rows(table), notes: table_notes(table)}
+        }).collect()
+    }
+
+}
+
+//the table's contents row by row, blank where a row has no cell
+fn table_rows(table: &Table) -> Vec<Vec<String>>{
+    (1..table.num_rows()+1).map(|y| {
+        (1..table.num_cols()+1).map(|x| {
+            table.get(x, y).map(|cell| cell.value().to_string()).unwrap_or_default()
+        }).collect()
+    }).collect()
+}
+
+fn table_notes(table: &Table) -> Vec<(usize, usize, String)>{
+    table.notes.iter().map(|(&(x, y), text)| (x, y, text.clone())).collect()
+}
+
+//the delimiter some extensions stand for (.tsv, .tab and .psv, also compressed like .tsv.gz); other
+//files are sniffed
+pub fn extension_delimiter(filename: &str) -> Option<char>{
+    let filename = compression::inner_name(filename);
+    if filename.ends_with(".tsv") || filename.ends_with(".tab"){
+        Some('\t')
+    }
+    else if filename.ends_with(".psv"){
+        Some('|')
+    }
+    else{
+        None
+    }
+}
+
+//opening for writing (without truncating) tells us whether the OS will let us save
+fn is_read_only(filename: &str) -> bool{
+    fs::OpenOptions::new().write(true).open(filename)
+        .is_err_and(|e| e.kind() == ErrorKind::PermissionDenied)
+}
+
+//writes a new version of a file through `write`, which is handed a path beside it ending in the
+//same name (so the extension still picks the format). Once that is on disk it is renamed over the
+//file, after a copy of the old version is kept as file.bak when `backup` is set. A file that can't
+//be written to is refused, though the directory would let the rename through
+fn replace_safely(file_name: &str, backup: bool, write: impl FnOnce(&str) -> Result<(),Error>) -> Result<(),Error>{
+    let exists = fs::symlink_metadata(file_name).is_ok();
+    if exists{
+        fs::OpenOptions::new().write(true).open(file_name)?;
+    }
+    //through a link, the file it points to is the one replaced
+    let target = match fs::canonicalize(file_name){
+        Ok(path) if exists => path,
+        _ => std::path::PathBuf::from(file_name),
+    };
+    let name = target.file_name().and_then(|name| name.to_str()).unwrap_or("file");
+    let temp = target.with_file_name(format!(".clicsv-{}-{}", std::process::id(), name));
+    let temp_name = temp.to_str().ok_or_else(|| Error::new(ErrorKind::InvalidInput, "the file name isn't UTF-8"))?;
+    //a directory that won't take another file only leaves writing in place
+    if fs::File::create(&temp).is_err_and(|e| e.kind() == ErrorKind::PermissionDenied){
+        return write(file_name);
+    }
+    let written = write(temp_name).and_then(|_| {
+        fs::File::open(&temp)?.sync_all()?;
+        if exists{
+            fs::set_permissions(&temp, fs::metadata(&target)?.permissions())?;
+            if backup{
+                let mut copy = target.clone().into_os_string();
+                copy.push(".bak");
+                fs::copy(&target, copy)?;
+            }
+        }
+        fs::rename(&temp, &target)
+    });
+    if written.is_err(){
+        let _ = fs::remove_file(&temp);
+    }
+    written?;
+    //the rename itself is only safe once the directory is on disk too
+    if let Some(dir) = target.parent().filter(|dir| !dir.as_os_str().is_empty()){
+        let _ = fs::File::open(dir).and_then(|dir| dir.sync_all());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn document(csv: &str) -> Document{
+        Document::from_bytes(csv.as_bytes(), Some(',')).unwrap()
+    }
+
+    //the cells flagged as highlighted, in row-major order
+    fn flagged(document: &Document) -> Vec<(usize, usize)>{
+        let mut flagged: Vec<(usize, usize)> = document.table.cells.iter()
+            .filter(|cell| cell.highlighted)
+            .map(|cell| (cell.x_loc, cell.y_loc))
+            .collect();
+        flagged.sort_by_key(|&(x, y)| (y, x));
+        flagged
+    }
+
+    #[test]
+    fn highlighted_set_follows_the_cells(){
+        let mut document = document("a,b,c\n1,2,3\n4,5,6\n");
+        document.highlight(&Position{x: 1, y: 2});
+        assert_eq!(document.highlighted_positions(), flagged(&document));
+        for x in 1..4{
+            document.multi_highlight(&Position{x, y: 3});
+        }
+        //outside the table, so nothing is highlighted
+        document.multi_highlight(&Position{x: 9, y: 9});
+        assert_eq!(document.highlighted_positions(), [(1, 2), (1, 3), (2, 3), (3, 3)]);
+        assert_eq!(document.highlighted_positions(), flagged(&document));
+        document.highlight(&Position{x: 2, y: 1});
+        assert_eq!(document.highlighted_positions(), [(2, 1)]);
+        assert_eq!(document.highlighted_positions(), flagged(&document));
+        document.clear_highlights();
+        assert!(document.highlighted_positions().is_empty());
+        assert!(flagged(&document).is_empty());
+    }
+
+    #[test]
+    fn impute_fills_only_the_selected_blanks(){
+        let mut document = document("a,b,c\n1,,3\n , 5,\n7,8,\n");
+        //A2:B3 holds two blank cells, one of them only spaces; C3 and C4 are blank but not selected
+        for (x, y) in [(1, 2), (2, 2), (1, 3), (2, 3)]{
+            document.multi_highlight(&Position{x, y});
+        }
+        let before: Vec<String> = document.table.cells.iter().map(|cell| cell.contents.clone()).collect();
+        assert_eq!(document.impute("N/A"), 2);
+        assert_eq!(document.value(2, 2).as_deref(), Some("N/A"));
+        assert_eq!(document.value(1, 3).as_deref(), Some("N/A"));
+        let changed: Vec<(usize, usize)> = document.table.cells.iter().zip(&before)
+            .filter(|(cell, before)| cell.contents != **before)
+            .map(|(cell, _)| (cell.x_loc, cell.y_loc))
+            .collect();
+        assert_eq!(changed.len(), 2);
+        assert!(changed.contains(&(2, 2)) && changed.contains(&(1, 3)));
+        //one undo takes both back
+        document.undo();
+        let after: Vec<String> = document.table.cells.iter().map(|cell| cell.contents.clone()).collect();
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn dialect_indicator_reads_like_the_file(){
+        assert_eq!(Dialect::default().indicator(), "UTF-8 LF ,");
+        let dialect = Dialect{bom: true, line_ending: LineEnding::CrLf, delimiter: '\t', ..Dialect::default()};
+        assert_eq!(dialect.indicator(), "UTF-8 BOM CRLF TAB");
+        //as sniffed from the bytes of a file
+        let document = Document::from_bytes(b"\xef\xbb\xbfa;b;c\r\n1;2;3\r\n4;5;6\r\n", None).unwrap();
+        assert_eq!(document.dialect.indicator(), "UTF-8 BOM CRLF ;");
+    }
+
+    #[test]
+    fn edits_besides_typing_are_kept_in_cell_history(){
+        let mut document = document("b,x\na,y\n");
+        let row = document.copy_rows(2, 2);
+        document.paste(&Position{x: 1, y: 1}, &row);
+        assert_eq!(document.cell_history(1, 1), ["b"]);
+        assert_eq!(document.cell_history(2, 1), ["x"]);
+        document.apply(vec![(1, 2, String::from("c"))]);
+        //a cell's history goes with its row, and the sort adds what was in its place before
+        document.sort_by_column(1, false, false);
+        assert_eq!(document.rows(), [["c", "y"], ["a", "y"]]);
+        assert_eq!(document.cell_history(1, 2), ["b", "c"]);
+        document.merge_columns(1, 2, "-");
+        assert_eq!(document.cell_history(1, 1), ["a", "c"]);
+    }
+
+    #[test]
+    fn cutting_rows_copies_only_the_rows_that_went(){
+        let mut document = document("a\nb\nc\nd\n");
+        document.table.locked_rows.insert(3);
+        let cut = document.cut_rows(2, 4);
+        assert_eq!(cut.height, 2);
+        document.put_rows(document.table.num_rows(), &cut);
+        assert_eq!(document.rows(), [["a"], ["c"], ["b"], ["d"]]);
+    }
+
+    #[test]
+    fn unreadable_bytes_are_an_error(){
+        //a gzip header with nothing sensible after it
+        assert!(Document::from_bytes(&[0x1f, 0x8b, 0x08, 0x00, 0xff], None).is_err());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzipped_bytes_are_unpacked(){
+        let packed = compression::compress(b"a,b\n1,2\n".to_vec(), Compression::Gzip).unwrap();
+        let document = Document::from_bytes(&packed, None).unwrap();
+        assert_eq!(document.value(2, 2).as_deref(), Some("2"));
+    }
+
+    //a parquet file written as three row groups of two rows each, of a name and a count
+    #[cfg(feature = "parquet")]
+    fn write_parquet(path: &str){
+        use parquet::data_type::{ByteArray, ByteArrayType, Int32Type};
+        use parquet::file::properties::WriterProperties;
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::parser::parse_message_type;
+        use std::sync::Arc;
+
+        let schema = Arc::new(parse_message_type("message rows { REQUIRED BYTE_ARRAY name (UTF8); REQUIRED INT32 count; }").unwrap());
+        let file = fs::File::create(path).unwrap();
+        let mut writer = SerializedFileWriter::new(file, schema, Arc::new(WriterProperties::builder().build())).unwrap();
+        for group in 0..3{
+            let mut row_group = writer.next_row_group().unwrap();
+            let names: Vec<ByteArray> = (0..2).map(|i| ByteArray::from(format!("row {}", group * 2 + i).as_str())).collect();
+            let mut column = row_group.next_column().unwrap().unwrap();
+            column.typed::<ByteArrayType>().write_batch(&names, None, None).unwrap();
+            column.close().unwrap();
+            let mut column = row_group.next_column().unwrap().unwrap();
+            column.typed::<Int32Type>().write_batch(&[group * 2, group * 2 + 1], None, None).unwrap();
+            column.close().unwrap();
+            row_group.close().unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_reads_every_row_group(){
+        let path = std::env::temp_dir().join(format!("clicsv-test-{}.parquet", std::process::id()));
+        let path = path.to_str().unwrap();
+        write_parquet(path);
+        let document = Document::open(path);
+        fs::remove_file(path).ok();
+        let document = document.unwrap();
+        //the header and six rows
+        assert_eq!((document.table.num_rows(), document.table.num_cols()), (7, 2));
+        assert_eq!(document.value(1, 1).as_deref(), Some("name"));
+        assert_eq!(document.value(1, 7).as_deref(), Some("row 5"));
+        assert_eq!(document.value(2, 7).as_deref(), Some("5"));
+    }
 }
@@ -1,22 +1,67 @@
 use crate::Document;
+use crate::aggregates::{self, Aggregate, Blanks, Frequencies, Totals};
+use crate::document::{self, Join, Region};
+use crate::Position;
 use crate::Terminal;
+use crate::terminal;
 use crate::table;
+use crate::table::{Align, Progress, SortKey};
+use crate::state;
+use crate::diff::{self, Change, Diff};
+use crate::fetch;
+use crate::formula;
+use crate::fuzzy;
+use crate::git;
+use crate::json;
+use crate::latex;
+use crate::script;
+use crate::command::Command;
+use crate::compression::{self, Compression};
+use crate::config::{self, Colors, Config};
+use crate::filter::Predicate;
+use crate::highlight::Highlight;
+use crate::numbers::NumberFormat;
+use crate::stats::{self, Statistic, Summary};
+use crate::theme::{self, Depth, Shade};
+use crate::transform::{Split, Transform};
+use crate::validation::Rule;
+use crate::watch::Watch;
 
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
 use std::time::{Duration, Instant};
-use termion::{color, event::Key};
+use termion::event::{Event, Key, MouseButton, MouseEvent};
+use termion::{color, style};
 use table::Cell;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-const STATUS_FG_COLOR: color::Rgb = color::Rgb(63,63,63);
-const STATUS_BG_COLOR: color::Rgb = color::Rgb(239, 239, 239);
 const VERSION: &str = env!("CARGO_PKG_VERSION");
-
-#[derive(Default, PartialEq, Clone)]
-pub struct Position 
-{
-    pub x: usize,
-    pub y: usize,
-}
+//columns are capped at this width unless --max-col-width says otherwise, so one long cell can't take over the screen
+const DEFAULT_MAX_COL_WIDTH: usize = 40;
+//how many lines a cell may take up when wrapping
+const MAX_WRAP_LINES: usize = 5;
+//how often unsaved changes are written to the swap file
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(10);
+//how often the loading bar moves while a file opens
+const LOAD_REFRESH: Duration = Duration::from_millis(100);
+//how often the screen is drawn again while no keys come
+const IDLE_REFRESH: Duration = Duration::from_millis(500);
+const LOAD_BAR_WIDTH: usize = 12;
+//how many changes a transform shows before asking to go ahead
+const PREVIEW_LINES: usize = 10;
+const READ_ONLY_MESSAGE: &str = "File is read-only (permission denied)";
+const VIEW_ONLY_MESSAGE: &str = "Read-only: the table can't be changed here";
+const VIM_HELP_TEXT: &str = "hjkl: move | 0/$: first/last column | {/}: jump up/down to the edge of the data | i: edit (Esc keeps, Ctrl-c cancels) | x: clear | dd: delete row | yy/p/P: copy row/put below/above | o/O: insert row below/above | u/Ctrl-r: undo/redo | /: find | q/@: record/replay macro | :w :q :q! :wq :%s/find/replace/ | other Ctrl and Alt keys as usual";
+const HELP_TEXT: &str = "Type/Return: edit | Ctrl-s: save | Alt-s: save as | Ctrl-q: quit | Ctrl-c/x/v: copy/cut/paste | Alt-y/Y: copy/cut rows | Alt-U/I: put rows below/above | Alt-c: duplicate rows | Ctrl-d: fill down | Ctrl-z/r: undo/redo | Ctrl-f/h: find/replace | Ctrl-g: go to cell | Ctrl-t: fuzzy find in column | Ctrl-p: all commands | Alt-l/W: filter rows/pick column values | Ctrl-o: sort | Alt-g: sort by columns | Alt-o/O/a/i/d/D: insert/delete rows and columns | Alt-Left/Right: move column | Alt-r: rename column | Alt-m: note | Alt-b: cell history | Alt-v/e: column rule/next break | Alt-k/K: highlight/show duplicate rows | Alt-x: transpose | Alt-j/J: align column/number format | Alt-z/Z: hide column/show hidden | Alt-L/R: lock column/row | Alt-C: highlight rules | Alt-S/B: take/restore snapshot | Alt-E: export selection or table | Alt-T: transform cells | Alt-P/M: split/merge columns | Alt-V: view whole cell | Alt-X: pivot | Alt-G: describe columns | Alt-A: append file | Alt-Q/N: blank counts/next blank | Alt-Up/Down/</>: jump to the edge of the data | Alt-f: freeze header | Alt-=/-: resize column | Alt-w: wrap | Alt-H: crosshair | Alt-u: totals | Alt-h: column names | Alt-n/p/t: next/previous/pick sheet | Ctrl-n/b: next/previous file | Alt-q/@: record/replay macro | =: statistics | Alt-F: value counts | :: command | Ctrl-`: formulas";
 
 struct StatusMessage 
 {
@@ -25,6 +70,211 @@ struct StatusMessage
 }
 
 
+//what happens when the cursor is moved past the last row/column
+#[derive(Default, PartialEq, Clone, Copy, Debug)]
+pub enum EdgeBehavior
+{
+    //add a new row/column to the table
+    #[default]
+    Grow,
+    //continue on the next row/column
+    Wrap,
+    //stay on the last cell
+    Stop,
+}
+
+impl EdgeBehavior{
+    fn parse(value: &str) -> Option<Self>
+    {
+        match value {
+            "grow" => Some(EdgeBehavior::Grow),
+            "wrap" => Some(EdgeBehavior::Wrap),
+            "stop" => Some(EdgeBehavior::Stop),
+            _ => None,
+        }
+    }
+}
+
+//command line options: `clicsv [--max-col-width N] [--max-cell-width N] [--edge-behavior grow|wrap|stop] [--show-dialect] [--delimiter C] [--no-header] [--to-stdout] [-R|--readonly] [file | -] [more files]`
+#[derive(Default)]
+struct Args
+{
+    file_name: Option<String>,
+    //opened as buffers behind the first
+    more_files: Vec<String>,
+    max_col_width: Option<usize>,
+    max_cell_width: Option<usize>,
+    edge_behavior: EdgeBehavior,
+    show_dialect: bool,
+    delimiter: Option<char>,
+    no_header: bool,
+    //saving keeps the table to write to stdout on quitting, rather than writing a file
+    to_stdout: bool,
+    //open every file as if it couldn't be written, to page through without risking edits
+    read_only: bool,
+}
+
+impl Args{
+    fn parse(args: &[String]) -> Result<Self, String>
+    {
+        let mut parsed = Args::default();
+        let mut iter = args.iter().skip(1);
+        while let Some(arg) = iter.next()
+        {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let invalid = |value: &str| format!("Err: invalid value for {}: {}", flag, value);
+            match flag {
+                "--max-col-width" | "--max-cell-width" => {
+                    let value = flag_value(flag, inline_value, &mut iter)?;
+                    let width = value.parse::<usize>().map_err(|_| invalid(&value))?.max(1);
+                    if flag == "--max-col-width" {
+                        parsed.max_col_width = Some(width);
+                    } else {
+                        parsed.max_cell_width = Some(width);
+                    }
+                }
+                "--show-dialect" => parsed.show_dialect = true,
+                "--no-header" => parsed.no_header = true,
+                "--to-stdout" => parsed.to_stdout = true,
+                "-R" | "--readonly" => parsed.read_only = true,
+                "-d" | "--delimiter" => {
+                    let value = flag_value(flag, inline_value, &mut iter)?;
+                    parsed.delimiter = Some(parse_delimiter(&value).ok_or_else(|| invalid(&value))?);
+                }
+                "--edge-behavior" => {
+                    let value = flag_value(flag, inline_value, &mut iter)?;
+                    parsed.edge_behavior = EdgeBehavior::parse(&value).ok_or_else(|| invalid(&value))?;
+                }
+                _ => {
+                    if parsed.file_name.is_none() {
+                        parsed.file_name = Some(arg.clone());
+                    } else {
+                        parsed.more_files.push(arg.clone());
+                    }
+                }
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+//accepts a single character, or an escape/name for the awkward ones: `\t`, `tab`, `space`
+pub fn parse_delimiter(value: &str) -> Option<char>
+{
+    match value {
+        "\\t" | "tab" | "TAB" => Some('\t'),
+        "space" => Some(' '),
+        _ => {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c != '"' && c != '\n' => Some(c),
+                _ => None,
+            }
+        }
+    }
+}
+
+//the value of a flag, given either as `--flag=value` or `--flag value`
+fn flag_value<'a>(flag: &str, inline_value: Option<String>, iter: &mut impl Iterator<Item = &'a String>) -> Result<String, String>
+{
+    inline_value.or_else(|| iter.next().cloned())
+        .ok_or_else(|| format!("Err: {} needs a value", flag))
+}
+
+//the text of a cell being edited in place, split into graphemes so an accented letter or an
+//emoji made of several code points moves and deletes as one, and the cursor (a grapheme index)
+struct CellEdit
+{
+    buffer: Vec<String>,
+    cursor: usize,
+}
+
+impl CellEdit{
+    fn new(text: &str) -> Self
+    {
+        let buffer: Vec<String> = text.graphemes(true).map(String::from).collect();
+        let cursor = buffer.len();
+        Self{ buffer, cursor }
+    }
+
+    fn text(&self) -> String
+    {
+        self.buffer.concat()
+    }
+
+    //a combining mark or joiner typed after a grapheme becomes part of it
+    fn insert(&mut self, c: char)
+    {
+        if let Some(before) = self.cursor.checked_sub(1).and_then(|i| self.buffer.get_mut(i))
+        {
+            let joined = format!("{}{}", before, c);
+            if joined.graphemes(true).count() == 1
+            {
+                *before = joined;
+                return;
+            }
+        }
+        self.buffer.insert(self.cursor, c.to_string());
+        self.cursor += 1;
+    }
+
+    fn backspace(&mut self)
+    {
+        if self.cursor > 0
+        {
+            self.cursor -= 1;
+            self.buffer.remove(self.cursor);
+        }
+    }
+
+    fn delete(&mut self)
+    {
+        if self.cursor < self.buffer.len()
+        {
+            self.buffer.remove(self.cursor);
+        }
+    }
+
+    fn move_left(&mut self)
+    {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    fn move_right(&mut self)
+    {
+        self.cursor = (self.cursor + 1).min(self.buffer.len());
+    }
+
+    //the part of the buffer that fits in `width` columns around the cursor, with the cursor shown inverted.
+    //returns the rendered text and its display width
+    fn render(&self, width: usize) -> (String, usize)
+    {
+        let grapheme_width = |g: &String| g.width();
+        let cursor_grapheme = self.buffer.get(self.cursor).map(String::as_str).unwrap_or(" ");
+        let cursor_width = cursor_grapheme.width().max(1);
+        let mut start = self.cursor;
+        let mut used = cursor_width;
+        while start > 0 && used + grapheme_width(&self.buffer[start - 1]) <= width
+        {
+            start -= 1;
+            used += grapheme_width(&self.buffer[start]);
+        }
+        let mut end = (self.cursor + 1).min(self.buffer.len());
+        while end < self.buffer.len() && used + grapheme_width(&self.buffer[end]) <= width
+        {
+            used += grapheme_width(&self.buffer[end]);
+            end += 1;
+        }
+        let before = self.buffer[start..self.cursor].concat();
+        let after: String = self.buffer[self.cursor.min(end)..end].iter().skip(1).map(String::as_str).collect();
+        let rendered = format!("{}{}{}{}{}", before, style::Invert, cursor_grapheme, style::NoInvert, after);
+        (rendered, used)
+    }
+}
+
 impl StatusMessage{
     fn from(message: String) -> Self 
     {
@@ -44,28 +294,324 @@ pub struct Editor
     offset: Position,
     document: Document,
     status_message: StatusMessage,
-    copy: Vec<Cell>
+    copy: Region,
+    show_formulas: bool,
+    //the register being recorded into and the keys so far
+    recording: Option<(char, Vec<Key>)>,
+    //recorded macros by register, and the one replayed last for @@
+    macros: HashMap<char, Vec<Key>>,
+    last_macro: Option<char>,
+    pending_keys: VecDeque<Key>,
+    edge_behavior: EdgeBehavior,
+    show_dialect: bool,
+    editing: Option<CellEdit>,
+    //the active search; while set, n/N jump between matches and Esc clears it
+    search: Option<String>,
+    //whether the first row holds column names, which sorting leaves in place
+    header: bool,
+    //the column and direction of the last sort, so sorting the same column again reverses it
+    last_sort: Option<(usize, bool)>,
+    //keep the first row on screen while scrolling down
+    freeze_header: bool,
+    //show long cells over several lines instead of cutting them off
+    wrap: bool,
+    //shade the current row and column, to follow a row across a wide table
+    crosshair: bool,
+    //bindings from the config file, checked before the built in ones
+    keys: HashMap<Key, Command>,
+    //the files of the config's [scripts], run by Command::Script
+    scripts: Vec<std::path::PathBuf>,
+    colors: Colors,
+    last_autosave: Instant,
+    //name columns in the header bar after the first row instead of A, B, C
+    header_labels: bool,
+    filter: Option<Filter>,
+    //another file the document is being compared with
+    comparison: Option<Comparison>,
+    to_stdout: bool,
+    //what was last saved with --to-stdout, written out once the screen is given back
+    output: Option<Vec<u8>>,
+    //stdout when it is piped, since the screen has taken its place
+    piped_stdout: Option<File>,
+    //a footer under the table with a sum, mean, count, min or max for each column
+    show_totals: bool,
+    totals: Totals,
+    //the quality view: how many blank cells each column has, beside its letter
+    show_blanks: bool,
+    blanks: Blanks,
+    duplicates: Option<Duplicates>,
+    //notices the file being changed on disk by something else
+    watch: Option<Watch>,
+    //vim-style keys, and the first key of a pair like dd or yy while waiting for the second
+    vim: bool,
+    //whether the row numbers are drawn down the left, and the number of the first row below the header
+    gutter: bool,
+    number_from: usize,
+    //whether saving a file git knows first shows what changed since its last commit
+    git_preview: bool,
+    //whether saving keeps the previous version of the file as file.csv.bak
+    backup: bool,
+    vim_pending: Option<char>,
+    screen: RefCell<Screen>,
+    //every open file, and which one is on screen
+    buffers: Vec<Buffer>,
+    buffer: usize,
+    no_header: bool,
+    read_only: bool,
+}
+
+//the table area as last written to the terminal, so a refresh only rewrites lines that changed
+#[derive(Default)]
+struct Screen
+{
+    //the terminal size and scroll offset the lines were drawn at; None when they must all be drawn
+    drawn_at: Option<((u16, u16), Position)>,
+    lines: Vec<String>,
+}
+
+//a view of only the rows passing a test; the document keeps every row, and
+//since the cursor stays in document rows, edits land on the row shown
+struct Filter
+{
+    text: String,
+    test: RowTest,
+    //the document rows shown, in order; a header row always is
+    rows: Vec<usize>,
+}
+
+//what decides the rows a filter shows
+enum RowTest
+{
+    Predicate(Predicate),
+    //rows that are the same cell for cell as another
+    Duplicates,
+    //rows whose value in each column is one of those picked for it
+    Values(BTreeMap<usize, HashSet<String>>),
+}
+
+//rows repeating another row cell for cell, highlighted until turned off
+struct Duplicates
+{
+    //the document revision, and whether the first row was left out, when `rows` was worked out
+    worked_out: (u64, bool),
+    rows: HashSet<usize>,
+}
+
+//an open file, with where the cursor and view were left in it. The one on screen lives in the
+//editor's own fields, leaving an empty Buffer in its place
+#[derive(Default)]
+struct Buffer
+{
+    document: Document,
+    cell_index: Position,
+    offset: Position,
+    header: bool,
+    search: Option<String>,
+    last_sort: Option<(usize, bool)>,
+    filter: Option<Filter>,
+    comparison: Option<Comparison>,
+    totals: Totals,
+    blanks: Blanks,
+    duplicates: Option<Duplicates>,
+    //notices the file being changed on disk by something else
+    watch: Option<Watch>,
+}
+
+//the rows of the file given to :diff, and how the document differs from them
+struct Comparison
+{
+    file_name: String,
+    rows: Vec<Vec<String>>,
+    diff: Diff,
 }
 
 impl Editor 
 {
     pub fn run(&mut self) 
     {
+        //each file given is checked for a swap file in turn, ending up back on the first
+        for index in (0..self.buffers.len()).rev()
+        {
+            self.switch_buffer(index);
+            if let Err(error) = self.offer_recovery()
+            {
+                die(error);
+            }
+            //a database opens on a table picked from the list
+            if self.document.is_database()
+            {
+                if let Err(error) = self.pick_sheet()
+                {
+                    die(error);
+                }
+            }
+        }
         loop 
         {
             if let Err(error) = self.refresh_screen() 
             {
+                self.document.write_swap().ok();
                 die(error);
             }
             if self.should_quit 
             {
+                //quitting means the changes were saved or thrown away on purpose
+                self.document.remove_swap();
+                self.buffers.iter().for_each(|buffer| buffer.document.remove_swap());
                 Terminal::cursor_show();
                 break;
             }
             if let Err(error) = self.process_keypress()
             {
+                self.document.write_swap().ok();
                 die(error);
             }
+            self.autosave();
+            if let Err(error) = self.watch_file()
+            {
+                self.document.write_swap().ok();
+                die(error);
+            }
+        }
+    }
+
+    //with --to-stdout, writes what was last saved to stdout once the screen is back to normal
+    pub fn write_output(mut self)
+    {
+        let (output, piped_stdout) = (self.output.take(), self.piped_stdout.take());
+        drop(self);
+        let Some(output) = output else { return };
+        let result = match piped_stdout
+        {
+            Some(mut file) => file.write_all(&output),
+            None => io::stdout().write_all(&output),
+        };
+        if let Err(e) = result
+        {
+            eprintln!("Err: Couldn't write to stdout: {}", e);
+        }
+    }
+
+    //a swap file left behind means the last session ended without saving, like vim asks what to do with it
+    fn offer_recovery(&mut self) -> Result<(), std::io::Error>
+    {
+        if !self.document.has_swap()
+        {
+            return Ok(());
+        }
+        self.status_message = StatusMessage::from(String::from(
+            "Found unsaved changes from a previous session. (r)ecover them, (d)elete them, or any other key to ignore"
+        ));
+        self.refresh_screen()?;
+        match self.read_key()?
+        {
+            Key::Char('r') | Key::Char('R') => {
+                let message = match self.document.recover_swap()
+                {
+                    Ok(()) => "Recovered unsaved changes. Ctrl-s to keep them.",
+                    Err(_) => "Error: Unable to read the swap file",
+                };
+                self.status_message = StatusMessage::from(String::from(message));
+                self.document.highlight(&self.cell_index);
+            }
+            Key::Char('d') | Key::Char('D') => {
+                self.document.remove_swap();
+                self.status_message = StatusMessage::from(String::from("Deleted the swap file."));
+            }
+            _ => self.status_message = StatusMessage::from(String::new()),
+        }
+        Ok(())
+    }
+
+    //offers to load the file again when something else changed it on disk, or just does so when
+    //the table can't be edited anyway. The file is only watched from the first look at it, so
+    //changes made before then go unnoticed
+    fn watch_file(&mut self) -> Result<(), std::io::Error>
+    {
+        let file_name = match &self.document.file_name
+        {
+            Some(file_name) if !self.document.is_database() => file_name.clone(),
+            _ => return Ok(()),
+        };
+        match &mut self.watch
+        {
+            Some(watch) if watch.file_name == file_name => if !watch.changed() { return Ok(()) },
+            //a new file, or one saved under a new name
+            _ => {
+                self.watch = Some(Watch::new(&file_name));
+                return Ok(());
+            }
+        }
+        if self.document.read_only
+        {
+            return self.reload();
+        }
+        let question = if self.document.is_saved() { "Reload it?" } else { "Reload it, losing your unsaved changes?" };
+        self.status_message = StatusMessage::from(format!("{} changed on disk. {} (y/n)", file_name, question));
+        self.refresh_screen()?;
+        match self.read_key()?
+        {
+            Key::Char('y') | Key::Char('Y') => self.reload(),
+            _ => {
+                self.status_message = StatusMessage::from(String::from("Kept this version; saving writes it over the one on disk."));
+                Ok(())
+            }
+        }
+    }
+
+    //reads the file again in place of the table, keeping the cursor where it was as far as the
+    //new table allows. Edits made since the last save, and their undo history, are gone
+    fn reload(&mut self) -> Result<(), std::io::Error>
+    {
+        let Some(file_name) = self.document.file_name.clone() else {
+            self.status_message = StatusMessage::from(String::from("Nothing to reload: the table has no file."));
+            return Ok(());
+        };
+        if self.document.is_database()
+        {
+            self.status_message = StatusMessage::from(String::from("Databases can't be reloaded."));
+            return Ok(());
+        }
+        let mut document = match Self::load(&mut self.terminal, &file_name, Some(self.document.dialect.delimiter))
+        {
+            Ok(document) => document,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                self.status_message = StatusMessage::from(String::from("Reload cancelled."));
+                return Ok(());
+            }
+            Err(e) => {
+                self.status_message = StatusMessage::from(format!("Err: Couldn't reload {}: {}", file_name, e));
+                return Ok(());
+            }
+        };
+        document.read_only |= self.read_only;
+        document.table.max_col_width = self.document.table.max_col_width;
+        document.table.max_cell_width = self.document.table.max_cell_width;
+        self.document.remove_swap();
+        self.document = document;
+        self.keep_cursor_in_table();
+        self.refilter();
+        self.document.highlight(&self.cell_index);
+        self.redraw_table();
+        if let Some(watch) = &mut self.watch
+        {
+            watch.settle();
+        }
+        self.status_message = StatusMessage::from(format!("Reloaded {}.", file_name));
+        Ok(())
+    }
+
+    //writes unsaved changes to the swap file every so often
+    fn autosave(&mut self)
+    {
+        if self.document.is_saved() || self.last_autosave.elapsed() < AUTOSAVE_INTERVAL
+        {
+            return;
+        }
+        self.last_autosave = Instant::now();
+        if self.document.write_swap().is_err()
+        {
+            self.status_message = StatusMessage::from(String::from("Warning: Unable to write the swap file"));
         }
     }
 
@@ -73,581 +619,4552 @@ impl Editor
     {
         let args: Vec<String> = env::args().collect();
         let mut initial_status = String::from("HELP: Ctrl-q to Quit, Ctrl-s to Save, Return to Edit");
-        let document = if let Some(file_name) = args.get(1) 
+        let args = Args::parse(&args).unwrap_or_else(|e| {
+            initial_status = e;
+            Args::default()
+        });
+        let (config, config_error) = match Config::load() {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default(), Some(e)),
+        };
+        theme::set_depth(config.depth.unwrap_or_else(Depth::detect));
+        //`clicsv -` reads the table from stdin, before the terminal takes its place
+        let stdin_table = match args.file_name.as_deref()
+        {
+            Some("-") => {
+                let mut bytes = Vec::new();
+                if let Err(e) = io::stdin().read_to_end(&mut bytes)
+                {
+                    initial_status = format!("Err: Couldn't read stdin: {}", e);
+                }
+                Some(bytes)
+            }
+            _ => None,
+        };
+        let piped_stdout = terminal::attach_tty().expect("Failed to open the terminal");
+        let mut terminal = Terminal::default().expect("Failed to init terminal");
+        let mut document = if let Some(bytes) = stdin_table
         {
-            let doc = Document::open(file_name);
-            if !file_name.ends_with(".csv")
+            match Document::from_bytes(&bytes, args.delimiter)
             {
-                initial_status = format!("Warning: This editor currently only supports utf-8 encoded csv files.");
+                Ok(doc) => {
+                    if initial_status.starts_with("HELP")
+                    {
+                        initial_status = doc.dialect.detected();
+                    }
+                    doc
+                }
+                Err(e) => {
+                    initial_status = format!("Err: Couldn't read stdin: {}", e);
+                    Document::default()
+                }
             }
-            if let Ok(doc) = doc 
+        }
+        else if let Some(file_name) = &args.file_name 
+        {
+            let doc = Self::load(&mut terminal, file_name, args.delimiter);
+            let supported = [".csv", ".tsv", ".tab", ".psv", ".gz", ".zst", ".zstd", ".xlsx", ".ods", ".json", ".ndjson", ".jsonl", ".db", ".sqlite", ".sqlite3"].iter().any(|ext| fetch::file_name(file_name).ends_with(ext));
+            if !supported
             {
-                doc
+                initial_status = String::from("Warning: This editor currently only supports utf-8 encoded csv files.");
             }
-            else 
+            match doc 
             {
-                initial_status = format!("Err: Couldn't open file");
-                Document::default()
+                Ok(doc) => {
+                    if doc.source.is_some()
+                    {
+                        initial_status = format!("Downloaded {}; it is read-only here, Alt-s saves a copy", fetch::file_name(file_name));
+                    }
+                    else if doc.read_only
+                    {
+                        initial_status = String::from(READ_ONLY_MESSAGE);
+                    }
+                    //report what was guessed about delimited text files
+                    else if supported && doc.sheet_count() == 0 && !json::is_any_json(file_name)
+                    {
+                        initial_status = doc.dialect.detected();
+                    }
+                    doc
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                    initial_status = format!("Stopped loading {}", file_name);
+                    Document::default()
+                }
+                Err(e) => {
+                    initial_status = format!("Err: Couldn't open file: {}", e);
+                    Document::default()
+                }
             }
         }
         else
         {
             Document::default()
         };
+        //a broken config file matters more than what was detected about the file
+        if let Some(e) = config_error
+        {
+            initial_status = e;
+        }
+        if document.file_name.is_none()
+        {
+            if let Some(delimiter) = args.delimiter.or(config.delimiter)
+            {
+                document.dialect.delimiter = delimiter;
+            }
+        }
+        if config.mouse
+        {
+            terminal.enable_mouse();
+        }
+        if args.read_only
+        {
+            document.read_only = true;
+            if !initial_status.starts_with("Err") && !initial_status.starts_with("Warning")
+            {
+                initial_status = String::from(VIEW_ONLY_MESSAGE);
+            }
+        }
+        let mut editor = Self::new(terminal, document, &args, config, initial_status, piped_stdout);
+        for file_name in &args.more_files
+        {
+            if let Err(e) = editor.add_buffer(file_name, args.delimiter)
+            {
+                editor.status_message = StatusMessage::from(format!("Err: Couldn't open {}: {}", file_name, e));
+            }
+        }
+        editor
+    }
 
-        Self 
+    //an editor showing `document` in `terminal`, set up by the command line and the config
+    fn new(terminal: Terminal, mut document: Document, args: &Args, config: Config, initial_status: String, piped_stdout: Option<File>) -> Self
+    {
+        let header = !args.no_header && document.dialect.header;
+        document.table.max_col_width = args.max_col_width.or(Some(DEFAULT_MAX_COL_WIDTH));
+        document.table.max_cell_width = args.max_cell_width;
+
+        Self
         {
             should_quit: false,
-            terminal: Terminal::default().expect("Failed to init terminal"),
+            terminal,
             document,
             cell_index: Position {x:1,y:2,},
             offset: Position {x:0,y:1},
             status_message: StatusMessage::from(initial_status),
-            copy: Vec::new(),
+            copy: Region::default(),
+            show_formulas: false,
+            recording: None,
+            macros: HashMap::new(),
+            last_macro: None,
+            pending_keys: VecDeque::new(),
+            edge_behavior: args.edge_behavior,
+            show_dialect: args.show_dialect,
+            editing: None,
+            search: None,
+            header,
+            last_sort: None,
+            freeze_header: config.freeze_header,
+            wrap: config.wrap,
+            crosshair: config.crosshair,
+            keys: config.keys,
+            scripts: config.scripts,
+            colors: config.colors,
+            last_autosave: Instant::now(),
+            header_labels: false,
+            filter: None,
+            comparison: None,
+            show_totals: false,
+            show_blanks: false,
+            vim: config.vim,
+            gutter: config.gutter,
+            number_from: config.number_from,
+            git_preview: config.git_preview,
+            backup: config.backup,
+            vim_pending: None,
+            screen: RefCell::default(),
+            totals: Totals::default(),
+            blanks: Blanks::default(),
+            duplicates: None,
+            watch: None,
+            to_stdout: args.to_stdout,
+            output: None,
+            piped_stdout,
+            buffers: vec![Buffer::default()],
+            buffer: 0,
+            no_header: args.no_header,
+            read_only: args.read_only,
         }
     }
 
+    //opens a file in a new buffer behind the others, without switching to it
+    fn add_buffer(&mut self, file_name: &str, delimiter: Option<char>) -> Result<usize, std::io::Error>
+    {
+        let mut document = Self::load(&mut self.terminal, file_name, delimiter)?;
+        document.read_only |= self.read_only;
+        Ok(self.push_buffer(document))
+    }
 
-    fn refresh_screen(&self) -> Result<(), std::io::Error> {
-        Terminal::cursor_hide();
-        Terminal::cursor_position(&Position::default());
-        if self.should_quit {
-            Terminal::clear_screen();
-        } else {
-            self.draw_table();
-            self.draw_status_bar();
-            self.draw_message_bar();
-            Terminal::cursor_position(&Position {
-                x: self.cell_index.x.saturating_sub(self.offset.x),
-                y: self.cell_index.y.saturating_sub(self.offset.y),
-            });
+    //puts a document in a new buffer behind the others, without switching to it
+    fn push_buffer(&mut self, mut document: Document) -> usize
+    {
+        document.table.max_col_width = self.document.table.max_col_width;
+        document.table.max_cell_width = self.document.table.max_cell_width;
+        let header = !self.no_header && document.dialect.header;
+        document.highlight(&Position {x: 1, y: 2});
+        self.buffers.push(Buffer {
+            document,
+            cell_index: Position {x: 1, y: 2},
+            offset: Position {x: 0, y: 1},
+            header,
+            ..Buffer::default()
+        });
+        self.buffers.len() - 1
+    }
+
+    //puts the file on screen away in its buffer and brings out buffer `index`
+    fn switch_buffer(&mut self, index: usize)
+    {
+        if index == self.buffer || index >= self.buffers.len()
+        {
+            return;
         }
-        Terminal::flush()
+        //a file left with unsaved changes keeps them safe in its swap file
+        if !self.document.is_saved()
+        {
+            self.document.write_swap().ok();
+        }
+        for i in [self.buffer, index]
+        {
+            let buffer = &mut self.buffers[i];
+            std::mem::swap(&mut self.document, &mut buffer.document);
+            std::mem::swap(&mut self.cell_index, &mut buffer.cell_index);
+            std::mem::swap(&mut self.offset, &mut buffer.offset);
+            std::mem::swap(&mut self.header, &mut buffer.header);
+            std::mem::swap(&mut self.search, &mut buffer.search);
+            std::mem::swap(&mut self.last_sort, &mut buffer.last_sort);
+            std::mem::swap(&mut self.filter, &mut buffer.filter);
+            std::mem::swap(&mut self.comparison, &mut buffer.comparison);
+            std::mem::swap(&mut self.totals, &mut buffer.totals);
+            std::mem::swap(&mut self.blanks, &mut buffer.blanks);
+            std::mem::swap(&mut self.duplicates, &mut buffer.duplicates);
+            std::mem::swap(&mut self.watch, &mut buffer.watch);
+        }
+        self.buffer = index;
+        self.status_message = StatusMessage::from(format!(
+            "File {}/{}: {}",
+            index + 1,
+            self.buffers.len(),
+            self.document.file_name.clone().unwrap_or_else(|| String::from("[No Name]"))
+        ));
     }
-    
-    fn save(&mut self) 
+
+    //opens a file, or switches to it when it is already open
+    fn open_buffer(&mut self, file_name: &str) -> Result<(), std::io::Error>
     {
-        if self.document.file_name.is_none() 
+        let open = (0..self.buffers.len()).find(|&i| self.buffer_document(i).file_name.as_deref() == Some(file_name));
+        let index = match open
+        {
+            Some(index) => index,
+            None => self.add_buffer(file_name, None)?,
+        };
+        self.switch_buffer(index);
+        if open.is_none()
         {
-            let new_name = self.prompt("Save as: ").unwrap_or(None);
-            if new_name.is_none()
+            self.offer_recovery()?;
+            if self.document.is_database()
             {
-                self.status_message = StatusMessage::from(format!("Not Saving"));
-                return;
+                self.pick_sheet()?;
             }
-            self.document.file_name = new_name;
         }
-        if self.document.save().is_ok()
+        Ok(())
+    }
+
+    //closes the file on screen, moving to the one before it. The last one stays open
+    fn close_buffer(&mut self)
+    {
+        if self.buffers.len() < 2
         {
-            self.status_message = StatusMessage::from(format!("Saved!"));
+            self.status_message = StatusMessage::from(String::from("Only one file is open"));
+            return;
         }
-        else 
+        let closing = self.buffer;
+        self.switch_buffer(if closing == 0 { 1 } else { closing - 1 });
+        self.buffers[closing].document.remove_swap();
+        self.buffers.remove(closing);
+        if self.buffer > closing
         {
-            self.status_message = StatusMessage::from(format!("Error: Unable to save changes"));
+            self.buffer -= 1;
         }
     }
 
-    fn process_keypress(&mut self) -> Result<(), std::io::Error> {
-        let pressed_key = Terminal::read_key()?;
-        match pressed_key {
-            Key::Ctrl('q') => {
-                if !self.document.is_saved(){
-                    self.status_message = StatusMessage::from(format!(
-                        "WARNING! File has unsaved changes. Press Ctrl-Q to quit"
-                    ));
-                    self.refresh_screen()?;
-                    let read = Terminal::read_key()?;
-                    if read == Key::Ctrl('q'){
-                        self.should_quit = true;
-                    }
-                    return Ok(());
-                }
-                else{
-                    self.should_quit = true;
-                }
-            }
-            //save file
-            Key::Ctrl('s') => {
-                self.save()
-            },
-            Key::Char(c) => {
-                //enter data into cell at current position
-                if c == '\n'{
-                    let content = self.prompt("INSERT: ").unwrap_or(None);
-                    if content.is_none(){
-                        self.status_message = StatusMessage::from(format!("Not Saved"));
-                    }
-                    else
-                    {
-                        self.document.last_action.cells_affected = self.document.get_highlight_cells();
-                        self.document.last_action.key = pressed_key;
-                        let pos = self.cell_index.clone();
-                        self.document.insert(pos,&content.unwrap());
-                    }
-                }
-                //get statstical infomation for highlighted cell
-                if c == '='{
-                    match self.document.table.calc_summary() {
-                       Err(e) => {
-                           self.status_message = StatusMessage::from(e);
-                        },
-                       Ok((n, sum, mean, std)) => {
-                           self.status_message = StatusMessage::from(format!(
-                               "Statitics for selected cells: n = {}, sum = {}, mean = {}, std = {}"
-                               ,n, sum as f32, mean as f32, std as f32
-                            ));
-                        },
-                    }
-                }
-                return Ok(());
-            }
-            //copy highlighted cell data
-            Key::Ctrl('c') => {
-                self.copy = self.document.copy().unwrap_or(Vec::new());
-                self.status_message=StatusMessage::from(String::from("Copied"));
-            }
-            //paste copied data to current position
-            Key::Ctrl('v') => {
-                if self.copy.is_empty(){
-                    self.status_message=StatusMessage::from(String::from("Error: Nothing to paste"));
-                    return Ok(());
-                } 
-                self.document.last_action.key = pressed_key;
-                self.document.paste(&self.cell_index,&self.copy.clone())?;
-                self.status_message=StatusMessage::from(String::from("Pasted"));
-            }
-            //copy and delete highlighted cell data
-            Key::Ctrl('x') => {
-                self.document.last_action.cells_affected = self.document.get_highlight_cells();
-                self.document.last_action.key = pressed_key;
-                self.copy = self.document.copy().unwrap_or(Vec::new());
-                self.document.delete();
-                self.status_message=StatusMessage::from(String::from("Cut"));
-            }
-            //delete contents from highlighted cells
-            Key::Delete =>{
-                self.document.last_action.key = pressed_key;
-                self.document.last_action.cells_affected = self.document.get_highlight_cells();
-                self.document.delete();
-                self.status_message=StatusMessage::from(String::from("Deleted."));
+    fn buffer_document(&self, index: usize) -> &Document
+    {
+        if index == self.buffer { &self.document } else { &self.buffers[index].document }
+    }
+
+    //how many open files have changes that haven't been saved
+    fn unsaved_buffers(&self) -> usize
+    {
+        (0..self.buffers.len()).filter(|&i| !self.buffer_document(i).is_saved()).count()
+    }
+
+
+    //opens a file on another thread, showing how many rows have been read so a big workbook doesn't
+    //look like a hang. Esc gives up on it, which ends in ErrorKind::Interrupted
+    fn load(terminal: &mut Terminal, file_name: &str, delimiter: Option<char>) -> Result<Document, std::io::Error>
+    {
+        let progress = Arc::new(Progress::default());
+        let (sender, receiver) = mpsc::channel();
+        {
+            let progress = Arc::clone(&progress);
+            let file_name = file_name.to_string();
+            thread::spawn(move || {
+                //nobody is waiting any more when the load was cancelled
+                sender.send(Document::open_with_progress(&file_name, delimiter, &progress)).ok();
+            });
+        }
+        let started = Instant::now();
+        loop
+        {
+            match receiver.recv_timeout(LOAD_REFRESH)
+            {
+                Ok(result) => return result.map(|mut document| {
+                    state::restore(&mut document);
+                    document
+                }),
+                Err(RecvTimeoutError::Disconnected) => return Err(std::io::Error::other("loading failed")),
+                Err(RecvTimeoutError::Timeout) => (),
             }
-            //undo the last edit to document
-            Key::Ctrl('z') => {
-                self.document.undo();
-                if self.document.last_action.key == pressed_key{
-                    self.status_message=StatusMessage::from(String::from("Cannot undo more than one event."));
-                    return Ok(());
+            while let Some(event) = terminal.poll_event()
+            {
+                if let Ok(Event::Key(Key::Esc | Key::Ctrl('q'))) = event
+                {
+                    //a workbook can't stop halfway through a sheet, so it finishes unseen
+                    progress.cancel();
+                    return Err(std::io::Error::new(std::io::ErrorKind::Interrupted, "loading was cancelled"));
                 }
-                self.status_message=StatusMessage::from(String::from("Undone."));
             }
-            //highlight cells to the given direction...
-            Key::CtrlLeft => {
-                self.status_message=StatusMessage::from(String::from("Selection mode."));
-                let mut count :usize= 1;
-                self.highlight_col(self.cell_index.x-count, self.cell_index.x);
-                self.refresh_screen()?;
-                let mut next_key = pressed_key;
-                while next_key == Key::CtrlLeft{
-                    count += 1;
-                    let startx = self.cell_index.x.saturating_sub(count);
-                    self.highlight_col(startx, self.cell_index.x);
-                    self.refresh_screen()?;
-                    next_key = Terminal::read_key()?;
+            //the rows read so far have no end to measure against, so a block sweeps back and forth
+            let steps = (started.elapsed().as_millis() / LOAD_REFRESH.as_millis()) as usize % (2 * LOAD_BAR_WIDTH - 2);
+            let at = if steps < LOAD_BAR_WIDTH { steps } else { 2 * LOAD_BAR_WIDTH - 2 - steps };
+            let bar: String = (0..LOAD_BAR_WIDTH).map(|i| if i == at { '█' } else { '░' }).collect();
+            Terminal::cursor_hide();
+            Terminal::cursor_position(&Position{x: 0, y: terminal.size().height as usize + 2});
+            Terminal::clear_current_line();
+            //a download counts bytes until it has them all and the rows can be read
+            let read = match (progress.rows(), progress.bytes())
+            {
+                (0, bytes) if bytes > 0 => format!("{} KB downloaded", bytes / 1024),
+                (rows, _) => format!("{} rows read", rows),
+            };
+            print!("Loading {} {} {} (Esc to cancel)", file_name, bar, read);
+            Terminal::flush()?;
+        }
+    }
+
+    fn refresh_screen(&self) -> Result<(), std::io::Error> {
+        Terminal::cursor_hide();
+        Terminal::cursor_position(&Position::default());
+        if self.should_quit {
+            Terminal::clear_screen();
+            self.redraw_table();
+        } else {
+            self.draw_table();
+            self.draw_cell_line();
+            self.draw_status_bar();
+            self.draw_message_bar();
+            Terminal::cursor_position(&Position {
+                x: self.cell_index.x.saturating_sub(self.offset.x),
+                y: self.cell_index.y.saturating_sub(self.offset.y),
+            });
+        }
+        Terminal::flush()
+    }
+    
+    fn save(&mut self) 
+    {
+        if self.to_stdout
+        {
+            match self.choose_saved_numbers()
+            {
+                Ok(true) => (),
+                Ok(false) => {
+                    self.status_message = StatusMessage::from(String::from("Not saved"));
+                    return;
                 }
-                self.status_message=StatusMessage::from(String::from("Stopped selection."));
-                return Ok(());
-            }
-            Key::CtrlRight => {
-                self.status_message=StatusMessage::from(String::from("Selection mode."));
-                let mut count :usize= 1;
-                self.highlight_col(self.cell_index.x, self.cell_index.x+count);
-                self.refresh_screen()?;
-                let mut next_key = pressed_key;
-                while next_key == Key::CtrlRight{
-                    count += 1;
-                    self.highlight_col(self.cell_index.x, self.cell_index.x+count);
-                    self.refresh_screen()?;
-                    next_key = Terminal::read_key()?;
+                Err(e) => {
+                    self.status_message = StatusMessage::from(save_error_message(&e));
+                    return;
                 }
-                self.status_message=StatusMessage::from(String::from("Stopped selection."));
-                return Ok(());
             }
-            Key::CtrlUp => {
-                self.status_message=StatusMessage::from(String::from("Selection mode."));
-                let mut count :usize= 1;
-                self.highlight_row(self.cell_index.y-count, self.cell_index.y);
-                self.refresh_screen()?;
-                let mut next_key = pressed_key;
-                while next_key == Key::CtrlUp{
-                    count += 1;
-                    let starty = self.cell_index.y.saturating_sub(count);
-                    self.highlight_row(starty, self.cell_index.y);
-                    self.refresh_screen()?;
-                    next_key = Terminal::read_key()?;
+            let message = match self.document.csv_bytes()
+            {
+                Ok(bytes) => {
+                    self.output = Some(bytes);
+                    self.document.mark_saved();
+                    String::from("Saved; the table goes to stdout on quitting")
                 }
-                self.status_message=StatusMessage::from(String::from("Stopped selection."));
-                return Ok(());
+                Err(e) => save_error_message(&e),
+            };
+            self.status_message = StatusMessage::from(message);
+            return;
+        }
+        if self.document.file_name.is_none() 
+        {
+            if let Err(e) = self.save_as()
+            {
+                self.status_message = StatusMessage::from(save_error_message(&e));
             }
-            Key::CtrlDown => {
-                self.status_message=StatusMessage::from(String::from("Selection mode."));
-                let mut next_key: Key = pressed_key;
-                let mut count :usize= 1;
-                while next_key == Key::CtrlDown{
-                    count += 1;
-                    self.highlight_row(self.cell_index.y, self.cell_index.y+count);
-                    self.refresh_screen()?;
-                    next_key = Terminal::read_key()?;
-                }
-                self.status_message=StatusMessage::from(String::from("Stopped selection."));
-                return Ok(());
+            return;
+        }
+        match self.choose_saved_numbers().and_then(|go_on| Ok(go_on && self.confirm_against_head()?))
+        {
+            Ok(true) => self.write_document(),
+            Ok(false) => self.status_message = StatusMessage::from(String::from("Not saved")),
+            Err(e) => self.status_message = StatusMessage::from(save_error_message(&e)),
+        }
+    }
+
+    //with number formats set, asks whether the file gets the numbers as they were typed or as their
+    //columns show them. The formatted ones are written into the cells, which Ctrl-z takes back.
+    //False when the save is called off
+    fn choose_saved_numbers(&mut self) -> Result<bool, std::io::Error>
+    {
+        if self.document.table.number_formats.is_empty()
+        {
+            return Ok(true);
+        }
+        self.status_message = StatusMessage::from(String::from("Save the numbers as typed (Return) or as formatted (f)? Esc cancels"));
+        self.refresh_screen()?;
+        match self.read_key()?
+        {
+            Key::Char('f') | Key::Char('F') => {
+                self.document.bake_number_formats(self.header);
+                self.report_refused();
+                Ok(true)
             }
-            //highlight all data from current positon to the end of document in the selected direction
-            Key::ShiftUp => {
-                self.document.highlight(&self.cell_index);
-                self.highlight_row(1,self.cell_index.y);
-                return Ok(());
+            Key::Char('\n') => Ok(true),
+            _ => Ok(false),
+        }
+    }
+
+    //when the file is delimited text committed to git, shows cell by cell how saving changes it
+    //from the last commit and asks whether to go ahead. True when there is nothing to ask
+    fn confirm_against_head(&mut self) -> Result<bool, std::io::Error>
+    {
+        let file_name = match &self.document.file_name
+        {
+            Some(file_name) if self.git_preview && self.document.swap_path().is_some()
+                && !json::is_any_json(file_name) && !latex::is_latex(file_name) => file_name.clone(),
+            _ => return Ok(true),
+        };
+        let committed = match git::head_version(&file_name)
+        {
+            Some(bytes) => compression::decompress(bytes, self.document.dialect.compression)?,
+            None => return Ok(true),
+        };
+        let committed = Document::from_bytes(&committed, Some(self.document.dialect.delimiter))?.rows();
+        let current = self.document.rows();
+        let difference = diff::diff(&committed, &current);
+        if difference.is_empty()
+        {
+            return Ok(true);
+        }
+        let lines = difference.describe(&committed, &current);
+        let title = String::from("Changes since the last commit");
+        let hint = "y: save, n/Esc: cancel, Up/Down: scroll";
+        let mut top = 0;
+        loop
+        {
+            self.refresh_screen()?;
+            let visible = self.draw_popup(&title, hint, &lines, &mut top, None)?;
+            let last_top = lines.len().saturating_sub(visible);
+            match self.read_key()?
+            {
+                Key::Char('y') | Key::Char('Y') => return Ok(true),
+                Key::Up => top = top.saturating_sub(1),
+                Key::Down => top = (top + 1).min(last_top),
+                Key::PageUp => top = top.saturating_sub(visible),
+                Key::PageDown => top = (top + visible).min(last_top),
+                Key::Char('n') | Key::Char('N') | Key::Esc | Key::Ctrl('q') => return Ok(false),
+                _ => (),
             }
-            Key::ShiftDown => {
-                self.document.highlight(&self.cell_index);
-                self.highlight_row(self.cell_index.y,self.document.table.num_rows()+1);
+        }
+    }
+
+    //asks for a new name to save under, starting from the current one
+    fn save_as(&mut self) -> Result<(), std::io::Error>
+    {
+        //a downloaded table starts from the name it had on the web
+        let current = self.document.file_name.clone()
+            .or_else(|| self.document.source.as_deref().map(|url| fetch::file_name(url).to_string()))
+            .unwrap_or_default();
+        match self.prompt_with("Save as: ", current)?
+        {
+            Some(new_name) if !new_name.trim().is_empty() => self.save_to(new_name.trim())?,
+            _ => self.status_message = StatusMessage::from(String::from("Not Saving")),
+        }
+        Ok(())
+    }
+
+    //saves under a new name, whose extension picks the format (.xlsx, .ods, .json, .db or delimited
+    //text) and, for .csv, .tsv and .psv, the delimiter, with .gz or .zst on the end compressing it. The file it came from is left as it was, and
+    //the new one is what Ctrl-s writes to from then on
+    fn save_to(&mut self, file_name: &str) -> Result<(), std::io::Error>
+    {
+        if self.document.file_name.as_deref() != Some(file_name) && Path::new(file_name).exists()
+        {
+            self.status_message = StatusMessage::from(format!("{} exists. Overwrite it? (y/n)", file_name));
+            self.refresh_screen()?;
+            if !matches!(self.read_key()?, Key::Char('y') | Key::Char('Y'))
+            {
+                self.status_message = StatusMessage::from(String::from("Not Saving"));
                 return Ok(());
             }
-            Key::ShiftLeft => {
-                self.document.highlight(&self.cell_index);
-                self.highlight_col(1,self.cell_index.x);
-                return Ok(());
+        }
+        if !self.choose_saved_numbers()?
+        {
+            self.status_message = StatusMessage::from(String::from("Not Saving"));
+            return Ok(());
+        }
+        let previous_name = self.document.file_name.replace(file_name.to_string());
+        let previous_delimiter = self.document.dialect.delimiter;
+        let previous_compression = self.document.dialect.compression;
+        if let Some(delimiter) = document::extension_delimiter(file_name).or_else(|| compression::inner_name(file_name).ends_with(".csv").then_some(','))
+        {
+            self.document.dialect.delimiter = delimiter;
+        }
+        self.document.dialect.compression = Compression::from_extension(file_name);
+        self.document.backup = self.backup;
+        match self.document.save()
+        {
+            Ok(()) => {
+                //a file that couldn't be written is left behind, but --readonly still holds
+                self.document.read_only = self.read_only;
+                self.status_message = StatusMessage::from(format!("Saved as {}", file_name));
             }
-            Key::ShiftRight => {
-                self.document.highlight(&self.cell_index);
-                self.highlight_col(self.cell_index.x,self.document.table.num_cols()+1);
-                return Ok(());
+            Err(e) => {
+                self.document.file_name = previous_name;
+                self.document.dialect.delimiter = previous_delimiter;
+                self.document.dialect.compression = previous_compression;
+                self.status_message = StatusMessage::from(save_error_message(&e));
             }
-            Key::Up
-            | Key::Down
-            | Key::Left
-            | Key::Right
-            | Key::PageUp
-            | Key::PageDown
-            | Key::End
-            | Key::Home => self.move_position(pressed_key),
-            _ => (),
         }
+        Ok(())
+    }
 
-        //updating document information after actions
-        let num_rows = self.document.table.num_rows();
-        let num_cols = self.document.table.num_cols();        
-        
-        if self.cell_index.y > num_rows{
-            self.document.insert_newrow(&self.cell_index);
+    fn write_document(&mut self)
+    {
+        self.document.backup = self.backup;
+        match self.document.save()
+        {
+            //locked rows move with sorting and inserting, so they are remembered as saved
+            Ok(()) if !self.document.table.locked_rows.is_empty() => self.status_message = StatusMessage::from(self.remember(String::from("Saved!"))),
+            Ok(()) => self.status_message = StatusMessage::from(String::from("Saved!")),
+            Err(e) => self.status_message = StatusMessage::from(save_error_message(&e)),
         }
-        if self.cell_index.x > num_cols{
-            self.document.insert_newcol(&self.cell_index);
+        //our own save isn't a change to offer to reload
+        if let Some(watch) = &mut self.watch
+        {
+            watch.settle();
         }
+    }
 
-        //if trying to escape the boundaries of a page, highlight the cells for that row/column
-        if self.cell_index.y == 0{
-            self.cell_index.y+=1;
-            self.document.highlight(&self.cell_index);
-            self.highlight_row(1,self.document.table.num_rows()+1);
-            return Ok(());
-        }
-        if self.cell_index.x == 0{
-            self.cell_index.x+=1;
-            self.document.highlight(&self.cell_index);
-            self.highlight_col(1,self.document.table.num_cols()+1);
-            return Ok(());
+    //keys queued by a macro replay are consumed before reading from the terminal
+    fn read_event(&mut self) -> Result<Event, std::io::Error>
+    {
+        if let Some(key) = self.pending_keys.pop_front()
+        {
+            return Ok(Event::Key(key));
         }
-        self.document.highlight(&self.cell_index);
-        self.scroll();
-        Ok(())
+        let event = self.terminal.read_event()?;
+        Ok(self.record(event))
     }
 
-    //highight group of cells in the y direction
-    fn highlight_row(&mut self,starty: usize, endy: usize){
-        let mut pos: Position;
-        let mut x: usize;
-        if starty < 1 && endy > self.document.table.num_rows(){
-            return;
+    //like read_event, but gives up with None when nothing comes within `timeout`
+    fn read_event_timeout(&mut self, timeout: Duration) -> Result<Option<Event>, std::io::Error>
+    {
+        if let Some(key) = self.pending_keys.pop_front()
+        {
+            return Ok(Some(Event::Key(key)));
         }
-        for y in starty..endy{
-            x = self.cell_index.x;
-            pos = Position{x,y};
-            self.document.multi_highlight(&pos);
+        match self.terminal.read_event_timeout(timeout)
+        {
+            Some(event) => Ok(Some(self.record(event?))),
+            None => Ok(None),
         }
     }
-    //highlight group of cells in the x direction
-    fn highlight_col(&mut self, startx: usize, endx: usize){
-        let mut pos: Position;
-        let mut y: usize;
-        if startx < 1 || endx > self.document.table.num_cols()+1{
-            return;
-        }
-        for x in startx..endx{
-            y = self.cell_index.y;
-            pos= Position{x,y};
-            self.document.multi_highlight(&pos);
+
+    //adds a key pressed while a macro is being recorded to it
+    fn record(&mut self, event: Event) -> Event
+    {
+        if let (Some((_, recording)), Event::Key(key)) = (&mut self.recording, &event)
+        {
+            recording.push(*key);
         }
+        event
     }
-    //changing current position and adjusting document crop to fit in terminal
-    fn scroll(&mut self){
-        let Position {x , y} = self.cell_index;
-        let width = self.terminal.size().width as usize;
-        let height = (self.terminal.size().height as usize)-1;
-        let offset = &mut self.offset;
-        //y is straight forward, one row for one terminal pixel
-        if y < offset.y{
-            offset.y = y;
+
+    //the next key press, skipping mouse events
+    fn read_key(&mut self) -> Result<Key, std::io::Error>
+    {
+        loop
+        {
+            if let Event::Key(key) = self.read_event()?
+            {
+                return Ok(key);
+            }
         }
-        else if y >= offset.y.saturating_add(height){
-            offset.y = y.saturating_sub(height).saturating_add(1);
+    }
+
+    //starts recording into the register named by the next key, or stops the recording under way
+    fn toggle_recording(&mut self) -> Result<(), std::io::Error>
+    {
+        if let Some((register, mut keys)) = self.recording.take()
+        {
+            //drop the key that stopped the recording
+            keys.pop();
+            self.status_message = StatusMessage::from(format!("Recorded {} keys into @{}.", keys.len(), register));
+            self.macros.insert(register, keys);
+            self.last_macro = Some(register);
+            return Ok(());
+        }
+        self.status_message = StatusMessage::from(String::from("Record a macro into register (a-z):"));
+        self.refresh_screen()?;
+        match self.read_key()?
+        {
+            Key::Char(register) if register.is_ascii_alphanumeric() => {
+                self.recording = Some((register, Vec::new()));
+                self.status_message = StatusMessage::from(format!("Recording @{}... Alt-q to stop.", register));
+            }
+            _ => self.status_message = StatusMessage::from(String::from("Not recording.")),
+        }
+        Ok(())
+    }
+
+    //replays the register named by the next key (@ for the last one) a number of times, or given *,
+    //down to the last row
+    fn replay_macro(&mut self) -> Result<(), std::io::Error>
+    {
+        if let Some((_, keys)) = &mut self.recording
+        {
+            keys.pop();
+            self.status_message = StatusMessage::from(String::from("Cannot replay a macro while recording."));
+            return Ok(());
+        }
+        if self.macros.is_empty()
+        {
+            self.status_message = StatusMessage::from(String::from("Error: No macro recorded"));
+            return Ok(());
+        }
+        self.status_message = StatusMessage::from(String::from("Replay register (a-z, @ for the last one):"));
+        self.refresh_screen()?;
+        let register = match self.read_key()?
+        {
+            Key::Char('@') => self.last_macro,
+            Key::Char(register) => Some(register),
+            _ => None,
+        };
+        let Some((register, keys)) = register.and_then(|register| Some((register, self.macros.get(&register)?.clone()))) else {
+            self.status_message = StatusMessage::from(String::from("Error: Nothing recorded in that register"));
+            return Ok(());
+        };
+        self.last_macro = Some(register);
+        let times = match self.prompt("Replay how many times (* for each row down to the last): ")?
+        {
+            None => Some(1),
+            Some(times) if times.trim() == "*" => None,
+            Some(times) => match times.trim().parse::<usize>() {
+                Ok(times) => Some(times),
+                Err(_) => {
+                    self.status_message = StatusMessage::from(format!("Error: invalid count: {}", times));
+                    return Ok(());
+                }
+            },
+        };
+        let times = match times
+        {
+            Some(times) => {
+                for _ in 0..times
+                {
+                    self.run_keys(&keys)?;
+                }
+                times
+            }
+            None => self.replay_to_end(&keys)?,
+        };
+        self.status_message = StatusMessage::from(format!("Replayed @{} {} times.", register, times));
+        Ok(())
+    }
+
+    //feeds keys through as if they were typed
+    fn run_keys(&mut self, keys: &[Key]) -> Result<(), std::io::Error>
+    {
+        self.pending_keys.extend(keys.iter().copied());
+        while !self.pending_keys.is_empty() && !self.should_quit
+        {
+            self.process_keypress()?;
+        }
+        Ok(())
+    }
+
+    //replays the keys until a run leaves the cursor where it started, as it does at the last row
+    //since the table isn't grown meanwhile. Returns how many runs it took
+    fn replay_to_end(&mut self, keys: &[Key]) -> Result<usize, std::io::Error>
+    {
+        let edge_behavior = std::mem::replace(&mut self.edge_behavior, EdgeBehavior::Stop);
+        //a macro that keeps adding rows would otherwise never reach the end
+        let limit = self.document.table.num_rows() * self.document.table.num_cols();
+        let mut times = 0;
+        let result = loop
+        {
+            let before = self.cell_index.clone();
+            if let Err(e) = self.run_keys(keys)
+            {
+                break Err(e);
+            }
+            times += 1;
+            if self.cell_index == before || self.should_quit || times >= limit
+            {
+                break Ok(times);
+            }
+        };
+        self.edge_behavior = edge_behavior;
+        result
+    }
+
+    fn run_command_line(&mut self, line: &str)
+    {
+        let line = line.trim();
+        //:%s/find/replace/ works on every cell, :s/find/replace/ on the current one
+        if let Some((whole, pattern)) = line.strip_prefix("%s").map(|p| (true, p)).or(line.strip_prefix('s').map(|p| (false, p))) {
+            if pattern.starts_with(|c: char| !c.is_alphanumeric() && !c.is_whitespace()) {
+                self.substitute(pattern, whole);
+                return;
+            }
+        }
+        let (name, argument) = match line.split_once(' ') {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (line, ""),
+        };
+        let message = match name {
+            "impute" | "datefmt" | "encoding" | "transform" | "tr" | "split" | "merge" | "script" | "join" | "append" | "transpose" | "dedup" | "w" | "wq" | "x"
+                if self.document.read_only => String::from(VIEW_ONLY_MESSAGE),
+            "saveas" | "sav" => {
+                let result = if argument.is_empty() { self.save_as() } else { self.save_to(argument) };
+                if let Err(e) = result {
+                    self.status_message = StatusMessage::from(save_error_message(&e));
+                }
+                return;
+            }
+            "impute" => {
+                if argument.is_empty() {
+                    String::from("Usage: :impute <value>")
+                } else {
+                    let count = self.document.impute(argument);
+                    format!("Filled {} blank cells with {}", count, argument)
+                }
+            }
+            "datefmt" => {
+                if argument.is_empty() {
+                    String::from("Usage: :datefmt <pattern>, e.g. :datefmt %d/%m/%Y")
+                } else {
+                    let count = self.document.format_dates(argument, self.cell_index.x);
+                    format!("Reformatted {} dates as {}", count, argument)
+                }
+            }
+            "diff" => {
+                if argument.is_empty() {
+                    String::from("Usage: :diff <file>")
+                } else {
+                    match Document::open(argument) {
+                        Ok(other) => {
+                            self.comparison = Some(Comparison {
+                                file_name: argument.to_string(),
+                                rows: other.rows(),
+                                diff: Diff::default(),
+                            });
+                            self.refresh_comparison();
+                            self.jump_to_difference(true);
+                            return;
+                        }
+                        Err(e) => format!("Error: Unable to open {}: {}", argument, e),
+                    }
+                }
+            }
+            "encoding" => {
+                if argument.is_empty() {
+                    format!("Saving as {}. Usage: :encoding <name>, e.g. :encoding utf-8", self.document.dialect.encoding)
+                } else {
+                    match self.document.set_encoding(argument) {
+                        Some(name) => format!("Will save as {}", name),
+                        None => format!("Unknown encoding: {}", argument),
+                    }
+                }
+            }
+            "validate" => self.set_rule(self.cell_index.x, argument),
+            "dups" | "duplicates" => {
+                self.show_rows("duplicates", RowTest::Duplicates);
+                return;
+            }
+            "format" => {
+                self.format_column(self.cell_index.x, argument);
+                return;
+            }
+            "align" => {
+                match argument {
+                    "auto" | "automatic" => self.align_column(self.cell_index.x, None),
+                    name => match Align::parse(name) {
+                        Some(align) => self.align_column(self.cell_index.x, Some(align)),
+                        None => self.status_message = StatusMessage::from(String::from("Usage: :align <left|center|right|auto>")),
+                    },
+                }
+                return;
+            }
+            "export" => {
+                self.export_selection(argument).ok();
+                return;
+            }
+            "stat" => {
+                match Statistic::parse(argument) {
+                    Ok(statistic) => {
+                        self.show_stats(Some(statistic)).ok();
+                        return;
+                    }
+                    Err(e) => format!("Err: {}", e),
+                }
+            }
+            "transform" | "tr" => {
+                self.transform_cells(argument).ok();
+                return;
+            }
+            "theme" => match theme::named(argument)
+            {
+                Some(colors) => {
+                    self.colors = colors;
+                    self.redraw_table();
+                    format!("Theme {}. theme = \"{}\" in the config keeps it.", argument, argument)
+                }
+                None => format!("Themes: {}", theme::NAMES.join(", ")),
+            },
+            "blanks" => {
+                self.blank_report().ok();
+                return;
+            }
+            "pivot" => {
+                self.pivot(argument);
+                return;
+            }
+            "describe" => {
+                self.describe();
+                return;
+            }
+            "view" => {
+                self.view_cell().ok();
+                return;
+            }
+            "editor" => {
+                self.edit_in_editor().ok();
+                return;
+            }
+            "split" if argument.is_empty() => String::from("Usage: :split SEPARATOR|space|tab|WIDTHS"),
+            "split" => {
+                self.split_column(argument);
+                return;
+            }
+            "merge" => {
+                self.merge_columns(if argument.is_empty() { " " } else { argument });
+                return;
+            }
+            "script" if argument.is_empty() => String::from("Usage: :script FILE"),
+            "script" => {
+                self.run_script(Path::new(argument));
+                return;
+            }
+            "append" => {
+                self.append(argument).ok();
+                return;
+            }
+            "reload" => {
+                self.reload().ok();
+                return;
+            }
+            "join" => {
+                self.join(argument).ok();
+                return;
+            }
+            "transpose" => {
+                self.transpose();
+                return;
+            }
+            "history" => {
+                self.cell_history().ok();
+                return;
+            }
+            "sort" => {
+                self.sort_by(argument).ok();
+                return;
+            }
+            "freq" => {
+                self.frequencies(argument).ok();
+                return;
+            }
+            "pick" => {
+                self.pick_values().ok();
+                return;
+            }
+            "hide" => match self.named_columns(argument) {
+                Ok(columns) => self.hide_columns(&columns),
+                Err(e) => e,
+            },
+            "unhide" if argument.is_empty() => self.show_columns(None),
+            "unhide" => match self.named_columns(argument) {
+                Ok(columns) => self.show_columns(Some(&columns)),
+                Err(e) => e,
+            },
+            "lock" | "unlock" => {
+                let lock = name == "lock";
+                match argument
+                {
+                    "" if !lock => {
+                        let table = &mut self.document.table;
+                        table.locked_rows.clear();
+                        table.locked_cols.clear();
+                        self.remember(String::from("Unlocked every row and column."))
+                    }
+                    "header" => self.lock(true, &[1], lock),
+                    "row" => {
+                        let (top, bottom) = self.document.selected_rows(self.cell_index.y);
+                        self.lock(true, &(top..bottom+1).collect::<Vec<_>>(), lock)
+                    }
+                    columns => match self.named_columns(columns) {
+                        Ok(columns) => self.lock(false, &columns, lock),
+                        Err(e) => e,
+                    },
+                }
+            }
+            "highlight" | "hl" if argument.is_empty() => {
+                self.edit_highlights().ok();
+                return;
+            }
+            "highlight" | "hl" => match self.parse_highlight(argument) {
+                Ok(highlight) => {
+                    self.document.table.highlights.push(highlight);
+                    self.remember(String::from("Highlighting the cells that pass it."))
+                }
+                Err(e) => format!("Err: {}", e),
+            },
+            "snapshot" | "snap" if argument.is_empty() => {
+                let name = format!("snapshot {}", self.document.checkpoints().len() + 1);
+                self.take_snapshot(&name);
+                return;
+            }
+            "snapshot" | "snap" => {
+                self.take_snapshot(argument);
+                return;
+            }
+            "restore" if self.document.read_only => String::from(VIEW_ONLY_MESSAGE),
+            "restore" => {
+                self.restore_snapshot(argument).ok();
+                return;
+            }
+            "nohighlight" | "nohl" => {
+                self.document.table.highlights.clear();
+                self.remember(String::from("Took off every highlight."))
+            }
+            "locks" => match argument {
+                "off" => {
+                    self.document.override_locks = true;
+                    String::from("Locked cells can be changed until :locks on.")
+                }
+                "on" => {
+                    self.document.override_locks = false;
+                    String::from("Locked cells are protected again.")
+                }
+                _ => String::from("Usage: :locks <on|off>"),
+            },
+            "dedup" => {
+                self.delete_duplicates();
+                return;
+            }
+            "total" => match Aggregate::parse(argument) {
+                Some(aggregate) => {
+                    self.totals.choose(self.cell_index.x, aggregate);
+                    self.show_totals = true;
+                    format!("Column {} totals its {}", self.column_label(self.cell_index.x), aggregate.name())
+                }
+                None => String::from("Usage: :total <sum|mean|count|min|max>"),
+            },
+            "w" => {
+                self.save();
+                return;
+            }
+            "q" if self.unsaved_buffers() > 0 => String::from("No write since last change (:q! quits anyway)"),
+            "q" | "q!" => {
+                self.should_quit = true;
+                return;
+            }
+            "wq" | "x" => {
+                self.save();
+                self.should_quit = self.document.is_saved();
+                return;
+            }
+            "e" | "edit" | "open" => {
+                if argument.is_empty() {
+                    String::from("Usage: :e <file>")
+                } else {
+                    match self.open_buffer(argument) {
+                        Ok(()) => return,
+                        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => format!("Stopped loading {}", argument),
+                        Err(e) => format!("Err: Couldn't open {}: {}", argument, e),
+                    }
+                }
+            }
+            "bn" | "bnext" => {
+                self.switch_buffer((self.buffer + 1) % self.buffers.len());
+                return;
+            }
+            "bp" | "bprevious" => {
+                self.switch_buffer((self.buffer + self.buffers.len() - 1) % self.buffers.len());
+                return;
+            }
+            "b" | "buffer" => match argument.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= self.buffers.len() => {
+                    self.switch_buffer(n - 1);
+                    return;
+                }
+                _ => format!("Usage: :b <1-{}>", self.buffers.len()),
+            },
+            "bd" | "close" if !self.document.is_saved() => String::from("The file has unsaved changes; save it first"),
+            "bd" | "close" => {
+                self.close_buffer();
+                return;
+            }
+            "sheet" => match self.document.find_sheet(argument) {
+                Some(index) => {
+                    self.open_sheet(index);
+                    return;
+                }
+                None if self.document.sheet_count() == 0 => String::from("Only workbooks and databases have sheets"),
+                None => format!("No sheet named {}", argument),
+            },
+            _ => format!("Unknown command: {}", name),
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+
+    //replaces text like vim's :s, where `pattern` is e.g. /find/replace/ (any punctuation can stand
+    //in for the slashes), in every cell or only the current one
+    fn substitute(&mut self, pattern: &str, whole: bool)
+    {
+        if self.document.read_only
+        {
+            self.status_message = StatusMessage::from(String::from(VIEW_ONLY_MESSAGE));
+            return;
+        }
+        let mut chars = pattern.chars();
+        let separator = chars.next().unwrap_or('/');
+        let parts: Vec<&str> = chars.as_str().split(separator).collect();
+        let (Some(&query), Some(&replacement)) = (parts.first(), parts.get(1)) else {
+            self.status_message = StatusMessage::from(String::from("Usage: :%s/find/replace/"));
+            return;
+        };
+        if query.is_empty()
+        {
+            self.status_message = StatusMessage::from(String::from("Usage: :%s/find/replace/"));
+            return;
+        }
+        self.document.find(query);
+        let positions = if whole
+        {
+            self.document.matches().to_vec()
+        }
+        else
+        {
+            vec![(self.cell_index.x, self.cell_index.y)]
+        };
+        let replaced = self.document.replace(query, replacement, &positions);
+        //the matches were only needed to know where to replace, so the last search shows again
+        match self.search.clone()
+        {
+            Some(search) => { self.document.find(&search); }
+            None => self.document.clear_matches(),
+        }
+        self.status_message = StatusMessage::from(format!("Replaced \"{}\" in {} cells", query, replaced));
+    }
+
+    fn pick_sheet(&mut self) -> Result<(), std::io::Error>
+    {
+        let names = self.document.sheet_names();
+        if names.len() < 2
+        {
+            self.status_message = StatusMessage::from(String::from("No other sheets to switch to"));
+            return Ok(());
+        }
+        let title = if self.document.is_database() { "Tables" } else { "Sheets" };
+        if let Some(index) = self.pick(title, &names, self.document.sheet_index())?
+        {
+            self.open_sheet(index);
+        }
+        Ok(())
+    }
+
+    fn open_sheet(&mut self, index: usize)
+    {
+        if self.document.sheet_count() < 2
+        {
+            self.status_message = StatusMessage::from(String::from("No other sheets to switch to"));
+            return;
+        }
+        if self.document.switch_sheet(index)
+        {
+            self.cell_index = Position {x: 1, y: 2};
+            self.offset = Position {x: 0, y: 1};
+            self.search = None;
+            self.last_sort = None;
+            self.filter = None;
+            self.document.highlight(&self.cell_index);
+        }
+        self.status_message = StatusMessage::from(format!(
+            "Sheet {}/{}: {}",
+            self.document.sheet_index() + 1,
+            self.document.sheet_count(),
+            self.document.sheet_name().unwrap_or_default()
+        ));
+    }
+
+    //starts a new search and jumps to the first match after the cursor
+    fn find(&mut self, query: String)
+    {
+        let count = self.document.find(&query);
+        if count == 0
+        {
+            self.search = None;
+            self.status_message = StatusMessage::from(format!("No matches for \"{}\"", query));
+            return;
+        }
+        self.search = Some(query);
+        self.jump_to_match(true);
+    }
+
+    //compares the document again, since it may have been edited
+    fn refresh_comparison(&mut self)
+    {
+        if let Some(comparison) = &mut self.comparison
+        {
+            comparison.diff = diff::diff(&comparison.rows, &self.document.rows());
+        }
+    }
+
+    //moves to the next (or previous) difference from the file being compared with
+    fn jump_to_difference(&mut self, forward: bool)
+    {
+        let Some(comparison) = &self.comparison else { return };
+        let positions = comparison.diff.positions(self.document.table.num_rows());
+        if positions.is_empty()
+        {
+            self.status_message = StatusMessage::from(format!("No differences from {}", comparison.file_name));
+            return;
+        }
+        let here = (self.cell_index.y, self.cell_index.x);
+        let found = if forward
+        {
+            positions.iter().position(|&(x, y)| (y, x) > here).unwrap_or(0)
+        }
+        else
+        {
+            positions.iter().rposition(|&(x, y)| (y, x) < here).unwrap_or(positions.len() - 1)
+        };
+        let (x, y) = positions[found];
+        let removed = comparison.diff.removed_above(y)
+            + if y == self.document.table.num_rows() { comparison.diff.removed_above(y + 1) } else { 0 };
+        let detail = match comparison.diff.change(x, y)
+        {
+            Some(Change::Changed(before)) => format!("was \"{}\"", before),
+            Some(Change::Added) => String::from("added"),
+            None if removed > 0 => format!("{} rows removed here", removed),
+            None => String::new(),
+        };
+        self.status_message = StatusMessage::from(format!(
+            "Difference {} of {} from {}: {} (]/[: next/previous, Esc: stop comparing)",
+            found + 1, positions.len(), comparison.file_name, detail
+        ));
+        self.jump_to(Position{x, y});
+    }
+
+    //sets the rule for column `x` from text like `numeric`, or with empty text removes it, saying
+    //how many cells break it
+    fn set_rule(&mut self, x: usize, text: &str) -> String
+    {
+        let column = self.column_label(x).trim_end().to_string();
+        if text.trim().is_empty()
+        {
+            self.document.set_rule(x, None);
+            return format!("Column {} takes anything", column);
+        }
+        match Rule::parse(text)
+        {
+            Ok(rule) =>
+            {
+                let description = rule.describe();
+                self.document.set_rule(x, Some(rule));
+                let count = self.violations().iter().filter(|&&(col, _)| col == x).count();
+                format!("Column {}: {}; {} cells break it (Alt-e: next)", column, description, count)
+            }
+            Err(e) => format!("Err: {}", e),
+        }
+    }
+
+    //cells below the header that break their column's rule
+    fn violations(&self) -> Vec<(usize, usize)>
+    {
+        self.document.rule_violations(if self.header { 2 } else { 1 })
+    }
+
+    fn breaks_rule(&self, x: usize, y: usize) -> bool
+    {
+        !(self.header && y == 1) && self.document.breaks_rule(x, y)
+    }
+
+    //moves to the next cell after the cursor that breaks its column's rule, going round to the top
+    fn jump_to_violation(&mut self)
+    {
+        let violations = self.violations();
+        if violations.is_empty()
+        {
+            let message = if self.document.table.rules.is_empty() { "No column rules (Alt-v sets one)" } else { "Every cell keeps to its column's rule" };
+            self.status_message = StatusMessage::from(String::from(message));
+            return;
+        }
+        let here = (self.cell_index.y, self.cell_index.x);
+        let found = violations.iter().position(|&(x, y)| (y, x) > here).unwrap_or(0);
+        let (x, y) = violations[found];
+        let rule = self.document.table.rules.get(&x).map(Rule::describe).unwrap_or_default();
+        self.status_message = StatusMessage::from(format!("{} of {} cells breaking their column's rule ({}: {})", found + 1, violations.len(), self.column_label(x).trim_end(), rule));
+        self.jump_to(Position{x, y});
+    }
+
+    //shows only the rows whose column passes a test like `B contains error` or `C > 100`
+    fn apply_filter(&mut self, text: &str)
+    {
+        let names: Vec<String> = if self.header
+        {
+            (1..self.document.table.num_cols()+1).map(|x| self.column_label(x)).collect()
+        }
+        else
+        {
+            Vec::new()
+        };
+        let predicate = match Predicate::parse(text, &names)
+        {
+            Ok(Predicate { column: Some(x), .. }) if x > self.document.table.num_cols() => {
+                self.status_message = StatusMessage::from(format!("Error: no column {}", formula::column_name(x)));
+                return;
+            }
+            Ok(predicate) => predicate,
+            Err(e) => {
+                self.status_message = StatusMessage::from(format!("Error: {}", e));
+                return;
+            }
+        };
+        self.show_rows(text, RowTest::Predicate(predicate));
+    }
+
+    //filters the table down to the rows that pass `test`, named `text` in the status bar
+    fn show_rows(&mut self, text: &str, test: RowTest)
+    {
+        let rows = self.filtered_rows(&test);
+        let shown = rows.iter().filter(|&&y| !(self.header && y == 1)).count();
+        if shown == 0
+        {
+            self.status_message = StatusMessage::from(format!("No rows match {}", text));
+            return;
+        }
+        self.filter = Some(Filter{text: text.to_string(), test, rows});
+        self.keep_cursor_shown();
+        self.offset.y = 1;
+        self.status_message = StatusMessage::from(format!(
+            "Showing {} of {} rows. Alt-l shows them all again.", shown, self.document.table.num_rows()
+        ));
+    }
+
+    fn filtered_rows(&self, test: &RowTest) -> Vec<usize>
+    {
+        let repeated = match test
+        {
+            RowTest::Duplicates => self.document.repeated_rows(self.first_data_row()),
+            RowTest::Predicate(_) | RowTest::Values(_) => BTreeMap::new(),
+        };
+        (1..self.document.table.num_rows()+1)
+            .filter(|&y| (self.header && y == 1) || match test
+            {
+                RowTest::Predicate(predicate) => match predicate.column
+                {
+                    Some(x) => predicate.matches(&self.document.value(x, y).unwrap_or_default()),
+                    None => (1..self.document.table.num_cols()+1).any(|x| predicate.matches(&self.document.value(x, y).unwrap_or_default())),
+                },
+                RowTest::Duplicates => repeated.contains_key(&y),
+                RowTest::Values(picked) => picked.iter()
+                    .all(|(&x, values)| values.contains(&self.document.value(x, y).unwrap_or_default())),
+            })
+            .collect()
+    }
+
+    //the first row holding data rather than column names
+    fn first_data_row(&self) -> usize
+    {
+        if self.header { 2 } else { 1 }
+    }
+
+    //highlights the rows that repeat another row, or turns the highlighting off
+    fn toggle_duplicates(&mut self)
+    {
+        if self.duplicates.take().is_some()
+        {
+            self.status_message = StatusMessage::from(String::from("Stopped highlighting duplicate rows."));
+            return;
+        }
+        let repeated = self.document.repeated_rows(self.first_data_row());
+        let copies = repeated.iter().filter(|(y, original)| y != original).count();
+        self.status_message = StatusMessage::from(if copies == 0
+        {
+            String::from("No duplicate rows.")
+        }
+        else
+        {
+            format!("{} rows repeat an earlier row. :dedup deletes them, :dups shows only duplicates.", copies)
+        });
+        self.duplicates = Some(Duplicates{worked_out: (self.document.revision(), self.header), rows: repeated.into_keys().collect()});
+    }
+
+    //works the highlighted duplicates out again if the document has changed
+    fn refresh_duplicates(&mut self)
+    {
+        let worked_out = (self.document.revision(), self.header);
+        if self.duplicates.as_ref().is_none_or(|duplicates| duplicates.worked_out == worked_out)
+        {
+            return;
+        }
+        let rows = self.document.repeated_rows(self.first_data_row()).into_keys().collect();
+        self.duplicates = Some(Duplicates{worked_out, rows});
+    }
+
+    fn is_duplicate(&self, y: usize) -> bool
+    {
+        self.duplicates.as_ref().is_some_and(|duplicates| duplicates.rows.contains(&y))
+    }
+
+    //deletes the rows that repeat an earlier row, keeping the first of each
+    fn delete_duplicates(&mut self)
+    {
+        let count = self.document.remove_repeated_rows(self.first_data_row());
+        self.cell_index.y = self.cell_index.y.min(self.document.table.num_rows());
+        self.refilter();
+        self.keep_cursor_shown();
+        self.status_message = StatusMessage::from(if count == 0
+        {
+            String::from("No duplicate rows.")
+        }
+        else
+        {
+            format!("Deleted {} duplicate rows, keeping the first of each.", count)
+        });
+    }
+
+    //tests every row again, after rows were reordered or changed wholesale
+    fn refilter(&mut self)
+    {
+        let Some(filter) = &self.filter else { return };
+        let rows = self.filtered_rows(&filter.test);
+        if let Some(filter) = &mut self.filter
+        {
+            filter.rows = rows;
+        }
+        self.keep_cursor_shown();
+    }
+
+    //keeps the filter's rows pointing at the same document rows when one is inserted or deleted at `y`.
+    //an inserted row stays on screen until the next refilter, so it can be filled in
+    fn shift_filter(&mut self, y: usize, inserted: bool)
+    {
+        let Some(filter) = &mut self.filter else { return };
+        if inserted
+        {
+            filter.rows.iter_mut().filter(|row| **row >= y).for_each(|row| *row += 1);
+            let at = filter.rows.partition_point(|&row| row < y);
+            filter.rows.insert(at, y);
+        }
+        else
+        {
+            filter.rows.retain(|&row| row != y);
+            filter.rows.iter_mut().filter(|row| **row > y).for_each(|row| *row -= 1);
+        }
+        self.keep_cursor_shown();
+    }
+
+    fn is_shown(&self, y: usize) -> bool
+    {
+        self.filter.as_ref().is_none_or(|filter| filter.rows.binary_search(&y).is_ok())
+    }
+
+    //the row `steps` shown rows away from `y`, stopping at the first and last; None without a filter
+    fn shown_row_by(&self, y: usize, steps: isize) -> Option<usize>
+    {
+        let rows = &self.filter.as_ref()?.rows;
+        let at = rows.partition_point(|&row| row < y) as isize;
+        let target = (at + steps).clamp(0, rows.len() as isize - 1);
+        rows.get(target as usize).copied()
+    }
+
+    //moves the cursor off a row the filter hides, to the next shown row (or the last one)
+    fn keep_cursor_shown(&mut self)
+    {
+        let Some(filter) = &self.filter else { return };
+        if filter.rows.binary_search(&self.cell_index.y).is_ok()
+        {
+            return;
+        }
+        let at = filter.rows.partition_point(|&row| row < self.cell_index.y);
+        if let Some(&row) = filter.rows.get(at).or(filter.rows.last())
+        {
+            self.cell_index.y = row;
+        }
+    }
+
+    //sets how column `x` shows its numbers from text like `thousands 2`, or with `off` takes that
+    //away again, and remembers it for the next time the file is opened
+    fn format_column(&mut self, x: usize, text: &str)
+    {
+        let column = self.column_label(x).trim_end().to_string();
+        let message = match text.trim()
+        {
+            "" | "off" | "none" => match self.document.table.number_formats.remove(&x)
+            {
+                Some(_) => format!("Column {} shows its numbers as typed.", column),
+                None => format!("Column {} has no number format.", column),
+            },
+            text => match NumberFormat::parse(text)
+            {
+                Ok(format) => {
+                    self.document.table.number_formats.insert(x, format);
+                    format!("Column {} shows its numbers as {}.", column, format.text())
+                }
+                Err(e) => {
+                    self.status_message = StatusMessage::from(format!("Err: {}", e));
+                    return;
+                }
+            },
+        };
+        self.status_message = StatusMessage::from(self.remember(message));
+    }
+
+    //sets where column `x` puts its contents, with None going back to numbers on the right, and
+    //remembers it for the next time the file is opened
+    fn align_column(&mut self, x: usize, align: Option<Align>)
+    {
+        match align
+        {
+            Some(align) => self.document.table.alignments.insert(x, align),
+            None => self.document.table.alignments.remove(&x),
+        };
+        let column = self.column_label(x).trim_end().to_string();
+        let message = match align
+        {
+            Some(align) => format!("Column {} aligned {}.", column, align.name()),
+            None => format!("Column {} aligned automatically: numbers right, text left.", column),
+        };
+        self.status_message = StatusMessage::from(self.remember(message));
+    }
+
+    //keeps what is remembered about the file up to date, saying so after `message` when that fails
+    fn remember(&self, message: String) -> String
+    {
+        match state::remember(&self.document)
+        {
+            Ok(()) => message,
+            Err(e) => format!("{} Couldn't remember it: {}", message, e),
+        }
+    }
+
+    //protects whole rows (or columns) from edits, pastes and deletes, or takes that off again, and
+    //remembers it for the file
+    fn lock(&mut self, rows: bool, positions: &[usize], lock: bool) -> String
+    {
+        let table = &mut self.document.table;
+        let locked = if rows { &mut table.locked_rows } else { &mut table.locked_cols };
+        for at in positions
+        {
+            if lock { locked.insert(*at) } else { locked.remove(at) };
+        }
+        //rows go by the numbers in the gutter
+        let names: Vec<String> = positions.iter()
+            .map(|&at| match self.row_number(at) {
+                Some(number) if rows => number.to_string(),
+                None if rows => String::from("the header"),
+                _ => formula::column_name(at),
+            })
+            .collect();
+        let message = format!(
+            "{} {} {}.",
+            if lock { "Locked" } else { "Unlocked" },
+            if rows { "row" } else { "column" },
+            names.join(", ")
+        );
+        self.remember(message)
+    }
+
+    fn take_snapshot(&mut self, name: &str)
+    {
+        self.document.checkpoint(name);
+        self.status_message = StatusMessage::from(format!("Took snapshot \"{}\". Alt-B goes back to it.", name));
+    }
+
+    //puts the table back the way it was at the snapshot called `name`, or at one picked from a list
+    //when no name is given. One undo brings back what it replaced
+    fn restore_snapshot(&mut self, name: &str) -> Result<(), std::io::Error>
+    {
+        let names: Vec<String> = self.document.checkpoints().iter().map(|checkpoint| checkpoint.name.clone()).collect();
+        if names.is_empty()
+        {
+            self.status_message = StatusMessage::from(String::from("No snapshots yet; Alt-S takes one."));
+            return Ok(());
+        }
+        let name = if name.is_empty()
+        {
+            let lines: Vec<String> = self.document.checkpoints().iter().map(|checkpoint| {
+                let (rows, cols) = checkpoint.size();
+                format!("{}  ({} rows by {} columns, {})", checkpoint.name, rows, cols, ago(checkpoint.taken))
+            }).collect();
+            match self.pick("Go back to snapshot", &lines, lines.len() - 1)?
+            {
+                Some(i) => names[i].clone(),
+                None => return Ok(()),
+            }
+        }
+        else
+        {
+            name.to_string()
+        };
+        let message = if self.document.restore_checkpoint(&name)
+        {
+            self.refilter();
+            self.keep_cursor_in_table();
+            format!("Back to snapshot \"{}\". Ctrl-z undoes it.", name)
+        }
+        else
+        {
+            format!("No snapshot called \"{}\"", name)
+        };
+        self.status_message = StatusMessage::from(message);
+        Ok(())
+    }
+
+    //a highlight rule, with columns named by the header row when there is one
+    fn parse_highlight(&self, text: &str) -> Result<Highlight, String>
+    {
+        let ncols = self.document.table.num_cols();
+        let names: Vec<String> = if self.header { (1..ncols+1).map(|x| self.column_label(x)).collect() } else { Vec::new() };
+        let highlight = Highlight::parse(text, &names)?;
+        match highlight.predicate.column
+        {
+            Some(x) if x > ncols => Err(format!("no column {}", formula::column_name(x))),
+            _ => Ok(highlight),
+        }
+    }
+
+    //lists the highlight rules to change or remove one, or to add another, and remembers them for the file
+    fn edit_highlights(&mut self) -> Result<(), std::io::Error>
+    {
+        let mut lines: Vec<String> = self.document.table.highlights.iter().map(Highlight::text).collect();
+        lines.push(String::from("(add a rule)"));
+        let Some(i) = self.pick("Highlights", &lines, lines.len() - 1)? else { return Ok(()) };
+        let adding = i == lines.len() - 1;
+        let text = if adding
+        {
+            self.prompt("Highlight (e.g. D < 0 -> red, * contains FAIL -> yellow background): ")?
+        }
+        else
+        {
+            self.prompt_with("Highlight (empty removes it): ", lines[i].clone())?
+        };
+        let Some(text) = text else { return Ok(()) };
+        if !adding && text.trim().is_empty()
+        {
+            self.document.table.highlights.remove(i);
+            let message = self.remember(format!("Removed the highlight {}.", lines[i]));
+            self.status_message = StatusMessage::from(message);
+            return Ok(());
+        }
+        let message = match self.parse_highlight(&text)
+        {
+            Ok(highlight) => {
+                if adding
+                {
+                    self.document.table.highlights.push(highlight);
+                }
+                else
+                {
+                    self.document.table.highlights[i] = highlight;
+                }
+                self.remember(String::from("Highlighting the cells that pass it."))
+            }
+            Err(e) => format!("Err: {}", e),
+        };
+        self.status_message = StatusMessage::from(message);
+        Ok(())
+    }
+
+    //the first highlight rule a cell passes. The header row names the columns, so it never does
+    fn highlight_for(&self, cell: &Cell) -> Option<&Highlight>
+    {
+        let highlights = &self.document.table.highlights;
+        if highlights.is_empty() || (self.header && cell.y_loc == 1)
+        {
+            return None;
+        }
+        let value = self.document.value(cell.x_loc, cell.y_loc).unwrap_or_default();
+        highlights.iter().find(|highlight| highlight.applies(cell.x_loc, &value))
+    }
+
+    //says when the last command left locked cells alone
+    fn report_refused(&mut self)
+    {
+        let refused = self.document.take_refused();
+        if refused > 0
+        {
+            self.status_message = StatusMessage::from(match refused
+            {
+                1 => String::from("A change to a locked cell was refused; :locks off lets it through."),
+                _ => format!("{} changes to locked cells were refused; :locks off lets them through.", refused),
+            });
+        }
+    }
+
+    //lists what the current cell held before each of this session's edits, newest first, and puts
+    //back the one picked. Putting one back is an edit too, so what it replaced joins the list
+    fn cell_history(&mut self) -> Result<(), std::io::Error>
+    {
+        let (x, y) = (self.cell_index.x, self.cell_index.y);
+        let address = format!("{}{}", formula::column_name(x), y);
+        let values: Vec<String> = self.document.cell_history(x, y).iter().rev().cloned().collect();
+        if values.is_empty()
+        {
+            self.status_message = StatusMessage::from(format!("{} hasn't been edited this session.", address));
+            return Ok(());
+        }
+        let lines: Vec<String> = values.iter()
+            .map(|value| match value.as_str()
+            {
+                value if value.trim().is_empty() => String::from("(empty)"),
+                value => value.replace("\r\n", "↵").replace('\n', "↵"),
+            })
+            .collect();
+        let title = format!("Earlier values of {}", address);
+        if let Some(i) = self.pick(&title, &lines, 0)?
+        {
+            self.document.apply(vec![(x, y, values[i].clone())]);
+            self.status_message = StatusMessage::from(format!("Restored {} to {}", address, lines[i]));
+        }
+        Ok(())
+    }
+
+    //the columns named in `text`, by letter or header name and separated by commas; the current
+    //column when there are none
+    fn named_columns(&self, text: &str) -> Result<Vec<usize>, String>
+    {
+        let ncols = self.document.table.num_cols();
+        if text.trim().is_empty()
+        {
+            return Ok(vec![self.cell_index.x]);
+        }
+        let names: Vec<String> = if self.header { (1..ncols+1).map(|x| self.column_label(x)).collect() } else { Vec::new() };
+        text.split(',')
+            .map(|name| find_column(name, &names).filter(|&x| x <= ncols).ok_or_else(|| format!("No column {}", name.trim())))
+            .collect()
+    }
+
+    //sorts the rows by the columns in `text`, like `C desc, A`: later columns only order the rows
+    //the earlier ones leave tied, and rows tied on all of them keep their order. Asks for the
+    //columns when none are given
+    fn sort_by(&mut self, text: &str) -> Result<(), std::io::Error>
+    {
+        let text = match text
+        {
+            "" => match self.prompt_with("Sort by (e.g. C desc, A): ", self.column_label(self.cell_index.x).trim().to_string())?
+            {
+                Some(text) => text,
+                None => return Ok(()),
+            },
+            text => text.to_string(),
+        };
+        let keys = match self.sort_keys(&text)
+        {
+            Ok(keys) => keys,
+            Err(e) => {
+                self.status_message = StatusMessage::from(e);
+                return Ok(());
+            }
+        };
+        let collations = self.document.sort(&keys, self.header);
+        self.last_sort = keys.first().map(|key| (key.x, key.ascending));
+        let described: Vec<String> = keys.iter().zip(&collations)
+            .map(|(key, collation)| format!(
+                "{} {} ({})",
+                num_to_let(key.x),
+                if key.ascending { "ascending" } else { "descending" },
+                collation.name()
+            ))
+            .collect();
+        self.status_message = StatusMessage::from(format!("Sorted by {}", described.join(", then ")));
+        self.refilter();
+        Ok(())
+    }
+
+    //columns by letter or header name separated by commas, each optionally followed by asc or desc
+    fn sort_keys(&self, text: &str) -> Result<Vec<SortKey>, String>
+    {
+        let ncols = self.document.table.num_cols();
+        let names: Vec<String> = if self.header { (1..ncols+1).map(|x| self.column_label(x)).collect() } else { Vec::new() };
+        text.split(',')
+            .map(|part| {
+                let part = part.trim();
+                let (name, ascending) = match part.rsplit_once(' ').map(|(name, order)| (name, order.to_lowercase()))
+                {
+                    Some((name, order)) if order == "asc" || order == "ascending" => (name, true),
+                    Some((name, order)) if order == "desc" || order == "descending" => (name, false),
+                    _ => (part, true),
+                };
+                let x = find_column(name, &names).filter(|&x| x <= ncols).ok_or_else(|| format!("No column {}", name.trim()))?;
+                Ok(SortKey { x, ascending })
+            })
+            .collect()
+    }
+
+    //takes columns off the screen without touching their cells. One column always stays shown
+    fn hide_columns(&mut self, columns: &[usize]) -> String
+    {
+        let ncols = self.document.table.num_cols();
+        let table = &mut self.document.table;
+        if (1..ncols+1).all(|x| table.hidden.contains(&x) || columns.contains(&x))
+        {
+            return String::from("Can't hide every column.");
+        }
+        table.hidden.extend(columns.iter().copied());
+        self.cell_index.x = self.shown_col(self.cell_index.x, true);
+        let names: Vec<String> = columns.iter().map(|&x| formula::column_name(x)).collect();
+        self.remember(format!("Hid column {}; Alt-Z shows hidden columns again.", names.join(", ")))
+    }
+
+    //puts hidden columns back on the screen; all of them when `columns` is None
+    fn show_columns(&mut self, columns: Option<&[usize]>) -> String
+    {
+        let hidden = &mut self.document.table.hidden;
+        let before = hidden.len();
+        match columns
+        {
+            Some(columns) => hidden.retain(|x| !columns.contains(x)),
+            None => hidden.clear(),
+        }
+        match before - hidden.len()
+        {
+            0 => String::from("No hidden columns to show."),
+            1 => self.remember(String::from("Showing 1 hidden column.")),
+            shown => self.remember(format!("Showing {} hidden columns.", shown)),
+        }
+    }
+
+    //the nearest column to `x` that isn't hidden, looking right first, or left first when `right`
+    //is false
+    fn shown_col(&self, x: usize, right: bool) -> usize
+    {
+        let hidden = &self.document.table.hidden;
+        if !hidden.contains(&x)
+        {
+            return x;
+        }
+        let ncols = self.document.table.num_cols();
+        let mut after = (x+1..ncols+1).filter(|c| !hidden.contains(c));
+        let mut before = (1..x).rev().filter(|c| !hidden.contains(c));
+        let found = if right { after.next().or_else(|| before.next()) } else { before.next().or_else(|| after.next()) };
+        found.unwrap_or(x)
+    }
+
+    //writes the selected cells, under the header row when there is one, to a new file whose
+    //extension picks the format, or with no file name to the terminal's clipboard
+    fn export_selection(&mut self, file_name: &str) -> Result<(), std::io::Error>
+    {
+        //the whole table when fewer than two cells are selected
+        let mut selection = match self.document.highlighted_positions().len() {
+            0 | 1 => self.document.values(),
+            _ => match self.document.selection(self.header) {
+                Some(selection) => selection,
+                None => return Ok(()),
+            },
+        };
+        let rows = selection.table.num_rows();
+        if file_name.is_empty()
+        {
+            let message = match selection.csv_bytes()
+            {
+                //OSC 52 asks the terminal to put text on the system clipboard
+                Ok(bytes) => {
+                    print!("\x1b]52;c;{}\x07", base64(&bytes));
+                    format!("Copied {} rows to the clipboard.", rows)
+                }
+                Err(e) => format!("Err: {}", e),
+            };
+            self.status_message = StatusMessage::from(message);
+            return Ok(());
+        }
+        if self.document.file_name.as_deref() == Some(file_name)
+        {
+            self.status_message = StatusMessage::from(String::from("That is the open file; export to another."));
+            return Ok(());
+        }
+        if Path::new(file_name).exists()
+        {
+            self.status_message = StatusMessage::from(format!("{} exists. Overwrite it? (y/n)", file_name));
+            self.refresh_screen()?;
+            if !matches!(self.read_key()?, Key::Char('y') | Key::Char('Y'))
+            {
+                self.status_message = StatusMessage::from(String::from("Not exported."));
+                return Ok(());
+            }
+        }
+        selection.file_name = Some(file_name.to_string());
+        if let Some(delimiter) = document::extension_delimiter(file_name)
+        {
+            selection.dialect.delimiter = delimiter;
+        }
+        self.status_message = StatusMessage::from(match selection.save()
+        {
+            Ok(()) => format!("Exported {} rows to {}.", rows, file_name),
+            Err(e) => save_error_message(&e),
+        });
+        Ok(())
+    }
+
+    //adds the columns of another file, matching its rows to these on a key column in each. Asks for
+    //the file when none is given, then for the key columns and whether to keep rows without a match
+    fn join(&mut self, file_name: &str) -> Result<(), std::io::Error>
+    {
+        let file_name = match file_name
+        {
+            "" => match self.prompt("Join with file: ")?
+            {
+                Some(file_name) => file_name.trim().to_string(),
+                None => return Ok(()),
+            },
+            file_name => file_name.to_string(),
+        };
+        let rows = match Document::open(&file_name)
+        {
+            Ok(other) => other.value_rows(),
+            Err(e) => {
+                self.status_message = StatusMessage::from(format!("Err: Couldn't open {}: {}", file_name, e));
+                return Ok(());
+            }
+        };
+        let ncols = self.document.table.num_cols();
+        let (names, other_names) = if self.header
+        {
+            ((1..ncols+1).map(|x| self.column_label(x)).collect(), rows.first().cloned().unwrap_or_default())
+        }
+        else
+        {
+            (Vec::new(), Vec::new())
+        };
+        let current = if self.header { self.column_label(self.cell_index.x).trim().to_string() } else { num_to_let(self.cell_index.x).to_string() };
+        let Some(text) = self.prompt_with("Key column here: ", current)? else { return Ok(()) };
+        let Some(key) = find_column(&text, &names).filter(|&x| x <= ncols) else {
+            self.status_message = StatusMessage::from(format!("No column {}", text.trim()));
+            return Ok(());
+        };
+        //a column of the same name is the likely match
+        let guess = if other_names.iter().any(|name| name.trim() == text.trim()) { text.trim().to_string() } else { String::from("A") };
+        let Some(text) = self.prompt_with(&format!("Key column in {}: ", file_name), guess)? else { return Ok(()) };
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let Some(other_key) = find_column(&text, &other_names).filter(|&x| x <= width) else {
+            self.status_message = StatusMessage::from(format!("No column {} in {}", text.trim(), file_name));
+            return Ok(());
+        };
+        self.status_message = StatusMessage::from(String::from("Keep rows with no match? (y: left join, n: inner join, Esc: cancel)"));
+        self.refresh_screen()?;
+        let kind = match self.read_key()?
+        {
+            Key::Char('y') | Key::Char('Y') => Join::Left,
+            Key::Char('n') | Key::Char('N') => Join::Inner,
+            _ => {
+                self.status_message = StatusMessage::from(String::from("Join cancelled."));
+                return Ok(());
+            }
+        };
+        let data_rows = self.document.table.num_rows() + 1 - self.first_data_row();
+        let matched = self.document.join(&rows, key, other_key, kind, self.header);
+        self.keep_cursor_in_table();
+        self.refilter();
+        self.status_message = StatusMessage::from(format!(
+            "Added {} columns from {}; {} of {} rows matched.",
+            width.saturating_sub(1), file_name, matched, data_rows
+        ));
+        Ok(())
+    }
+
+    //adds the rows of another file under these, asking whether to leave out its first row when this
+    //table has a header (unless the two headers are the same, when it always is). A file with a
+    //different number of columns is still appended, and the difference reported
+    fn append(&mut self, file_name: &str) -> Result<(), std::io::Error>
+    {
+        let file_name = match file_name
+        {
+            "" => match self.prompt("Append file: ")?
+            {
+                Some(file_name) => file_name.trim().to_string(),
+                None => return Ok(()),
+            },
+            file_name => file_name.to_string(),
+        };
+        let mut rows = match Document::open(&file_name)
+        {
+            Ok(other) => other.value_rows(),
+            Err(e) => {
+                self.status_message = StatusMessage::from(format!("Err: Couldn't open {}: {}", file_name, e));
+                return Ok(());
+            }
+        };
+        let ncols = self.document.table.num_cols();
+        let header: Vec<String> = (1..ncols+1).map(|x| self.column_label(x).trim().to_string()).collect();
+        let same_header = self.header && rows.first().is_some_and(|first| first.iter().map(|name| name.trim()).eq(header.iter().map(String::as_str)));
+        let skip = if same_header
+        {
+            true
+        }
+        else if self.header && !rows.is_empty()
+        {
+            self.status_message = StatusMessage::from(format!("Leave out the first row of {}, its header? (y/n, Esc: cancel)", file_name));
+            self.refresh_screen()?;
+            match self.read_key()?
+            {
+                Key::Char('y') | Key::Char('Y') => true,
+                Key::Char('n') | Key::Char('N') => false,
+                _ => {
+                    self.status_message = StatusMessage::from(String::from("Append cancelled."));
+                    return Ok(());
+                }
+            }
+        }
+        else
+        {
+            false
+        };
+        if skip
+        {
+            rows.remove(0);
+        }
+        if rows.is_empty()
+        {
+            self.status_message = StatusMessage::from(format!("{} has no rows to append", file_name));
+            return Ok(());
+        }
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let count = rows.len();
+        self.document.append_rows(rows);
+        self.refilter();
+        let mismatch = match width.cmp(&ncols)
+        {
+            std::cmp::Ordering::Equal => String::new(),
+            std::cmp::Ordering::Greater => format!(" It has {} columns to this table's {}, so the table grew to {}.", width, ncols, width),
+            std::cmp::Ordering::Less => format!(" It has {} columns to this table's {}; its rows are blank in the rest.", width, ncols),
+        };
+        self.status_message = StatusMessage::from(format!("Appended {} rows from {}.{} Ctrl-z undoes it.", count, file_name, mismatch));
+        Ok(())
+    }
+
+    //rewrites every value in the selection, or the current column below the header, showing the
+    //first few changes to confirm before making them. Formulas are left alone, and one undo puts
+    //everything back
+    fn transform_cells(&mut self, text: &str) -> Result<(), std::io::Error>
+    {
+        let transform = match Transform::parse(text)
+        {
+            Ok(transform) => transform,
+            Err(e) => {
+                self.status_message = StatusMessage::from(format!("Err: {}", e));
+                return Ok(());
+            }
+        };
+        let mut positions = self.document.highlighted_positions();
+        if positions.len() < 2
+        {
+            let x = self.cell_index.x;
+            positions = (self.first_data_row()..self.document.table.num_rows()+1).map(|y| (x, y)).collect();
+        }
+        positions.sort_by_key(|&(x, y)| (y, x));
+        let edits: Vec<(usize, usize, String, String)> = positions.into_iter()
+            .filter_map(|(x, y)| {
+                let before = self.document.table.get(x, y)?.value().to_string();
+                let after = transform.apply(&before);
+                (!formula::is_formula(&before) && after != before).then_some((x, y, before, after))
+            })
+            .collect();
+        if edits.is_empty()
+        {
+            self.status_message = StatusMessage::from(String::from("Nothing would change."));
+            return Ok(());
+        }
+        let mut preview: Vec<String> = edits.iter().take(PREVIEW_LINES)
+            .map(|(x, y, before, after)| format!("{}{}: {} → {}", num_to_let(*x), y, before, after))
+            .collect();
+        if edits.len() > PREVIEW_LINES
+        {
+            preview.push(format!("...and {} more", edits.len() - PREVIEW_LINES));
+        }
+        let title = format!("{} cells change. Return applies, Esc cancels", edits.len());
+        if self.pick(&title, &preview, 0)?.is_none()
+        {
+            self.status_message = StatusMessage::from(String::from("Transform cancelled."));
+            return Ok(());
+        }
+        let changed = self.document.apply(edits.into_iter().map(|(x, y, _, after)| (x, y, after)).collect());
+        self.refilter();
+        self.status_message = StatusMessage::from(format!("Transformed {} cells. Ctrl-z undoes it.", changed));
+        Ok(())
+    }
+
+    //splits the current column into several on `text` (see Split::parse), as one undoable change
+    fn split_column(&mut self, text: &str)
+    {
+        let split = match Split::parse(text)
+        {
+            Ok(split) => split,
+            Err(e) => {
+                self.status_message = StatusMessage::from(format!("Err: {}", e));
+                return;
+            }
+        };
+        match self.document.split_column(self.cell_index.x, &split, self.header)
+        {
+            None => self.report_refused(),
+            Some(1) => self.status_message = StatusMessage::from(String::from("Nothing to split.")),
+            Some(count) => {
+                self.refilter();
+                self.status_message = StatusMessage::from(format!("Split into {} columns. Ctrl-z undoes it.", count));
+            }
+        }
+    }
+
+    //joins the columns the selection spans, or the current one and the next, into one with
+    //`separator` between their values
+    fn merge_columns(&mut self, separator: &str)
+    {
+        let positions = self.document.highlighted_positions();
+        let (from, to) = match (positions.iter().map(|&(x, _)| x).min(), positions.iter().map(|&(x, _)| x).max())
+        {
+            (Some(from), Some(to)) if to > from => (from, to),
+            _ => (self.cell_index.x, self.cell_index.x + 1),
+        };
+        if to > self.document.table.num_cols()
+        {
+            self.status_message = StatusMessage::from(String::from("There is no column to the right to merge with."));
+            return;
+        }
+        if !self.document.merge_columns(from, to, separator)
+        {
+            self.report_refused();
+            return;
+        }
+        self.cell_index.x = from;
+        self.keep_cursor_in_table();
+        self.refilter();
+        self.status_message = StatusMessage::from(format!("Merged {} columns. Ctrl-z undoes it.", to + 1 - from));
+    }
+
+    //runs a Rhai script (see script.rs) on the document. The cells it sets change together, as one
+    //undoable edit, and the last thing it printed goes in the status bar. Esc stops a script that
+    //runs on, leaving the document as it was
+    fn run_script(&mut self, file: &Path)
+    {
+        let source = match std::fs::read_to_string(file)
+        {
+            Ok(source) => source,
+            Err(e) => {
+                self.status_message = StatusMessage::from(format!("Err: Couldn't read {}: {}", file.display(), e));
+                return;
+            }
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let input = script::Input {
+            document: &self.document,
+            header: self.header,
+            cursor: (self.cell_index.x, self.cell_index.y),
+            selected: self.document.highlighted_positions(),
+            stop: Arc::clone(&stop),
+        };
+        let terminal = &mut self.terminal;
+        let finished = AtomicBool::new(false);
+        let result = thread::scope(|scope| {
+            scope.spawn(|| while !finished.load(Ordering::Relaxed)
+            {
+                while let Some(event) = terminal.poll_event()
+                {
+                    if let Ok(Event::Key(Key::Esc)) = event
+                    {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                }
+                thread::sleep(LOAD_REFRESH);
+            });
+            let result = script::run(&source, input);
+            finished.store(true, Ordering::Relaxed);
+            result
+        });
+        let message = match result
+        {
+            Ok(outcome) => {
+                let changed = if outcome.changes.is_empty() { 0 } else { self.document.apply(outcome.changes) };
+                self.refilter();
+                match outcome.printed.last()
+                {
+                    Some(line) => line.clone(),
+                    None if changed == 0 => String::from("The script changed nothing."),
+                    None => format!("The script changed {} cells. Ctrl-z undoes it.", changed),
+                }
+            }
+            Err(e) => format!("Err: {}", e),
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+
+    //swaps rows and columns, keeping the cursor on the same cell
+    fn transpose(&mut self)
+    {
+        self.document.transpose();
+        self.filter = None;
+        let Position{x, y} = self.cell_index.clone();
+        self.cell_index = Position{x: y, y: x};
+        self.offset = Position{x: 0, y: 1};
+        self.status_message = StatusMessage::from(format!(
+            "Transposed to {} rows and {} columns. Ctrl-z turns it back.",
+            self.document.table.num_rows(), self.document.table.num_cols()
+        ));
+    }
+
+    //brings the cursor back inside the table after it shrank under it, e.g. when a transpose is undone
+    fn keep_cursor_in_table(&mut self)
+    {
+        self.cell_index.x = self.cell_index.x.clamp(1, self.document.table.num_cols().max(1));
+        self.cell_index.y = self.cell_index.y.clamp(1, self.document.table.num_rows().max(1));
+    }
+
+    //moves the cursor to a cell given as a reference like C125, or as row,column like 125,3
+    fn goto(&mut self, target: &str)
+    {
+        let at = formula::parse_reference(target.trim()).or_else(|| {
+            let (y, x) = target.split_once(',')?;
+            Some(Position{x: x.trim().parse().ok()?, y: y.trim().parse().ok()?})
+        });
+        let Some(at) = at else {
+            self.status_message = StatusMessage::from(format!("Error: Not a cell: {}", target));
+            return;
+        };
+        if at.x == 0 || at.y == 0 || at.x > self.document.table.num_cols() || at.y > self.document.table.num_rows()
+        {
+            self.status_message = StatusMessage::from(format!("Error: {} is outside the table", target));
+            return;
+        }
+        self.jump_to(at);
+    }
+
+    //moves the cursor to `at`, scrolling as far as it takes to bring it on screen
+    fn jump_to(&mut self, at: Position)
+    {
+        self.cell_index = at;
+        //scroll moves one column at a time, so keep going until the cell is on screen
+        loop
+        {
+            let before = self.offset.clone();
+            self.scroll();
+            if self.offset == before
+            {
+                break;
+            }
+        }
+    }
+
+    fn jump_to_match(&mut self, forward: bool)
+    {
+        let Some(query) = &self.search else { return };
+        //cells may have been edited since the search, so look again
+        let count = self.document.find(query);
+        let mut found = self.document.next_match(&self.cell_index, forward);
+        //matches in rows a filter hides are skipped
+        for _ in 0..count
+        {
+            match &found
+            {
+                Some(at) if !self.is_shown(at.y) => found = self.document.next_match(at, forward),
+                _ => break,
+            }
+        }
+        match found.filter(|at| self.is_shown(at.y))
+        {
+            Some(at) => {
+                let index = self.document.matches().iter().position(|&(x, y)| x == at.x && y == at.y).unwrap_or(0);
+                self.cell_index = at;
+                self.status_message = StatusMessage::from(format!(
+                    "Match {} of {} for \"{}\" (n/N: next/previous, Esc: clear)", index + 1, count, query
+                ));
+            }
+            None => {
+                self.status_message = StatusMessage::from(format!("No matches for \"{}\"", query));
+                self.search = None;
+            }
+        }
+    }
+
+    //replaces text in the current cell or in every match of the search
+    fn replace(&mut self) -> Result<(), std::io::Error>
+    {
+        let query = match self.search.clone() {
+            Some(query) => query,
+            None => match self.prompt("Find: ")? {
+                Some(query) => query,
+                None => return Ok(()),
+            },
+        };
+        let count = self.document.find(&query);
+        if count == 0
+        {
+            self.status_message = StatusMessage::from(format!("No matches for \"{}\"", query));
+            return Ok(());
+        }
+        self.search = Some(query.clone());
+        let Some(replacement) = self.prompt(&format!("Replace \"{}\" with: ", query))? else {
+            self.status_message = StatusMessage::from(String::from("Replace cancelled"));
+            return Ok(());
+        };
+        self.status_message = StatusMessage::from(format!("Replace in (c)urrent cell or (a)ll {} matches?", count));
+        self.refresh_screen()?;
+        let positions = match self.read_key()? {
+            Key::Char('c') => vec![(self.cell_index.x, self.cell_index.y)],
+            Key::Char('a') => self.document.matches().to_vec(),
+            _ => {
+                self.status_message = StatusMessage::from(String::from("Replace cancelled"));
+                return Ok(());
+            }
+        };
+        let replaced = self.document.replace(&query, &replacement, &positions);
+        if self.document.find(&query) == 0
+        {
+            self.search = None;
+        }
+        self.status_message = StatusMessage::from(format!("Replaced \"{}\" in {} cells", query, replaced));
+        Ok(())
+    }
+
+    //edits the current cell in place, starting from `initial`. Enter keeps the change, Esc throws it away.
+    //With several cells selected, what is kept goes into all of them as one undoable edit
+    fn edit_cell(&mut self, initial: &str) -> Result<(), std::io::Error>
+    {
+        let selected = self.document.highlighted_positions();
+        let batch = selected.len() > 1;
+        if !batch && self.document.is_locked(self.cell_index.x, self.cell_index.y)
+        {
+            let address = format!("{}{}", formula::column_name(self.cell_index.x), self.cell_index.y);
+            self.status_message = StatusMessage::from(format!("{} is locked; :locks off lets locked cells change.", address));
+            return Ok(());
+        }
+        self.editing = Some(CellEdit::new(initial));
+        //vim leaves insert mode with Esc, keeping what was typed
+        let (keep_key, cancel_key, hint) = if self.vim {
+            (Key::Esc, Key::Ctrl('c'), "-- INSERT -- Esc or Enter to keep, Ctrl-c to cancel")
+        } else {
+            (Key::Char('\n'), Key::Esc, "EDIT: Enter to keep, Esc to cancel")
+        };
+        let hint = if batch { format!("{} ({} selected cells)", hint, selected.len()) } else { String::from(hint) };
+        self.status_message = StatusMessage::from(hint);
+        let keep = loop
+        {
+            self.refresh_screen()?;
+            let key = self.read_key()?;
+            let Some(edit) = self.editing.as_mut() else { break false };
+            match key
+            {
+                Key::Char('\n') => break true,
+                key if key == keep_key => break true,
+                key if key == cancel_key => break false,
+                Key::Char(c) if !c.is_control() => edit.insert(c),
+                Key::Backspace => edit.backspace(),
+                Key::Delete => edit.delete(),
+                Key::Left => edit.move_left(),
+                Key::Right => edit.move_right(),
+                Key::Home => edit.cursor = 0,
+                Key::End => edit.cursor = edit.buffer.len(),
+                _ => (),
+            }
+        };
+        let edit = self.editing.take();
+        if let (true, Some(edit), true) = (keep, &edit, batch)
+        {
+            let text = edit.text();
+            let changed = self.document.apply(selected.into_iter().map(|(x, y)| (x, y, text.clone())).collect());
+            self.status_message = StatusMessage::from(match changed
+            {
+                1 => String::from("Changed 1 cell. Ctrl-z undoes it."),
+                changed => format!("Changed {} cells. Ctrl-z undoes them.", changed),
+            });
+            self.report_refused();
+        }
+        else if let (true, Some(edit)) = (keep, edit)
+        {
+            let pos = self.cell_index.clone();
+            self.document.insert(pos, &edit.text());
+            self.status_message = StatusMessage::from(String::new());
+        }
+        else
+        {
+            self.status_message = StatusMessage::from(String::from("Edit cancelled"));
+        }
+        Ok(())
+    }
+
+    //the current cell's whole contents in a popup, wrapped to fit and scrolled with the arrows, with
+    //e to edit them in $EDITOR
+    fn view_cell(&mut self) -> Result<(), std::io::Error>
+    {
+        let Position{x, y} = self.cell_index.clone();
+        let contents = self.document.table.get_content_from(Position{x, y});
+        let width = (self.terminal.size().width as usize).saturating_sub(4).clamp(1, 100);
+        let lines: Vec<String> = contents.lines()
+            .flat_map(|line| table::wrap_words(line, width))
+            .collect();
+        let title = format!("{}{}", formula::column_name(x), y);
+        let hint = "Up/Down: scroll, e: edit in $EDITOR, other keys: close";
+        let mut top = 0;
+        loop
+        {
+            self.refresh_screen()?;
+            let visible = self.draw_popup(&title, hint, &lines, &mut top, None)?;
+            let last_top = lines.len().saturating_sub(visible);
+            match self.read_key()?
+            {
+                Key::Up => top = top.saturating_sub(1),
+                Key::Down => top = (top + 1).min(last_top),
+                Key::PageUp => top = top.saturating_sub(visible),
+                Key::PageDown => top = (top + visible).min(last_top),
+                Key::Char('e') => return self.edit_in_editor(),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    //opens the current cell in $VISUAL or $EDITOR (vi when neither is set) and keeps what it is
+    //saved with. The newline editors end files with is left off
+    fn edit_in_editor(&mut self) -> Result<(), std::io::Error>
+    {
+        let position = self.cell_index.clone();
+        if self.document.read_only
+        {
+            self.status_message = StatusMessage::from(String::from(VIEW_ONLY_MESSAGE));
+            return Ok(());
+        }
+        if self.document.is_locked(position.x, position.y)
+        {
+            let address = format!("{}{}", formula::column_name(position.x), position.y);
+            self.status_message = StatusMessage::from(format!("{} is locked; :locks off lets locked cells change.", address));
+            return Ok(());
+        }
+        let before = self.document.table.get_content_from(position.clone());
+        let file = env::temp_dir().join(format!("clicsv-{}-cell.txt", std::process::id()));
+        std::fs::write(&file, format!("{}\n", before))?;
+        let program = env::var("VISUAL").or_else(|_| env::var("EDITOR")).unwrap_or_else(|_| String::from("vi"));
+        self.terminal.suspend()?;
+        //through the shell, so the variable can hold arguments too, like `code --wait`
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", program))
+            .arg("sh")
+            .arg(&file)
+            .status();
+        self.terminal.resume()?;
+        self.redraw_table();
+        let after = std::fs::read_to_string(&file);
+        std::fs::remove_file(&file).ok();
+        let message = match (status, after)
+        {
+            (Err(e), _) => format!("Err: Couldn't run {}: {}", program, e),
+            (Ok(status), _) if !status.success() => format!("Err: {} exited with {}; the cell is unchanged.", program, status),
+            (Ok(_), Err(e)) => format!("Err: Couldn't read the edited cell back: {}", e),
+            (Ok(_), Ok(after)) => {
+                let after = after.strip_suffix('\n').unwrap_or(&after);
+                let after = after.strip_suffix('\r').unwrap_or(after);
+                if after == before
+                {
+                    String::from("The cell is unchanged.")
+                }
+                else
+                {
+                    self.document.insert(position, after);
+                    String::from("Cell changed. Ctrl-z undoes it.")
+                }
+            }
+        };
+        self.status_message = StatusMessage::from(message);
+        Ok(())
+    }
+
+    //maps a key press to the command it triggers, without performing it
+    pub fn key_to_action(&self, key: Key) -> Command {
+        if let Some(&command) = self.keys.get(&key) {
+            return command;
+        }
+        match key {
+            Key::Ctrl('q') => Command::Quit,
+            Key::Ctrl('s') => Command::Save,
+            Key::Alt('s') => Command::SaveAs,
+            Key::Char('\n') => Command::Edit,
+            Key::Char('?') => Command::Help,
+            Key::Char('=') => Command::Stats,
+            Key::Char(':') => Command::Prompt,
+            Key::Char('n') if self.search.is_some() => Command::FindNext,
+            Key::Char('N') if self.search.is_some() => Command::FindPrevious,
+            Key::Esc if self.search.is_some() => Command::ClearSearch,
+            Key::Char(']') if self.comparison.is_some() => Command::NextDifference,
+            Key::Char('[') if self.comparison.is_some() => Command::PreviousDifference,
+            Key::Esc if self.comparison.is_some() => Command::ClearDiff,
+            Key::Char(c) if !c.is_control() => Command::EditWith(c),
+            Key::Ctrl('c') => Command::Copy,
+            Key::Ctrl('v') => Command::Paste,
+            Key::Ctrl('x') => Command::Cut,
+            Key::Ctrl('d') => Command::FillDown,
+            Key::Alt('y') => Command::YankRows,
+            Key::Alt('Y') => Command::CutRows,
+            Key::Alt('U') => Command::PutRows,
+            Key::Alt('I') => Command::PutRowsAbove,
+            Key::Alt('c') => Command::DuplicateRows,
+            Key::Delete => Command::Delete,
+            Key::Alt('q') => Command::RecordMacro,
+            Key::Alt('@') => Command::ReplayMacro,
+            //Ctrl-` sends a null byte
+            Key::Null => Command::ToggleFormulas,
+            Key::Ctrl('z') => Command::Undo,
+            Key::Ctrl('r') => Command::Redo,
+            Key::Ctrl('f') => Command::Find,
+            Key::Ctrl('g') => Command::Goto,
+            Key::Ctrl('t') => Command::FuzzyFind,
+            Key::Ctrl('p') => Command::Palette,
+            Key::Ctrl('n') => Command::NextFile,
+            Key::Ctrl('b') => Command::PreviousFile,
+            Key::Ctrl('h') => Command::Replace,
+            Key::Ctrl('o') => Command::Sort,
+            Key::Alt('o') => Command::InsertRowBelow,
+            Key::Alt('O') => Command::InsertRowAbove,
+            Key::Alt('a') => Command::InsertColRight,
+            Key::Alt('i') => Command::InsertColLeft,
+            Key::Alt('d') => Command::DeleteRow,
+            Key::Alt('D') => Command::DeleteCol,
+            Key::AltLeft => Command::MoveColLeft,
+            Key::AltRight => Command::MoveColRight,
+            Key::Alt('r') => Command::RenameCol,
+            Key::Alt('m') => Command::EditNote,
+            Key::Alt('v') => Command::ValidateColumn,
+            Key::Alt('e') => Command::NextViolation,
+            Key::Alt('k') => Command::HighlightDuplicates,
+            Key::Alt('K') => Command::FilterDuplicates,
+            Key::Alt('x') => Command::Transpose,
+            Key::Alt('j') => Command::AlignColumn,
+            Key::Alt('J') => Command::NumberFormat,
+            Key::Alt('z') => Command::HideColumn,
+            Key::Alt('Z') => Command::ShowColumns,
+            Key::Alt('L') => Command::LockColumn,
+            Key::Alt('C') => Command::Highlights,
+            Key::Alt('S') => Command::TakeSnapshot,
+            Key::Alt('B') => Command::RestoreSnapshot,
+            Key::Alt('R') => Command::LockRow,
+            Key::Alt('b') => Command::CellHistory,
+            Key::Alt('g') => Command::SortBy,
+            Key::Alt('E') => Command::ExportSelection,
+            Key::Alt('T') => Command::TransformCells,
+            Key::Alt('P') => Command::SplitColumn,
+            Key::Alt('M') => Command::MergeColumns,
+            Key::Alt('V') => Command::ViewCell,
+            Key::Alt('X') => Command::Pivot,
+            Key::Alt('G') => Command::Describe,
+            Key::Alt('Q') => Command::ToggleBlanks,
+            Key::Alt('N') => Command::NextBlank,
+            Key::Alt('A') => Command::Append,
+            Key::Alt('f') => Command::FreezeHeader,
+            Key::Alt('F') => Command::Frequencies,
+            Key::Alt('=') => Command::WidenColumn,
+            Key::Alt('-') => Command::NarrowColumn,
+            Key::Alt('w') => Command::ToggleWrap,
+            Key::Alt('H') => Command::ToggleCrosshair,
+            Key::Alt('u') => Command::ToggleTotals,
+            Key::Alt('h') => Command::ToggleHeaderLabels,
+            Key::Alt('l') => Command::Filter,
+            Key::Alt('W') => Command::PickValues,
+            Key::Alt('n') => Command::NextSheet,
+            Key::Alt('t') => Command::PickSheet,
+            Key::Alt('p') => Command::PreviousSheet,
+            Key::CtrlLeft => Command::GrowSelectionLeft,
+            Key::CtrlRight => Command::GrowSelectionRight,
+            Key::CtrlUp => Command::GrowSelectionUp,
+            Key::CtrlDown => Command::GrowSelectionDown,
+            Key::AltUp => Command::JumpUp,
+            Key::AltDown => Command::JumpDown,
+            Key::Alt('<') => Command::JumpLeft,
+            Key::Alt('>') => Command::JumpRight,
+            Key::ShiftUp => Command::SelectRowUp,
+            Key::ShiftDown => Command::SelectRowDown,
+            Key::ShiftLeft => Command::SelectColLeft,
+            Key::ShiftRight => Command::SelectColRight,
+            Key::Up => Command::MoveUp,
+            Key::Down => Command::MoveDown,
+            Key::Left => Command::MoveLeft,
+            Key::Right => Command::MoveRight,
+            Key::PageUp => Command::PageUp,
+            Key::PageDown => Command::PageDown,
+            Key::Home => Command::Home,
+            Key::End => Command::End,
+            _ => Command::Nothing,
+        }
+    }
+
+    //in vim mode letters are commands rather than the start of an edit. Pairs like dd wait for their
+    //second key; everything else goes to the usual bindings
+    fn vim_action(&mut self, key: Key) -> Command {
+        if let Some(&command) = self.keys.get(&key) {
+            self.vim_pending = None;
+            return command;
+        }
+        let Key::Char(c) = key else {
+            self.vim_pending = None;
+            return self.key_to_action(key);
+        };
+        match (self.vim_pending.take(), c) {
+            (Some('d'), 'd') => return Command::DeleteRow,
+            (Some('y'), 'y') => return Command::YankRows,
+            (Some(_), _) => return Command::Nothing,
+            (None, 'd' | 'y') => {
+                self.vim_pending = Some(c);
+                return Command::Nothing;
+            }
+            _ => (),
+        }
+        match c {
+            'h' => Command::MoveLeft,
+            'j' => Command::MoveDown,
+            'k' => Command::MoveUp,
+            'l' => Command::MoveRight,
+            '0' => Command::Home,
+            '$' => Command::End,
+            '{' => Command::JumpUp,
+            '}' => Command::JumpDown,
+            'i' => Command::Edit,
+            'x' => Command::Delete,
+            'p' => Command::PutRows,
+            'P' => Command::PutRowsAbove,
+            'o' => Command::InsertRowBelow,
+            'O' => Command::InsertRowAbove,
+            'u' => Command::Undo,
+            '/' => Command::Find,
+            'q' => Command::RecordMacro,
+            '@' => Command::ReplayMacro,
+            _ => match self.key_to_action(key) {
+                Command::EditWith(_) => Command::Nothing,
+                command => command,
+            },
+        }
+    }
+
+    //moving onto a cell with a note shows it in the message bar
+    fn show_note(&mut self, from: &Position) {
+        if *from == self.cell_index {
+            return;
+        }
+        if let Some(note) = self.document.note(self.cell_index.x, self.cell_index.y) {
+            self.status_message = StatusMessage::from(format!("Note: {}", note.replace('\n', " ↵ ")));
+        }
+    }
+
+    //waits for a key, but only so long: when none comes, the screen is drawn again anyway so it keeps
+    //up with the clock (an old message goes away) and the size of the window
+    fn process_keypress(&mut self) -> Result<(), std::io::Error> {
+        let Some(event) = self.read_event_timeout(IDLE_REFRESH)? else {
+            self.terminal.update_size();
+            return Ok(());
+        };
+        let command = match event {
+            Event::Key(pressed_key) => {
+                let command = if self.vim { self.vim_action(pressed_key) } else { self.key_to_action(pressed_key) };
+                let from = self.cell_index.clone();
+                let result = self.execute(command, pressed_key);
+                self.show_note(&from);
+                self.refresh_comparison();
+                if self.show_totals
+                {
+                    self.totals.refresh(&self.document, self.header);
+                }
+                if self.show_blanks
+                {
+                    self.blanks.refresh(&self.document, self.header);
+                }
+                self.refresh_duplicates();
+                return result;
+            }
+            Event::Mouse(MouseEvent::Press(MouseButton::Left, column, row)) => Command::Click(column, row),
+            Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, _, _)) => Command::MoveUp,
+            Event::Mouse(MouseEvent::Press(MouseButton::WheelDown, _, _)) => Command::MoveDown,
+            _ => Command::Nothing,
+        };
+        let from = self.cell_index.clone();
+        let result = self.execute(command, Key::Null);
+        self.show_note(&from);
+        result
+    }
+
+    fn execute(&mut self, command: Command, pressed_key: Key) -> Result<(), std::io::Error> {
+        if self.document.read_only && command.changes_file()
+        {
+            self.status_message = StatusMessage::from(String::from(VIEW_ONLY_MESSAGE));
+            return Ok(());
+        }
+        let was_saved = self.document.is_saved();
+        match command {
+            Command::Quit => {
+                let unsaved = self.unsaved_buffers();
+                if unsaved > 0{
+                    let message = if self.buffers.len() > 1 {
+                        format!("WARNING! {} of {} files have unsaved changes. Press Ctrl-Q to quit", unsaved, self.buffers.len())
+                    } else {
+                        String::from("WARNING! File has unsaved changes. Press Ctrl-Q to quit")
+                    };
+                    self.status_message = StatusMessage::from(message);
+                    self.refresh_screen()?;
+                    let read = self.read_key()?;
+                    if self.key_to_action(read) == Command::Quit{
+                        self.should_quit = true;
+                    }
+                    return Ok(());
+                }
+                else{
+                    self.should_quit = true;
+                }
+            }
+            //save file
+            Command::Save => {
+                self.save()
+            },
+            Command::SaveAs => {
+                self.save_as()?;
+            }
+            //enter data into cell at current position
+            Command::Edit => {
+                let contents = self.document.table.get_content_from(self.cell_index.clone());
+                self.edit_cell(&contents)?;
+                return Ok(());
+            }
+            //typing a character replaces the cell, starting with that character
+            Command::EditWith(c) => {
+                self.edit_cell(&c.to_string())?;
+                return Ok(());
+            }
+            //show a summary of the keybindings
+            Command::Help => {
+                let help = if self.vim { VIM_HELP_TEXT } else { HELP_TEXT };
+                self.status_message = StatusMessage::from(String::from(help));
+                return Ok(());
+            }
+            //run a typed command such as `:impute 0`
+            Command::Prompt => {
+                if let Some(line) = self.prompt(":")?{
+                    self.run_command_line(&line);
+                    self.report_refused();
+                }
+                return Ok(());
+            }
+            //get statstical infomation for highlighted cell
+            Command::Stats => {
+                //the whole summary, or one of the statistics functions
+                let choices: Vec<String> = std::iter::once(format!("{:<10} {}", "all", "the summary of every figure"))
+                    .chain(stats::FUNCTIONS.iter().map(|function| format!("{:<10} {}", function.name, function.description)))
+                    .collect();
+                let statistic = match self.pick("Statistics", &choices, 0)?{
+                    None => return Ok(()),
+                    Some(0) => None,
+                    Some(i) => {
+                        let function = &stats::FUNCTIONS[i - 1];
+                        let name = match function.argument {
+                            Some(meaning) => match self.prompt(&format!("{} ({}): ", function.name, meaning))?{
+                                Some(number) => format!("{} {}", function.name, number),
+                                None => return Ok(()),
+                            },
+                            None => function.name.to_string(),
+                        };
+                        match Statistic::parse(&name){
+                            Ok(statistic) => Some(statistic),
+                            Err(e) => {
+                                self.status_message = StatusMessage::from(format!("Err: {}", e));
+                                return Ok(());
+                            }
+                        }
+                    }
+                };
+                return self.show_stats(statistic);
+            }
+            Command::Frequencies => {
+                let prompt = format!("Count values of {}, also working out (e.g. sum D; Return for none): ", self.column_label(self.cell_index.x).trim());
+                let text = self.prompt(&prompt)?.unwrap_or_default();
+                return self.frequencies(&text);
+            }
+            //copy highlighted cell data
+            Command::Copy => {
+                self.copy = self.document.copy();
+                self.status_message=StatusMessage::from(format!("Copied {} rows by {} columns", self.copy.height, self.copy.width));
+            }
+            //paste copied data to current position
+            Command::Paste => {
+                if self.copy.is_empty(){
+                    self.status_message=StatusMessage::from(String::from("Error: Nothing to paste"));
+                    return Ok(());
+                } 
+                self.document.paste(&self.cell_index, &self.copy.clone());
+                self.refilter();
+                self.status_message=StatusMessage::from(String::from("Pasted"));
+            }
+            //copy the whole rows the selection covers, ready to paste in column A
+            Command::YankRows => {
+                let (top, bottom) = self.document.selected_rows(self.cell_index.y);
+                self.copy = self.document.copy_rows(top, bottom);
+                self.status_message=StatusMessage::from(format!("Copied {} whole rows", self.copy.height));
+            }
+            //copy the whole rows the selection covers and take them out of the table
+            Command::CutRows => {
+                let (top, bottom) = self.document.selected_rows(self.cell_index.y);
+                let cut = self.document.cut_rows(top, bottom);
+                if cut.height == 0 {
+                    self.status_message=StatusMessage::from(String::from("None of the rows could be cut."));
+                    return Ok(());
+                }
+                //only the rows that went, so putting them back can't double a locked row
+                self.copy = cut;
+                self.refilter();
+                self.cell_index.y = top.min(self.document.table.num_rows());
+                self.keep_cursor_shown();
+                self.status_message=StatusMessage::from(format!("Cut {} whole rows; Alt-U or Alt-I puts them back in.", self.copy.height));
+            }
+            //put copied rows in below or above the current one, as new rows
+            Command::PutRows | Command::PutRowsAbove => {
+                let above = command == Command::PutRowsAbove;
+                let after = if above { self.cell_index.y - 1 } else { self.cell_index.y };
+                let count = self.document.put_rows(after, &self.copy.clone());
+                if count == 0 {
+                    self.status_message=StatusMessage::from(String::from("Error: Nothing to paste"));
+                    return Ok(());
+                }
+                //the current row moved down to make room
+                if above {
+                    self.cell_index.y += count;
+                }
+                self.refilter();
+                let place = if above { "above" } else { "below" };
+                self.status_message=StatusMessage::from(format!("Put {} rows {}.", count, place));
+            }
+            Command::DuplicateRows => {
+                let (top, bottom) = self.document.selected_rows(self.cell_index.y);
+                let count = self.document.duplicate_rows(top, bottom);
+                self.refilter();
+                self.status_message=StatusMessage::from(format!("Duplicated {} rows.", count));
+            }
+            Command::FillDown => {
+                let message = if self.document.highlighted_positions().len() < 2{
+                    String::from("Select the cells to fill, with the value to fill them from at the top.")
+                } else {
+                    format!("Filled {} cells.", self.document.fill_down())
+                };
+                self.status_message=StatusMessage::from(message);
+            }
+            //copy and delete highlighted cell data
+            Command::Cut => {
+                self.copy = self.document.copy();
+                self.document.delete();
+                self.status_message=StatusMessage::from(String::from("Cut"));
+            }
+            //delete contents from highlighted cells
+            Command::Delete =>{
+                self.document.delete();
+                self.status_message=StatusMessage::from(String::from("Deleted."));
+            }
+            //start/stop recording a macro, and replay it
+            Command::RecordMacro => {
+                return self.toggle_recording();
+            }
+            Command::ReplayMacro => {
+                return self.replay_macro();
+            }
+            //toggle between showing formula results and formula text
+            Command::ToggleFormulas => {
+                self.show_formulas = !self.show_formulas;
+                let mode = if self.show_formulas { "formulas" } else { "values" };
+                self.status_message=StatusMessage::from(format!("Showing {}.", mode));
+            }
+            //undo/redo edits to document
+            Command::Undo => {
+                let message = if self.document.undo() { "Undone." } else { "Nothing to undo." };
+                self.status_message=StatusMessage::from(String::from(message));
+                self.keep_cursor_in_table();
+                self.refilter();
+            }
+            Command::Redo => {
+                let message = if self.document.redo() { "Redone." } else { "Nothing to redo." };
+                self.status_message=StatusMessage::from(String::from(message));
+                self.keep_cursor_in_table();
+                self.refilter();
+            }
+            //search for cells containing some text and jump between them
+            Command::Find => {
+                if let Some(query) = self.prompt("Find: ")?{
+                    self.find(query);
+                }
+            }
+            Command::Filter => {
+                if self.filter.take().is_some(){
+                    self.status_message=StatusMessage::from(String::from("Showing all rows."));
+                } else if let Some(text) = self.prompt("Filter (e.g. B contains error, C > 100): ")?{
+                    self.apply_filter(&text);
+                }
+            }
+            Command::FuzzyFind => {
+                self.fuzzy_find()?;
+            }
+            Command::PickValues => {
+                self.pick_values()?;
+            }
+            Command::NextFile | Command::PreviousFile => {
+                if self.buffers.len() < 2 {
+                    self.status_message = StatusMessage::from(String::from("No other files open; :e FILE opens one"));
+                    return Ok(());
+                }
+                let count = self.buffers.len();
+                let step = if command == Command::NextFile { 1 } else { count - 1 };
+                self.switch_buffer((self.buffer + step) % count);
+                return Ok(());
+            }
+            Command::Palette => {
+                if let Some(command) = self.command_palette()? {
+                    let key = self.key_for(command).unwrap_or(Key::Null);
+                    return self.execute(command, key);
+                }
+                return Ok(());
+            }
+            Command::Goto => {
+                if let Some(target) = self.prompt("Go to (C125 or row,col): ")?{
+                    self.goto(&target);
+                }
+            }
+            Command::FindNext | Command::FindPrevious => {
+                self.jump_to_match(command == Command::FindNext);
+            }
+            Command::NextDifference | Command::PreviousDifference => {
+                self.jump_to_difference(command == Command::NextDifference);
+            }
+            Command::ClearDiff => {
+                self.comparison = None;
+                self.status_message=StatusMessage::from(String::new());
+            }
+            Command::ClearSearch => {
+                self.search = None;
+                self.document.clear_matches();
+                self.status_message=StatusMessage::from(String::new());
+            }
+            Command::Replace => {
+                self.replace()?;
+            }
+            //sort rows by the current column, reversing the order when sorting the same column twice
+            Command::Sort => {
+                let x = self.cell_index.x;
+                let ascending = !matches!(self.last_sort, Some((col, true)) if col == x);
+                let numeric = self.document.sort_by_column(x, ascending, self.header);
+                self.last_sort = Some((x, ascending));
+                self.status_message=StatusMessage::from(format!(
+                    "Sorted by column {} ({}, {})",
+                    num_to_let(x),
+                    if ascending { "ascending" } else { "descending" },
+                    if numeric { "numeric" } else { "text" }
+                ));
+                self.refilter();
+            }
+            Command::SortBy => self.sort_by("")?,
+            //cycle through the sheets of a workbook
+            Command::NextSheet | Command::PreviousSheet => {
+                let count = self.document.sheet_count().max(1);
+                let current = self.document.sheet_index();
+                let index = if command == Command::NextSheet { (current + 1) % count } else { (current + count - 1) % count };
+                self.open_sheet(index);
+                return Ok(());
+            }
+            //choose a sheet of a workbook, or a table of a database, from a list
+            Command::PickSheet => {
+                self.pick_sheet()?;
+                return Ok(());
+            }
+            //resize the current column by hand, and wrap long cells
+            Command::WidenColumn | Command::NarrowColumn => {
+                let delta = if command == Command::WidenColumn { 1 } else { -1 };
+                let width = self.document.table.resize_column(self.cell_index.x, delta);
+                self.status_message=StatusMessage::from(self.remember(format!("Column {} is {} wide.", num_to_let(self.cell_index.x), width)));
+            }
+            Command::ToggleBlanks => {
+                self.show_blanks = !self.show_blanks;
+                self.blanks.refresh(&self.document, self.header);
+                let state = if self.show_blanks { "on" } else { "off" };
+                self.status_message=StatusMessage::from(format!("Blank counts {}.", state));
+            }
+            Command::NextBlank => self.next_blank(),
+            Command::JumpUp | Command::JumpDown | Command::JumpLeft | Command::JumpRight => self.jump_to_data_edge(command),
+            Command::BlankReport => self.blank_report()?,
+            Command::ToggleTotals => {
+                self.show_totals = !self.show_totals;
+                let state = if self.show_totals { "on" } else { "off" };
+                self.status_message=StatusMessage::from(format!("Totals {}.", state));
+            }
+            Command::ToggleWrap => {
+                self.wrap = !self.wrap;
+                let state = if self.wrap { "on" } else { "off" };
+                self.status_message=StatusMessage::from(format!("Wrapping {}.", state));
+            }
+            Command::ToggleCrosshair => {
+                self.crosshair = !self.crosshair;
+                let state = if self.crosshair { "on" } else { "off" };
+                self.status_message=StatusMessage::from(format!("Crosshair {}.", state));
+            }
+            Command::ToggleHeaderLabels => {
+                self.header_labels = !self.header_labels;
+                let message = if self.header_labels { "Naming columns after the first row." } else { "Naming columns by letter." };
+                self.status_message=StatusMessage::from(String::from(message));
+            }
+            Command::FreezeHeader => {
+                self.freeze_header = !self.freeze_header;
+                let state = if self.freeze_header { "frozen" } else { "unfrozen" };
+                self.status_message=StatusMessage::from(format!("Header row {}.", state));
+            }
+            //add or remove whole rows and columns around the cursor
+            Command::InsertRowAbove | Command::InsertRowBelow => {
+                if command == Command::InsertRowBelow{
+                    self.cell_index.y += 1;
+                }
+                self.document.insert_row(self.cell_index.y);
+                self.shift_filter(self.cell_index.y, true);
+                self.status_message=StatusMessage::from(String::from("Inserted row."));
+            }
+            Command::InsertColLeft | Command::InsertColRight => {
+                if command == Command::InsertColRight{
+                    self.cell_index.x += 1;
+                }
+                self.document.insert_col(self.cell_index.x);
+                self.status_message=StatusMessage::from(String::from("Inserted column."));
+            }
+            Command::DeleteRow => {
+                let message = if self.document.delete_row(self.cell_index.y){
+                    self.shift_filter(self.cell_index.y, false);
+                    self.cell_index.y = self.cell_index.y.min(self.document.table.num_rows());
+                    self.keep_cursor_shown();
+                    "Deleted row."
+                } else {
+                    "Cannot delete the only row."
+                };
+                self.status_message=StatusMessage::from(String::from(message));
+            }
+            Command::DeleteCol => {
+                let message = if self.document.delete_col(self.cell_index.x){
+                    self.cell_index.x = self.cell_index.x.min(self.document.table.num_cols());
+                    "Deleted column."
+                } else {
+                    "Cannot delete the only column."
+                };
+                self.status_message=StatusMessage::from(String::from(message));
+            }
+            Command::MoveColLeft | Command::MoveColRight => {
+                let right = command == Command::MoveColRight;
+                let message = if self.document.move_col(self.cell_index.x, right){
+                    //the cursor goes with the column
+                    if right { self.cell_index.x += 1 } else { self.cell_index.x -= 1 }
+                    self.keep_cursor_shown();
+                    "Moved column."
+                } else {
+                    "Column is already at the edge."
+                };
+                self.status_message=StatusMessage::from(String::from(message));
+            }
+            Command::RenameCol => {
+                let x = self.cell_index.x;
+                if !self.header{
+                    self.status_message=StatusMessage::from(String::from("No header row to rename; the first row is data."));
+                } else if let Some(name) = self.prompt_with("Rename column: ", self.column_label(x).trim_end().to_string())?{
+                    self.document.insert(Position{x, y: 1}, &name);
+                    self.status_message=StatusMessage::from(format!("Renamed column {} to {}.", num_to_let(x), name));
+                }
+            }
+            Command::ValidateColumn => {
+                let x = self.cell_index.x;
+                let current = self.document.table.rules.get(&x).map(Rule::describe).unwrap_or_default();
+                let prompt = format!("Rule for column {} (numeric, matches PATTERN, one of A, B; empty removes): ", self.column_label(x).trim_end());
+                if let Some(text) = self.prompt_with(&prompt, current)?{
+                    let message = self.set_rule(x, &text);
+                    self.status_message=StatusMessage::from(message);
+                }
+            }
+            Command::NextViolation => self.jump_to_violation(),
+            Command::HighlightDuplicates => self.toggle_duplicates(),
+            Command::FilterDuplicates => {
+                if matches!(self.filter, Some(Filter{test: RowTest::Duplicates, ..})){
+                    self.filter = None;
+                    self.status_message=StatusMessage::from(String::from("Showing all rows."));
+                } else {
+                    self.show_rows("duplicates", RowTest::Duplicates);
+                }
+            }
+            Command::DeleteDuplicates => self.delete_duplicates(),
+            Command::Transpose => self.transpose(),
+            Command::CellHistory => self.cell_history()?,
+            Command::HideColumn => {
+                let message = self.hide_columns(&[self.cell_index.x]);
+                self.status_message = StatusMessage::from(message);
+            }
+            Command::ShowColumns => {
+                let message = self.show_columns(None);
+                self.status_message = StatusMessage::from(message);
+            }
+            Command::LockRow => {
+                let (top, bottom) = self.document.selected_rows(self.cell_index.y);
+                let lock = !self.document.table.locked_rows.contains(&top);
+                let rows: Vec<usize> = (top..bottom+1).collect();
+                self.status_message = StatusMessage::from(self.lock(true, &rows, lock));
+            }
+            Command::Highlights => return self.edit_highlights(),
+            Command::TakeSnapshot => {
+                let name = format!("snapshot {}", self.document.checkpoints().len() + 1);
+                if let Some(name) = self.prompt_with("Snapshot name: ", name)?{
+                    self.take_snapshot(name.trim());
+                }
+            }
+            Command::RestoreSnapshot => return self.restore_snapshot(""),
+            Command::LockColumn => {
+                let x = self.cell_index.x;
+                let lock = !self.document.table.locked_cols.contains(&x);
+                self.status_message = StatusMessage::from(self.lock(false, &[x], lock));
+            }
+            Command::Join => self.join("")?,
+            Command::Append => self.append("")?,
+            Command::RunScript => {
+                if let Some(file_name) = self.prompt("Run script: ")?{
+                    self.run_script(Path::new(file_name.trim()));
+                }
+            }
+            Command::Script(index) => {
+                if let Some(file) = self.scripts.get(index).cloned(){
+                    self.run_script(&file);
+                }
+            }
+            Command::TransformCells => {
+                let prompt = "Transform (s/find/replace/, trim, upper, lower, prefix X, suffix X, strip X): ";
+                if let Some(text) = self.prompt(prompt)?{
+                    self.transform_cells(&text)?;
+                }
+            }
+            Command::ViewCell => self.view_cell()?,
+            Command::Pivot => {
+                let x = self.cell_index.x;
+                let rows = if self.header { self.column_label(x).trim().to_string() } else { formula::column_name(x) };
+                if let Some(text) = self.prompt_with("Pivot (rows, columns, sum|mean|count|min|max values): ", format!("{}, ", rows))?{
+                    self.pivot(&text);
+                }
+            }
+            Command::Describe => self.describe(),
+            Command::EditInEditor => self.edit_in_editor()?,
+            Command::SplitColumn => {
+                if let Some(text) = self.prompt("Split column on (text, space, tab or widths like 3,2,4): ")?{
+                    self.split_column(&text);
+                }
+            }
+            Command::MergeColumns => {
+                if let Some(separator) = self.prompt_with("Merge columns with: ", String::from(" "))?{
+                    self.merge_columns(&separator);
+                }
+            }
+            Command::ExportSelection => {
+                let what = if self.document.highlighted_positions().len() < 2 { "table" } else { "selection" };
+                if let Some(file_name) = self.prompt(&format!("Export {} to (empty copies it to the clipboard): ", what))?{
+                    self.export_selection(file_name.trim())?;
+                }
+            }
+            Command::AlignColumn => {
+                //automatic, then left, centered and right in turn
+                let x = self.cell_index.x;
+                let next = match self.document.table.alignments.get(&x) {
+                    None => Some(Align::Left),
+                    Some(Align::Left) => Some(Align::Center),
+                    Some(Align::Center) => Some(Align::Right),
+                    Some(Align::Right) => None,
+                };
+                self.align_column(x, next);
+            }
+            Command::NumberFormat => {
+                let current = self.document.table.number_formats.get(&self.cell_index.x).map(NumberFormat::text).unwrap_or_default();
+                if let Some(text) = self.prompt_with("Number format (fixed, thousands, percent or scientific, then decimals; off to clear): ", current)?{
+                    self.format_column(self.cell_index.x, &text);
+                }
+            }
+            Command::EditNote => {
+                let (x, y) = (self.cell_index.x, self.cell_index.y);
+                let current = self.document.note(x, y).unwrap_or_default().to_string();
+                if let Some(text) = self.prompt_with("Note (empty to remove): ", current)?{
+                    self.document.set_note(x, y, text.trim());
+                    let message = if text.trim().is_empty() { String::from("Note removed.") } else { format!("Note: {}", text.trim()) };
+                    self.status_message=StatusMessage::from(message);
+                }
+            }
+            //highlight cells to the given direction...
+            Command::GrowSelectionLeft => {
+                self.status_message=StatusMessage::from(String::from("Selection mode."));
+                let mut count :usize= 1;
+                self.highlight_col(self.cell_index.x-count, self.cell_index.x);
+                self.refresh_screen()?;
+                let mut next_key = pressed_key;
+                while self.key_to_action(next_key) == command{
+                    count += 1;
+                    let startx = self.cell_index.x.saturating_sub(count);
+                    self.highlight_col(startx, self.cell_index.x);
+                    self.refresh_screen()?;
+                    next_key = self.read_key()?;
+                }
+                self.status_message=StatusMessage::from(String::from("Stopped selection."));
+                return Ok(());
+            }
+            Command::GrowSelectionRight => {
+                self.status_message=StatusMessage::from(String::from("Selection mode."));
+                let mut count :usize= 1;
+                self.highlight_col(self.cell_index.x, self.cell_index.x+count);
+                self.refresh_screen()?;
+                let mut next_key = pressed_key;
+                while self.key_to_action(next_key) == command{
+                    count += 1;
+                    self.highlight_col(self.cell_index.x, self.cell_index.x+count);
+                    self.refresh_screen()?;
+                    next_key = self.read_key()?;
+                }
+                self.status_message=StatusMessage::from(String::from("Stopped selection."));
+                return Ok(());
+            }
+            Command::GrowSelectionUp => {
+                self.status_message=StatusMessage::from(String::from("Selection mode."));
+                let mut count :usize= 1;
+                self.highlight_row(self.cell_index.y-count, self.cell_index.y);
+                self.refresh_screen()?;
+                let mut next_key = pressed_key;
+                while self.key_to_action(next_key) == command{
+                    count += 1;
+                    let starty = self.cell_index.y.saturating_sub(count);
+                    self.highlight_row(starty, self.cell_index.y);
+                    self.refresh_screen()?;
+                    next_key = self.read_key()?;
+                }
+                self.status_message=StatusMessage::from(String::from("Stopped selection."));
+                return Ok(());
+            }
+            Command::GrowSelectionDown => {
+                self.status_message=StatusMessage::from(String::from("Selection mode."));
+                let mut next_key: Key = pressed_key;
+                let mut count :usize= 1;
+                while self.key_to_action(next_key) == command{
+                    count += 1;
+                    self.highlight_row(self.cell_index.y, self.cell_index.y+count);
+                    self.refresh_screen()?;
+                    next_key = self.read_key()?;
+                }
+                self.status_message=StatusMessage::from(String::from("Stopped selection."));
+                return Ok(());
+            }
+            //highlight all data from current positon to the end of document in the selected direction
+            Command::SelectRowUp => {
+                self.document.highlight(&self.cell_index);
+                self.highlight_row(1,self.cell_index.y);
+                return Ok(());
+            }
+            Command::SelectRowDown => {
+                self.document.highlight(&self.cell_index);
+                self.highlight_row(self.cell_index.y,self.document.table.num_rows()+1);
+                return Ok(());
+            }
+            Command::SelectColLeft => {
+                self.document.highlight(&self.cell_index);
+                self.highlight_col(1,self.cell_index.x);
+                return Ok(());
+            }
+            Command::SelectColRight => {
+                self.document.highlight(&self.cell_index);
+                self.highlight_col(self.cell_index.x,self.document.table.num_cols()+1);
+                return Ok(());
+            }
+            Command::MoveUp
+            | Command::MoveDown
+            | Command::MoveLeft
+            | Command::MoveRight
+            | Command::PageUp
+            | Command::PageDown
+            | Command::End
+            | Command::Home => self.move_position(command),
+            Command::Click(column, row) => {
+                if let Some(at) = self.cell_at(column as usize, row as usize){
+                    self.cell_index = at;
+                }
+            }
+            Command::Nothing => (),
+        }
+        self.report_refused();
+        //sorting a read-only file (or undoing that) only changes the view, so there's nothing to save
+        if self.document.read_only && was_saved
+        {
+            self.document.mark_saved();
+        }
+
+        self.update_document_dimensions();
+        //a search or jump can land in a hidden column, which the cursor can't sit on
+        self.cell_index.x = self.shown_col(self.cell_index.x, true);
+
+        //if trying to escape the boundaries of a page, highlight the cells for that row/column
+        if self.cell_index.y == 0{
+            self.cell_index.y+=1;
+            self.document.highlight(&self.cell_index);
+            self.highlight_row(1,self.document.table.num_rows()+1);
+            return Ok(());
+        }
+        if self.cell_index.x == 0{
+            self.cell_index.x+=1;
+            self.document.highlight(&self.cell_index);
+            self.highlight_col(1,self.document.table.num_cols()+1);
+            return Ok(());
+        }
+        self.document.highlight(&self.cell_index);
+        self.scroll();
+        Ok(())
+    }
+
+    //updating document information after actions
+    fn update_document_dimensions(&mut self){
+        if self.edge_behavior != EdgeBehavior::Grow{
+            return;
+        }
+        let num_rows = self.document.table.num_rows();
+        let num_cols = self.document.table.num_cols();        
+        
+        if self.cell_index.y > num_rows{
+            self.document.insert_newrow(&self.cell_index);
+        }
+        if self.cell_index.x > num_cols{
+            self.document.insert_newcol(&self.cell_index);
+        }
+    }
+
+    //highight group of cells in the y direction
+    fn highlight_row(&mut self,starty: usize, endy: usize){
+        let mut pos: Position;
+        let mut x: usize;
+        if starty < 1 && endy > self.document.table.num_rows(){
+            return;
+        }
+        for y in starty..endy{
+            if !self.is_shown(y){
+                continue;
+            }
+            x = self.cell_index.x;
+            pos = Position{x,y};
+            self.document.multi_highlight(&pos);
+        }
+    }
+    //highlight group of cells in the x direction
+    fn highlight_col(&mut self, startx: usize, endx: usize){
+        let mut pos: Position;
+        let mut y: usize;
+        if startx < 1 || endx > self.document.table.num_cols()+1{
+            return;
+        }
+        for x in startx..endx{
+            y = self.cell_index.y;
+            pos= Position{x,y};
+            self.document.multi_highlight(&pos);
+        }
+    }
+    //changing current position and adjusting document crop to fit in terminal
+    fn scroll(&mut self){
+        let Position {x , y} = self.cell_index;
+        let width = self.terminal.size().width as usize;
+        let height = self.table_height();
+        let offset = &mut self.offset;
+        //y is straight forward, one row for one terminal pixel.
+        //a frozen header is always on screen, but covers the row at offset.y
+        if self.freeze_header && offset.y > 1 && (2..offset.y+1).contains(&y){
+            offset.y = y - 1;
+        }
+        else if y < offset.y && !(self.freeze_header && y == 1){
+            offset.y = y;
+        }
+        else if self.filter.is_none() && y >= offset.y.saturating_add(height){
+            offset.y = y.saturating_sub(height).saturating_add(1);
         }
         /* need to convert the length of row to the number of terminal pixels.
         This is to detemine how far and when to scroll */
         let mut strlen = 0;
         for i in offset.x..x+1{
+            if self.document.table.hidden.contains(&i){
+                continue;
+            }
             strlen += self.document.table.column_width(i);
             strlen += 4; //to offset added printer characters between lines
         }
-        if strlen.saturating_sub(4) <= self.document.table.column_width(offset.x) && offset.x >= 1{
-            offset.x = offset.x.saturating_sub(1);
+        if strlen.saturating_sub(4) <= self.document.table.column_width(offset.x) && offset.x >= 1{
+            offset.x = offset.x.saturating_sub(1);
+        }
+        else if strlen >= offset.x.saturating_add(width) {
+            offset.x = offset.x.saturating_add(1);
+        }
+        //with a filter, only the rows it leaves count towards filling the screen
+        if let Some(filter) = &self.filter {
+            let top = filter.rows.partition_point(|&row| row < self.offset.y);
+            let at = filter.rows.partition_point(|&row| row < y);
+            if at >= top + height {
+                self.offset.y = filter.rows[at + 1 - height];
+            }
+        }
+        //wrapped rows take up more than one line, so keep scrolling until the cursor row fits
+        if self.wrap {
+            while self.offset.y < y && self.lines_through(y) > height {
+                self.offset.y = self.shown_row_by(self.offset.y, 1).unwrap_or(self.offset.y + 1);
+            }
+        }
+    }
+
+    //terminal lines used by the visible rows up to and including row `y`
+    fn lines_through(&self, y: usize) -> usize {
+        self.visible_rows()
+            .take_while(|&row| row <= y || row == 1)
+            .map(|row| self.row_lines(row).len())
+            .sum()
+    }
+
+    //does what is says it does
+    fn move_position(&mut self, command: Command){
+        let terminal_height = self.terminal.size().height as usize;
+        let height = self.document.table.num_rows();
+        let width = self.document.table.num_cols();
+        let Position {mut x, mut y,} = self.cell_index;
+        //with a filter, up and down step over the rows it hides
+        let steps = match command{
+            Command::MoveUp => -1,
+            Command::MoveDown => 1,
+            Command::PageUp => -(terminal_height as isize),
+            Command::PageDown => terminal_height as isize,
+            _ => 0,
+        };
+        if self.filter.is_some() && steps != 0{
+            if let Some(row) = self.shown_row_by(y, steps){
+                self.cell_index.y = row;
+            }
+            return;
+        }
+        let edge = self.edge_behavior;
+        match command{
+            Command::MoveUp if edge == EdgeBehavior::Wrap && y == 1 && x > 1 => {
+                x -= 1;
+                y = height;
+            }
+            Command::MoveUp => y = y.saturating_sub(1),
+            Command::MoveDown if y >= height && edge == EdgeBehavior::Wrap && x < width => {
+                x += 1;
+                y = 1;
+            }
+            Command::MoveDown if y < height || (y == height && edge == EdgeBehavior::Grow) => y = y.saturating_add(1),
+            Command::MoveLeft if edge == EdgeBehavior::Wrap && x == 1 && y > 1 => {
+                x = width;
+                y -= 1;
+            }
+            Command::MoveLeft => x = x.saturating_sub(1),
+            Command::MoveRight if x >= width && edge == EdgeBehavior::Wrap && y < height => {
+                x = 1;
+                y += 1;
+            }
+            Command::MoveRight if x < width || (x == width && edge == EdgeBehavior::Grow) => x += 1,
+            Command::PageUp => {
+                y = if y > terminal_height+1 {
+                    y.saturating_sub(terminal_height)
+                } else {
+                    1
+                }
+            }
+            Command::PageDown => {
+                y = if y.saturating_add(terminal_height) < height {
+                    y.saturating_add(terminal_height)
+                }
+                else {
+                    height-2
+                }
+            }
+            Command::Home => x=1,
+            Command::End => x = width,
+            _ => {},
         }
-        else if strlen >= offset.x.saturating_add(width) {
-            offset.x = offset.x.saturating_add(1);
+        //hidden columns are stepped over in the direction of the move
+        let x = self.shown_col(x, !matches!(command, Command::MoveLeft | Command::End));
+        self.cell_index = Position{x , y}
+        
+    }
+
+    //the rest of the code is just a bunch of string formatting to display data on the screen neatly
+    fn welcome_lines(&self) -> Vec<String>
+    {
+        vec![
+            format!("CSVEDIT -- version: {}", VERSION),
+            String::new(),
+            String::from("Open a file with: clicsv <file.csv>"),
+            String::from("Press ? for help"),
+            String::from("Start typing to edit"),
+            String::from("Ctrl-S to save"),
+        ]
+    }
+
+    fn welcome_message(&self, line: &str) -> String
+    {
+        let width = self.terminal.size().width as usize;
+        #[allow(clippy::arithmetic_side_effects, clippy::integer_division)]
+        let padding = width.saturating_sub(line.width())/2;
+        let spaces = " ".repeat(padding);
+        let welcome_message = format!("{}{}",spaces,line);
+        format!("{}{}", color::Fg(self.colors.status_fg), table::truncate_width(&welcome_message, width))
+    }
+
+    fn draw_status_bar(&self) 
+    {
+        let mut status;
+        let width = self.terminal.size().width as usize;
+        let modified_indicator = if self.document.read_only
+        {
+            " (read-only)"
+        }
+        else if !self.document.is_saved() 
+        {
+            " (modified)"
+        }
+        else
+        {
+            ""
+        };
+
+        let mut file_name = match &self.document.source
+        {
+            Some(url) => table::truncate_width(fetch::file_name(url), 20).to_string(),
+            None => "[No Name]".to_string(),
+        };
+        if let Some(name) = &self.document.file_name 
+        {
+            file_name = table::truncate_width(name, 20).to_string();
+        }
+        if let Some(sheet) = self.document.sheet_name()
+        {
+            file_name = format!("{} [{}]", file_name, sheet);
+        }
+        //with several files open, the others are listed around this one, numbered for :b N and
+        //marked + when they have unsaved changes
+        if self.buffers.len() > 1
+        {
+            file_name = (0..self.buffers.len()).map(|i| {
+                if i == self.buffer
+                {
+                    return format!("[{}:{}]", i + 1, file_name);
+                }
+                let document = self.buffer_document(i);
+                let name: String = document.file_name.as_deref().unwrap_or("[No Name]").chars().take(20).collect();
+                format!("{}:{}{}", i + 1, name, if document.is_saved() { "" } else { "+" })
+            }).collect::<Vec<_>>().join(" ");
+        }
+        status = format!(
+            "{} - rows:{} cols:{}{}",
+            file_name,
+            self.document.table.num_rows(),
+            self.document.table.num_cols(),
+            modified_indicator
+        );
+
+        let dialect_indicator = if self.show_dialect
+        {
+            format!("{} | ", self.document.dialect.indicator())
+        }
+        else
+        {
+            String::new()
+        };
+        let column_label = if self.header_labels
+        {
+            format!(" ({})", self.column_label(self.cell_index.x))
+        }
+        else
+        {
+            String::new()
+        };
+        let filter_indicator = match &self.filter
+        {
+            Some(filter) => format!("[{}] ", filter.text),
+            None => String::new(),
+        };
+        let hidden_indicator = match self.document.table.hidden.len()
+        {
+            0 => String::new(),
+            hidden => format!("[{} hidden] ", hidden),
+        };
+        let locks_indicator = if self.document.override_locks { "[locks off] " } else { "" };
+        //the corners of a selection of more than one cell, so it isn't lost track of off screen
+        let positions = self.document.highlighted_positions();
+        let selection_indicator = match positions.len()
+        {
+            0 | 1 => String::new(),
+            _ => {
+                let (xs, ys): (Vec<usize>, Vec<usize>) = positions.into_iter().unzip();
+                let corner = |x: Option<&usize>, y: Option<&usize>| format!("{}{}", formula::column_name(*x.unwrap_or(&1)), y.unwrap_or(&1));
+                format!("[{}:{} selected] ", corner(xs.iter().min(), ys.iter().min()), corner(xs.iter().max(), ys.iter().max()))
+            }
+        };
+        let recording_indicator = match &self.recording
+        {
+            Some((register, _)) => format!("[recording @{}] ", register),
+            None => String::new(),
+        };
+        //the current cell says when it is locked
+        let locked = if self.document.table.is_locked(self.cell_index.x, self.cell_index.y) { "locked " } else { "" };
+        let line_indicator = format!(
+            "{}{}{}{}{}{}{}{} | y: {}/{} x: {}/{}{}",
+            recording_indicator,
+            selection_indicator,
+            locks_indicator,
+            hidden_indicator,
+            filter_indicator,
+            dialect_indicator,
+            locked,
+            self.document.cell_type(self.cell_index.x, self.cell_index.y).name(),
+            self.cell_index.y,
+            self.document.table.num_rows(),
+            self.cell_index.x,
+            self.document.table.num_cols(),
+            column_label
+        );
+
+        #[allow(clippy::arithmetic_side_effects)]
+        let len = status.width() + line_indicator.width();
+        status.push_str(&" ".repeat(width.saturating_sub(len)));
+        status = format!("{}{}", status, line_indicator);
+        status = table::truncate_width(&status, width).to_string();
+        Terminal::set_bg_color(self.colors.status_bg);
+        Terminal::set_fg_color(self.colors.status_fg);
+        println!("{}\r", status);
+        Terminal::reset_fg_color();
+        Terminal::reset_bg_color();
+    }
+
+    //the selected cell's address and its whole contents, which the grid may have cut short;
+    //a formula also shows what it works out to
+    fn draw_cell_line(&self)
+    {
+        Terminal::clear_current_line();
+        let (x, y) = (self.cell_index.x, self.cell_index.y);
+        let address = format!("{}{}", formula::column_name(x), y);
+        let mut contents = match self.document.table.get(x, y)
+        {
+            Some(cell) => cell.value().replace("\r\n", "↵").replace('\n', "↵"),
+            None => String::new(),
+        };
+        if formula::is_formula(&contents)
+        {
+            contents = format!("{} → {}", contents, self.document.formula_display(x, y));
+        }
+        let mut line = table::truncate_width(&format!("{} {}", address, contents), self.terminal.size().width as usize).to_string();
+        let contents = line.split_off(address.len().min(line.len()));
+        println!("{}{}{}{}\r", color::Fg(self.colors.status_fg), address, color::Fg(color::Reset), contents);
+    }
+
+    fn draw_message_bar(&self)
+    {
+        Terminal::clear_current_line();
+        let message = &self.status_message;
+        if Instant::now() - message.time < Duration::new(5, 0)
+        {
+            print!("{}", table::truncate_width(&message.text, self.terminal.size().width as usize));
+        }
+    }
+
+    //formula cells show their computed value unless formula display is toggled on
+    fn cell_display(&self, cell: &Cell) -> String {
+        if self.show_formulas{
+            return cell.contents.replace("\r\n", "↵").replace('\n', "↵");
+        }
+        let text = if formula::is_formula(&cell.contents){
+            self.document.formula_display(cell.x_loc, cell.y_loc)
+        } else {
+            //line breaks inside quoted fields would break the grid
+            cell.contents.replace("\r\n", "↵").replace('\n', "↵")
+        };
+        //a column's number format leaves its header alone
+        let format = self.document.table.number_formats.get(&cell.x_loc).filter(|_| !(self.header && cell.y_loc == 1));
+        format.and_then(|format| format.apply(&text)).unwrap_or(text)
+    }
+
+    //where a cell sits in its column: where the column was set to, or else numbers on the right.
+    //A cell being edited, or a formula on show, sits on the left
+    fn alignment(&self, cell: &Cell) -> Align {
+        let editing = self.editing.is_some() && cell.x_loc == self.cell_index.x && cell.y_loc == self.cell_index.y;
+        let formula_shown = self.show_formulas && formula::is_formula(&cell.contents);
+        if editing || formula_shown {
+            return Align::Left;
+        }
+        match self.document.table.alignments.get(&cell.x_loc) {
+            Some(&align) => align,
+            None if self.document.cell_type(cell.x_loc, cell.y_loc).is_number() => Align::Right,
+            None => Align::Left,
+        }
+    }
+
+    //the lines of a cell's contents: one cut to the column width, or with wrapping on, several
+    fn cell_lines(&self, cell: &Cell, column_width: usize) -> Vec<(String, usize)> {
+        if let Some(edit) = &self.editing {
+            if cell.x_loc == self.cell_index.x && cell.y_loc == self.cell_index.y {
+                return vec![edit.render(column_width)];
+            }
+        }
+        let text = self.cell_display(cell);
+        let lines = if self.wrap {
+            table::wrap_width(&text, column_width, MAX_WRAP_LINES)
+        } else {
+            vec![table::fit_width(&text, column_width)]
+        };
+        lines.into_iter().map(|line| {
+            let width = line.width();
+            (line, width)
+        }).collect()
+    }
+
+    //the terminal lines showing document row `y`; more than one when a wrapped cell needs it
+    fn row_lines(&self, y: usize) -> Vec<String>{
+        let ncols: usize = self.document.table.num_cols();
+        let width: usize = self.terminal.size().width as usize;
+        let row: Vec<&Cell> = self.document.get_row(y);
+        let nrows: usize = self.document.table.num_rows();
+        if row.len() != ncols{
+            Terminal::clear_screen();
+            println!("Error: rows have unequal amount of columns. Exiting...");
+            std::process::exit(1);
+        }
+        let cells: Vec<_> = row.iter().take(ncols).skip(self.offset.x)
+            .filter(|cell| !self.document.table.hidden.contains(&cell.x_loc))
+            .map(|cell| {
+                let column_width = self.document.table.column_width(cell.x_loc);
+                (*cell, column_width, self.cell_lines(cell, column_width))
+            })
+            .collect();
+        let height = cells.iter().map(|(_, _, lines)| lines.len()).max().unwrap_or(1);
+        let label = self.row_number(y).map(|number| number.to_string()).unwrap_or_default();
+        let gutter_width = self.gutter_width().saturating_sub(1);
+        //rows removed since the compared file are marked on the row now in their place
+        let removed = self.comparison.as_ref().is_some_and(|comparison| {
+            comparison.diff.removed_above(y) > 0 || (y == nrows && comparison.diff.removed_above(y + 1) > 0)
+        });
+        let gutter_color = if removed { self.colors.removed_fg } else { self.colors.status_fg };
+
+        (0..height).map(|line| {
+            let mut row_str: String = String::new();
+            //bytes that take up no room on screen: colour codes and multi-byte characters
+            let mut diff: usize = 0;
+            for (cell, column_width, lines) in &cells{
+                let s:String;
+                let (contents, contents_width) = lines.get(line).cloned().unwrap_or_default();
+                diff += contents.len().saturating_sub(contents_width);
+                //the last column of the width is the space drawn after every cell, and the rest of the
+                //room left goes before the contents for right and centered cells
+                let filling_width = column_width.saturating_sub(contents_width + 1);
+                let (before, _) = self.alignment(cell).padding(filling_width);
+                let contents = " ".repeat(before) + contents.as_str();
+                let filling_width = column_width.saturating_sub(contents.width());
+                //a cell with a note has a mark in its top right corner, like a spreadsheet's
+                let marker = if line == 0 && self.document.note(cell.x_loc, cell.y_loc).is_some() {
+                    let marker = format!("{}◥{}", color::Fg(self.colors.note_fg), color::Fg(color::Reset));
+                    diff += marker.len() - 1;
+                    marker
+                } else {
+                    String::from(" ")
+                };
+                let change = self.comparison.as_ref().and_then(|comparison| comparison.diff.change(cell.x_loc, cell.y_loc));
+                //cells that have no background of their own take the crosshair's
+                let crossed = self.crosshair && (cell.x_loc == self.cell_index.x || cell.y_loc == self.cell_index.y);
+                let shade = if crossed { color::Bg(self.colors.crosshair_bg).to_string() } else { String::new() };
+                if cell.x_loc == self.cell_index.x && cell.y_loc == self.cell_index.y{
+                    //the current cell stands out from the rest of the selection
+                    let styled = format!("{}{}{}", style::Bold, color::Fg(self.colors.cursor_fg), color::Bg(self.colors.cursor_bg));
+                    let reset = format!("{}", style::Reset);
+                    diff += styled.len() + reset.len();
+                    s = format!("{}{}{}{}{}{} ", styled, contents, &" ".repeat(filling_width), reset, marker, "│");
+                } else if let (false, Some(change)) = (cell.highlighted, change){
+                    let background = match change { Change::Added => self.colors.added_bg, Change::Changed(_) => self.colors.changed_bg };
+                    let styled = format!("{}{}", color::Fg(self.colors.match_fg), color::Bg(background));
+                    let reset = format!("{}{}", color::Bg(color::Reset), color::Fg(color::Reset));
+                    diff += styled.len() + reset.len();
+                    s = format!("{}{}{}{}{}{} ", styled, contents, &" ".repeat(filling_width), reset, marker, "│");
+                } else if !cell.highlighted && self.document.is_match(cell.x_loc, cell.y_loc){
+                    let styled = format!("{}{}", color::Fg(self.colors.match_fg), color::Bg(self.colors.match_bg));
+                    let reset = format!("{}{}", color::Bg(color::Reset), color::Fg(color::Reset));
+                    diff += styled.len() + reset.len();
+                    s = format!("{}{}{}{}{}{} ", styled, contents, &" ".repeat(filling_width), reset, marker, "│");
+                } else if cell.highlighted{
+                    let styled = format!("{}{}", color::Fg(self.colors.status_fg), color::Bg(self.colors.status_bg));
+                    let reset = format!("{}{}", color::Bg(color::Reset), color::Fg(color::Reset));
+                    diff += styled.len() + reset.len();
+                    s = format!("{}{}{}{}{}{} ", styled, contents, &" ".repeat(filling_width), reset, marker, "│");
+                } else if self.is_duplicate(cell.y_loc){
+                    let styled = format!("{}{}", color::Fg(self.colors.match_fg), color::Bg(self.colors.duplicate_bg));
+                    let reset = format!("{}{}", color::Bg(color::Reset), color::Fg(color::Reset));
+                    diff += styled.len() + reset.len();
+                    s = format!("{}{}{}{}{}{} ", styled, contents, &" ".repeat(filling_width), reset, marker, "│");
+                } else if self.breaks_rule(cell.x_loc, cell.y_loc){
+                    let styled = format!("{}{}", shade, color::Fg(self.colors.invalid_fg));
+                    let reset = format!("{}{}", color::Bg(color::Reset), color::Fg(color::Reset));
+                    diff += styled.len() + reset.len();
+                    s = format!("{}{}{}{}{}{} ", styled, contents, &" ".repeat(filling_width), reset, marker, "│");
+                } else if let Some(highlight) = self.highlight_for(cell){
+                    let (r, g, b) = highlight.color;
+                    let styled = match highlight.background {
+                        true => format!("{}{}", color::Fg(self.colors.match_fg), color::Bg(Shade(color::Rgb(r, g, b)))),
+                        false => format!("{}{}", shade, color::Fg(Shade(color::Rgb(r, g, b)))),
+                    };
+                    let reset = format!("{}{}", color::Bg(color::Reset), color::Fg(color::Reset));
+                    diff += styled.len() + reset.len();
+                    s = format!("{}{}{}{}{}{} ", styled, contents, &" ".repeat(filling_width), reset, marker, "│");
+                } else if self.document.table.is_locked(cell.x_loc, cell.y_loc){
+                    let styled = format!("{}{}", shade, color::Fg(self.colors.locked_fg));
+                    let reset = format!("{}{}", color::Bg(color::Reset), color::Fg(color::Reset));
+                    diff += styled.len() + reset.len();
+                    s = format!("{}{}{}{}{}{} ", styled, contents, &" ".repeat(filling_width), reset, marker, "│");
+                } else if crossed{
+                    let reset = format!("{}", color::Bg(color::Reset));
+                    diff += shade.len() + reset.len();
+                    s = format!("{}{}{}{}{}{} ", shade, contents, &" ".repeat(filling_width), reset, marker, "│");
+                } else {
+                    s = format!(
+                        "{}{}{}{} ", 
+                        contents, 
+                        &" ".repeat(filling_width),
+                        marker,
+                        "│");
+                }
+                row_str.push_str(&s);
+                if row_str.len() > width+diff{
+                    break;
+                }
+            }
+            if !self.gutter {
+                return row_str;
+            }
+            //only the first line of a row is numbered
+            let terminal_row_str = if line == 0 {
+                format!("{:<1$}", label, gutter_width)
+            } else {
+                " ".repeat(gutter_width)
+            };
+            format!(
+                "{}{}│{}{}",
+                color::Fg(gutter_color),
+                terminal_row_str, 
+                color::Fg(color::Reset),
+                row_str
+            )
+        }).collect()
+    }
+
+    //the number a row goes by in the gutter: rows below the header count from `number_from`, and
+    //the header row has none
+    fn row_number(&self, y: usize) -> Option<usize> {
+        let first = self.first_data_row();
+        (y >= first).then(|| y - first + self.number_from)
+    }
+
+    //the terminal columns the gutter takes: room for the highest row number and the line after it,
+    //or none when it is hidden
+    fn gutter_width(&self) -> usize {
+        if !self.gutter {
+            return 0;
+        }
+        let nrows = self.document.table.num_rows();
+        self.row_number(nrows).unwrap_or(0).to_string().len() + 1
+    }
+
+    //the cell drawn at a (1-based) terminal column and row, if any
+    fn cell_at(&self, column: usize, row: usize) -> Option<Position> {
+        //the column letters and the line under them come first
+        let line = row.checked_sub(3).filter(|&line| line < self.table_height())?;
+        let y = self.visible_rows()
+            .flat_map(|y| std::iter::repeat_n(y, self.row_lines(y).len()))
+            .nth(line)?;
+        let mut left = self.gutter_width();
+        for x in self.offset.x+1..self.document.table.num_cols()+1 {
+            if self.document.table.hidden.contains(&x) {
+                continue;
+            }
+            //each cell is followed by " │ "
+            let right = left + self.document.table.column_width(x) + 3;
+            if (left..right).contains(&(column - 1)) {
+                return Some(Position{x, y});
+            }
+            left = right;
+        }
+        None
+    }
+
+    //terminal lines of cells that fit between the column letters and the footer
+    fn table_height(&self) -> usize {
+        let footer = if self.show_totals { 1 } else { 0 };
+        (self.terminal.size().height as usize).saturating_sub(2 + footer)
+    }
+
+    //the document rows to draw from the top of the screen down.
+    //a frozen header takes the place of the first visible row once scrolled
+    fn visible_rows(&self) -> Box<dyn Iterator<Item = usize> + '_> {
+        let nrows = self.document.table.num_rows();
+        let frozen = self.freeze_header && self.offset.y > 1;
+        let start = if frozen { self.offset.y + 1 } else { self.offset.y };
+        let rows: Box<dyn Iterator<Item = usize>> = match &self.filter {
+            Some(filter) => Box::new(filter.rows[filter.rows.partition_point(|&row| row < start)..].iter().copied()),
+            None => Box::new(start..nrows+1),
+        };
+        Box::new(frozen.then_some(1).into_iter().chain(rows))
+    }
+
+    //the column letters (or names) and the line under them
+    fn header_lines(&self) -> Vec<String>{
+        let width: usize = self.terminal.size().width as usize;
+        let ncols: usize = self.document.table.num_cols();
+        let mut col_str: String = String::new();
+        (self.offset.x+1..ncols+1).filter(|x| !self.document.table.hidden.contains(x)).for_each(|x| {
+            let column_width = self.document.table.column_width(x);
+            let label = if self.header_labels {
+                table::fit_width(&self.column_label(x), column_width)
+            } else {
+                num_to_let(x).to_string()
+            };
+            //the quality view puts the count of blank cells at the right, like A  ∅3
+            let blanks = match self.blanks.count(x) {
+                count if self.show_blanks && count > 0 => format!("∅{}", count),
+                _ => String::new(),
+            };
+            let label = table::truncate_width(&label, column_width.saturating_sub(blanks.width()).max(1));
+            let fill: usize = column_width.saturating_sub(label.width() + blanks.width());
+            col_str += &format!("{}{}{} {} ", label, &" ".repeat(fill), blanks, "|");
+        });
+        col_str = format!("{}{}", " ".repeat(self.gutter_width()), col_str);
+        //cut before coloring, so the escape code doesn't count toward the width
+        col_str = format!("{}{}", color::Fg(self.colors.status_fg), table::truncate_width(&col_str, width));
+        vec![col_str, format!("{}{}", color::Fg(self.colors.status_fg), "-".repeat(width))]
+    }
+
+
+    fn show_stats(&mut self, statistic: Option<Statistic>) -> Result<(), std::io::Error>
+    {
+        let lines = self.selection_stats(statistic);
+        if lines.is_empty()
+        {
+            self.status_message = StatusMessage::from(String::from("Nothing to summarize"));
+            return Ok(());
+        }
+        let title = statistic.map_or_else(|| String::from("Statistics"), |statistic| statistic.label());
+        self.show_popup(&title, &lines)
+    }
+
+    //statistics for each column of the selection, with formulas counting as their results: every
+    //figure of the summary, or only `statistic`
+    fn selection_stats(&self, statistic: Option<Statistic>) -> Vec<String>
+    {
+        let mut columns: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for (x, y) in self.document.highlighted_positions()
+        {
+            //a header cell names the column rather than being one of its values
+            if self.header && y == 1
+            {
+                continue;
+            }
+            columns.entry(x).or_default().push(self.document.value(x, y).unwrap_or_default());
+        }
+        let mut lines = Vec::new();
+        for (x, values) in columns
+        {
+            if !lines.is_empty()
+            {
+                lines.push(String::new());
+            }
+            let name = formula::column_name(x);
+            if self.header
+            {
+                lines.push(format!("Column {} ({})", name, self.column_label(x)));
+            }
+            else
+            {
+                lines.push(format!("Column {}", name));
+            }
+            match statistic
+            {
+                Some(statistic) => lines.push(format!("  {} {}", statistic.label(), statistic.of(values.iter().map(String::as_str)))),
+                None => {
+                    let summary = Summary::of(values.iter().map(String::as_str));
+                    lines.extend(summary.lines().into_iter().map(|line| format!("  {}", line)));
+                }
+            }
+        }
+        lines
+    }
+
+    //a popup counting each distinct value of the current column over the rows shown, with the share
+    //of them each makes up. `text` like `sum D` also works out that figure of column D for each
+    //value's rows
+    fn frequencies(&mut self, text: &str) -> Result<(), std::io::Error>
+    {
+        let ncols = self.document.table.num_cols();
+        let names: Vec<String> = if self.header { (1..ncols+1).map(|x| self.column_label(x)).collect() } else { Vec::new() };
+        let other = match text.trim().split_once(' ')
+        {
+            None if text.trim().is_empty() => None,
+            Some((aggregate, column)) => match (Aggregate::parse(aggregate), find_column(column, &names).filter(|&x| x <= ncols))
+            {
+                (Some(aggregate), Some(x)) => Some((aggregate, x)),
+                (None, _) => {
+                    self.status_message = StatusMessage::from(format!("Expected sum, mean, count, min or max, not {}", aggregate));
+                    return Ok(());
+                }
+                (_, None) => {
+                    self.status_message = StatusMessage::from(format!("No column {}", column.trim()));
+                    return Ok(());
+                }
+            },
+            None => {
+                self.status_message = StatusMessage::from(String::from("Usage: :freq [sum|mean|count|min|max COLUMN]"));
+                return Ok(());
+            }
+        };
+        let x = self.cell_index.x;
+        let first = if self.header { 2 } else { 1 };
+        let rows: Vec<(String, String)> = (first..self.document.table.num_rows()+1)
+            .filter(|&y| self.is_shown(y))
+            .map(|y| (
+                self.document.value(x, y).unwrap_or_default(),
+                other.and_then(|(_, other)| self.document.value(other, y)).unwrap_or_default(),
+            ))
+            .collect();
+        if rows.is_empty()
+        {
+            self.status_message = StatusMessage::from(String::from("Nothing to count"));
+            return Ok(());
+        }
+        let frequencies = Frequencies::of(&rows, other.map(|(aggregate, _)| aggregate));
+        let figure = other.map(|(aggregate, other)| format!("{} of {}", aggregate.name(), self.column_label(other).trim()));
+        let title = format!("{} values of {} in {} rows", frequencies.groups.len(), self.column_label(x).trim(), rows.len());
+        self.show_popup(&title, &frequencies.lines(figure.as_deref()))
+    }
+
+    //a cross tabulation of the rows shown, opened as a new file. `text` names the column whose values
+    //make the rows, the one whose values make the columns, and the figure where they meet, like
+    //`region, month, sum sales`; count needs no column
+    fn pivot(&mut self, text: &str)
+    {
+        let (row_x, column_x, aggregate, value_x) = match self.pivot_columns(text)
+        {
+            Ok(columns) => columns,
+            Err(e) => {
+                self.status_message = StatusMessage::from(e);
+                return;
+            }
+        };
+        let first = if self.header { 2 } else { 1 };
+        let rows: Vec<(String, String, String)> = (first..self.document.table.num_rows()+1)
+            .filter(|&y| self.is_shown(y))
+            .map(|y| (
+                self.document.value(row_x, y).unwrap_or_default(),
+                self.document.value(column_x, y).unwrap_or_default(),
+                //every row counts when there is no column of values
+                value_x.map_or(Some(String::from("1")), |x| self.document.value(x, y)).unwrap_or_default(),
+            ))
+            .collect();
+        if rows.is_empty()
+        {
+            self.status_message = StatusMessage::from(String::from("Nothing to pivot"));
+            return;
+        }
+        let name = |x: usize| if self.header { self.column_label(x).trim().to_string() } else { formula::column_name(x) };
+        let pivoted = aggregates::pivot(&rows, aggregate, &name(row_x));
+        let size = (pivoted.len() - 1, pivoted[0].len() - 1);
+        let figure = match value_x
+        {
+            Some(x) => format!("{} of {}", aggregate.name(), name(x)),
+            None => String::from("count"),
+        };
+        let message = format!(
+            "The {} by {} ({} rows) and {} ({} columns), unsaved; Alt-s saves it.",
+            figure, name(row_x), size.0, name(column_x), size.1
+        );
+        let index = self.push_buffer(self.document.derived(pivoted));
+        self.switch_buffer(index);
+        self.status_message = StatusMessage::from(message);
+    }
+
+    //a summary of every column over the rows shown, a row each with its type, count, share of blanks,
+    //range and mean or most common values, opened as a new file to scroll through like any table
+    fn describe(&mut self)
+    {
+        let first = self.first_data_row();
+        let rows: Vec<usize> = (first..self.document.table.num_rows()+1).filter(|&y| self.is_shown(y)).collect();
+        if rows.is_empty()
+        {
+            self.status_message = StatusMessage::from(String::from("Nothing to describe"));
+            return;
+        }
+        let ncols = self.document.table.num_cols();
+        let mut described = vec![stats::DESCRIBE_HEADER.iter().map(|name| name.to_string()).collect()];
+        for x in 1..ncols+1
+        {
+            let name = if self.header { self.column_label(x).trim().to_string() } else { formula::column_name(x) };
+            let values: Vec<String> = rows.iter().map(|&y| self.document.value(x, y).unwrap_or_default()).collect();
+            described.push(stats::describe(&name, &values));
         }
+        let message = format!("The {} columns over {} rows, unsaved; Ctrl-b goes back.", ncols, rows.len());
+        let index = self.push_buffer(self.document.derived(described));
+        self.switch_buffer(index);
+        self.status_message = StatusMessage::from(message);
+    }
+
+    //the row, column and value columns and the aggregate in `text`, for pivot
+    fn pivot_columns(&self, text: &str) -> Result<(usize, usize, Aggregate, Option<usize>), String>
+    {
+        let ncols = self.document.table.num_cols();
+        let names: Vec<String> = if self.header { (1..ncols+1).map(|x| self.column_label(x)).collect() } else { Vec::new() };
+        let column = |name: &str| find_column(name, &names).filter(|&x| x <= ncols).ok_or_else(|| format!("No column {}", name.trim()));
+        let [rows, columns, figure] = text.split(',').collect::<Vec<_>>()[..] else {
+            return Err(String::from("Usage: :pivot ROWS, COLUMNS, sum|mean|count|min|max [VALUES]"));
+        };
+        let (aggregate, values) = match figure.trim().split_once(' ')
+        {
+            Some((aggregate, values)) => (aggregate, Some(values)),
+            None => (figure.trim(), None),
+        };
+        let aggregate = Aggregate::parse(aggregate).ok_or_else(|| format!("Expected sum, mean, count, min or max, not {}", aggregate))?;
+        let values = match values
+        {
+            Some(values) => Some(column(values)?),
+            None if aggregate == Aggregate::Count => None,
+            None => return Err(format!("{} needs a column of values", aggregate.name())),
+        };
+        Ok((column(rows)?, column(columns)?, aggregate, values))
+    }
+
+    //moves to the next blank cell below the header, along the row and then down, starting over at
+    //the top after the last. Filtered out rows and hidden columns are passed over
+    fn next_blank(&mut self)
+    {
+        let first = self.first_data_row();
+        let (ncols, nrows) = (self.document.table.num_cols(), self.document.table.num_rows());
+        let cells: Vec<(usize, usize)> = (first..nrows+1)
+            .filter(|&y| self.is_shown(y))
+            .flat_map(|y| (1..ncols+1).map(move |x| (x, y)))
+            .filter(|(x, _)| !self.document.table.hidden.contains(x))
+            .collect();
+        let blanks: Vec<(usize, usize)> = cells.into_iter()
+            .filter(|&(x, y)| self.document.value(x, y).unwrap_or_default().trim().is_empty())
+            .collect();
+        let here = (self.cell_index.y, self.cell_index.x);
+        let next = blanks.iter().position(|&(x, y)| (y, x) > here).unwrap_or(0);
+        let message = match blanks.get(next)
+        {
+            Some(&(x, y)) => {
+                self.cell_index = Position{x, y};
+                format!("Blank cell {}{} ({} of {})", formula::column_name(x), y, next + 1, blanks.len())
+            }
+            None => String::from("No blank cells"),
+        };
+        self.status_message = StatusMessage::from(message);
     }
 
-    //does what is says it does
-    fn move_position(&mut self, key: Key){
-        let terminal_height = self.terminal.size().height as usize;
-        let height = self.document.table.num_rows();
-        let width = self.document.table.num_cols();
-        let Position {mut x, mut y,} = self.cell_index;
-        match key{
-            Key::Up => {
-                if y > 0{
-                    y = y.saturating_sub(1)
-                }
-            } 
-            Key::Down => {
-                if y <= height{
-                    y = y.saturating_add(1);
-                }
-            }
-            Key::Left => {
-                if x > 0 {
-                    x -= 1;
-                } 
+    //moves the cursor the way spreadsheets do with Ctrl and an arrow: from inside a run of filled cells
+    //to its last one in that direction, and otherwise to the next filled cell, or to the edge of the
+    //table when there is none. Filtered out rows and hidden columns are passed over
+    fn jump_to_data_edge(&mut self, command: Command)
+    {
+        let Position{x, y} = self.cell_index;
+        let filled = |x: usize, y: usize| !self.document.value(x, y).unwrap_or_default().trim().is_empty();
+        let (ncols, nrows) = (self.document.table.num_cols(), self.document.table.num_rows());
+        let rows = (1..nrows+1).filter(|&row| self.is_shown(row));
+        let cols = (1..ncols+1).filter(|col| !self.document.table.hidden.contains(col));
+        let here = filled(x, y);
+        match command
+        {
+            Command::JumpUp => {
+                let ahead: Vec<usize> = rows.filter(|&row| row < y).rev().collect();
+                self.cell_index.y = block_edge(&ahead, here, |row| filled(x, row)).unwrap_or(y);
             }
-            Key::Right => {
-                if x <= width {
-                    x += 1;
-                }
+            Command::JumpDown => {
+                let ahead: Vec<usize> = rows.filter(|&row| row > y).collect();
+                self.cell_index.y = block_edge(&ahead, here, |row| filled(x, row)).unwrap_or(y);
             }
-            Key::PageUp => {
-                y = if y > terminal_height+1 {
-                    y.saturating_sub(terminal_height)
-                } else {
-                    1
-                }
+            Command::JumpLeft => {
+                let ahead: Vec<usize> = cols.filter(|&col| col < x).rev().collect();
+                self.cell_index.x = block_edge(&ahead, here, |col| filled(col, y)).unwrap_or(x);
             }
-            Key::PageDown => {
-                y = if y.saturating_add(terminal_height) < height {
-                    y.saturating_add(terminal_height)
-                }
-                else {
-                    height-2
-                }
+            _ => {
+                let ahead: Vec<usize> = cols.filter(|&col| col > x).collect();
+                self.cell_index.x = block_edge(&ahead, here, |col| filled(col, y)).unwrap_or(x);
             }
-            Key::Home => x=1,
-            Key::End => x = width,
-            _ => {},
         }
-        self.cell_index = Position{x , y}
-        
     }
 
-    //the rest of the code is just a bunch of string formatting to display data on the screen neatly
-    fn draw_welcome_message(&self) 
+    //how many blank cells each column has below the header, in a popup
+    fn blank_report(&mut self) -> Result<(), std::io::Error>
     {
-        let mut welcome_message = format!("CSVEDIT -- version: {}", VERSION);
-        let width = self.terminal.size().width as usize;
-        let len = welcome_message.len();
-        #[allow(clippy::integer_arithmetic, clippy::integer_division)]
-        let padding = width.saturating_sub(len)/2;
-        let spaces = " ".repeat(padding.saturating_sub(1));
-        welcome_message = format!("{}{}{}",(self.terminal.size().height/3).to_string(),spaces,welcome_message);
-        welcome_message.truncate(width);
-        println!("{}\r", welcome_message);
+        self.blanks.refresh(&self.document, self.header);
+        let rows = self.document.table.num_rows() + 1 - self.first_data_row();
+        let ncols = self.document.table.num_cols();
+        let names: Vec<String> = (1..ncols+1)
+            .map(|x| match self.header {
+                true => format!("{} {}", formula::column_name(x), self.column_label(x).trim()),
+                false => formula::column_name(x),
+            })
+            .collect();
+        let name_width = names.iter().map(|name| name.width()).max().unwrap_or(0);
+        let count_width = rows.to_string().len();
+        let mut lines: Vec<String> = (1..ncols+1)
+            .map(|x| {
+                let count = self.blanks.count(x);
+                let share = count as f64 * 100.0 / rows.max(1) as f64;
+                let name = &names[x - 1];
+                format!("{}{}  {:>count_width$} blank  {:>5.1}%", name, " ".repeat(name_width - name.width()), count, share)
+            })
+            .collect();
+        let total: usize = (1..ncols+1).map(|x| self.blanks.count(x)).sum();
+        lines.push(String::new());
+        lines.push(format!("{} of {} cells are blank; Alt-N goes to the next", total, rows * ncols));
+        self.show_popup(&format!("Blank cells in {} rows", rows), &lines)
     }
 
-    fn draw_status_bar(&self) 
+    //shows lines in a box over the table; the arrow and page keys scroll, anything else closes it
+    fn show_popup(&mut self, title: &str, lines: &[String]) -> Result<(), std::io::Error>
+    {
+        self.popup(title, lines, None).map(|_| ())
+    }
+
+    //a popup to choose one of `lines` from, starting at `selected`. None when it is cancelled
+    fn pick(&mut self, title: &str, lines: &[String], selected: usize) -> Result<Option<usize>, std::io::Error>
+    {
+        self.popup(title, lines, Some(selected))
+    }
+
+    //a bordered panel in the middle of the screen. With a selection it is a picker, otherwise it only scrolls
+    fn popup(&mut self, title: &str, lines: &[String], mut selected: Option<usize>) -> Result<Option<usize>, std::io::Error>
+    {
+        let hint = if selected.is_some() { "Up/Down: move, Return: choose, Esc: cancel" } else { "Up/Down: scroll, any other key: close" };
+        let mut top = 0;
+        loop
+        {
+            self.refresh_screen()?;
+            let visible = self.draw_popup(title, hint, lines, &mut top, selected)?;
+            let last_top = lines.len().saturating_sub(visible);
+            let last = lines.len().saturating_sub(1);
+            match (self.read_key()?, selected)
+            {
+                (Key::Up, Some(i)) => selected = Some(i.saturating_sub(1)),
+                (Key::Down, Some(i)) => selected = Some((i + 1).min(last)),
+                (Key::PageUp, Some(i)) => selected = Some(i.saturating_sub(visible)),
+                (Key::PageDown, Some(i)) => selected = Some((i + visible).min(last)),
+                (Key::Char('\n'), Some(i)) if !lines.is_empty() => return Ok(Some(i)),
+                (Key::Esc, Some(_)) | (Key::Ctrl('q'), Some(_)) => return Ok(None),
+                (_, Some(_)) => (),
+                (Key::Up, None) => top = top.saturating_sub(1),
+                (Key::Down, None) => top = (top + 1).min(last_top),
+                (Key::PageUp, None) => top = top.saturating_sub(visible),
+                (Key::PageDown, None) => top = (top + visible).min(last_top),
+                (_, None) => return Ok(None),
+            }
+        }
+    }
+
+    //draws a bordered panel over the table showing `lines` from `top`, moved so the selected line is
+    //in view. Returns how many lines fit
+    fn draw_popup(&self, title: &str, hint: &str, lines: &[String], top: &mut usize, selected: Option<usize>) -> Result<usize, std::io::Error>
     {
-        let mut status;
         let width = self.terminal.size().width as usize;
-        let modified_indicator = if !self.document.is_saved() 
+        let height = self.terminal.size().height as usize;
+        let visible = lines.len().min(height.saturating_sub(4)).max(1);
+        let inner = lines.iter().map(|line| line.width())
+            //the title and hint sit in the border with a space either side
+            .chain([title.width() + 2, hint.width() + 2])
+            .max()
+            .unwrap_or(0)
+            .min(width.saturating_sub(4));
+        let left = width.saturating_sub(inner + 4) / 2;
+        let first = height.saturating_sub(visible + 2) / 2;
+        let border = |label: &str, left_corner: char, right_corner: char| {
+            let label = table::fit_width(label, inner);
+            format!("{}{}{}{}", left_corner, label, "─".repeat(inner + 2 - label.width()), right_corner)
+        };
+        if let Some(selected) = selected
         {
-            " (modified)"
-        } else 
+            *top = (*top).min(selected).max((selected + 1).saturating_sub(visible));
+        }
+        let mut rows = vec![border(&format!(" {} ", title), '┌', '┐')];
+        for i in *top..*top + visible
         {
-            ""
-        };
-
-        let mut file_name = "[No Name]".to_string();
-        if let Some(name) = &self.document.file_name 
+            let line = table::fit_width(lines.get(i).map(String::as_str).unwrap_or_default(), inner);
+            let padded = format!("{}{}", line, " ".repeat(inner - line.width()));
+            if selected == Some(i)
+            {
+                rows.push(format!("│ {}{}{} │", style::Invert, padded, style::NoInvert));
+            }
+            else
+            {
+                rows.push(format!("│ {} │", padded));
+            }
+        }
+        rows.push(border(&format!(" {} ", hint), '└', '┘'));
+        self.redraw_table();
+        Terminal::set_bg_color(self.colors.status_bg);
+        Terminal::set_fg_color(self.colors.status_fg);
+        for (i, row) in rows.iter().enumerate()
         {
-            file_name = name.clone();
-            file_name.truncate(20);
+            Terminal::cursor_position(&Position{x: left, y: first + i});
+            print!("{}", row);
         }
-        status = format!(
-            "{} - rows:{} cols:{}{}",
-            file_name,
-            self.document.table.num_rows(),
-            self.document.table.num_cols(),
-            modified_indicator
-        );
-
-        let line_indicator = format!(
-            "y: {}/{} x: {}/{}",
-            self.cell_index.y,
-            self.document.table.num_rows(),
-            self.cell_index.x,
-            self.document.table.num_cols()
-        );
-
-        #[allow(clippy::integer_arithmetic)]
-        let len = status.len() + line_indicator.len();
-        status.push_str(&" ".repeat(width.saturating_sub(len)));
-        status = format!("{}{}", status, line_indicator);
-        status.truncate(width);
-        Terminal::set_bg_color(STATUS_BG_COLOR);
-        Terminal::set_fg_color(STATUS_FG_COLOR);
-        println!("{}\r", status);
         Terminal::reset_fg_color();
         Terminal::reset_bg_color();
+        Terminal::flush()?;
+        Ok(visible)
     }
 
-    fn draw_message_bar(&self)
+    //a popup that narrows the current column's values down to those fuzzily matching what is typed,
+    //best first. Return jumps to the chosen one's row
+    fn fuzzy_find(&mut self) -> Result<(), std::io::Error>
     {
-        Terminal::clear_current_line();
-        let message = &self.status_message;
-        if Instant::now() - message.time < Duration::new(5, 0)
+        let x = self.cell_index.x;
+        let first = if self.header { 2 } else { 1 };
+        let values: Vec<(usize, String)> = (first..self.document.table.num_rows()+1)
+            .filter(|&y| self.is_shown(y))
+            .map(|y| (y, self.document.value(x, y).unwrap_or_default()))
+            .collect();
+        let label_width = self.gutter_width().saturating_sub(1).max(1);
+        let column = if self.header { self.column_label(x) } else { num_to_let(x).to_string() };
+        let hint = "Type to narrow down, Up/Down: choose, Return: jump, Esc: cancel";
+        let mut query = String::new();
+        let mut selected = 0;
+        let mut top = 0;
+        loop
         {
-            let mut text = message.text.clone();
-            text.truncate(self.terminal.size().width as usize);
-            print!("{}", text);
+            let mut matches: Vec<(i64, usize, &String)> = values.iter()
+                .filter_map(|(y, value)| Some((fuzzy::score(&query, value)?, *y, value)))
+                .collect();
+            matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+            //rows are numbered as in the gutter
+            let lines: Vec<String> = matches.iter()
+                .map(|(_, y, value)| format!("{:>2$}  {}", self.row_number(*y).unwrap_or_default(), value.replace('\n', "↵"), label_width))
+                .collect();
+            selected = selected.min(lines.len().saturating_sub(1));
+            let title = format!("{} {}/{} > {}", column, matches.len(), values.len(), query);
+            self.refresh_screen()?;
+            let visible = self.draw_popup(&title, hint, &lines, &mut top, Some(selected))?;
+            match self.read_key()?
+            {
+                Key::Up => selected = selected.saturating_sub(1),
+                Key::Down => selected += 1,
+                Key::PageUp => selected = selected.saturating_sub(visible),
+                Key::PageDown => selected += visible,
+                Key::Char('\n') => {
+                    if let Some(&(_, y, _)) = matches.get(selected)
+                    {
+                        self.jump_to(Position{x, y});
+                    }
+                    return Ok(());
+                }
+                Key::Esc | Key::Ctrl('q') => return Ok(()),
+                Key::Backspace => {
+                    query.pop();
+                    selected = 0;
+                }
+                Key::Char(c) if !c.is_control() => {
+                    query.push(c);
+                    selected = 0;
+                }
+                _ => (),
+            }
         }
     }
 
-    fn draw_row(&self, ridx : u16){
-        let ncols: usize = self.document.table.num_cols();
-        let width: usize = self.terminal.size().width as usize;
-        let row: Vec<&Cell> = self.document.get_row((ridx as usize)+self.offset.y-1);
-        let mut row_str: String = String::new();
-        let nrows: usize = self.document.table.num_rows();
-        let mut diff: usize = 0;
-        if row.len() != ncols{
-            Terminal::clear_screen();
-            println!("Error: rows have unequal amount of columns. Exiting...");
-            std::process::exit(1);
+    //a popup of the current column's distinct values with a box to tick for each; only rows holding a
+    //ticked value stay shown. Picks in other columns are kept, so several columns narrow the rows
+    //down together, and only values the other picks leave are listed
+    fn pick_values(&mut self) -> Result<(), std::io::Error>
+    {
+        let x = self.cell_index.x;
+        let mut picked = match &self.filter
+        {
+            Some(Filter{test: RowTest::Values(picked), ..}) => picked.clone(),
+            _ => BTreeMap::new(),
+        };
+        let before = picked.remove(&x);
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for y in self.first_data_row()..self.document.table.num_rows()+1
+        {
+            if picked.iter().all(|(&other, values)| values.contains(&self.document.value(other, y).unwrap_or_default()))
+            {
+                *counts.entry(self.document.value(x, y).unwrap_or_default()).or_default() += 1;
+            }
         }
-        for i in self.offset.x..ncols{
-            let cell: &&Cell = &row[i];
-            let s:String;
-            let filling_width = self.document.table.column_width(cell.x_loc)-cell.width;
-            if cell.highlighted{
-                s = format!(
-                    "{}{}{}{}{}{} {} ", 
-                    color::Fg(STATUS_FG_COLOR),
-                    color::Bg(STATUS_BG_COLOR),
-                    cell.contents.clone(), 
-                    &" ".repeat(filling_width),
-                    color::Bg(color::Reset),
-                    color::Fg(color::Reset),
-                    "│");
-                    diff += 45; //45 is the length added to string by fomatting color
-            } else {
-                s = format!(
-                    "{}{} {} ", 
-                    cell.contents.clone(), 
-                    &" ".repeat(filling_width),
-                    "│");
-            }
-            row_str = row_str.clone() + &s;
-            if row_str.len() > width+diff{
-                break;
+        let mut values: Vec<(String, usize)> = counts.into_iter().collect();
+        //numbers by value, blanks last
+        values.sort_by(|(a, _), (b, _)| match (a.trim().parse::<f64>(), b.trim().parse::<f64>())
+        {
+            (Ok(a), Ok(b)) => a.total_cmp(&b),
+            _ => a.is_empty().cmp(&b.is_empty()).then(a.cmp(b)),
+        });
+        if values.is_empty()
+        {
+            self.status_message = StatusMessage::from(String::from("No values to pick from"));
+            return Ok(());
+        }
+        let mut ticked: Vec<bool> = values.iter().map(|(value, _)| before.as_ref().is_none_or(|before| before.contains(value))).collect();
+        let column = if self.header { self.column_label(x) } else { num_to_let(x).to_string() };
+        let hint = "Space: tick, a: all or none, Return: show, Esc: cancel";
+        let last = values.len() - 1;
+        let mut selected = 0;
+        let mut top = 0;
+        loop
+        {
+            let lines: Vec<String> = values.iter().zip(&ticked)
+                .map(|((value, count), &on)| {
+                    let value = if value.is_empty() { String::from("(blank)") } else { value.replace('\n', "↵") };
+                    format!("[{}] {}  ({})", if on { 'x' } else { ' ' }, value, count)
+                })
+                .collect();
+            let title = format!("{}: {} of {} values", column.trim(), ticked.iter().filter(|&&on| on).count(), values.len());
+            self.refresh_screen()?;
+            let visible = self.draw_popup(&title, hint, &lines, &mut top, Some(selected))?;
+            match self.read_key()?
+            {
+                Key::Up => selected = selected.saturating_sub(1),
+                Key::Down => selected = (selected + 1).min(last),
+                Key::PageUp => selected = selected.saturating_sub(visible),
+                Key::PageDown => selected = (selected + visible).min(last),
+                Key::Char(' ') => ticked[selected] = !ticked[selected],
+                Key::Char('a') => {
+                    let all = ticked.iter().all(|&on| on);
+                    ticked.iter_mut().for_each(|on| *on = !all);
+                }
+                Key::Char('\n') => break,
+                Key::Esc | Key::Ctrl('q') => return Ok(()),
+                _ => (),
             }
         }
-        let len_term_str = (ridx as usize) + self.offset.y-2;
-        let row_filling = nrows.to_string().len() - len_term_str.to_string().len();
-        let terminal_row_str = String::from(len_term_str.to_string() + &" ".repeat(row_filling));
-        let display_str = format!(
-            "{}{}│{}{}\r",
-            color::Fg(STATUS_FG_COLOR),
-            terminal_row_str, 
-            color::Fg(color::Reset),
-            row_str
-        );
-        println!("{}\r",display_str);
+        if !ticked.contains(&true)
+        {
+            self.status_message = StatusMessage::from(String::from("No values ticked; the rows are left as they were"));
+            return Ok(());
+        }
+        //every value ticked is the same as no pick for the column
+        if ticked.contains(&false)
+        {
+            let chosen = values.into_iter().zip(ticked).filter(|&(_, on)| on).map(|((value, _), _)| value).collect();
+            picked.insert(x, chosen);
+        }
+        if picked.is_empty()
+        {
+            self.filter = None;
+            self.status_message = StatusMessage::from(String::from("Showing all rows."));
+            return Ok(());
+        }
+        let text = picked.iter()
+            .map(|(&x, values)| {
+                let name = if self.header { self.column_label(x).trim().to_string() } else { num_to_let(x).to_string() };
+                format!("{} in {} value{}", name, values.len(), if values.len() == 1 { "" } else { "s" })
+            })
+            .collect::<Vec<String>>()
+            .join(" and ");
+        self.show_rows(&text, RowTest::Values(picked));
+        Ok(())
     }
 
-    fn draw_header(&self){
-        let width: usize = self.terminal.size().width as usize;
-        let ncols: usize = self.document.table.num_cols();
-        let nrows: usize = self.document.table.num_rows();
-        let mut col_str: String = String::new();
-        (self.offset.x+1..ncols+1).for_each(|x| {
-            let fill: usize = self.document.table.column_width(x)-1;
-            col_str += &format!("{}{} {} ", num_to_let(x) ,&" ".repeat(fill), "|");
-        });
-        let row_fill: usize = nrows.to_string().len()+1;
-        col_str = format!("{}{}{}",color::Fg(STATUS_FG_COLOR),String::from(&" ".repeat(row_fill)),&col_str.clone());
-        col_str.truncate(width);
-        println!("{}\r",col_str);
-        Terminal::clear_current_line();
-        println!("{}\r",&"-".repeat(width));
+    //lists every command with its key, narrowed down by fuzzy search over names and descriptions.
+    //Returns the one chosen
+    fn command_palette(&mut self) -> Result<Option<Command>, std::io::Error>
+    {
+        let commands: Vec<(Command, String)> = Command::all()
+            .map(|&(name, command, description)| {
+                let key = self.key_for(command).and_then(config::key_name).unwrap_or_default();
+                (command, format!("{:<22} {:<12} {}", name.replace('_', " "), key, description))
+            })
+            .collect();
+        let hint = "Type to narrow down, Up/Down: choose, Return: run, Esc: cancel";
+        let mut query = String::new();
+        let mut selected = 0;
+        let mut top = 0;
+        loop
+        {
+            let mut matches: Vec<(i64, usize)> = commands.iter().enumerate()
+                .filter_map(|(i, (_, line))| Some((fuzzy::score(&query, line)?, i)))
+                .collect();
+            //with nothing typed the commands keep their usual order
+            matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+            let lines: Vec<String> = matches.iter().map(|&(_, i)| commands[i].1.clone()).collect();
+            selected = selected.min(lines.len().saturating_sub(1));
+            let title = format!("Commands {}/{} > {}", matches.len(), commands.len(), query);
+            self.refresh_screen()?;
+            let visible = self.draw_popup(&title, hint, &lines, &mut top, Some(selected))?;
+            match self.read_key()?
+            {
+                Key::Up => selected = selected.saturating_sub(1),
+                Key::Down => selected += 1,
+                Key::PageUp => selected = selected.saturating_sub(visible),
+                Key::PageDown => selected += visible,
+                Key::Char('\n') => return Ok(matches.get(selected).map(|&(_, i)| commands[i].0)),
+                Key::Esc | Key::Ctrl('q') | Key::Ctrl('p') => return Ok(None),
+                Key::Backspace => {
+                    query.pop();
+                    selected = 0;
+                }
+                Key::Char(c) if !c.is_control() => {
+                    query.push(c);
+                    selected = 0;
+                }
+                _ => (),
+            }
+        }
     }
 
+    //a key that runs `command`, preferring ones bound in the config
+    fn key_for(&self, command: Command) -> Option<Key> {
+        let mut bound: Vec<Key> = self.keys.iter().filter(|&(_, &c)| c == command).map(|(&key, _)| key).collect();
+        bound.sort_by_key(|key| format!("{:?}", key));
+        let named = [
+            Key::Char('\n'), Key::Char('?'), Key::Char('='), Key::Char(':'), Key::Null, Key::Delete,
+            Key::Up, Key::Down, Key::Left, Key::Right, Key::PageUp, Key::PageDown, Key::Home, Key::End,
+            Key::ShiftUp, Key::ShiftDown, Key::ShiftLeft, Key::ShiftRight,
+            Key::CtrlUp, Key::CtrlDown, Key::CtrlLeft, Key::CtrlRight, Key::AltLeft, Key::AltRight, Key::AltUp, Key::AltDown,
+        ];
+        let letters = ('a'..='z').map(Key::Ctrl).chain((' '..='~').map(Key::Alt));
+        bound.into_iter()
+            .chain(named.iter().copied().chain(letters).filter(|&key| self.key_to_action(key) == command))
+            .next()
+    }
+
+    //the name the first row gives column `x`
+    fn column_label(&self, x: usize) -> String {
+        self.document.value(x, 1).unwrap_or_default()
+    }
 
+    //writes out the lines of the table area that differ from what is already on screen, leaving the
+    //cursor on the line below it. A change of size or scrolling redraws every line
     fn draw_table(&self){
+        let lines = self.table_lines();
+        let size = self.terminal.size();
+        let drawn_at = ((size.width, size.height), self.offset.clone());
+        let mut screen = self.screen.borrow_mut();
+        if screen.drawn_at.as_ref() != Some(&drawn_at) {
+            screen.lines.clear();
+            screen.drawn_at = Some(drawn_at);
+        }
+        for (i, line) in lines.iter().enumerate() {
+            if screen.lines.get(i) != Some(line) {
+                Terminal::cursor_position(&Position{x: 0, y: i});
+                Terminal::clear_current_line();
+                print!("{}{}", line, color::Fg(color::Reset));
+            }
+        }
+        Terminal::cursor_position(&Position{x: 0, y: lines.len()});
+        screen.lines = lines;
+    }
+
+    //forgets what is on screen, after something was drawn over the table, so it is all drawn again
+    fn redraw_table(&self){
+        self.screen.borrow_mut().drawn_at = None;
+    }
+
+    //each terminal line from the top of the screen to the footer, each setting its own colors
+    fn table_lines(&self) -> Vec<String>{
         let height = self.terminal.size().height;
-        let nrows = self.document.table.num_rows();
-        Terminal::clear_current_line();
-        self.draw_header();
-        for terminal_row in 2..height {
-            Terminal::clear_current_line();
-            if terminal_row as usize <= nrows+1 && !self.document.is_empty(){            
-                self.draw_row(terminal_row-1);
+        let welcome = self.welcome_lines();
+        let welcome_start = height/3;
+        let mut screen = self.header_lines();
+        let mut lines = self.visible_rows().flat_map(|y| self.row_lines(y));
+        //lines below the last row are left blank
+        for terminal_row in 2..self.table_height() as u16 + 2 {
+            let welcome_idx = terminal_row.saturating_sub(welcome_start) as usize;
+            let line = if self.document.is_empty() {
+                if terminal_row >= welcome_start && welcome_idx < welcome.len(){
+                    self.welcome_message(&welcome[welcome_idx])
+                } else {
+                    String::new()
+                }
             }
-            else if self.document.is_empty() && terminal_row == height/3{
-                self.draw_welcome_message();
+            else {
+                lines.next().unwrap_or_default()
+            };
+            screen.push(line);
+        }
+        if self.show_totals {
+            screen.push(self.totals_line());
+        }
+        screen
+    }
+
+    //each column's figure, lined up under the column
+    fn totals_line(&self) -> String{
+        let width = self.terminal.size().width as usize;
+        let mut line = match self.gutter_width() {
+            0 => String::new(),
+            gutter_width => format!("{:<1$}│", "Σ", gutter_width - 1),
+        };
+        for x in self.offset.x+1..self.document.table.num_cols()+1 {
+            if self.document.table.hidden.contains(&x) {
+                continue;
             }
-            else
-            {
-                let edgenumber = terminal_row-2;
-                println!("{}{}\r",color::Fg(STATUS_FG_COLOR),edgenumber.to_string());
+            let column_width = self.document.table.column_width(x);
+            let figure = match self.totals.figure(x) {
+                //narrow columns leave the name out to have room for the value
+                Some((aggregate, value)) => {
+                    let named = format!("{} {}", aggregate.name(), value);
+                    table::fit_width(if named.width() > column_width { value } else { &named }, column_width)
+                }
+                None => String::new(),
+            };
+            line += &format!("{}{} │ ", " ".repeat(column_width.saturating_sub(figure.width())), figure);
+            if line.width() > width {
+                break;
             }
         }
+        format!("{}{}", color::Fg(self.colors.status_fg), table::truncate_width(&line, width))
     }
 
     fn prompt(&mut self, prompt: &str) -> Result<Option<String>, std::io::Error>
     {
-        let mut result = String::new();
+        self.prompt_with(prompt, String::new())
+    }
+
+    //like prompt, but the input starts out holding `initial`
+    fn prompt_with(&mut self, prompt: &str, initial: String) -> Result<Option<String>, std::io::Error>
+    {
+        let mut result = initial;
         loop 
         {
             self.status_message = StatusMessage::from(format!("{}{}",prompt,result));
             self.refresh_screen()?;
-            match Terminal::read_key()? 
+            match self.read_key()? 
             {
-                Key::Backspace => result.truncate(result.len().saturating_sub(1)),
-                Key::Char('\n') => break,
-                Key::Char(c) => 
-                {
-                    if !c.is_control() 
-                    {
-                        result.push(c);
-                    }
-
+                Key::Backspace => {
+                    result.pop();
                 }
+                Key::Char('\n') => break,
+                Key::Char(c) if !c.is_control() => result.push(c),
                 Key::Esc => 
                 {
                     result.truncate(0);
@@ -669,19 +5186,240 @@ impl Editor
 fn num_to_let(num: usize) -> char {
     let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
     let mut idx = num;
-    if num % 26 == 0{
+    if num.is_multiple_of(26){
         return 'A';
     }
     if 26 < num{
-        let div = (num/26)*26 as usize;
+        let div = (num/26)*26;
         idx = num - div;
     }
     let c = alphabet.chars().nth(idx-1).unwrap();
     c
 }
 
+fn save_error_message(e: &std::io::Error) -> String {
+    if e.kind() == std::io::ErrorKind::PermissionDenied{
+        return String::from(READ_ONLY_MESSAGE);
+    }
+    format!("Error: Unable to save changes: {}", e)
+}
+
+//how long ago, roughly, like "5 min ago"
+fn ago(then: Instant) -> String
+{
+    match then.elapsed().as_secs()
+    {
+        seconds if seconds < 60 => String::from("just now"),
+        seconds if seconds < 3600 => format!("{} min ago", seconds / 60),
+        seconds => format!("{} h ago", seconds / 3600),
+    }
+}
+
+//where a jump to the edge of the data stops among `ahead`, the rows or columns past the cursor in
+//the order they are passed. `here` is whether the cursor's own cell is filled. None when there are none
+fn block_edge(ahead: &[usize], here: bool, filled: impl Fn(usize) -> bool) -> Option<usize>
+{
+    let &next = ahead.first()?;
+    if here && filled(next)
+    {
+        return ahead.iter().take_while(|&&at| filled(at)).last().copied();
+    }
+    ahead.iter().find(|&&at| filled(at)).or(ahead.last()).copied()
+}
+
+//a column given by its letter, or by its name among `names` (the header row) when there are any
+fn find_column(text: &str, names: &[String]) -> Option<usize>
+{
+    let text = text.trim();
+    names.iter().position(|name| name.trim() == text).map(|x| x + 1)
+        .or_else(|| formula::column_index(text))
+}
+
+//standard base64 with padding, as OSC 52 wants
+fn base64(bytes: &[u8]) -> String
+{
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in bytes.chunks(3)
+    {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4
+        {
+            if i <= chunk.len()
+            {
+                text.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            }
+            else
+            {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
 fn die(e: std::io::Error) 
 {
     Terminal::clear_screen();
     panic!("{}\n",e);
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    //an editor over `csv` in an 80 by 24 terminal that is never drawn to
+    fn editor(csv: &str) -> Editor
+    {
+        let document = Document::from_bytes(csv.as_bytes(), Some(',')).unwrap();
+        let mut editor = Editor::new(Terminal::headless(80, 24, &[]), document, &Args::default(), Config::default(), String::new(), None);
+        editor.document.highlight(&Position{x: 1, y: 2});
+        editor
+    }
+
+    //`text` without its color escape codes
+    fn visible(text: &str) -> String
+    {
+        let mut shown = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next()
+        {
+            if c == '\x1b'
+            {
+                chars.by_ref().find(|&c| c == 'm');
+            }
+            else
+            {
+                shown.push(c);
+            }
+        }
+        shown
+    }
+
+    #[test]
+    fn formulas_show_their_result_until_toggled()
+    {
+        let mut editor = editor("a,b,total\n1,2,=A2+B2\n");
+        let cell = editor.document.table.get(3, 2).unwrap().clone();
+        assert_eq!(editor.cell_display(&cell), "3");
+        assert!(!editor.table_lines().concat().contains("=A2+B2"));
+        editor.execute(Command::ToggleFormulas, Key::Null).unwrap();
+        assert_eq!(editor.cell_display(&cell), "=A2+B2");
+        assert!(editor.table_lines().concat().contains("=A2+B2"));
+        //cells without formulas look the same either way
+        let plain = editor.document.table.get(1, 2).unwrap().clone();
+        assert_eq!(editor.cell_display(&plain), "1");
+    }
+
+    #[test]
+    fn welcome_lists_hints_within_the_terminal()
+    {
+        for width in [80, 20]
+        {
+            let editor = Editor::new(Terminal::headless(width, 24, &[]), Document::default(), &Args::default(), Config::default(), String::new(), None);
+            let lines: Vec<String> = editor.table_lines().iter().map(|line| visible(line)).collect();
+            if width == 80
+            {
+                for hint in ["Press ? for help", "Start typing to edit", "Ctrl-S to save"]
+                {
+                    assert!(lines.iter().any(|line| line.trim() == hint), "no {:?} in {:?}", hint, lines);
+                }
+            }
+            assert!(lines.iter().all(|line| line.width() <= width as usize), "{:?}", lines);
+        }
+    }
+
+    #[test]
+    fn width_flags_cap_column_widths()
+    {
+        let args: Vec<String> = ["clicsv", "--max-col-width", "12", "--max-cell-width=8", "wide.csv"].iter().map(|arg| arg.to_string()).collect();
+        let args = Args::parse(&args).unwrap();
+        let document = Document::from_bytes(b"name,id\na much longer value than either cap,7\n", Some(',')).unwrap();
+        let editor = Editor::new(Terminal::headless(80, 24, &[]), document, &args, Config::default(), String::new(), None);
+        assert_eq!(editor.document.table.column_width(1), 8);
+        //narrow columns are left as they are
+        assert_eq!(editor.document.table.column_width(2), 3);
+        let mut table = editor.document.table;
+        table.max_cell_width = None;
+        assert_eq!(table.column_width(1), 12);
+        //values are clamped to at least 1
+        let args: Vec<String> = ["clicsv", "--max-col-width", "0"].iter().map(|arg| arg.to_string()).collect();
+        assert_eq!(Args::parse(&args).unwrap().max_col_width, Some(1));
+    }
+
+    #[test]
+    fn replaying_a_macro_repeats_its_edits()
+    {
+        let keys = [
+            //record into @a: write x over the cell and move down
+            Key::Alt('q'), Key::Char('a'), Key::Char('x'), Key::Char('\n'), Key::Down, Key::Alt('q'),
+            //replay @a twice
+            Key::Alt('@'), Key::Char('a'), Key::Char('2'), Key::Char('\n'),
+        ];
+        let document = Document::from_bytes(b"n\n1\n2\n3\n4\n5\n", Some(',')).unwrap();
+        let mut editor = Editor::new(Terminal::headless(80, 24, &keys), document, &Args::default(), Config::default(), String::new(), None);
+        while editor.process_keypress().is_ok() {}
+        let column: Vec<String> = (2..7).map(|y| editor.document.value(1, y).unwrap_or_default()).collect();
+        assert_eq!(column, ["x", "x", "x", "4", "5"]);
+        assert_eq!((editor.cell_index.x, editor.cell_index.y), (1, 5));
+    }
+
+    //moves right from the last column of the first data row with `edge_behavior`, giving where the
+    //cursor ends up and how many columns the table has then
+    fn move_past_right_edge(edge_behavior: EdgeBehavior) -> ((usize, usize), usize)
+    {
+        let mut editor = editor("a,b\n1,2\n3,4\n");
+        editor.edge_behavior = edge_behavior;
+        editor.cell_index = Position{x: 2, y: 2};
+        editor.execute(Command::MoveRight, Key::Right).unwrap();
+        ((editor.cell_index.x, editor.cell_index.y), editor.document.table.num_cols())
+    }
+
+    #[test]
+    fn growing_past_the_right_edge_adds_a_column()
+    {
+        assert_eq!(move_past_right_edge(EdgeBehavior::Grow), ((3, 2), 3));
+    }
+
+    #[test]
+    fn wrapping_past_the_right_edge_goes_to_the_next_row()
+    {
+        assert_eq!(move_past_right_edge(EdgeBehavior::Wrap), ((1, 3), 2));
+    }
+
+    #[test]
+    fn stopping_at_the_right_edge_stays_put()
+    {
+        assert_eq!(move_past_right_edge(EdgeBehavior::Stop), ((2, 2), 2));
+    }
+
+    #[test]
+    fn permission_denied_reads_as_read_only()
+    {
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert_eq!(save_error_message(&denied), READ_ONLY_MESSAGE);
+        let full = std::io::Error::other("disk full");
+        assert_eq!(save_error_message(&full), "Error: Unable to save changes: disk full");
+    }
+
+    #[test]
+    fn keys_map_to_commands()
+    {
+        let editor = editor("a,b\n1,2\n");
+        let expected = [
+            (Key::Ctrl('q'), Command::Quit),
+            (Key::ShiftDown, Command::SelectRowDown),
+            (Key::Ctrl('s'), Command::Save),
+            (Key::Ctrl('z'), Command::Undo),
+            (Key::Up, Command::MoveUp),
+            (Key::Char('\n'), Command::Edit),
+            (Key::Char('x'), Command::EditWith('x')),
+            (Key::Null, Command::ToggleFormulas),
+        ];
+        for (key, command) in expected
+        {
+            assert_eq!(editor.key_to_action(key), command, "{:?}", key);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use std::io::{Error, ErrorKind};
+
+//text read from a file, and how it was encoded so it can be written back the same way
+pub struct Decoded {
+    pub text: String,
+    //the encoding's name, e.g. UTF-8, UTF-16LE or windows-1252
+    pub encoding: String,
+    pub bom: bool,
+}
+
+//works out a file's encoding from its byte order mark, or failing that its bytes: valid UTF-8 is
+//UTF-8, text with every other byte zero is UTF-16, and anything else is taken as Windows-1252
+//(which covers Latin-1)
+pub fn decode(bytes: &[u8]) -> Decoded {
+    let (encoding, bom) = match Encoding::for_bom(bytes) {
+        Some((encoding, length)) => (encoding, length),
+        None if std::str::from_utf8(bytes).is_ok() => (UTF_8, 0),
+        None => (sniff_utf16(bytes).unwrap_or(WINDOWS_1252), 0),
+    };
+    let (text, _) = encoding.decode_without_bom_handling(&bytes[bom..]);
+    Decoded { text: text.into_owned(), encoding: encoding.name().to_string(), bom: bom > 0 }
+}
+
+//the encoding called `label`, e.g. utf-8, latin1, cp1252 or utf-16le
+pub fn find(label: &str) -> Option<&'static str> {
+    Encoding::for_label_no_replacement(label.trim().as_bytes()).map(|encoding| encoding.name())
+}
+
+//`text` in the named encoding, with its byte order mark first when `bom` is set. Characters the
+//encoding has no room for are an error rather than being replaced
+pub fn encode(text: &str, encoding: &str, bom: bool) -> Result<Vec<u8>, Error> {
+    let encoding = Encoding::for_label_no_replacement(encoding.as_bytes())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("unknown encoding {}", encoding)))?;
+    //encoding_rs only decodes UTF-16, so it is written by hand
+    let utf16 = |to_bytes: fn(u16) -> [u8; 2], mark: [u8; 2]| {
+        let mut bytes = if bom { mark.to_vec() } else { Vec::new() };
+        bytes.extend(text.encode_utf16().flat_map(to_bytes));
+        bytes
+    };
+    if encoding == UTF_16LE {
+        return Ok(utf16(u16::to_le_bytes, [0xFF, 0xFE]));
+    }
+    if encoding == UTF_16BE {
+        return Ok(utf16(u16::to_be_bytes, [0xFE, 0xFF]));
+    }
+    let (bytes, _, unmappable) = encoding.encode(text);
+    if unmappable {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("some characters can't be written as {}; use :encoding utf-8 to save as UTF-8", encoding.name()),
+        ));
+    }
+    let mut encoded = if bom && encoding == UTF_8 { "\u{feff}".as_bytes().to_vec() } else { Vec::new() };
+    encoded.extend_from_slice(&bytes);
+    Ok(encoded)
+}
+
+//UTF-16 without a byte order mark shows up as ASCII with a zero byte beside each character
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(4096) & !1];
+    if sample.is_empty() {
+        return None;
+    }
+    let zeros = |offset: usize| sample.iter().skip(offset).step_by(2).filter(|&&b| b == 0).count();
+    let half = sample.len() / 2;
+    //most, but not necessarily all, characters are ASCII
+    match (zeros(0) * 10 >= half * 7, zeros(1) * 10 >= half * 7) {
+        (false, true) => Some(UTF_16LE),
+        (true, false) => Some(UTF_16BE),
+        _ => None,
+    }
+}
@@ -0,0 +1,42 @@
+use crate::table::Progress;
+
+use std::io::Error;
+
+pub fn is_url(name: &str) -> bool {
+    name.starts_with("http://") || name.starts_with("https://")
+}
+
+//the last part of a URL's path, e.g. report.csv for https://example.com/data/report.csv?v=2, which
+//stands in for a file name when working out the format
+pub fn file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').find(|part| !part.is_empty()).unwrap_or(path)
+}
+
+//downloads the body of a URL, counting bytes as they arrive. A cancelled download stops with
+//ErrorKind::Interrupted
+#[cfg(feature = "http")]
+pub fn download(url: &str, progress: &Progress) -> Result<Vec<u8>, Error> {
+    use std::io::{ErrorKind, Read};
+
+    let response = ureq::get(url).call().map_err(|e| Error::other(e.to_string()))?;
+    let mut reader = response.into_reader();
+    let mut bytes = Vec::new();
+    let mut chunk = [0u8; 64 * 1024];
+    loop {
+        if progress.is_cancelled() {
+            return Err(Error::new(ErrorKind::Interrupted, "the download was cancelled"));
+        }
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            return Ok(bytes);
+        }
+        bytes.extend_from_slice(&chunk[..read]);
+        progress.add_bytes(read);
+    }
+}
+
+#[cfg(not(feature = "http"))]
+pub fn download(_url: &str, _progress: &Progress) -> Result<Vec<u8>, Error> {
+    Err(Error::new(std::io::ErrorKind::Unsupported, "clicsv was built without http support"))
+}
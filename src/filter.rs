@@ -0,0 +1,87 @@
+use crate::formula;
+
+//a test on one column's values, written like `B contains error`, `C > 100` or `price <= 3.5`, or
+//on every column's with `*`, like `* contains error`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Predicate {
+    //None for any column
+    pub column: Option<usize>,
+    op: Op,
+    value: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Op {
+    Contains,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+const OPS: &[(&str, Op)] = &[
+    ("contains", Op::Contains),
+    ("!=", Op::NotEqual),
+    ("<=", Op::LessOrEqual),
+    (">=", Op::GreaterOrEqual),
+    ("=", Op::Equal),
+    ("<", Op::Less),
+    (">", Op::Greater),
+];
+
+impl Predicate {
+    //the column is a letter, or one of `names` (the header row) when given
+    pub fn parse(text: &str, names: &[String]) -> Result<Self, String> {
+        let (at, name, op) = OPS.iter()
+            .filter_map(|&(name, op)| text.find(name).map(|at| (at, name, op)))
+            .min_by_key(|&(at, _, _)| at)
+            .ok_or_else(|| String::from("expected COLUMN contains|=|!=|<|<=|>|>= VALUE"))?;
+        let column = text[..at].trim();
+        let value = text[at + name.len()..].trim();
+        let column = match column {
+            "*" => None,
+            column => Some(names.iter().position(|name| name.trim() == column).map(|x| x + 1)
+                .or_else(|| formula::column_index(column))
+                .ok_or_else(|| format!("no column {}", column))?),
+        };
+        //quotes are optional, and let a value start or end with spaces
+        let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+        Ok(Self { column, op, value: value.to_string() })
+    }
+
+    //the predicate written out again, with the column by its letter, so it reads the same after the
+    //header row changes
+    pub fn text(&self) -> String {
+        let column = self.column.map_or_else(|| String::from("*"), formula::column_name);
+        let op = OPS.iter().find(|&&(_, op)| op == self.op).map_or("=", |&(name, _)| name);
+        let needs_quotes = self.value.is_empty() || self.value.trim() != self.value;
+        match needs_quotes {
+            true => format!("{} {} \"{}\"", column, op, self.value),
+            false => format!("{} {} {}", column, op, self.value),
+        }
+    }
+
+    //numbers compare by value, anything else as text
+    pub fn matches(&self, contents: &str) -> bool {
+        let contents = contents.trim();
+        if self.op == Op::Contains {
+            return contents.contains(&self.value);
+        }
+        let ordering = match (contents.parse::<f64>(), self.value.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b),
+            _ => Some(contents.cmp(self.value.as_str())),
+        };
+        let Some(ordering) = ordering else { return false };
+        match self.op {
+            Op::Equal => ordering.is_eq(),
+            Op::NotEqual => ordering.is_ne(),
+            Op::Less => ordering.is_lt(),
+            Op::LessOrEqual => ordering.is_le(),
+            Op::Greater => ordering.is_gt(),
+            Op::GreaterOrEqual => ordering.is_ge(),
+            Op::Contains => unreachable!(),
+        }
+    }
+}
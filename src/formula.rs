@@ -0,0 +1,499 @@
+use crate::table::Table;
+use crate::Position;
+
+use std::collections::{HashMap, HashSet};
+
+//a cell as (x, y)
+type At = (usize, usize);
+
+//a cell is treated as a formula when its (trimmed) contents start with '='
+pub fn is_formula(contents: &str) -> bool {
+    contents.trim_start().starts_with('=')
+}
+
+//works out a formula, taking the results of the formulas it reads from `values`
+fn evaluate_with(contents: &str, table: &Table, values: &HashMap<(usize, usize), Result<f64, String>>) -> Result<f64, String> {
+    let expr = contents.trim().trim_start_matches('=');
+    let mut parser = Parser{
+        chars: expr.chars().filter(|c| !c.is_whitespace()).collect(),
+        pos: 0,
+        table,
+        values,
+    };
+    let value = parser.expression()?;
+    if parser.pos != parser.chars.len(){
+        return Err(String::from("#PARSE"));
+    }
+    Ok(value)
+}
+
+pub fn format_number(value: f64) -> String {
+    if value.is_finite() && value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+//converts a column name like "A" or "AB" to its 1-based x location, or None when it is too long to be one
+pub fn column_index(name: &str) -> Option<usize> {
+    if name.is_empty() {
+        return None;
+    }
+    let mut idx = 0usize;
+    for c in name.chars() {
+        if !c.is_ascii_alphabetic() {
+            return None;
+        }
+        idx = idx.checked_mul(26)?.checked_add(c.to_ascii_uppercase() as usize - 'A' as usize + 1)?;
+    }
+    Some(idx)
+}
+
+//the spreadsheet name of a 1-based column, e.g. 28 is "AB"
+pub fn column_name(x: usize) -> String {
+    let mut name = Vec::new();
+    let mut rest = x;
+    while rest > 0 {
+        let digit = (rest - 1) % 26;
+        name.push((b'A' + digit as u8) as char);
+        rest = (rest - 1) / 26;
+    }
+    name.iter().rev().collect()
+}
+
+//parses a reference like "B3" into a position (x = column, y = row)
+pub fn parse_reference(reference: &str) -> Option<Position> {
+    let split = reference.find(|c: char| c.is_ascii_digit())?;
+    let x = column_index(&reference[..split])?;
+    let y = reference[split..].parse::<usize>().ok()?;
+    if y == 0 {
+        return None;
+    }
+    Some(Position{x, y})
+}
+
+//every cell of the table a formula reads, with ranges like A1:B3 expanded
+pub fn references(contents: &str, table: &Table) -> Vec<Position> {
+    let chars: Vec<char> = contents.trim().trim_start_matches('=').chars().filter(|c| !c.is_whitespace()).collect();
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        if !chars[pos].is_ascii_alphanumeric() {
+            pos += 1;
+            continue;
+        }
+        let (first, next) = word(&chars, pos);
+        pos = next;
+        let Some(from) = parse_reference(&first) else { continue };
+        if chars.get(pos) == Some(&':') {
+            let (second, next) = word(&chars, pos + 1);
+            if let Some(to) = parse_reference(&second) {
+                pos = next;
+                found.extend(range(&from, &to, table));
+                continue;
+            }
+        }
+        found.push(from);
+    }
+    found
+}
+
+//the alphanumeric run starting at `start`, and where it ends
+fn word(chars: &[char], start: usize) -> (String, usize) {
+    let mut end = start;
+    while end < chars.len() && chars[end].is_ascii_alphanumeric() {
+        end += 1;
+    }
+    (chars[start..end].iter().collect(), end)
+}
+
+//every position in the rectangle between two corners that is inside the table, row by row.
+//Cells past its edges are blank, so A1:ZZZ999999 costs no more than the table has
+fn range(from: &Position, to: &Position, table: &Table) -> impl Iterator<Item = Position> {
+    let (x1, x2) = (from.x.min(to.x), from.x.max(to.x).min(table.num_cols()));
+    let (y1, y2) = (from.y.min(to.y), from.y.max(to.y).min(table.num_rows()));
+    (y1..y2+1).flat_map(move |y| (x1..x2+1).map(move |x| Position{x, y}))
+}
+
+//computed values of every formula cell, and which formulas read each cell,
+//so that an edit only recalculates the formulas that depend on it
+#[derive(Default)]
+pub struct Formulas {
+    values: HashMap<(usize, usize), Result<f64, String>>,
+    //formula cell -> cells it reads
+    precedents: HashMap<(usize, usize), Vec<(usize, usize)>>,
+    //cell -> formula cells that read it
+    dependents: HashMap<(usize, usize), HashSet<(usize, usize)>>,
+}
+
+impl Formulas {
+    //forgets everything and evaluates every formula in the table
+    pub fn rebuild(&mut self, table: &Table) {
+        *self = Self::default();
+        let formulas: Vec<(usize, usize)> = table.cells.iter()
+            .filter(|c| is_formula(&c.contents))
+            .map(|c| (c.x_loc, c.y_loc))
+            .collect();
+        for &at in &formulas {
+            self.link(table, at);
+        }
+        self.evaluate(table, formulas.into_iter().collect());
+    }
+
+    //call after the cell at (x, y) changed; recalculates it (if it is a formula) and everything depending on it
+    pub fn update(&mut self, table: &Table, x: usize, y: usize) {
+        self.unlink((x, y));
+        self.values.remove(&(x, y));
+        let contents = table.get_content_from(Position{x, y});
+        if is_formula(&contents) {
+            self.link(table, (x, y));
+        }
+        let mut pending = vec![(x, y)];
+        let mut seen = HashSet::new();
+        while let Some(at) = pending.pop() {
+            if !seen.insert(at) {
+                continue;
+            }
+            if let Some(dependents) = self.dependents.get(&at) {
+                pending.extend(dependents.iter().copied());
+            }
+        }
+        seen.retain(|at| self.precedents.contains_key(at));
+        self.evaluate(table, seen);
+    }
+
+    pub fn value(&self, x: usize, y: usize) -> Option<&Result<f64, String>> {
+        self.values.get(&(x, y))
+    }
+
+    fn link(&mut self, table: &Table, at: (usize, usize)) {
+        let reads: Vec<(usize, usize)> = references(&table.get_content_from(Position{x: at.0, y: at.1}), table)
+            .into_iter()
+            .map(|p| (p.x, p.y))
+            .collect();
+        for &read in &reads {
+            self.dependents.entry(read).or_default().insert(at);
+        }
+        self.precedents.insert(at, reads);
+    }
+
+    fn unlink(&mut self, at: (usize, usize)) {
+        for read in self.precedents.remove(&at).unwrap_or_default() {
+            if let Some(dependents) = self.dependents.get_mut(&read) {
+                dependents.remove(&at);
+                if dependents.is_empty() {
+                    self.dependents.remove(&read);
+                }
+            }
+        }
+    }
+
+    //works out the formula cells in `dirty`, each after the formulas it reads, so every formula is
+    //evaluated once and reads the others' results from `values`
+    fn evaluate(&mut self, table: &Table, dirty: HashSet<At>) {
+        let (order, cyclic) = self.order(&dirty);
+        for at in order {
+            let value = if cyclic.contains(&at) {
+                Err(String::from("#CYCLE"))
+            } else {
+                evaluate_with(&table.get_content_from(Position{x: at.0, y: at.1}), table, &self.values)
+            };
+            self.values.insert(at, value);
+        }
+    }
+
+    //the formulas in `dirty` with each after the dirty formulas it reads, and those found reading
+    //themselves, directly or through others. The walk keeps its own stack, so a long chain of
+    //formulas can't overflow the real one
+    fn order(&self, dirty: &HashSet<At>) -> (Vec<At>, HashSet<At>) {
+        let mut order = Vec::new();
+        let mut cyclic = HashSet::new();
+        let mut done = HashSet::new();
+        //the formulas being visited, each with how many of the cells it reads have been looked at
+        let mut visiting: Vec<(At, usize)> = Vec::new();
+        let mut on_path = HashSet::new();
+        let mut starts: Vec<At> = dirty.iter().copied().collect();
+        starts.sort_unstable();
+        for start in starts {
+            if done.contains(&start) {
+                continue;
+            }
+            visiting.push((start, 0));
+            on_path.insert(start);
+            while let Some(&(at, next)) = visiting.last() {
+                let Some(&read) = self.precedents.get(&at).and_then(|reads| reads.get(next)) else {
+                    visiting.pop();
+                    on_path.remove(&at);
+                    done.insert(at);
+                    order.push(at);
+                    continue;
+                };
+                let top = visiting.len() - 1;
+                visiting[top].1 += 1;
+                if !dirty.contains(&read) || done.contains(&read) {
+                    continue;
+                }
+                if on_path.contains(&read) {
+                    let from = visiting.iter().rposition(|&(cell, _)| cell == read).unwrap_or(0);
+                    cyclic.extend(visiting[from..].iter().map(|&(cell, _)| cell));
+                    continue;
+                }
+                visiting.push((read, 0));
+                on_path.insert(read);
+            }
+        }
+        (order, cyclic)
+    }
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    table: &'a Table,
+    values: &'a HashMap<(usize, usize), Result<f64, String>>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expression(&mut self) -> Result<f64, String> {
+        let mut value = self.term()?;
+        while let Some(op) = self.peek() {
+            match op {
+                '+' => { self.pos += 1; value += self.term()?; }
+                '-' => { self.pos += 1; value -= self.term()?; }
+                _ => break,
+            }
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<f64, String> {
+        let mut value = self.factor()?;
+        while let Some(op) = self.peek() {
+            match op {
+                '*' => { self.pos += 1; value *= self.factor()?; }
+                '/' => {
+                    self.pos += 1;
+                    let divisor = self.factor()?;
+                    if divisor == 0.0 {
+                        return Err(String::from("#DIV/0"));
+                    }
+                    value /= divisor;
+                }
+                _ => break,
+            }
+        }
+        Ok(value)
+    }
+
+    fn factor(&mut self) -> Result<f64, String> {
+        match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                Ok(-self.factor()?)
+            }
+            Some('+') => {
+                self.pos += 1;
+                self.factor()
+            }
+            Some('(') => {
+                self.pos += 1;
+                let value = self.expression()?;
+                if self.peek() != Some(')') {
+                    return Err(String::from("#PARSE"));
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => self.identifier(),
+            _ => Err(String::from("#PARSE")),
+        }
+    }
+
+    //either a cell reference like B3 or a function call like SUM(A1:A10)
+    fn identifier(&mut self) -> Result<f64, String> {
+        let (text, end) = word(&self.chars, self.pos);
+        self.pos = end;
+        if self.peek() == Some('(') {
+            self.pos += 1;
+            return self.function(&text.to_ascii_uppercase());
+        }
+        let at = parse_reference(&text).ok_or_else(|| String::from("#REF"))?;
+        self.cell_value(at)
+    }
+
+    fn function(&mut self, name: &str) -> Result<f64, String> {
+        let mut values = Vec::new();
+        if self.peek() != Some(')') {
+            loop {
+                self.argument(&mut values)?;
+                match self.peek() {
+                    Some(',') => self.pos += 1,
+                    Some(')') => break,
+                    _ => return Err(String::from("#PARSE")),
+                }
+            }
+        }
+        self.pos += 1;
+        match name {
+            "SUM" => Ok(values.iter().sum()),
+            "AVERAGE" | "AVG" => {
+                if values.is_empty() {
+                    return Err(String::from("#DIV/0"));
+                }
+                Ok(values.iter().sum::<f64>() / values.len() as f64)
+            }
+            "MIN" => Ok(values.iter().copied().reduce(f64::min).unwrap_or(0.0)),
+            "MAX" => Ok(values.iter().copied().reduce(f64::max).unwrap_or(0.0)),
+            "COUNT" => Ok(values.len() as f64),
+            _ => Err(String::from("#NAME")),
+        }
+    }
+
+    //a function argument: a range like A1:B3, whose numeric cells are all added, or any expression
+    fn argument(&mut self, values: &mut Vec<f64>) -> Result<(), String> {
+        let start = self.pos;
+        let (first, end) = word(&self.chars, start);
+        if let (Some(from), Some(':')) = (parse_reference(&first), self.chars.get(end).copied()) {
+            let (second, end) = word(&self.chars, end + 1);
+            let to = parse_reference(&second).ok_or_else(|| String::from("#REF"))?;
+            self.pos = end;
+            for at in range(&from, &to, self.table) {
+                //like spreadsheets, blank and text cells in a range are skipped
+                if self.table.get_content_from(at.clone()).trim().is_empty() {
+                    continue;
+                }
+                match self.cell_value(at) {
+                    Ok(value) => values.push(value),
+                    Err(e) if e == "#VALUE" => (),
+                    Err(e) => return Err(e),
+                }
+            }
+            return Ok(());
+        }
+        values.push(self.expression()?);
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<f64, String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || c == '.' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse::<f64>().map_err(|_| String::from("#PARSE"))
+    }
+
+    //a cell's number; for a formula, the result it was already given
+    fn cell_value(&self, at: Position) -> Result<f64, String> {
+        let contents = self.table.get_content_from(at.clone());
+        if is_formula(&contents) {
+            //only a formula reading itself is read before it has a result
+            return self.values.get(&(at.x, at.y)).cloned().unwrap_or_else(|| Err(String::from("#CYCLE")));
+        }
+        let trimmed = contents.trim();
+        if trimmed.is_empty() {
+            return Ok(0.0);
+        }
+        trimmed.parse::<f64>().map_err(|_| String::from("#VALUE"))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn table(rows: &[&[&str]]) -> Table {
+        Table::from_rows(rows.iter().map(|row| row.iter().map(|cell| cell.to_string()).collect()).collect())
+    }
+
+    fn calculated(table: &Table) -> Formulas {
+        let mut formulas = Formulas::default();
+        formulas.rebuild(table);
+        formulas
+    }
+
+    fn value(formulas: &Formulas, x: usize, y: usize) -> Result<f64, String> {
+        formulas.value(x, y).cloned().unwrap()
+    }
+
+    #[test]
+    fn formulas_parse_like_arithmetic(){
+        let table = table(&[&["2", "x", ""], &["4", "5", "3"]]);
+        let values = HashMap::new();
+        let evaluate = |formula: &str| evaluate_with(formula, &table, &values);
+        assert_eq!(evaluate("=1+2*3"), Ok(7.0));
+        assert_eq!(evaluate("=-(1+2)/4"), Ok(-0.75));
+        assert_eq!(evaluate("=SUM(A1:C2)"), Ok(14.0));
+        assert_eq!(evaluate("=AVERAGE(A1:A2, 6)"), Ok(4.0));
+        assert_eq!(evaluate("=MAX(A1:A2)+COUNT(A1:C1)"), Ok(5.0));
+        assert_eq!(evaluate("=A1/C1").unwrap_err(), "#DIV/0");
+        assert_eq!(evaluate("=B1+1").unwrap_err(), "#VALUE");
+        assert_eq!(evaluate("=NOPE(1)").unwrap_err(), "#NAME");
+        assert_eq!(evaluate("=1+").unwrap_err(), "#PARSE");
+    }
+
+    #[test]
+    fn ranges_stop_at_the_table_edges(){
+        let table = table(&[&["1", "2"], &["3", "=SUM(A1:ZZZ999999)"]]);
+        assert_eq!(references("=SUM(A1:ZZZ999999)", &table).len(), 4);
+        assert_eq!(references("=SUM(C3:ZZZ999999)", &table).len(), 0);
+        assert_eq!(value(&calculated(&table), 2, 2).unwrap_err(), "#CYCLE");
+        let table = self::table(&[&["1", "2"], &["3", "=SUM(A1:ZZZ1)+A2"]]);
+        assert_eq!(value(&calculated(&table), 2, 2), Ok(6.0));
+    }
+
+    #[test]
+    fn a_long_chain_is_worked_out_in_order(){
+        let mut rows = vec![vec![String::from("1")]];
+        rows.extend((2..5_001).map(|y| vec![format!("=A{}+1", y - 1)]));
+        let mut table = Table::from_rows(rows);
+        let mut formulas = calculated(&table);
+        assert_eq!(value(&formulas, 1, 5_000), Ok(5_000.0));
+        table.set_contents(1, 1, "10");
+        formulas.update(&table, 1, 1);
+        assert_eq!(value(&formulas, 1, 5_000), Ok(5_009.0));
+    }
+
+    #[test]
+    fn an_edit_recalculates_what_reads_it_after_what_it_reads(){
+        //C1 reads A1 both directly and through B1
+        let mut table = table(&[&["1", "=A1*2", "=A1+B1"]]);
+        let mut formulas = calculated(&table);
+        assert_eq!(value(&formulas, 3, 1), Ok(3.0));
+        table.set_contents(1, 1, "2");
+        formulas.update(&table, 1, 1);
+        assert_eq!(value(&formulas, 3, 1), Ok(6.0));
+    }
+
+    #[test]
+    fn formulas_reading_themselves_are_cycles(){
+        let mut table = table(&[&["=B1", "=A1", "=A1+1", "=D1"], &["1", "=A2+1", "", ""]]);
+        let mut formulas = calculated(&table);
+        for x in 1..5 {
+            assert_eq!(value(&formulas, x, 1).unwrap_err(), "#CYCLE");
+        }
+        assert_eq!(value(&formulas, 2, 2), Ok(2.0));
+        table.set_contents(2, 1, "5");
+        formulas.update(&table, 2, 1);
+        assert_eq!(value(&formulas, 1, 1), Ok(5.0));
+        assert_eq!(value(&formulas, 3, 1), Ok(6.0));
+    }
+
+    #[test]
+    fn column_names_too_long_for_a_column_are_none(){
+        assert_eq!(column_index("AB"), Some(28));
+        assert_eq!(column_index(&"Z".repeat(13)), Some(2_580_398_988_131_886_038));
+        assert_eq!(column_index(&"Z".repeat(14)), None);
+        assert!(parse_reference("ZZZZZZZZZZZZZZ1").is_none());
+    }
+}
@@ -0,0 +1,48 @@
+//how well `query` matches `candidate`, like fzf: the query's characters have to appear in order,
+//ignoring case. None when they don't; otherwise higher is better. Runs of consecutive characters
+//and matches at the start of words count for more, gaps for less
+pub fn score(query: &str, candidate: &str) -> Option<i64> {
+    let query: Vec<char> = query.chars().map(fold).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let chars: Vec<char> = candidate.chars().collect();
+    //the best of the matches starting at each place the first character appears
+    (0..chars.len())
+        .filter(|&start| fold(chars[start]) == query[0])
+        .filter_map(|start| score_from(&query, &chars, start))
+        .max()
+        //shorter candidates are the closer matches
+        .map(|score| score * 100 - chars.len() as i64)
+}
+
+fn score_from(query: &[char], chars: &[char], start: usize) -> Option<i64> {
+    let mut score = 0;
+    let mut last: Option<usize> = None;
+    let mut matched = 0;
+    for i in start..chars.len() {
+        if matched == query.len() {
+            break;
+        }
+        if fold(chars[i]) != query[matched] {
+            continue;
+        }
+        score += 1;
+        match last {
+            Some(last) if last + 1 == i => score += 5,
+            Some(last) => score -= ((i - last - 1) as i64).min(5),
+            None => (),
+        }
+        let word_start = i == 0 || !chars[i - 1].is_alphanumeric() || (chars[i - 1].is_lowercase() && chars[i].is_uppercase());
+        if word_start {
+            score += 3;
+        }
+        last = Some(i);
+        matched += 1;
+    }
+    (matched == query.len()).then_some(score)
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
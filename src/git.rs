@@ -0,0 +1,18 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+//what a file held at the last commit, asked of git. None when git isn't installed, the file isn't
+//in a repository or it hasn't been committed
+pub fn head_version(file_name: &str) -> Option<Vec<u8>> {
+    let path = Path::new(file_name);
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name()?.to_str()?;
+    let output = Command::new("git")
+        .arg("-C").arg(dir)
+        .arg("show").arg(format!("HEAD:./{}", name))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if output.status.success() { Some(output.stdout) } else { None }
+}
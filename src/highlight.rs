@@ -0,0 +1,68 @@
+use crate::filter::Predicate;
+
+//a colour for the cells a predicate picks out, written like `D < 0 -> red` or
+//`* contains FAIL -> yellow background`: conditional formatting. The colour is a name from COLORS
+//or #rrggbb, and colours the text unless `background` follows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Highlight {
+    pub predicate: Predicate,
+    pub color: (u8, u8, u8),
+    pub background: bool,
+}
+
+const COLORS: &[(&str, (u8, u8, u8))] = &[
+    ("red", (215, 0, 0)),
+    ("orange", (255, 135, 0)),
+    ("yellow", (255, 215, 0)),
+    ("green", (0, 175, 0)),
+    ("blue", (0, 135, 255)),
+    ("purple", (135, 0, 215)),
+    ("gray", (128, 128, 128)),
+];
+
+impl Highlight {
+    //the column is a letter, or one of `names` (the header row) when given
+    pub fn parse(text: &str, names: &[String]) -> Result<Self, String> {
+        let usage = "expected COLUMN OP VALUE -> COLOR [background], e.g. D < 0 -> red";
+        let (predicate, color) = text.rsplit_once("->").ok_or(usage)?;
+        let predicate = Predicate::parse(predicate, names)?;
+        let mut words = color.split_whitespace();
+        let color = words.next().ok_or(usage)?;
+        let background = match words.next() {
+            None => false,
+            Some(word) if ["background", "bg"].contains(&word.to_lowercase().as_str()) && words.next().is_none() => true,
+            Some(_) => return Err(String::from(usage)),
+        };
+        let color = parse_color(color).ok_or_else(|| {
+            let names: Vec<&str> = COLORS.iter().map(|&(name, _)| name).collect();
+            format!("no color {}; use {} or #rrggbb", color, names.join(", "))
+        })?;
+        Ok(Self { predicate, color, background })
+    }
+
+    //the rule written out again, as it is remembered for the file
+    pub fn text(&self) -> String {
+        let color = COLORS.iter()
+            .find(|&&(_, rgb)| rgb == self.color)
+            .map(|&(name, _)| name.to_string())
+            .unwrap_or_else(|| format!("#{:02x}{:02x}{:02x}", self.color.0, self.color.1, self.color.2));
+        let background = if self.background { " background" } else { "" };
+        format!("{} -> {}{}", self.predicate.text(), color, background)
+    }
+
+    //whether a value in column `x` takes the colour
+    pub fn applies(&self, x: usize, value: &str) -> bool {
+        self.predicate.column.is_none_or(|column| column == x) && self.predicate.matches(value)
+    }
+}
+
+fn parse_color(name: &str) -> Option<(u8, u8, u8)> {
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some((channel(0)?, channel(2)?, channel(4)?));
+    }
+    COLORS.iter().find(|(color, _)| color.eq_ignore_ascii_case(name)).map(|&(_, rgb)| rgb)
+}
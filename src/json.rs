@@ -0,0 +1,104 @@
+use std::io::{Error, ErrorKind};
+
+//a JSON array of objects, one row per object
+pub fn is_json(filename: &str) -> bool {
+    filename.ends_with(".json")
+}
+
+//newline-delimited JSON: one object per line
+pub fn is_ndjson(filename: &str) -> bool {
+    filename.ends_with(".ndjson") || filename.ends_with(".jsonl")
+}
+
+pub fn is_any_json(filename: &str) -> bool {
+    is_json(filename) || is_ndjson(filename)
+}
+
+//reads objects into rows under a header made of every key, in the order they first appear.
+//nested arrays and objects are kept as JSON text
+#[cfg(feature = "json")]
+pub fn read_rows(filename: &str) -> Result<Vec<Vec<String>>, Error> {
+    use serde_json::Value;
+
+    let text = std::fs::read_to_string(filename)?;
+    let to_io = |e: serde_json::Error| Error::new(ErrorKind::InvalidData, e.to_string());
+    let records: Vec<Value> = if is_ndjson(filename) {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(to_io))
+            .collect::<Result<_, _>>()?
+    } else {
+        match serde_json::from_str(&text).map_err(to_io)? {
+            Value::Array(records) => records,
+            record => vec![record],
+        }
+    };
+    let mut header: Vec<String> = Vec::new();
+    for record in &records {
+        let Value::Object(fields) = record else {
+            return Err(Error::new(ErrorKind::InvalidData, "expected an object for every row"));
+        };
+        for key in fields.keys() {
+            if !header.contains(key) {
+                header.push(key.clone());
+            }
+        }
+    }
+    let mut rows = vec![header.clone()];
+    for record in &records {
+        rows.push(header.iter().map(|key| match record.get(key) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => s.clone(),
+            Some(value) => value.to_string(),
+        }).collect());
+    }
+    if header.is_empty() {
+        rows = vec![vec![String::new()]];
+    }
+    Ok(rows)
+}
+
+#[cfg(not(feature = "json"))]
+pub fn read_rows(_filename: &str) -> Result<Vec<Vec<String>>, Error> {
+    Err(Error::new(ErrorKind::Unsupported, "clicsv was built without json support"))
+}
+
+//writes every row after the first as an object keyed by the first row's names. Numbers, true/false
+//and nested JSON keep their type, empty cells become null
+#[cfg(feature = "json")]
+pub fn write_rows(filename: &str, rows: &[Vec<String>]) -> Result<(), Error> {
+    use crate::formula;
+    use serde_json::{Map, Value};
+
+    let Some((header, records)) = rows.split_first() else { return Ok(()) };
+    let keys: Vec<String> = header.iter().enumerate()
+        .map(|(i, name)| match name.trim() {
+            "" => formula::column_name(i + 1),
+            name => name.to_string(),
+        })
+        .collect();
+    let objects: Vec<String> = records.iter().map(|row| {
+        let object: Map<String, Value> = keys.iter().zip(row).map(|(key, value)| {
+            let value = match serde_json::from_str::<Value>(value.trim()) {
+                _ if value.trim().is_empty() => Value::Null,
+                Ok(typed @ (Value::Number(_) | Value::Bool(_) | Value::Array(_) | Value::Object(_))) => typed,
+                _ => Value::String(value.clone()),
+            };
+            (key.clone(), value)
+        }).collect();
+        Value::Object(object).to_string()
+    }).collect();
+    let text = if is_ndjson(filename) {
+        objects.iter().map(|object| format!("{}\n", object)).collect()
+    } else if objects.is_empty() {
+        String::from("[]\n")
+    } else {
+        format!("[\n  {}\n]\n", objects.join(",\n  "))
+    };
+    std::fs::write(filename, text)
+}
+
+#[cfg(not(feature = "json"))]
+pub fn write_rows(_filename: &str, _rows: &[Vec<String>]) -> Result<(), Error> {
+    Err(Error::new(ErrorKind::Unsupported, "clicsv was built without json support"))
+}
@@ -0,0 +1,74 @@
+use crate::table::{self, Align};
+
+use std::io::Error;
+
+pub fn is_latex(filename: &str) -> bool {
+    filename.ends_with(".tex")
+}
+
+//text set as itself in LaTeX: the ten characters it treats specially are escaped, and line
+//breaks inside a cell become spaces
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '\r' => {}
+            '\n' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+//l, c or r for each column: the alignment it was given, otherwise r when every value below the
+//header is a number and l when not
+fn column_spec(rows: &[Vec<String>], header: bool, alignments: &[Option<Align>]) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let body = if header { rows.get(1..).unwrap_or(&[]) } else { rows };
+    (0..columns).map(|x| match alignments.get(x).copied().flatten() {
+        Some(Align::Left) => 'l',
+        Some(Align::Center) => 'c',
+        Some(Align::Right) => 'r',
+        None => {
+            let mut values = body.iter()
+                .filter_map(|row| row.get(x))
+                .filter(|value| !value.trim().is_empty())
+                .peekable();
+            if values.peek().is_some() && values.all(|value| table::infer_type(value).is_number()) {
+                'r'
+            } else {
+                'l'
+            }
+        }
+    }).collect()
+}
+
+//a booktabs tabular block, with the first row set apart under its own rule when `header` is set.
+//`alignments` holds each column's, by position
+pub fn tabular(rows: &[Vec<String>], header: bool, alignments: &[Option<Align>]) -> String {
+    let spec = column_spec(rows, header, alignments);
+    let mut text = format!("\\begin{{tabular}}{{{}}}\n\\toprule\n", spec);
+    for (i, row) in rows.iter().enumerate() {
+        let cells: Vec<String> = (0..spec.len())
+            .map(|x| escape(row.get(x).map(String::as_str).unwrap_or("")))
+            .collect();
+        text.push_str(&cells.join(" & "));
+        text.push_str(" \\\\\n");
+        if header && i == 0 && rows.len() > 1 {
+            text.push_str("\\midrule\n");
+        }
+    }
+    text.push_str("\\bottomrule\n\\end{tabular}\n");
+    text
+}
+
+pub fn write(filename: &str, rows: &[Vec<String>], header: bool, alignments: &[Option<Align>]) -> Result<(), Error> {
+    std::fs::write(filename, tabular(rows, header, alignments))
+}
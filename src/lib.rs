@@ -0,0 +1,38 @@
+//the spreadsheet model: reading, editing and writing tables without a terminal.
+//the clicsv binary builds its editor and command line tools on top of this
+pub mod aggregates;
+pub mod compression;
+pub mod dates;
+pub mod diff;
+pub mod encoding;
+pub mod fetch;
+pub mod document;
+pub mod filter;
+pub mod formula;
+pub mod fuzzy;
+pub mod git;
+pub mod highlight;
+pub mod json;
+pub mod latex;
+pub mod notes;
+pub mod numbers;
+pub mod regex;
+pub mod script;
+pub mod series;
+pub mod sqlite;
+pub mod stats;
+pub mod table;
+pub mod transform;
+pub mod validation;
+pub mod watch;
+pub mod workbook;
+
+pub use document::Document;
+pub use table::Table;
+
+#[derive(Default, PartialEq, Clone)]
+pub struct Position 
+{
+    pub x: usize,
+    pub y: usize,
+}
@@ -1,15 +1,24 @@
-mod document;
+mod cli;
+mod command;
+mod config;
 mod editor;
-mod table;
+mod state;
 mod terminal;
+mod theme;
 
-pub use document::Document;
+pub use clicsv::{Document, Position, Table};
+use clicsv::{aggregates, compression, diff, document, fetch, filter, formula, fuzzy, git, highlight, json, latex, numbers, script, stats, table, transform, validation, watch, workbook};
 use editor::Editor;
-pub use editor::Position;
-pub use table::Table;
 pub use terminal::Terminal;
 
 
 fn main() {
-    Editor::default().run();
+    let args: Vec<String> = std::env::args().collect();
+    //`clicsv convert|stats|get|set ...` runs without opening the editor
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+    let mut editor = Editor::default();
+    editor.run();
+    editor.write_output();
 }
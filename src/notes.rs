@@ -0,0 +1,94 @@
+use crate::formula;
+
+use std::fs;
+use std::io::{Error, ErrorKind};
+
+//notes are kept beside the file they belong to, e.g. data.csv.clicsv-notes
+pub const SUFFIX: &str = ".clicsv-notes";
+
+//a note attached to a cell, for a sheet of a workbook when `sheet` is set
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub sheet: Option<String>,
+    pub x: usize,
+    pub y: usize,
+    pub text: String,
+}
+
+pub fn path(file_name: &str) -> String {
+    format!("{}{}", file_name, SUFFIX)
+}
+
+//the notes saved for `file_name`; none when it has no notes file
+pub fn read(file_name: &str) -> Result<Vec<Note>, Error> {
+    match fs::read_to_string(path(file_name)) {
+        Ok(text) => Ok(parse(&text)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+//saves the notes for `file_name`, removing the notes file once there are none
+pub fn write(file_name: &str, notes: &[Note]) -> Result<(), Error> {
+    if notes.is_empty() {
+        return match fs::remove_file(path(file_name)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    fs::write(path(file_name), format(notes))
+}
+
+//one note per line: the cell (like B3, or Sheet2!B3 in a workbook), a tab, then the text with
+//backslashes, tabs and line breaks escaped
+pub fn format(notes: &[Note]) -> String {
+    let mut text = String::new();
+    for note in notes {
+        if let Some(sheet) = &note.sheet {
+            text.push_str(sheet);
+            text.push('!');
+        }
+        text.push_str(&format!("{}{}\t", formula::column_name(note.x), note.y));
+        for c in note.text.chars() {
+            match c {
+                '\\' => text.push_str("\\\\"),
+                '\t' => text.push_str("\\t"),
+                '\n' => text.push_str("\\n"),
+                '\r' => text.push_str("\\r"),
+                c => text.push(c),
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+//lines that don't name a cell are skipped
+pub fn parse(text: &str) -> Vec<Note> {
+    text.lines()
+        .filter_map(|line| {
+            let (cell, escaped) = line.split_once('\t')?;
+            let (sheet, reference) = match cell.rsplit_once('!') {
+                Some((sheet, reference)) => (Some(sheet.to_string()), reference),
+                None => (None, cell),
+            };
+            let at = formula::parse_reference(reference)?;
+            let mut text = String::new();
+            let mut chars = escaped.chars();
+            while let Some(c) = chars.next() {
+                if c != '\\' {
+                    text.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('r') => text.push('\r'),
+                    Some(other) => text.push(other),
+                    None => text.push('\\'),
+                }
+            }
+            Some(Note { sheet, x: at.x, y: at.y, text })
+        })
+        .collect()
+}
@@ -0,0 +1,122 @@
+//how a column's numbers are shown, written like `thousands 2`: fixed decimals (`fixed 2`, or just
+//`2`), thousands separators (`thousands 2` gives 1,234.50), a percentage (`percent 1` gives 12.5%)
+//or scientific notation (`scientific 2` gives 1.23e3). Only what is drawn changes; cells keep what
+//was typed. Values that aren't numbers are shown as they are
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumberFormat {
+    pub style: Style,
+    pub decimals: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Fixed,
+    Thousands,
+    Percent,
+    Scientific,
+}
+
+const DEFAULT_DECIMALS: usize = 2;
+const MAX_DECIMALS: usize = 15;
+
+impl Style {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "fixed" | "decimals" => Some(Self::Fixed),
+            "thousands" | "comma" | "commas" => Some(Self::Thousands),
+            "percent" | "%" => Some(Self::Percent),
+            "scientific" | "sci" => Some(Self::Scientific),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Fixed => "fixed",
+            Self::Thousands => "thousands",
+            Self::Percent => "percent",
+            Self::Scientific => "scientific",
+        }
+    }
+}
+
+impl NumberFormat {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let usage = "expected fixed, thousands, percent or scientific, then the decimals, e.g. thousands 2";
+        let mut words = text.split_whitespace();
+        let first = words.next().ok_or(usage)?;
+        let (style, decimals) = match first.parse::<usize>() {
+            Ok(decimals) => (Style::Fixed, Some(decimals)),
+            Err(_) => {
+                let style = Style::parse(first).ok_or_else(|| format!("no number format {}; {}", first, usage))?;
+                let decimals = match words.next() {
+                    Some(word) => Some(word.parse::<usize>().map_err(|_| format!("{} isn't a number of decimals", word))?),
+                    None => None,
+                };
+                (style, decimals)
+            }
+        };
+        if words.next().is_some() {
+            return Err(String::from(usage));
+        }
+        let decimals = decimals.unwrap_or(DEFAULT_DECIMALS);
+        if decimals > MAX_DECIMALS {
+            return Err(format!("at most {} decimals", MAX_DECIMALS));
+        }
+        Ok(Self { style, decimals })
+    }
+
+    //the format written out again, as it is remembered for the file
+    pub fn text(&self) -> String {
+        format!("{} {}", self.style.name(), self.decimals)
+    }
+
+    //the value as the format shows it, or None when it isn't a number
+    pub fn apply(&self, value: &str) -> Option<String> {
+        let value = value.trim();
+        let number = value.parse::<f64>().ok().filter(|n| n.is_finite())?;
+        Some(match self.style {
+            Style::Fixed => format!("{:.*}", self.decimals, number),
+            Style::Thousands => group_thousands(&format!("{:.*}", self.decimals, number)),
+            Style::Percent => format!("{:.*}%", self.decimals, number * 100.0),
+            Style::Scientific => format!("{:.*e}", self.decimals, number),
+        })
+    }
+
+    //how wide a value `width` characters wide can become once formatted, as near as can be told
+    //without looking at it. Never narrower than `width`, so text in the column still fits
+    pub fn widest(&self, width: usize) -> usize {
+        let point = if self.decimals > 0 { self.decimals + 1 } else { 0 };
+        let formatted = match self.style {
+            Style::Fixed => width + point,
+            Style::Thousands => width + width / 3 + point,
+            Style::Percent => width + 2 + point + 1,
+            //a sign, a digit, the decimals and an exponent like e-10
+            Style::Scientific => 2 + point + 4,
+        };
+        formatted.max(width)
+    }
+}
+
+//puts a comma between each three digits before the decimal point
+fn group_thousands(number: &str) -> String {
+    let (sign, unsigned) = match number.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", number),
+    };
+    let (whole, fraction) = match unsigned.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (unsigned, None),
+    };
+    let mut grouped = String::with_capacity(whole.len() + whole.len() / 3);
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    match fraction {
+        Some(fraction) => format!("{}{}.{}", sign, grouped, fraction),
+        None => format!("{}{}", sign, grouped),
+    }
+}
@@ -0,0 +1,422 @@
+//a small backtracking regular expression engine, enough for checking and rewriting cells:
+//literals, `.`, classes like [a-z] and [^,], \d \w \s (and \D \W \S), anchors ^ and $, groups
+//(capturing, or (?:...) not), alternation with |, and the quantifiers * + ? {m} {m,} {m,n}, which
+//are greedy unless followed by ?. A leading (?i) ignores case
+
+//positions in the text, in chars, of the whole match (group 0) and of each capturing group
+type Groups = Vec<Option<(usize, usize)>>;
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    pattern: String,
+    root: Node,
+    groups: usize,
+    ignore_case: bool,
+}
+
+//two patterns are the same when they are written the same
+impl PartialEq for Regex {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Vec<Item>, bool),
+    Start,
+    End,
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+    Repeat { node: Box<Node>, min: usize, max: Option<usize>, greedy: bool },
+}
+
+//something a character class accepts
+#[derive(Debug, Clone, Copy)]
+enum Item {
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+impl Item {
+    fn matches(self, c: char, ignore_case: bool) -> bool {
+        match self {
+            Item::Range(low, high) if ignore_case => {
+                [c, fold(c), c.to_uppercase().next().unwrap_or(c)].iter().any(|c| (low..=high).contains(c))
+            }
+            Item::Range(low, high) => (low..=high).contains(&c),
+            Item::Digit(negated) => c.is_ascii_digit() != negated,
+            Item::Word(negated) => (c.is_alphanumeric() || c == '_') != negated,
+            Item::Space(negated) => c.is_whitespace() != negated,
+        }
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let (ignore_case, rest) = match pattern.strip_prefix("(?i)") {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        let mut parser = Parser { chars: rest.chars().collect(), at: 0, groups: 0 };
+        let root = parser.alternation()?;
+        if parser.at < parser.chars.len() {
+            return Err(format!("unmatched ) in {}", pattern));
+        }
+        Ok(Self { pattern: pattern.to_string(), root, groups: parser.groups, ignore_case })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    //whether the pattern matches anywhere in `text`
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        self.find_at(&chars, 0).is_some()
+    }
+
+    //whether the pattern matches the whole of `text`
+    pub fn is_full_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        let mut groups = vec![None; self.groups + 1];
+        self.match_node(&self.root, &chars, 0, &mut groups, &mut |end, _| end == chars.len())
+    }
+
+    //the first match's text and each group's, None for groups that took no part
+    pub fn captures(&self, text: &str) -> Option<Vec<Option<String>>> {
+        let chars: Vec<char> = text.chars().collect();
+        let groups = self.find_at(&chars, 0)?;
+        Some(groups.iter().map(|group| group.map(|(start, end)| chars[start..end].iter().collect())).collect())
+    }
+
+    //replaces every match, where the replacement can name groups as $1 or ${1}, the whole match as
+    //$0, and a dollar sign as $$
+    pub fn replace_all(&self, text: &str, replacement: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut result = String::new();
+        let mut at = 0;
+        while at <= chars.len() {
+            let Some(groups) = self.find_at(&chars, at) else { break };
+            let (start, end) = groups[0].unwrap_or((at, at));
+            result.extend(&chars[at..start]);
+            expand(replacement, &chars, &groups, &mut result);
+            //an empty match moves on a character, which is kept
+            if end == start {
+                if let Some(&c) = chars.get(end) {
+                    result.push(c);
+                }
+                at = end + 1;
+            } else {
+                at = end;
+            }
+        }
+        if at < chars.len() {
+            result.extend(&chars[at..]);
+        }
+        result
+    }
+
+    fn find_at(&self, chars: &[char], from: usize) -> Option<Groups> {
+        for start in from..chars.len() + 1 {
+            let mut groups = vec![None; self.groups + 1];
+            let mut found = None;
+            let matched = self.match_node(&self.root, chars, start, &mut groups, &mut |end, groups| {
+                found = Some((end, groups.clone()));
+                true
+            });
+            if let (true, Some((end, mut groups))) = (matched, found) {
+                groups[0] = Some((start, end));
+                return Some(groups);
+            }
+        }
+        None
+    }
+
+    //tries `node` at `at`, calling `then` with where it ended; backtracks into `node` whenever
+    //`then` says no
+    fn match_node(&self, node: &Node, chars: &[char], at: usize, groups: &mut Groups, then: &mut dyn FnMut(usize, &mut Groups) -> bool) -> bool {
+        match node {
+            Node::Empty => then(at, groups),
+            Node::Char(c) => {
+                let equal = |d: char| d == *c || (self.ignore_case && fold(d) == fold(*c));
+                chars.get(at).is_some_and(|&d| equal(d)) && then(at + 1, groups)
+            }
+            Node::Any => chars.get(at).is_some_and(|&c| c != '\n') && then(at + 1, groups),
+            Node::Class(items, negated) => {
+                let accepted = chars.get(at).is_some_and(|&c| items.iter().any(|item| item.matches(c, self.ignore_case)) != *negated);
+                accepted && then(at + 1, groups)
+            }
+            Node::Start => at == 0 && then(at, groups),
+            Node::End => at == chars.len() && then(at, groups),
+            Node::Group(inner, None) => self.match_node(inner, chars, at, groups, then),
+            Node::Group(inner, Some(index)) => {
+                let index = *index;
+                self.match_node(inner, chars, at, groups, &mut |end, groups| {
+                    let before = groups[index];
+                    groups[index] = Some((at, end));
+                    if then(end, groups) {
+                        return true;
+                    }
+                    groups[index] = before;
+                    false
+                })
+            }
+            Node::Concat(nodes) => self.match_sequence(nodes, chars, at, groups, then),
+            Node::Alternation(options) => options.iter().any(|option| self.match_node(option, chars, at, groups, then)),
+            Node::Repeat { node, min, max, greedy } => {
+                self.match_repeat(node, (*min, *max, *greedy), 0, chars, at, groups, then)
+            }
+        }
+    }
+
+    fn match_sequence(&self, nodes: &[Node], chars: &[char], at: usize, groups: &mut Groups, then: &mut dyn FnMut(usize, &mut Groups) -> bool) -> bool {
+        match nodes.split_first() {
+            None => then(at, groups),
+            Some((first, rest)) => self.match_node(first, chars, at, groups, &mut |end, groups| {
+                self.match_sequence(rest, chars, end, groups, then)
+            }),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn match_repeat(&self, node: &Node, bounds: (usize, Option<usize>, bool), count: usize, chars: &[char], at: usize, groups: &mut Groups, then: &mut dyn FnMut(usize, &mut Groups) -> bool) -> bool {
+        let (min, max, greedy) = bounds;
+        let can_repeat = max.is_none_or(|max| count < max);
+        let enough = count >= min;
+        let again = |groups: &mut Groups, then: &mut dyn FnMut(usize, &mut Groups) -> bool| {
+            can_repeat && self.match_node(node, chars, at, groups, &mut |end, groups| {
+                //a repeat that matched nothing would go round forever
+                (end != at || !enough) && self.match_repeat(node, bounds, count + 1, chars, end, groups, then)
+            })
+        };
+        //greedy repeats try one more first, lazy ones try stopping first
+        if greedy && again(groups, then) {
+            return true;
+        }
+        (enough && then(at, groups)) || (!greedy && again(groups, then))
+    }
+}
+
+//writes `replacement` out with its group references filled in
+fn expand(replacement: &str, chars: &[char], groups: &Groups, result: &mut String) {
+    let mut rest = replacement.chars().peekable();
+    while let Some(c) = rest.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+        let braced = rest.peek() == Some(&'{');
+        if braced {
+            rest.next();
+        }
+        let mut digits = String::new();
+        while let Some(&d) = rest.peek().filter(|d| d.is_ascii_digit()) {
+            digits.push(d);
+            rest.next();
+        }
+        if braced && rest.peek() == Some(&'}') {
+            rest.next();
+        }
+        match digits.parse::<usize>() {
+            Ok(index) => {
+                if let Some(Some((start, end))) = groups.get(index) {
+                    result.extend(&chars[*start..*end]);
+                }
+            }
+            Err(_) if rest.peek() == Some(&'$') => {
+                rest.next();
+                result.push('$');
+            }
+            Err(_) => result.push('$'),
+        }
+    }
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.at += 1;
+        c
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut options = vec![self.concat()?];
+        while self.peek() == Some('|') {
+            self.at += 1;
+            options.push(self.concat()?);
+        }
+        Ok(if options.len() == 1 { options.remove(0) } else { Node::Alternation(options) })
+    }
+
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.remove(0),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = self.next().ok_or("unexpected end of pattern")?;
+        Ok(match c {
+            '(' => {
+                let index = if self.chars[self.at..].starts_with(&['?', ':']) {
+                    self.at += 2;
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let inner = self.alternation()?;
+                if self.next() != Some(')') {
+                    return Err(String::from("missing )"));
+                }
+                Node::Group(Box::new(inner), index)
+            }
+            '[' => self.class()?,
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '\\' => match self.escape()? {
+                Ok(item) => Node::Class(vec![item], false),
+                Err(c) => Node::Char(c),
+            },
+            '*' | '+' | '?' => return Err(format!("nothing to repeat before {}", c)),
+            c => Node::Char(c),
+        })
+    }
+
+    //after a backslash: a shorthand class, or the character it stands for
+    fn escape(&mut self) -> Result<Result<Item, char>, String> {
+        let c = self.next().ok_or("pattern ends with \\")?;
+        Ok(match c {
+            'd' => Ok(Item::Digit(false)),
+            'D' => Ok(Item::Digit(true)),
+            'w' => Ok(Item::Word(false)),
+            'W' => Ok(Item::Word(true)),
+            's' => Ok(Item::Space(false)),
+            'S' => Ok(Item::Space(true)),
+            'n' => Err('\n'),
+            't' => Err('\t'),
+            'r' => Err('\r'),
+            c => Err(c),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.at += 1;
+        }
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or("missing ]")?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let low = match c {
+                '\\' => match self.escape()? {
+                    Ok(item) => {
+                        items.push(item);
+                        continue;
+                    }
+                    Err(c) => c,
+                },
+                c => c,
+            };
+            //a - at either end is itself
+            if self.peek() == Some('-') && self.chars.get(self.at + 1).is_some_and(|&c| c != ']') {
+                self.at += 1;
+                let high = match self.next().ok_or("missing ]")? {
+                    '\\' => self.escape()?.err().ok_or("a range can't end in a class")?,
+                    c => c,
+                };
+                if high < low {
+                    return Err(format!("backwards range {}-{}", low, high));
+                }
+                items.push(Item::Range(low, high));
+            } else {
+                items.push(Item::Range(low, low));
+            }
+        }
+        Ok(Node::Class(items, negated))
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('{') => match self.braces() {
+                Some(bounds) => bounds,
+                None => return Ok(atom),
+            },
+            Some(c) => {
+                let bounds = match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    '?' => (0, Some(1)),
+                    _ => return Ok(atom),
+                };
+                self.at += 1;
+                bounds
+            }
+            None => return Ok(atom),
+        };
+        if matches!(atom, Node::Start | Node::End) {
+            return Err(String::from("an anchor can't repeat"));
+        }
+        let greedy = self.peek() != Some('?');
+        if !greedy {
+            self.at += 1;
+        }
+        Ok(Node::Repeat { node: Box::new(atom), min, max, greedy })
+    }
+
+    //{m}, {m,} or {m,n}, moving past it; a { that starts none of these is an ordinary character
+    fn braces(&mut self) -> Option<(usize, Option<usize>)> {
+        let close = self.chars[self.at..].iter().position(|&c| c == '}')? + self.at;
+        let inside: String = self.chars[self.at + 1..close].iter().collect();
+        let bounds = match inside.split_once(',') {
+            None => {
+                let n = inside.parse().ok()?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+        };
+        if bounds.1.is_some_and(|max| max < bounds.0) {
+            return None;
+        }
+        self.at = close + 1;
+        Some(bounds)
+    }
+}
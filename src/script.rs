@@ -0,0 +1,191 @@
+use crate::document::Document;
+#[cfg(feature = "scripting")]
+use crate::formula;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+//how many operations a script may run before it is stopped, so `loop {}` can't hang the editor
+#[cfg(feature = "scripting")]
+const MAX_OPERATIONS: u64 = 100_000_000;
+
+//a script's view of the document, as the `table` variable. Positions count from 1, like the
+//status bar's, and rows include the header row when there is one:
+//  table.rows, table.cols           how many there are
+//  table.header                     whether the first row names the columns
+//  table.x, table.y                 the cursor
+//  table.selected                   the selected cells as [x, y] pairs
+//  table.get(x, y)                  what a cell shows, so formulas give their results
+//  table.contents(x, y)             what was typed into a cell
+//  table.set(x, y, value)           changes a cell
+//  table.row(y)                     a row's values as an array
+//  table.column(name)               a column's position from its letter or header name, or 0
+//The cells set are kept until the script ends and then changed together, as one undoable edit.
+//Anything printed is shown in the status bar
+pub struct Input<'a> {
+    pub document: &'a Document,
+    pub header: bool,
+    pub cursor: (usize, usize),
+    pub selected: Vec<(usize, usize)>,
+    //set from elsewhere, e.g. when Esc is pressed, to stop the script at its next operation
+    pub stop: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+pub struct Outcome {
+    //(x, y, contents) for each cell the script set
+    pub changes: Vec<(usize, usize, String)>,
+    pub printed: Vec<String>,
+}
+
+#[cfg(feature = "scripting")]
+pub fn run(source: &str, input: Input) -> Result<Outcome, String> {
+    use rhai::{Array, Dynamic, Engine, EvalAltResult, Scope};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::atomic::Ordering;
+
+    type Fallible<T> = Result<T, Box<EvalAltResult>>;
+
+    let table = Sheet::of(&input);
+    let shared = Rc::new(RefCell::new(table));
+    let printed = Rc::new(RefCell::new(Vec::new()));
+    let mut engine = Engine::new();
+    let sink = Rc::clone(&printed);
+    engine.on_print(move |text| sink.borrow_mut().push(text.to_string()));
+    engine.set_max_operations(MAX_OPERATIONS);
+    let stop = Arc::clone(&input.stop);
+    engine.on_progress(move |_| stop.load(Ordering::Relaxed).then_some(Dynamic::UNIT));
+    engine.register_type_with_name::<Handle>("Table")
+        .register_get("rows", |table: &mut Handle| table.0.borrow().values.len() as i64)
+        .register_get("cols", |table: &mut Handle| table.0.borrow().cols() as i64)
+        .register_get("header", |table: &mut Handle| table.0.borrow().header)
+        .register_get("x", |table: &mut Handle| table.0.borrow().cursor.0 as i64)
+        .register_get("y", |table: &mut Handle| table.0.borrow().cursor.1 as i64)
+        .register_get("selected", |table: &mut Handle| -> Array {
+            table.0.borrow().selected.iter()
+                .map(|&(x, y)| Dynamic::from_array(vec![Dynamic::from(x as i64), Dynamic::from(y as i64)]))
+                .collect()
+        })
+        .register_fn("get", |table: &mut Handle, x: i64, y: i64| -> Fallible<String> {
+            let sheet = table.0.borrow();
+            let (x, y) = sheet.position(x, y)?;
+            Ok(sheet.values[y][x].clone())
+        })
+        .register_fn("contents", |table: &mut Handle, x: i64, y: i64| -> Fallible<String> {
+            let sheet = table.0.borrow();
+            let (x, y) = sheet.position(x, y)?;
+            Ok(sheet.contents[y][x].clone())
+        })
+        .register_fn("set", |table: &mut Handle, x: i64, y: i64, value: Dynamic| -> Fallible<()> {
+            let mut sheet = table.0.borrow_mut();
+            let (x, y) = sheet.position(x, y)?;
+            sheet.set(x, y, value.to_string());
+            Ok(())
+        })
+        .register_fn("row", |table: &mut Handle, y: i64| -> Fallible<Array> {
+            let sheet = table.0.borrow();
+            let (_, y) = sheet.position(1, y)?;
+            Ok(sheet.values[y].iter().cloned().map(Dynamic::from).collect())
+        })
+        .register_fn("column", |table: &mut Handle, name: &str| table.0.borrow().column(name) as i64);
+    let mut scope = Scope::new();
+    scope.push("table", Handle(Rc::clone(&shared)));
+    engine.run_with_scope(&mut scope, source).map_err(|e| match *e {
+        EvalAltResult::ErrorTerminated(..) => String::from("The script was stopped. Nothing was changed."),
+        EvalAltResult::ErrorTooManyOperations(..) => String::from("The script ran too long and was stopped. Nothing was changed."),
+        e => e.to_string(),
+    })?;
+    drop(scope);
+    let sheet = shared.borrow();
+    let changes = sheet.changed.iter().map(|&(y, x)| (x + 1, y + 1, sheet.contents[y][x].clone())).collect();
+    let printed = printed.borrow().clone();
+    Ok(Outcome { changes, printed })
+}
+
+#[cfg(not(feature = "scripting"))]
+pub fn run(_source: &str, _input: Input) -> Result<Outcome, String> {
+    Err(String::from("clicsv was built without scripting support"))
+}
+
+//the table handed to a script
+#[cfg(feature = "scripting")]
+#[derive(Clone)]
+struct Handle(std::rc::Rc<std::cell::RefCell<Sheet>>);
+
+#[cfg(feature = "scripting")]
+struct Sheet {
+    values: Vec<Vec<String>>,
+    contents: Vec<Vec<String>>,
+    header: bool,
+    cursor: (usize, usize),
+    selected: Vec<(usize, usize)>,
+    //the cells set so far, 0-based and by row
+    changed: std::collections::BTreeSet<(usize, usize)>,
+}
+
+#[cfg(feature = "scripting")]
+impl Sheet {
+    fn of(input: &Input) -> Self {
+        let document = input.document;
+        let contents = (1..document.table.num_rows()+1)
+            .map(|y| (1..document.table.num_cols()+1).map(|x| document.table.get_content_from(crate::Position { x, y })).collect())
+            .collect();
+        Self {
+            values: document.value_rows(),
+            contents,
+            header: input.header,
+            cursor: input.cursor,
+            selected: input.selected.clone(),
+            changed: Default::default(),
+        }
+    }
+
+    fn cols(&self) -> usize {
+        self.values.first().map_or(0, Vec::len)
+    }
+
+    //a 1-based position as indexes into the rows, or an error for the script when outside the table
+    fn position(&self, x: i64, y: i64) -> Result<(usize, usize), Box<rhai::EvalAltResult>> {
+        let inside = |n: i64, size: usize| n >= 1 && (n as usize) <= size;
+        if !inside(x, self.cols()) || !inside(y, self.values.len()) {
+            return Err(format!("({}, {}) is outside the table", x, y).into());
+        }
+        Ok((x as usize - 1, y as usize - 1))
+    }
+
+    fn set(&mut self, x: usize, y: usize, value: String) {
+        //a formula's value is only known once the cells are changed
+        if !formula::is_formula(&value) {
+            self.values[y][x] = value.clone();
+        }
+        self.contents[y][x] = value;
+        self.changed.insert((y, x));
+    }
+
+    fn column(&self, name: &str) -> usize {
+        let name = name.trim();
+        let named = match self.values.first() {
+            Some(first) if self.header => first.iter().position(|value| value.trim() == name).map(|x| x + 1),
+            _ => None,
+        };
+        named.or_else(|| formula::column_index(name))
+            .filter(|&x| x <= self.cols())
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(all(test, feature = "scripting"))]
+mod tests{
+    use super::*;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn a_stopped_script_ends_without_changes(){
+        let document = Document::from_bytes(b"a,b\n1,2\n", None).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let input = Input { document: &document, header: true, cursor: (1, 1), selected: Vec::new(), stop: Arc::clone(&stop) };
+        stop.store(true, Ordering::Relaxed);
+        let error = run("table.set(1, 1, \"x\"); loop {}", input).err().unwrap();
+        assert!(error.starts_with("The script was stopped"), "{}", error);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::dates::{DateTime, MONTHS, WEEKDAYS};
+
+//the `count` values that come after `first` and `second` when the two start a series: numbers
+//going up or down by the same step, ISO 8601 dates a number of days apart, or month and weekday
+//names like Jan, Feb or Monday, Tuesday. None when they don't
+pub fn extend(first: &str, second: &str, count: usize) -> Option<Vec<String>> {
+    let (first, second) = (first.trim(), second.trim());
+    numbers(first, second, count)
+        .or_else(|| dates(first, second, count))
+        .or_else(|| names(first, second, count, &MONTHS))
+        .or_else(|| names(first, second, count, &WEEKDAYS))
+}
+
+fn numbers(first: &str, second: &str, count: usize) -> Option<Vec<String>> {
+    if let (Ok(a), Ok(b)) = (first.parse::<i64>(), second.parse::<i64>()) {
+        let step = b.checked_sub(a)?;
+        return (1..count as i64 + 1)
+            .map(|k| Some(b.checked_add(step.checked_mul(k)?)?.to_string()))
+            .collect();
+    }
+    let (a, b) = (first.parse::<f64>().ok()?, second.parse::<f64>().ok()?);
+    if !a.is_finite() || !b.is_finite() {
+        return None;
+    }
+    //as many decimal places as the more precise of the two
+    let decimals = [first, second].iter()
+        .map(|number| number.split_once('.').map_or(0, |(_, decimals)| decimals.len()))
+        .max()
+        .unwrap_or(0);
+    Some((1..count + 1).map(|k| format!("{:.*}", decimals, b + (b - a) * k as f64)).collect())
+}
+
+fn dates(first: &str, second: &str, count: usize) -> Option<Vec<String>> {
+    let (a, b) = (DateTime::parse_iso(first)?, DateTime::parse_iso(second)?);
+    let step = b.days() - a.days();
+    let pattern = match b.time {
+        None => "%Y-%m-%d",
+        Some(_) if second.contains(' ') => "%Y-%m-%d %H:%M:%S",
+        Some(_) => "%Y-%m-%dT%H:%M:%S",
+    };
+    Some((1..count as i64 + 1).map(|k| {
+        let next = DateTime { time: b.time, ..DateTime::from_days(b.days() + step * k) };
+        next.format(pattern)
+    }).collect())
+}
+
+//names go round, so Nov, Dec carries on with Jan. Three letters stand for the abbreviation, and the
+//case of `second` (JAN, jan or Jan) is kept
+fn names(first: &str, second: &str, count: usize, names: &[&str]) -> Option<Vec<String>> {
+    let short = first.chars().count() == 3;
+    if short != (second.chars().count() == 3) {
+        return None;
+    }
+    let find = |value: &str| names.iter().position(|name| {
+        let name = if short { &name[..3] } else { name };
+        name.eq_ignore_ascii_case(value)
+    });
+    let (a, b) = (find(first)? as i64, find(second)? as i64);
+    let len = names.len() as i64;
+    let step = (b - a).rem_euclid(len);
+    Some((1..count as i64 + 1).map(|k| {
+        let name = names[(b + step * k).rem_euclid(len) as usize];
+        let name = if short { &name[..3] } else { name };
+        if second.chars().all(|c| c.is_ascii_uppercase()) {
+            name.to_uppercase()
+        } else if second.chars().all(|c| c.is_ascii_lowercase()) {
+            name.to_lowercase()
+        } else {
+            name.to_string()
+        }
+    }).collect())
+}
@@ -0,0 +1,188 @@
+use crate::workbook::Sheet;
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+pub fn is_sqlite(filename: &str) -> bool {
+    filename.ends_with(".sqlite") || filename.ends_with(".sqlite3") || filename.ends_with(".db")
+}
+
+//every table as it was last read or written, so saving only touches rows that changed
+#[derive(Default, Debug, Clone)]
+pub struct Snapshot {
+    #[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+    tables: HashMap<String, Vec<Vec<String>>>,
+}
+
+//each table becomes a sheet whose first column holds the rowid, which is how rows are written back
+#[cfg(feature = "sqlite")]
+pub fn read(filename: &str) -> Result<(Vec<Sheet>, Snapshot), Error> {
+    use rusqlite::types::ValueRef;
+    use rusqlite::{Connection, OpenFlags};
+
+    let connection = Connection::open_with_flags(filename, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(to_io)?;
+    let mut statement = connection
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY rowid")
+        .map_err(to_io)?;
+    let names = statement.query_map([], |row| row.get::<_, String>(0)).map_err(to_io)?
+        .collect::<Result<Vec<_>, _>>().map_err(to_io)?;
+    let mut sheets = Vec::new();
+    for name in names {
+        //WITHOUT ROWID tables are shown as they are, but can't be written back
+        let mut statement = match connection.prepare(&format!("SELECT rowid AS rowid, * FROM {}", quote(&name))) {
+            Ok(statement) => statement,
+            Err(_) => connection.prepare(&format!("SELECT * FROM {}", quote(&name))).map_err(to_io)?,
+        };
+        let mut rows = vec![statement.column_names().iter().map(|column| column.to_string()).collect::<Vec<_>>()];
+        let count = rows[0].len();
+        let mut results = statement.query([]).map_err(to_io)?;
+        while let Some(row) = results.next().map_err(to_io)? {
+            let values = (0..count).map(|i| Ok(match row.get_ref(i)? {
+                ValueRef::Null => String::new(),
+                ValueRef::Integer(n) => n.to_string(),
+                ValueRef::Real(r) => r.to_string(),
+                ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+                ValueRef::Blob(blob) => format!("<blob of {} bytes>", blob.len()),
+            })).collect::<Result<Vec<_>, rusqlite::Error>>().map_err(to_io)?;
+            rows.push(values);
+        }
+        sheets.push(Sheet { name, rows, notes: Vec::new() });
+    }
+    if sheets.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidData, "the database has no tables"));
+    }
+    let snapshot = Snapshot {
+        tables: sheets.iter().map(|sheet| (sheet.name.clone(), sheet.rows.clone())).collect(),
+    };
+    Ok((sheets, snapshot))
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn read(_filename: &str) -> Result<(Vec<Sheet>, Snapshot), Error> {
+    Err(Error::new(ErrorKind::Unsupported, "clicsv was built without sqlite support"))
+}
+
+//writes the changes since the snapshot in one transaction: UPDATE for edited cells, INSERT for rows
+//without a rowid and DELETE for rows that are gone. Returns the rowid each inserted row was given,
+//as (sheet, row, rowid), so it can be filled in
+#[cfg(feature = "sqlite")]
+pub fn write(filename: &str, mut sheets: Vec<Sheet>, snapshot: &mut Snapshot) -> Result<Vec<(usize, usize, i64)>, Error> {
+    use rusqlite::types::Value;
+    use rusqlite::{params_from_iter, Connection};
+    use std::collections::HashSet;
+
+    let mut connection = Connection::open(filename).map_err(to_io)?;
+    let transaction = connection.transaction().map_err(to_io)?;
+    let mut inserted = Vec::new();
+    for (index, sheet) in sheets.iter_mut().enumerate() {
+        let table = quote(&sheet.name);
+        let original = snapshot.tables.get(&sheet.name)
+            .ok_or_else(|| invalid(format!("no table named {} in {}", sheet.name, filename)))?;
+        if &sheet.rows == original {
+            continue;
+        }
+        if original[0][0] != "rowid" {
+            return Err(invalid(format!("{} is a WITHOUT ROWID table, which can't be edited", sheet.name)));
+        }
+        let header = sheet.rows[0].clone();
+        if header.first().map(|name| name.trim()) != Some("rowid") {
+            return Err(invalid(format!("the first column of {} has to stay rowid", sheet.name)));
+        }
+        //where each column of the sheet was in the table as read
+        let columns = header.iter().skip(1).map(|name| {
+            original[0].iter().position(|column| column == name.trim())
+                .ok_or_else(|| invalid(format!("no column {} in {}", name.trim(), sheet.name)))
+        }).collect::<Result<Vec<_>, _>>()?;
+        let before: HashMap<i64, &Vec<String>> = original.iter().skip(1)
+            .filter_map(|row| Some((row[0].parse().ok()?, row)))
+            .collect();
+        let mut kept = HashSet::new();
+        for (y, row) in sheet.rows.iter_mut().enumerate().skip(1) {
+            let cell = |i: usize| row.get(i).map(String::as_str).unwrap_or_default();
+            let rowid = cell(0).trim();
+            let old = match rowid {
+                "" => None,
+                rowid => {
+                    let rowid = rowid.parse::<i64>().map_err(|_| invalid(format!("{} is not a rowid", rowid)))?;
+                    kept.insert(rowid);
+                    before.get(&rowid).map(|old| (rowid, old))
+                }
+            };
+            if let Some((rowid, old)) = old {
+                let changed: Vec<usize> = (1..header.len())
+                    .filter(|&i| old.get(columns[i - 1]).map(String::as_str).unwrap_or_default() != cell(i))
+                    .collect();
+                if changed.is_empty() {
+                    continue;
+                }
+                let assignments: Vec<String> = changed.iter()
+                    .map(|&i| format!("{} = ?", quote(&original[0][columns[i - 1]])))
+                    .collect();
+                let mut values: Vec<Value> = changed.iter().map(|&i| typed(cell(i))).collect();
+                values.push(Value::Integer(rowid));
+                let sql = format!("UPDATE {} SET {} WHERE rowid = ?", table, assignments.join(", "));
+                transaction.execute(&sql, params_from_iter(values)).map_err(to_io)?;
+                continue;
+            }
+            let names: Vec<String> = (0..header.len()).filter(|&i| !cell(i).is_empty())
+                .map(|i| if i == 0 { String::from("rowid") } else { quote(&original[0][columns[i - 1]]) })
+                .collect();
+            let values: Vec<Value> = (0..header.len()).filter(|&i| !cell(i).is_empty()).map(|i| typed(cell(i))).collect();
+            let sql = match names.is_empty() {
+                true => format!("INSERT INTO {} DEFAULT VALUES", table),
+                false => format!("INSERT INTO {} ({}) VALUES ({})", table, names.join(", "), vec!["?"; names.len()].join(", ")),
+            };
+            transaction.execute(&sql, params_from_iter(values)).map_err(to_io)?;
+            let rowid = transaction.last_insert_rowid();
+            kept.insert(rowid);
+            if rowid.to_string() != cell(0) {
+                row[0] = rowid.to_string();
+                inserted.push((index, y, rowid));
+            }
+        }
+        for rowid in before.keys().filter(|rowid| !kept.contains(rowid)) {
+            transaction.execute(&format!("DELETE FROM {} WHERE rowid = ?", table), [rowid]).map_err(to_io)?;
+        }
+    }
+    transaction.commit().map_err(to_io)?;
+    for sheet in sheets {
+        snapshot.tables.insert(sheet.name, sheet.rows);
+    }
+    Ok(inserted)
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn write(_filename: &str, _sheets: Vec<Sheet>, _snapshot: &mut Snapshot) -> Result<Vec<(usize, usize, i64)>, Error> {
+    Err(Error::new(ErrorKind::Unsupported, "clicsv was built without sqlite support"))
+}
+
+//empty cells are NULL and numbers are stored as numbers; the column's affinity does the rest
+#[cfg(feature = "sqlite")]
+fn typed(text: &str) -> rusqlite::types::Value {
+    use rusqlite::types::Value;
+
+    if text.is_empty() {
+        Value::Null
+    } else if let Ok(n) = text.trim().parse::<i64>() {
+        Value::Integer(n)
+    } else if let Ok(r) = text.trim().parse::<f64>() {
+        Value::Real(r)
+    } else {
+        Value::Text(text.to_string())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+#[cfg(feature = "sqlite")]
+fn to_io(e: rusqlite::Error) -> Error {
+    Error::new(ErrorKind::InvalidData, e.to_string())
+}
+
+#[cfg(feature = "sqlite")]
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
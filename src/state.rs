@@ -0,0 +1,176 @@
+use crate::formula;
+use crate::highlight::Highlight;
+use crate::numbers::NumberFormat;
+use crate::table::Align;
+use crate::Document;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use toml::Value;
+
+//what is remembered about each file between runs, kept under the file's full path:
+//
+//  ["/home/me/data.csv".align]
+//  B = "center"
+//
+//  ["/home/me/data.csv".lock]
+//  rows = [1]
+//  columns = ["A"]
+//
+//  ["/home/me/data.csv".format]
+//  D = "thousands 2"
+//
+//  ["/home/me/data.csv".widths]
+//  C = 40
+//
+//  ["/home/me/data.csv"]
+//  highlight = ["D < 0 -> red"]
+//  hidden = ["E"]
+
+//$XDG_STATE_HOME/clicsv/state.toml, falling back to ~/.local/state
+pub fn path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))?;
+    Some(base.join("clicsv").join("state.toml"))
+}
+
+//puts back what was remembered about the document's file
+pub fn restore(document: &mut Document) {
+    let Some(key) = document.file_name.as_deref().and_then(key) else { return };
+    let state = read();
+    let Some(file) = state.get(&key).and_then(Value::as_table) else { return };
+    if let Some(align) = file.get("align").and_then(Value::as_table) {
+        for (column, name) in align {
+            if let (Some(x), Some(align)) = (formula::column_index(column), name.as_str().and_then(Align::parse)) {
+                document.table.alignments.insert(x, align);
+            }
+        }
+    }
+    if let Some(format) = file.get("format").and_then(Value::as_table) {
+        for (column, text) in format {
+            if let (Some(x), Some(format)) = (formula::column_index(column), text.as_str().and_then(|text| NumberFormat::parse(text).ok())) {
+                document.table.number_formats.insert(x, format);
+            }
+        }
+    }
+    if let Some(lock) = file.get("lock").and_then(Value::as_table) {
+        let values = |name: &str| lock.get(name).and_then(Value::as_array).cloned().unwrap_or_default();
+        document.table.locked_rows.extend(values("rows").iter()
+            .filter_map(Value::as_integer)
+            .filter(|&y| y > 0)
+            .map(|y| y as usize));
+        document.table.locked_cols.extend(values("columns").iter()
+            .filter_map(Value::as_str)
+            .filter_map(formula::column_index));
+    }
+    //rules are remembered with column letters, so no header names are needed to read them
+    if let Some(highlights) = file.get("highlight").and_then(Value::as_array) {
+        document.table.highlights.extend(highlights.iter()
+            .filter_map(Value::as_str)
+            .filter_map(|text| Highlight::parse(text, &[]).ok()));
+    }
+    if let Some(widths) = file.get("widths").and_then(Value::as_table) {
+        for (column, width) in widths {
+            if let (Some(x), Some(width)) = (formula::column_index(column), width.as_integer().filter(|&w| w > 0)) {
+                document.table.fixed_widths.insert(x, width as usize);
+            }
+        }
+    }
+    //columns the file no longer has are let go, and at least one column is always left showing
+    if let Some(hidden) = file.get("hidden").and_then(Value::as_array) {
+        let ncols = document.table.num_cols();
+        let hidden: Vec<usize> = hidden.iter()
+            .filter_map(Value::as_str)
+            .filter_map(formula::column_index)
+            .filter(|&x| x <= ncols)
+            .collect();
+        if (1..ncols+1).any(|x| !hidden.contains(&x)) {
+            document.table.hidden.extend(hidden);
+        }
+    }
+}
+
+//remembers the document's column alignments, number formats, locks, highlights, widths and hidden columns for the next time its file is opened
+pub fn remember(document: &Document) -> Result<(), String> {
+    let Some(key) = document.file_name.as_deref().and_then(key) else { return Ok(()) };
+    let mut state = read();
+    let mut file = match state.remove(&key) {
+        Some(Value::Table(file)) => file,
+        _ => toml::Table::new(),
+    };
+    let align: toml::Table = document.table.alignments.iter()
+        .map(|(&x, align)| (formula::column_name(x), Value::from(align.name())))
+        .collect();
+    if align.is_empty() {
+        file.remove("align");
+    } else {
+        file.insert(String::from("align"), Value::Table(align));
+    }
+    let format: toml::Table = document.table.number_formats.iter()
+        .map(|(&x, format)| (formula::column_name(x), Value::from(format.text())))
+        .collect();
+    if format.is_empty() {
+        file.remove("format");
+    } else {
+        file.insert(String::from("format"), Value::Table(format));
+    }
+    let (mut rows, mut columns): (Vec<usize>, Vec<usize>) = (
+        document.table.locked_rows.iter().copied().collect(),
+        document.table.locked_cols.iter().copied().collect(),
+    );
+    rows.sort_unstable();
+    columns.sort_unstable();
+    if rows.is_empty() && columns.is_empty() {
+        file.remove("lock");
+    } else {
+        let mut lock = toml::Table::new();
+        lock.insert(String::from("rows"), Value::from(rows.into_iter().map(|y| y as i64).collect::<Vec<_>>()));
+        lock.insert(String::from("columns"), Value::from(columns.into_iter().map(formula::column_name).collect::<Vec<_>>()));
+        file.insert(String::from("lock"), Value::Table(lock));
+    }
+    let highlights: Vec<String> = document.table.highlights.iter().map(Highlight::text).collect();
+    if highlights.is_empty() {
+        file.remove("highlight");
+    } else {
+        file.insert(String::from("highlight"), Value::from(highlights));
+    }
+    let widths: toml::Table = document.table.fixed_widths.iter()
+        .map(|(&x, &width)| (formula::column_name(x), Value::from(width as i64)))
+        .collect();
+    if widths.is_empty() {
+        file.remove("widths");
+    } else {
+        file.insert(String::from("widths"), Value::Table(widths));
+    }
+    let mut hidden: Vec<usize> = document.table.hidden.iter().copied().collect();
+    hidden.sort_unstable();
+    if hidden.is_empty() {
+        file.remove("hidden");
+    } else {
+        file.insert(String::from("hidden"), Value::from(hidden.into_iter().map(formula::column_name).collect::<Vec<_>>()));
+    }
+    if !file.is_empty() {
+        state.insert(key, Value::Table(file));
+    }
+    let path = path().ok_or("nowhere to keep the state file")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(&path, state.to_string()).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+//a file is known by its full path, so it is found again from any directory
+fn key(file_name: &str) -> Option<String> {
+    Some(fs::canonicalize(file_name).ok()?.to_str()?.to_string())
+}
+
+//a missing or broken state file remembers nothing
+fn read() -> toml::Table {
+    path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|text| text.parse().ok())
+        .unwrap_or_default()
+}
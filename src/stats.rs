@@ -0,0 +1,300 @@
+use crate::formula;
+use crate::table::{self, CellType};
+
+use std::collections::{HashMap, HashSet};
+
+//what can be said about a column (or any run) of cell values
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    //cells with something in them
+    pub count: usize,
+    pub empty: usize,
+    pub distinct: usize,
+    //only when every non-empty cell holds a number
+    pub numbers: Option<Numbers>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Numbers {
+    pub sum: f64,
+    pub mean: f64,
+    pub std: f64,
+    pub min: f64,
+    pub max: f64,
+    pub q1: f64,
+    pub median: f64,
+    pub q3: f64,
+}
+
+impl Summary {
+    pub fn of<'a>(values: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut summary = Self::default();
+        let mut seen = HashSet::new();
+        let mut numbers = Some(Vec::new());
+        for value in values {
+            let value = value.trim();
+            if value.is_empty() {
+                summary.empty += 1;
+                continue;
+            }
+            summary.count += 1;
+            seen.insert(value);
+            numbers = numbers.and_then(|mut numbers| {
+                if !table::infer_type(value).is_number() {
+                    return None;
+                }
+                numbers.push(value.parse::<f64>().ok()?);
+                Some(numbers)
+            });
+        }
+        summary.distinct = seen.len();
+        summary.numbers = numbers.filter(|numbers| !numbers.is_empty()).map(Numbers::of);
+        summary
+    }
+
+    //one line per figure, for showing in a panel
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("count    {}", self.count),
+            format!("empty    {}", self.empty),
+            format!("distinct {}", self.distinct),
+        ];
+        match &self.numbers {
+            Some(numbers) => lines.extend([
+                ("sum", numbers.sum),
+                ("mean", numbers.mean),
+                ("std", numbers.std),
+                ("min", numbers.min),
+                ("q1", numbers.q1),
+                ("median", numbers.median),
+                ("q3", numbers.q3),
+                ("max", numbers.max),
+            ].iter().map(|(name, value)| format!("{:<8} {}", name, round(*value)))),
+            None if self.count > 0 => lines.push(String::from("(not all numeric)")),
+            None => (),
+        }
+        lines
+    }
+}
+
+//the names over the table describe makes
+pub const DESCRIBE_HEADER: [&str; 9] = ["column", "type", "count", "missing %", "distinct", "min", "max", "mean", "top values"];
+
+//how many of the most common values describe lists for a column that isn't numeric
+const TOP_VALUES: usize = 3;
+
+//a row describing a column named `name` holding `values`, under DESCRIBE_HEADER: its type (the one
+//its cells share, `mixed` when they don't), how many cells hold something and what share are blank,
+//then the range and mean of numbers, the range of dates, or else the most common values
+pub fn describe(name: &str, values: &[String]) -> Vec<String> {
+    let summary = Summary::of(values.iter().map(String::as_str));
+    let kinds: HashSet<CellType> = values.iter()
+        .map(|value| table::infer_type(value))
+        .filter(|&kind| kind != CellType::Empty)
+        .collect();
+    let kind = match kinds.len() {
+        0 => "empty",
+        1 => kinds.into_iter().next().unwrap_or_default().name(),
+        //whole numbers among decimals
+        _ if summary.numbers.is_some() => "float",
+        _ => "mixed",
+    };
+    let missing = if values.is_empty() { 0.0 } else { summary.empty as f64 * 100.0 / values.len() as f64 };
+    let (min, max, mean, top) = match &summary.numbers {
+        Some(numbers) => (round(numbers.min), round(numbers.max), round(numbers.mean), String::new()),
+        //ISO 8601 dates sort as text
+        None if kind == "date" => {
+            let dates = values.iter().map(|value| value.trim()).filter(|value| !value.is_empty());
+            let min = dates.clone().min().unwrap_or_default().to_string();
+            let max = dates.max().unwrap_or_default().to_string();
+            (min, max, String::new(), String::new())
+        }
+        None => (String::new(), String::new(), String::new(), top_values(values)),
+    };
+    vec![
+        name.to_string(),
+        kind.to_string(),
+        summary.count.to_string(),
+        format!("{:.1}", missing),
+        summary.distinct.to_string(),
+        min,
+        max,
+        mean,
+        top,
+    ]
+}
+
+//the most common values that aren't blank, each with how many cells hold it, like `north (12), south (9)`.
+//Values as common as each other keep the order they first turn up in
+fn top_values(values: &[String]) -> String {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    let mut places: HashMap<&str, usize> = HashMap::new();
+    for value in values.iter().map(|value| value.trim()).filter(|value| !value.is_empty()) {
+        match places.get(value) {
+            Some(&i) => counts[i].1 += 1,
+            None => {
+                places.insert(value, counts.len());
+                counts.push((value, 1));
+            }
+        }
+    }
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    counts.iter()
+        .take(TOP_VALUES)
+        .map(|(value, count)| format!("{} ({})", value.replace('\n', "↵"), count))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl Numbers {
+    fn of(mut values: Vec<f64>) -> Self {
+        values.sort_by(|a, b| a.total_cmp(b));
+        let n = values.len() as f64;
+        let sum: f64 = values.iter().sum();
+        let mean = sum / n;
+        let variance = values.iter().map(|value| (mean - value) * (mean - value)).sum::<f64>() / n;
+        Self {
+            sum,
+            mean,
+            std: variance.sqrt(),
+            min: values[0],
+            max: values[values.len() - 1],
+            q1: quantile(&values, 0.25),
+            median: quantile(&values, 0.5),
+            q3: quantile(&values, 0.75),
+        }
+    }
+}
+
+//a figure that can be asked for by name, worked out from the values of a column or selection.
+//Adding one to FUNCTIONS offers it everywhere a statistic is chosen
+pub struct Function {
+    pub name: &'static str,
+    pub description: &'static str,
+    //what the number after the name means, for functions that take one, like percentile 90
+    pub argument: Option<&'static str>,
+    //None when the values don't give the figure, like a mean without any numbers
+    pub compute: fn(&Sample, f64) -> Option<String>,
+}
+
+pub const FUNCTIONS: &[Function] = &[
+    Function { name: "count", description: "cells with something in them", argument: None, compute: |sample, _| Some(sample.values.len().to_string()) },
+    Function { name: "distinct", description: "different values", argument: None, compute: |sample, _| Some(sample.values.iter().collect::<HashSet<_>>().len().to_string()) },
+    Function { name: "sum", description: "the numbers added up", argument: None, compute: |sample, _| sample.number(|numbers| numbers.iter().sum()) },
+    Function { name: "mean", description: "the average number", argument: None, compute: |sample, _| sample.number(mean) },
+    Function { name: "median", description: "the middle number", argument: None, compute: |sample, _| sample.number(|numbers| quantile(numbers, 0.5)) },
+    Function { name: "mode", description: "the most common value", argument: None, compute: |sample, _| mode(&sample.values) },
+    Function { name: "percentile", description: "the number that many percent of the numbers are below", argument: Some("percent, 0 to 100"), compute: |sample, p| {
+        if !(0.0..=100.0).contains(&p) {
+            return None;
+        }
+        sample.number(|numbers| quantile(numbers, p / 100.0))
+    } },
+    Function { name: "geomean", description: "the geometric mean of positive numbers", argument: None, compute: |sample, _| {
+        if sample.numbers.iter().any(|&number| number <= 0.0) {
+            return None;
+        }
+        sample.number(|numbers| mean(&numbers.iter().map(|number| number.ln()).collect::<Vec<_>>()).exp())
+    } },
+    Function { name: "std", description: "the standard deviation", argument: None, compute: |sample, _| sample.number(|numbers| {
+        let mean = mean(numbers);
+        (numbers.iter().map(|number| (mean - number) * (mean - number)).sum::<f64>() / numbers.len() as f64).sqrt()
+    }) },
+    Function { name: "min", description: "the smallest number", argument: None, compute: |sample, _| sample.number(|numbers| numbers[0]) },
+    Function { name: "max", description: "the largest number", argument: None, compute: |sample, _| sample.number(|numbers| numbers[numbers.len() - 1]) },
+];
+
+//a function from FUNCTIONS with its number, if it takes one
+#[derive(Clone, Copy)]
+pub struct Statistic {
+    pub function: &'static Function,
+    pub argument: f64,
+}
+
+impl Statistic {
+    //a function's name followed by its number when it takes one, like `median` or `percentile 90`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let (name, argument) = text.split_once(' ').unwrap_or((text, ""));
+        let function = FUNCTIONS.iter()
+            .find(|function| function.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("no statistic {}; there are {}", name, names()))?;
+        let argument = match function.argument {
+            Some(meaning) => argument.trim().parse().map_err(|_| format!("{} needs a number ({})", function.name, meaning))?,
+            None if argument.trim().is_empty() => 0.0,
+            None => return Err(format!("{} takes no number", function.name)),
+        };
+        Ok(Self { function, argument })
+    }
+
+    //the name as asked for, like `percentile 90`
+    pub fn label(&self) -> String {
+        match self.function.argument {
+            Some(_) => format!("{} {}", self.function.name, formula::format_number(self.argument)),
+            None => self.function.name.to_string(),
+        }
+    }
+
+    //the figure for the values, or a dash when they don't give one
+    pub fn of<'a>(&self, values: impl IntoIterator<Item = &'a str>) -> String {
+        (self.function.compute)(&Sample::of(values), self.argument).unwrap_or_else(|| String::from("-"))
+    }
+}
+
+//the names in FUNCTIONS, for messages
+pub fn names() -> String {
+    FUNCTIONS.iter().map(|function| function.name).collect::<Vec<_>>().join(", ")
+}
+
+//what a function is worked out from: the values that aren't blank, and the numbers among them in order
+pub struct Sample<'a> {
+    pub values: Vec<&'a str>,
+    pub numbers: Vec<f64>,
+}
+
+impl<'a> Sample<'a> {
+    fn of(values: impl IntoIterator<Item = &'a str>) -> Self {
+        let values: Vec<&str> = values.into_iter().map(str::trim).filter(|value| !value.is_empty()).collect();
+        let mut numbers: Vec<f64> = values.iter()
+            .filter(|value| table::infer_type(value).is_number())
+            .filter_map(|value| value.parse().ok())
+            .collect();
+        numbers.sort_by(|a, b| a.total_cmp(b));
+        Self { values, numbers }
+    }
+
+    //a figure from the numbers, rounded for the screen; None when there are none
+    fn number(&self, figure: impl Fn(&[f64]) -> f64) -> Option<String> {
+        if self.numbers.is_empty() {
+            return None;
+        }
+        Some(round(figure(&self.numbers)))
+    }
+}
+
+fn mean(numbers: &[f64]) -> f64 {
+    numbers.iter().sum::<f64>() / numbers.len() as f64
+}
+
+//the value most cells hold, the first of them to appear on a tie, with how many hold it
+fn mode(values: &[&str]) -> Option<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for value in values {
+        *counts.entry(value).or_default() += 1;
+    }
+    let most = counts.values().copied().max()?;
+    let value = values.iter().find(|value| counts[*value] == most)?;
+    Some(format!("{} ({} of {})", value, most, values.len()))
+}
+
+//the q-th quantile of sorted values, interpolating between the two nearest ones
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let position = q * (sorted.len() - 1) as f64;
+    let (below, above) = (position.floor() as usize, position.ceil() as usize);
+    sorted[below] + (sorted[above] - sorted[below]) * (position - below as f64)
+}
+
+//six decimal places is plenty on screen
+fn round(value: f64) -> String {
+    formula::format_number((value * 1e6).round() / 1e6)
+}
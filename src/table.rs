@@ -1,5 +1,12 @@
 extern crate unicode_width;
+use crate::dates::DateTime;
+use crate::highlight::Highlight;
+use crate::numbers::NumberFormat;
 use crate::Position;
+use crate::validation::Rule;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
 #[derive(PartialEq, Debug, Clone)]
@@ -9,12 +16,170 @@ pub struct Cell {
     pub x_loc: usize,
     pub y_loc: usize,
     pub highlighted: bool,
+    //what the contents look like, worked out again whenever they change
+    pub kind: CellType,
+}
+
+//where a cell's contents sit in its column
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "left" => Some(Self::Left),
+            "center" | "centre" => Some(Self::Center),
+            "right" => Some(Self::Right),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Left => "left",
+            Self::Center => "center",
+            Self::Right => "right",
+        }
+    }
+
+    //the spaces to put before and after contents `filling` narrower than their column
+    pub fn padding(self, filling: usize) -> (usize, usize) {
+        match self {
+            Self::Left => (0, filling),
+            Self::Center => (filling / 2, filling - filling / 2),
+            Self::Right => (filling, 0),
+        }
+    }
+}
+
+//the kinds of value a cell can hold
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+pub enum CellType {
+    #[default]
+    Empty,
+    Integer,
+    Float,
+    Date,
+    Bool,
+    Text,
+}
+
+impl CellType {
+    pub fn is_number(self) -> bool {
+        matches!(self, CellType::Integer | CellType::Float)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CellType::Empty => "empty",
+            CellType::Integer => "integer",
+            CellType::Float => "float",
+            CellType::Date => "date",
+            CellType::Bool => "bool",
+            CellType::Text => "text",
+        }
+    }
+}
+
+//one column of a sort over several columns
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SortKey {
+    pub x: usize,
+    pub ascending: bool,
+}
+
+//how a sort compares the values in a column. Blank cells always go last, whatever the direction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Collation {
+    Number,
+    Date,
+    Text,
+}
+
+impl Collation {
+    //numbers or dates when every value in the column is one, text otherwise
+    fn of(kinds: impl Iterator<Item = CellType> + Clone) -> Self {
+        let only = |test: fn(CellType) -> bool| {
+            kinds.clone().any(test) && kinds.clone().all(|kind| kind == CellType::Empty || test(kind))
+        };
+        if only(CellType::is_number) {
+            Self::Number
+        } else if only(|kind| kind == CellType::Date) {
+            Self::Date
+        } else {
+            Self::Text
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Number => "numeric",
+            Self::Date => "date",
+            Self::Text => "text",
+        }
+    }
+}
+
+//a cell's contents read the way its column sorts; dates become seconds since 1970
+enum SortValue {
+    Number(f64),
+    Date(i64),
+    Text(String),
+}
+
+impl SortValue {
+    fn new(contents: &str, collation: Collation) -> Option<Self> {
+        let value = contents.trim();
+        if value.is_empty() {
+            return None;
+        }
+        Some(match collation {
+            Collation::Number => Self::Number(value.parse().unwrap_or(0.0)),
+            Collation::Date => Self::Date(DateTime::parse_iso(value).map_or(0, |date| {
+                let (hour, minute, second) = date.time.unwrap_or_default();
+                date.days() * 86_400 + i64::from(hour * 3600 + minute * 60 + second)
+            })),
+            Collation::Text => Self::Text(value.to_string()),
+        })
+    }
+
+    fn compare(&self, other: &Self) -> std::cmp::Ordering {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => a.total_cmp(b),
+            (Self::Date(a), Self::Date(b)) => a.cmp(b),
+            (Self::Text(a), Self::Text(b)) => a.cmp(b),
+            _ => std::cmp::Ordering::Equal,
+        }
+    }
+}
+
+//the type a cell's contents look like. Dates are ISO 8601, optionally with a time
+pub fn infer_type(contents: &str) -> CellType {
+    let value = contents.trim();
+    if value.is_empty() {
+        CellType::Empty
+    } else if value.parse::<i64>().is_ok() {
+        CellType::Integer
+    } else if value.parse::<f64>().is_ok_and(|n| n.is_finite()) {
+        CellType::Float
+    } else if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+        CellType::Bool
+    } else if DateTime::parse_iso(value).is_some() {
+        CellType::Date
+    } else {
+        CellType::Text
+    }
 }
 
 impl From<String> for Cell {
     fn from(string:String) -> Self{
         Self {
             width:UnicodeWidthStr::width(&*string),
+            kind: infer_type(&string),
             contents: string,
             x_loc: 0usize,
             y_loc: 0usize,
@@ -26,7 +191,8 @@ impl From<String> for Cell {
 impl <'a> From<&'a str> for Cell {
     fn from(string: &'a str) -> Self{
         Self {
-            width: UnicodeWidthStr::width(&*string),
+            width: UnicodeWidthStr::width(string),
+            kind: infer_type(string),
             contents: string.into(),
             x_loc: 0usize,
             y_loc: 0usize,
@@ -40,8 +206,9 @@ impl Cell {
         self.width-maximum_width+1
     }
     pub fn edit_content(&mut self, new_content: String){
+        self.kind = infer_type(&new_content);
         self.contents = new_content.clone();
-        self.width = new_content.len();
+        self.width = UnicodeWidthStr::width(new_content.as_str());
     }
     pub fn highlight(&mut self) {
         self.highlighted = true;
@@ -52,89 +219,421 @@ impl Cell {
     pub fn get_content(self) -> String{
         self.contents
     }
+    //the contents exactly as read or typed
+    pub fn value(&self) -> &str{
+        &self.contents
+    }
 }
 
 
 pub type Width = usize;
 
+//the longest start of a string that fits within `max_width` terminal columns. Cuts only fall
+//between graphemes, so an accent or an emoji made of several code points stays whole
+pub fn truncate_width(string: &str, max_width: Width) -> &str {
+    let mut width = 0usize;
+    for (at, grapheme) in string.grapheme_indices(true) {
+        width += UnicodeWidthStr::width(grapheme);
+        if width > max_width {
+            return &string[..at];
+        }
+    }
+    string
+}
+
+//shortens a string to fit within `max_width` terminal columns, marking the cut with an ellipsis
+pub fn fit_width(string: &str, max_width: Width) -> String {
+    if UnicodeWidthStr::width(string) <= max_width {
+        return string.to_string();
+    }
+    let mut fitted = truncate_width(string, max_width.saturating_sub(1)).to_string();
+    if max_width > 0 {
+        fitted.push('…');
+    }
+    fitted
+}
+
+//splits a string into lines of at most `max_width` terminal columns. Past `max_lines`, the last line is cut with an ellipsis
+pub fn wrap_width(string: &str, max_width: Width, max_lines: usize) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    let mut width = 0usize;
+    for grapheme in string.graphemes(true) {
+        let grapheme_width = UnicodeWidthStr::width(grapheme);
+        if width + grapheme_width > max_width && width > 0 {
+            lines.push(String::new());
+            width = 0;
+        }
+        width += grapheme_width;
+        if let Some(line) = lines.last_mut() {
+            line.push_str(grapheme);
+        }
+    }
+    if lines.len() > max_lines && max_lines > 0 {
+        let rest: String = lines[max_lines - 1..].concat();
+        lines.truncate(max_lines - 1);
+        lines.push(fit_width(&rest, max_width));
+    }
+    lines
+}
+
+//splits a string into lines of at most `max_width` terminal columns, breaking between words where
+//it can and inside words longer than a line
+pub fn wrap_words(string: &str, max_width: Width) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for word in string.split_word_bounds() {
+        let line = lines.last_mut().expect("there is always a line");
+        let width = UnicodeWidthStr::width(line.as_str());
+        if width + UnicodeWidthStr::width(word) <= max_width {
+            line.push_str(word);
+            continue;
+        }
+        let word = if width > 0 { word.trim_start() } else { word };
+        if word.is_empty() {
+            continue;
+        }
+        if width > 0 {
+            lines.push(String::new());
+        }
+        let mut pieces = wrap_width(word, max_width, 0);
+        let last = pieces.pop().unwrap_or_default();
+        lines.pop();
+        lines.extend(pieces);
+        lines.push(last);
+    }
+    lines
+}
+
 #[derive(PartialEq, Debug, Default)]
 pub struct Table {
     pub cells: Vec<Cell>,
     pub widest_cell_length: Width,
     pub width_sum: Width,
     pub cell_count: usize,
+    pub max_col_width: Option<Width>,
+    pub max_cell_width: Option<Width>,
+    //columns resized by hand, which ignore the caps above
+    pub fixed_widths: HashMap<usize, Width>,
+    //what each column's values have to look like, see validation.rs
+    pub rules: HashMap<usize, Rule>,
+    //columns set to sit left, centered or right; the rest put numbers on the right
+    pub alignments: HashMap<usize, Align>,
+    //how columns show their numbers, see numbers.rs
+    pub number_formats: HashMap<usize, NumberFormat>,
+    //columns left off the screen; they keep their cells and are saved as usual
+    pub hidden: HashSet<usize>,
+    //rows and columns whose cells are protected from edits, which move with their cells
+    pub locked_rows: HashSet<usize>,
+    pub locked_cols: HashSet<usize>,
+    //colours for cells that pass a test, tried in order, see highlight.rs
+    pub highlights: Vec<Highlight>,
+    //notes attached to cells by (x, y), which move with their cells
+    pub notes: HashMap<(usize, usize), String>,
+    //what each cell held before it was edited this session, oldest first, which also moves with its cell
+    pub history: HashMap<(usize, usize), Vec<String>>,
+    //maps (x, y) to the cell's index in `cells`
+    index: HashMap<(usize, usize), usize>,
+    //dimensions and the widest cell of each column (x - 1), kept up to date so drawing never scans every cell
+    rows: usize,
+    cols: usize,
+    column_widths: Vec<ColumnWidth>,
+}
+
+//the widest cell of a column and how many of its cells are that wide. A cell getting narrower only
+//means looking down the whole column again when it was the last one that wide
+#[derive(PartialEq, Debug, Default, Clone, Copy)]
+struct ColumnWidth {
+    widest: Width,
+    count: usize,
+}
+
+impl ColumnWidth {
+    fn of<'a>(cells: impl Iterator<Item = &'a Cell>) -> Self {
+        let mut column = Self::default();
+        cells.for_each(|cell| column.add(cell.width));
+        column
+    }
+
+    fn add(&mut self, width: Width) {
+        if width > self.widest || self.count == 0 {
+            *self = Self { widest: width, count: 1 };
+        } else if width == self.widest {
+            self.count += 1;
+        }
+    }
+
+    //false when the cell was the last of the widest, so the column needs looking over again
+    fn remove(&mut self, width: Width) -> bool {
+        if width == self.widest {
+            self.count -= 1;
+            return self.count > 0;
+        }
+        true
+    }
 }
 
 impl From<String> for Table
 {
     fn from(slice: String) -> Self 
     {
-        let mut cells = Vec::new();
-        let mut y = 0usize;
-        let mut cell_count = 0usize;
-        let mut widest_cell_length = 0usize;
-        let mut width_sum = 0usize;
-        
-        for value in slice.lines()
-        {
-            y += 1;
-            let mut j = 0usize;
-            let mut line = String::from(value);
-            if line.len() > width_sum 
-            {
-                width_sum = line.len()
+        Table::from_rows(parse_csv(&slice, ',', '"'))
+    }
+}
+
+//how far loading a file has got, shared with whoever is showing it. Cancelling asks the loader
+//to stop at the next row
+#[derive(Default, Debug)]
+pub struct Progress {
+    rows: AtomicUsize,
+    //downloaded so far, for files read from a URL
+    bytes: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    pub fn rows(&self) -> usize {
+        self.rows.load(Ordering::Relaxed)
+    }
+
+    pub fn add_row(&self) {
+        self.rows.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn add_bytes(&self, bytes: usize) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+//splits csv text into rows of fields following RFC 4180: fields may be wrapped in quotes (normally '"'),
+//inside which delimiters and line breaks are literal and a doubled quote stands for a single one
+pub fn parse_csv(input: &str, delimiter: char, quote: char) -> Vec<Vec<String>> {
+    parse_csv_with(input, delimiter, quote, &Progress::default())
+}
+
+//like parse_csv, counting rows as it goes; a cancelled load gives back the rows read so far
+pub fn parse_csv_with(input: &str, delimiter: char, quote: char, progress: &Progress) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c == quote {
+                if chars.peek() == Some(&quote) {
+                    field.push(quote);
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if c == quote && field.is_empty() {
+            in_quotes = true;
+        } else if c == delimiter {
+            row.push(std::mem::take(&mut field));
+        } else if c == '\r' && chars.peek() == Some(&'\n') {
+            continue;
+        } else if c == '\n' {
+            row.push(std::mem::take(&mut field));
+            rows.push(std::mem::take(&mut row));
+            progress.add_row();
+            if progress.is_cancelled() {
+                return rows;
             }
-            line.push(',');
-            let mut x = 0usize;
-            for (i, c) in line.chars().enumerate() 
-            {
-                if c == ',' 
-                {
-                    x +=1;
-                    let mut cell = Cell::from(String::from(&line[j..i])+&" ");
-                    cell_count += 1;
-                    cell.x_loc = x;
-                    cell.y_loc = y;
-                    if cell.width > widest_cell_length
-                    {
-                        widest_cell_length = cell.width;
-                    }                  
-                    cells.push(cell);
-                    j = i+1;
+        } else {
+            field.push(c);
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+//how many lines of a file are looked at to guess its dialect
+const SNIFF_LINES: usize = 10;
+
+//guesses the quote character: single quotes only win when more fields start with them than with double quotes
+pub fn sniff_quote(sample: &str) -> char {
+    let mut double = 0;
+    let mut single = 0;
+    for line in sample.lines().take(SNIFF_LINES) {
+        let mut field_start = true;
+        for c in line.chars() {
+            if field_start {
+                match c {
+                    '"' => double += 1,
+                    '\'' => single += 1,
+                    _ => (),
                 }
             }
+            field_start = matches!(c, ',' | ';' | '\t' | '|');
+        }
+    }
+    if single > double { '\'' } else { '"' }
+}
+
+//guesses whether the first row holds column names: it does when some column is numeric below the
+//first row but not in it. Without numeric columns, a first row of distinct, non-blank values counts
+pub fn sniff_header(rows: &[Vec<String>]) -> bool {
+    let Some((first, rest)) = rows.split_first() else { return false };
+    let rest = &rest[..rest.len().min(SNIFF_LINES * 2)];
+    if rest.is_empty() {
+        return false;
+    }
+    let is_number = |value: &str| value.trim().parse::<f64>().is_ok();
+    let mut votes = 0i32;
+    for (x, name) in first.iter().enumerate() {
+        let values: Vec<&str> = rest.iter()
+            .filter_map(|row| row.get(x))
+            .map(|value| value.as_str())
+            .filter(|value| !value.trim().is_empty())
+            .collect();
+        if values.is_empty() || !values.iter().all(|value| is_number(value)) {
+            continue;
+        }
+        votes += if is_number(name) { -1 } else { 1 };
+    }
+    if votes != 0 {
+        return votes > 0;
+    }
+    let mut seen = std::collections::HashSet::new();
+    first.iter().all(|name| !name.trim().is_empty() && seen.insert(name.trim()))
+}
+
+//guesses the delimiter of csv-like text: the candidate that appears the same (non-zero) number of
+//times on each of the first lines wins, preferring the one that splits lines into the most fields
+pub fn sniff_delimiter(sample: &str, quote: char) -> char {
+    let lines: Vec<&str> = sample.lines().filter(|l| !l.trim().is_empty()).take(SNIFF_LINES).collect();
+    let mut best = (',', 0usize, false);
+    for delimiter in [',', ';', '\t', '|'] {
+        let counts: Vec<usize> = lines.iter().map(|line| count_unquoted(line, delimiter, quote)).collect();
+        let first = counts.first().copied().unwrap_or(0);
+        if first == 0 {
+            continue;
+        }
+        let consistent = counts.iter().all(|&count| count == first);
+        if (consistent && !best.2) || (consistent == best.2 && first > best.1) {
+            best = (delimiter, first, consistent);
         }
-        Self 
-        {
-            cells: cells,
-            widest_cell_length: widest_cell_length,
-            width_sum: width_sum,
-            cell_count: cell_count,
+    }
+    best.0
+}
+
+fn count_unquoted(line: &str, delimiter: char, quote: char) -> usize {
+    let mut in_quotes = false;
+    let mut count = 0;
+    for c in line.chars() {
+        if c == quote {
+            in_quotes = !in_quotes;
+        } else if c == delimiter && !in_quotes {
+            count += 1;
         }
     }
+    count
+}
+
+//quotes a field for writing if it contains the delimiter, quotes or line breaks
+pub fn quote_field(field: &str, delimiter: char, quote: char) -> String {
+    if field.contains(delimiter) || field.contains(quote) || field.contains('\n') || field.contains('\r') {
+        let doubled: String = [quote, quote].iter().collect();
+        format!("{}{}{}", quote, field.replace(quote, &doubled), quote)
+    } else {
+        field.to_string()
+    }
 }
 
 impl Table{
+    //builds a table from already split rows, padding short rows so every row has the same number of cells
+    pub fn from_rows(rows: Vec<Vec<String>>) -> Self{
+        let ncols = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let mut table = Table::new();
+        let mut width_sum = 0usize;
+        for (y, row) in rows.into_iter().enumerate(){
+            let mut row_width = 0usize;
+            let mut values = row.into_iter();
+            for x in 1..ncols+1{
+                let value = values.next().unwrap_or_default();
+                let mut cell = Cell::from(value);
+                row_width += cell.width;
+                cell.x_loc = x;
+                cell.y_loc = y + 1;
+                table.add(cell);
+            }
+            width_sum = width_sum.max(row_width);
+        }
+        table.width_sum = width_sum;
+        table
+    }
+
     pub fn new() -> Self{
         let cells = Vec::new();
         Self {
             cells, 
             widest_cell_length: 0, 
             width_sum: 0, 
-            cell_count: 0
+            cell_count: 0,
+            max_col_width: None,
+            max_cell_width: None,
+            fixed_widths: HashMap::new(),
+            rules: HashMap::new(),
+            alignments: HashMap::new(),
+            number_formats: HashMap::new(),
+            hidden: HashSet::new(),
+            locked_rows: HashSet::new(),
+            locked_cols: HashSet::new(),
+            highlights: Vec::new(),
+            notes: HashMap::new(),
+            history: HashMap::new(),
+            index: HashMap::new(),
+            rows: 0,
+            cols: 0,
+            column_widths: Vec::new(),
         }
     }
 
-    // returns the terminal width taken by a column
+    // returns the terminal width taken by a column, respecting the cell and column width caps. Cells
+    // are drawn with a space after them, which the width takes in
     pub fn column_width(&self, x_loc: usize) -> Width {
-        let mut width = 0usize;
-        for cell in &self.cells {
-            if cell.x_loc == x_loc{
-                if cell.width > width{
-                    width = cell.width;
-                }
-            }
+        if let Some(&fixed) = self.fixed_widths.get(&x_loc) {
+            return fixed;
+        }
+        let mut width = x_loc.checked_sub(1)
+            .and_then(|i| self.column_widths.get(i))
+            .map(|column| column.widest)
+            .unwrap_or(0);
+        //formatted numbers can take more room than what was typed
+        if let Some(format) = self.number_formats.get(&x_loc) {
+            width = format.widest(width);
+        }
+        width += 1;
+        if let Some(max) = self.max_cell_width {
+            width = width.min(max);
+        }
+        match self.max_col_width {
+            Some(max) => width.min(max),
+            None => width,
         }
+    }
+
+    //widens (or with a negative `delta`, narrows) a column from its current width, returning the new width
+    pub fn resize_column(&mut self, x_loc: usize, delta: isize) -> Width {
+        let width = (self.column_width(x_loc) as isize + delta).max(1) as Width;
+        self.fixed_widths.insert(x_loc, width);
         width
     }
 
@@ -142,14 +641,230 @@ impl Table{
         self.width_sum + 2*self.num_cols() + self.num_rows().to_string().len()+1
     }
 
-    //returns the string contained within a cell at an index (perhaps I should have mapped cells based on postions...)
+    //returns the string contained within a cell at an index
     pub fn get_content_from(&self, at: Position) -> String {
-        for cell in &self.cells{
-            if cell.x_loc == at.x && cell.y_loc == at.y{
-                return cell.contents.clone();
+        match self.get(at.x, at.y) {
+            Some(cell) => cell.contents.clone(),
+            None => "".to_string(),
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&Cell> {
+        self.index.get(&(x, y)).map(|&i| &self.cells[i])
+    }
+
+    //the cells of row `y` from left to right
+    pub fn row(&self, y: usize) -> Vec<&Cell> {
+        (1..self.cols+1).filter_map(|x| self.get(x, y)).collect()
+    }
+
+    //for changing anything but a cell's contents; use set_contents for those so the column widths stay right
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Cell> {
+        match self.index.get(&(x, y)) {
+            Some(&i) => self.cells.get_mut(i),
+            None => None,
+        }
+    }
+
+    //replaces a cell's contents, returning the previous contents
+    pub fn set_contents(&mut self, x: usize, y: usize, contents: &str) -> Option<String> {
+        let &i = self.index.get(&(x, y))?;
+        let cell = &mut self.cells[i];
+        let old_width = cell.width;
+        cell.width = UnicodeWidthStr::width(contents);
+        let new_width = cell.width;
+        cell.kind = infer_type(contents);
+        let before = std::mem::replace(&mut cell.contents, contents.to_string());
+        let column = &mut self.column_widths[x - 1];
+        if column.remove(old_width) {
+            column.add(new_width);
+        } else {
+            //the last of the widest cells changed, so another cell may now be the widest
+            self.column_widths[x - 1] = ColumnWidth::of((1..self.rows+1).filter_map(|y| self.get(x, y)));
+        }
+        Some(before)
+    }
+
+    //puts other contents in the table, keeping what was set for its rows and columns
+    pub fn replace_contents(&mut self, rows: Vec<Vec<String>>) {
+        let table = Table::from_rows(rows);
+        self.cells = table.cells;
+        self.widest_cell_length = table.widest_cell_length;
+        self.width_sum = table.width_sum;
+        self.cell_count = table.cell_count;
+        self.index = table.index;
+        self.rows = table.rows;
+        self.cols = table.cols;
+        self.column_widths = table.column_widths;
+    }
+
+    //shifts row `y` and every row below it down one, leaving a blank row at `y`
+    pub fn insert_row(&mut self, y: usize) {
+        for cell in &mut self.cells {
+            if cell.y_loc >= y {
+                cell.y_loc += 1;
+            }
+        }
+        self.move_rows(|row| Some(if row >= y { row + 1 } else { row }));
+        self.move_notes(|(x, row)| Some((x, if row >= y { row + 1 } else { row })));
+        for x in 1..self.cols+1 {
+            self.cells.push(Self::blank_cell(x, y));
+        }
+        self.reshaped();
+    }
+
+    //shifts column `x` and every column right of it along one, leaving a blank column at `x`
+    pub fn insert_col(&mut self, x: usize) {
+        for cell in &mut self.cells {
+            if cell.x_loc >= x {
+                cell.x_loc += 1;
+            }
+        }
+        self.move_columns(|col| Some(if col >= x { col + 1 } else { col }));
+        self.move_notes(|(col, y)| Some((if col >= x { col + 1 } else { col }, y)));
+        for y in 1..self.rows+1 {
+            self.cells.push(Self::blank_cell(x, y));
+        }
+        self.reshaped();
+    }
+
+    //removes row `y`, moving the rows below it up, and returns its contents from left to right
+    pub fn remove_row(&mut self, y: usize) -> Vec<String> {
+        let contents = self.row(y).iter().map(|c| c.contents.clone()).collect();
+        self.cells.retain(|c| c.y_loc != y);
+        for cell in &mut self.cells {
+            if cell.y_loc > y {
+                cell.y_loc -= 1;
+            }
+        }
+        self.move_rows(|row| (row != y).then_some(if row > y { row - 1 } else { row }));
+        self.move_notes(|(x, row)| (row != y).then_some((x, if row > y { row - 1 } else { row })));
+        self.reshaped();
+        contents
+    }
+
+    //removes column `x`, moving the columns right of it along, and returns its contents from top to bottom
+    pub fn remove_col(&mut self, x: usize) -> Vec<String> {
+        let contents = (1..self.rows+1)
+            .filter_map(|y| self.get(x, y))
+            .map(|c| c.contents.clone())
+            .collect();
+        self.cells.retain(|c| c.x_loc != x);
+        for cell in &mut self.cells {
+            if cell.x_loc > x {
+                cell.x_loc -= 1;
+            }
+        }
+        self.move_columns(|col| (col != x).then_some(if col > x { col - 1 } else { col }));
+        self.move_notes(|(col, y)| (col != x).then_some((if col > x { col - 1 } else { col }, y)));
+        self.reshaped();
+        contents
+    }
+
+    //swaps columns `a` and `b` in every row, along with any widths, rules, alignments and hiding they were given
+    pub fn swap_cols(&mut self, a: usize, b: usize) {
+        for cell in &mut self.cells {
+            if cell.x_loc == a {
+                cell.x_loc = b;
+            } else if cell.x_loc == b {
+                cell.x_loc = a;
+            }
+        }
+        self.move_columns(|col| Some(if col == a { b } else if col == b { a } else { col }));
+        self.move_notes(|(col, y)| Some((if col == a { b } else if col == b { a } else { col }, y)));
+        self.reshaped();
+    }
+
+    //turns rows into columns and columns into rows. What was set for columns no longer applies,
+    //so it is dropped, but locked rows become locked columns and the other way around
+    pub fn transpose(&mut self) {
+        for cell in &mut self.cells {
+            std::mem::swap(&mut cell.x_loc, &mut cell.y_loc);
+        }
+        let locked_rows = std::mem::take(&mut self.locked_rows);
+        self.locked_rows = std::mem::take(&mut self.locked_cols);
+        self.move_columns(|_| None);
+        self.locked_cols = locked_rows;
+        self.move_notes(|(x, y)| Some((y, x)));
+        self.reshaped();
+    }
+
+    //gives what was set for each column (its width, rule, alignment, number format, highlights and whether it is
+    //hidden or locked) to the column `to` maps it to, dropping it when that is None
+    fn move_columns(&mut self, to: impl Fn(usize) -> Option<usize>) {
+        self.fixed_widths = self.fixed_widths.drain()
+            .filter_map(|(col, width)| Some((to(col)?, width)))
+            .collect();
+        self.rules = self.rules.drain()
+            .filter_map(|(col, rule)| Some((to(col)?, rule)))
+            .collect();
+        self.alignments = self.alignments.drain()
+            .filter_map(|(col, align)| Some((to(col)?, align)))
+            .collect();
+        self.number_formats = self.number_formats.drain()
+            .filter_map(|(col, format)| Some((to(col)?, format)))
+            .collect();
+        self.hidden = self.hidden.drain()
+            .filter_map(&to)
+            .collect();
+        self.locked_cols = self.locked_cols.drain()
+            .filter_map(&to)
+            .collect();
+        //highlights for any column stay as they are
+        self.highlights.retain_mut(|highlight| match highlight.predicate.column {
+            Some(col) => to(col).map(|col| highlight.predicate.column = Some(col)).is_some(),
+            None => true,
+        });
+    }
+
+    //gives each locked row the row `to` maps it to, dropping it when that is None
+    fn move_rows(&mut self, to: impl Fn(usize) -> Option<usize>) {
+        self.locked_rows = self.locked_rows.drain()
+            .filter_map(to)
+            .collect();
+    }
+
+    pub fn is_locked(&self, x: usize, y: usize) -> bool {
+        self.locked_rows.contains(&y) || self.locked_cols.contains(&x)
+    }
+
+    //gives each note and cell history the position `to` maps its cell to, dropping it when that is None
+    fn move_notes(&mut self, to: impl Fn((usize, usize)) -> Option<(usize, usize)>) {
+        self.notes = self.notes.drain()
+            .filter_map(|(at, note)| Some((to(at)?, note)))
+            .collect();
+        self.history = self.history.drain()
+            .filter_map(|(at, values)| Some((to(at)?, values)))
+            .collect();
+    }
+
+    fn blank_cell(x: usize, y: usize) -> Cell {
+        let mut cell = Cell::from("");
+        cell.x_loc = x;
+        cell.y_loc = y;
+        cell
+    }
+
+    //puts the cells back in row-major order and rebuilds everything derived from their positions
+    fn reshaped(&mut self) {
+        self.cells.sort_by_key(|c| (c.y_loc, c.x_loc));
+        self.cell_count = self.cells.len();
+        self.reindex();
+    }
+
+    //rebuilds the position index and dimensions, needed whenever cells are reordered or moved
+    pub fn reindex(&mut self) {
+        self.index = self.cells.iter().enumerate()
+            .map(|(i, cell)| ((cell.x_loc, cell.y_loc), i))
+            .collect();
+        self.rows = self.cells.iter().map(|c| c.y_loc).max().unwrap_or(0);
+        self.cols = self.cells.iter().map(|c| c.x_loc).max().unwrap_or(0);
+        self.column_widths = vec![ColumnWidth::default(); self.cols];
+        for cell in &self.cells {
+            if cell.x_loc > 0 {
+                self.column_widths[cell.x_loc - 1].add(cell.width);
             }
         }
-        return "".to_string();
     }
 
     //adds a cell to the table
@@ -159,6 +874,15 @@ impl Table{
         }
         self.width_sum += cell.width;
         self.cell_count += 1;
+        self.rows = self.rows.max(cell.y_loc);
+        self.cols = self.cols.max(cell.x_loc);
+        if cell.x_loc > 0 {
+            if self.column_widths.len() < cell.x_loc {
+                self.column_widths.resize(cell.x_loc, ColumnWidth::default());
+            }
+            self.column_widths[cell.x_loc - 1].add(cell.width);
+        }
+        self.index.insert((cell.x_loc, cell.y_loc), self.cells.len());
         self.cells.push(cell);
     }
 
@@ -169,53 +893,67 @@ impl Table{
 
     //returns number of rows
     pub fn num_rows(&self) -> usize {
-        let mut num_line = 0usize;
-        for cell in &self.cells {
-            if cell.y_loc > num_line{
-                num_line = cell.y_loc;
-            }
-        }
-        num_line
+        self.rows
     }
 
     //returns number of columns
     pub fn num_cols(&self) -> usize {
-        let mut num_col = 0usize;
-        for cell in &self.cells {
-            if cell.x_loc > num_col {
-                num_col = cell.x_loc;
-            }
-        }
-        num_col
+        self.cols
     }
 
-    //returns counts, total, mean, and standard devation of highlighted cells
-    pub fn calc_summary(&self) -> Result<(f64, f64, f64, f64),String> {
-        let mut arr: Vec<f64> = Vec::new();
-        for c in &self.cells{
-            if c.highlighted{
-                let mut content = c.contents.to_string();
-                content.retain(|c| !c.is_whitespace());
-                if content == "".to_string(){
-                    continue;
-                }
-                let val = content.parse::<f64>();
-                if val.is_err(){
-                    return Err("Unable to calculate stats. Make sure all highlighted cells contain numeric data".to_string());
-                }
-                arr.push(val.unwrap());
-            }
+    //reorders whole rows by the values in column `x_loc`, keeping the first row in place when `header` is set.
+    //returns whether the column was sorted numerically
+    pub fn sort_rows_by_column(&mut self, x_loc: usize, ascending: bool, header: bool) -> bool {
+        self.sort(&[SortKey { x: x_loc, ascending }], header) == [Collation::Number]
+    }
+
+    //reorders whole rows by the columns in `keys`: the first key decides and each later one breaks
+    //the ties left by those before it. Rows that tie on every key keep their order, and the first
+    //row stays in place when `header` is set. Returns how each key's column was compared
+    pub fn sort(&mut self, keys: &[SortKey], header: bool) -> Vec<Collation> {
+        let first = if header { 2 } else { 1 };
+        let nrows = self.num_rows();
+        let collations: Vec<Collation> = keys.iter()
+            .map(|key| Collation::of((first..nrows+1).map(|y| self.get(key.x, y).map(|c| c.kind).unwrap_or_default())))
+            .collect();
+        if nrows < first || keys.is_empty() {
+            return collations;
         }
-        let n = arr.len() as f64;
-        let sum = arr.iter().sum::<f64>();
-        let mean = sum/n;
-        let variance = arr.iter().map(|value| {
-            let diff = mean - value;
-            diff * diff
-        }).sum::<f64>()/n;
+        let values: Vec<Vec<Option<SortValue>>> = (first..nrows+1)
+            .map(|y| keys.iter().zip(&collations)
+                .map(|(key, &collation)| self.get(key.x, y).and_then(|c| SortValue::new(&c.contents, collation)))
+                .collect())
+            .collect();
+
+        //sort_by is stable, which is what keeps tied rows in their order
+        let mut order: Vec<usize> = (0..values.len()).collect();
+        order.sort_by(|&a, &b| {
+            keys.iter().zip(values[a].iter().zip(&values[b]))
+                .map(|(key, pair)| match pair {
+                    (None, None) => std::cmp::Ordering::Equal,
+                    (None, Some(_)) => std::cmp::Ordering::Greater,
+                    (Some(_), None) => std::cmp::Ordering::Less,
+                    (Some(va), Some(vb)) if key.ascending => va.compare(vb),
+                    (Some(va), Some(vb)) => va.compare(vb).reverse(),
+                })
+                .find(|&ordering| ordering != std::cmp::Ordering::Equal)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
 
-        let std = variance.sqrt();
-        return Ok((n, sum, mean, std));
+        //order[new row] = old row, so invert it to move each cell
+        let mut new_y = vec![0usize; values.len()];
+        for (new, &old) in order.iter().enumerate() {
+            new_y[old] = new + first;
+        }
+        for cell in &mut self.cells {
+            if cell.y_loc >= first {
+                cell.y_loc = new_y[cell.y_loc - first];
+            }
+        }
+        self.move_rows(|y| Some(if y >= first { new_y[y - first] } else { y }));
+        self.move_notes(|(x, y)| Some((x, if y >= first { new_y[y - first] } else { y })));
+        self.reshaped();
+        collations
     }
 
 }
\ No newline at end of file
@@ -1,8 +1,16 @@
 use crate::Position;
+use std::fs::File;
 use std::io::{self, stdout, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 use termion::color;
-use termion::event::Key;
-use termion::input::TermRead;
+use termion::event::Event;
+use termion::input::{MouseTerminal, TermRead};
 use termion::raw::{IntoRawMode, RawTerminal};
 
 pub struct Size 
@@ -14,34 +22,152 @@ pub struct Size
 pub struct Terminal 
 {
     size: Size,
-    _stdout: RawTerminal<std::io::Stdout>,
+    //None for a terminal that only pretends to be one, see headless
+    _stdout: Option<RawTerminal<std::io::Stdout>>,
+    //reports mouse events while it lives
+    _mouse: Option<MouseTerminal<std::io::Stdout>>,
+    //filled by a thread reading stdin for the whole session, so keys can be checked for without
+    //waiting. One reader holds on to bytes read ahead while telling Esc from Alt
+    events: Receiver<Result<Event, io::Error>>,
+    //set while another program has the keyboard, see suspend
+    paused: Arc<AtomicBool>,
+    //whether the mouse was reported before suspending, to turn it back on after
+    mouse_suspended: bool,
+}
+
+//stdin as the reading thread sees it: it holds off while `paused` is set, so the keys typed into
+//a program run in the foreground go to that program
+struct Input
+{
+    paused: Arc<AtomicBool>,
+}
+
+impl Read for Input
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        loop
+        {
+            if self.paused.load(Ordering::SeqCst)
+            {
+                thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+            let mut fd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+            //SAFETY: poll is given one pollfd that lives for the call
+            let ready = unsafe { libc::poll(&mut fd, 1, 50) };
+            if ready > 0 && !self.paused.load(Ordering::SeqCst)
+            {
+                //read straight from the descriptor: a buffered stdin would hide what it read ahead from poll
+                //SAFETY: the buffer is valid for writes of its whole length
+                let read = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+                return if read < 0 { Err(io::Error::last_os_error()) } else { Ok(read as usize) };
+            }
+        }
+    }
 }
 
 impl Terminal 
 {
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> Result<Self, std::io::Error> 
     {
-        let size = termion::terminal_size()?;
-        Ok(Self 
+        let size = Self::read_size()?;
+        let (sender, events) = mpsc::channel();
+        let paused = Arc::new(AtomicBool::new(false));
+        let input = Input { paused: Arc::clone(&paused) };
+        thread::spawn(move || {
+            for event in input.events()
             {
-            size: Size 
+                if sender.send(event).is_err()
+                {
+                    break;
+                }
+            }
+        });
+        Ok(Self 
             {
-                width: size.0,
-                height: size.1.saturating_sub(2),
-            },
-            _stdout: stdout().into_raw_mode()?,
+            size,
+            _stdout: Some(stdout().into_raw_mode()?),
+            _mouse: None,
+            events,
+            paused,
+            mouse_suspended: false,
         })
     }
+    //a terminal of the given size that leaves stdout as it is, for tests. `keys` are read as if
+    //typed, and after them stdin is closed
+    #[cfg(test)]
+    pub fn headless(width: u16, height: u16, keys: &[termion::event::Key]) -> Self
+    {
+        let (sender, events) = mpsc::channel();
+        for &key in keys
+        {
+            sender.send(Ok(Event::Key(key))).ok();
+        }
+        Self
+        {
+            size: Size { width, height },
+            _stdout: None,
+            _mouse: None,
+            events,
+            paused: Arc::new(AtomicBool::new(false)),
+            mouse_suspended: false,
+        }
+    }
+    //hands the screen and keyboard over to another program, like $EDITOR, until `resume`
+    pub fn suspend(&mut self) -> Result<(), std::io::Error>
+    {
+        self.paused.store(true, Ordering::SeqCst);
+        self.mouse_suspended = self._mouse.take().is_some();
+        print!("{}{}{}", termion::clear::All, termion::cursor::Goto(1, 1), termion::cursor::Show);
+        Self::flush()?;
+        match &self._stdout
+        {
+            Some(stdout) => stdout.suspend_raw_mode(),
+            None => Ok(()),
+        }
+    }
+    pub fn resume(&mut self) -> Result<(), std::io::Error>
+    {
+        if let Some(stdout) = &self._stdout
+        {
+            stdout.activate_raw_mode()?;
+        }
+        if std::mem::take(&mut self.mouse_suspended)
+        {
+            self.enable_mouse();
+        }
+        self.paused.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+    pub fn enable_mouse(&mut self) 
+    {
+        self._mouse = Some(MouseTerminal::from(stdout()));
+    }
     pub fn size(&self) -> &Size 
     {
         &self.size
     }
+    //the cell line, status bar and message bar take the bottom three rows
+    fn read_size() -> Result<Size, std::io::Error>
+    {
+        let (width, height) = termion::terminal_size()?;
+        Ok(Size { width, height: height.saturating_sub(3) })
+    }
+    //picks up a resized window
+    pub fn update_size(&mut self)
+    {
+        if let Ok(size) = Self::read_size()
+        {
+            self.size = size;
+        }
+    }
     pub fn clear_screen() 
     {
         print!("{}", termion::clear::All);
     }
     #[allow(clippy::cast_possible_truncation)]
-
     pub fn cursor_position(position: &Position) 
     {
         let Position { mut x, mut y } = position;
@@ -55,16 +181,26 @@ impl Terminal
     {
         io::stdout().flush()
     }
-    pub fn read_key() -> Result<Key, std::io::Error> 
+    pub fn read_event(&mut self) -> Result<Event, std::io::Error> 
     {
-        loop 
+        self.events.recv()
+            .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::UnexpectedEof, "stdin was closed")))
+    }
+    //the next event, or None when there is none within `timeout`
+    pub fn read_event_timeout(&mut self, timeout: Duration) -> Option<Result<Event, std::io::Error>> 
+    {
+        match self.events.recv_timeout(timeout)
         {
-            if let Some(key) = io::stdin().lock().keys().next() 
-            {
-                return key;
-            }
+            Ok(event) => Some(event),
+            Err(mpsc::RecvTimeoutError::Timeout) => None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "stdin was closed"))),
         }
     }
+    //the next event if one is waiting, without blocking
+    pub fn poll_event(&mut self) -> Option<Result<Event, std::io::Error>> 
+    {
+        self.events.try_recv().ok()
+    }
 
     pub fn cursor_hide() 
     {
@@ -78,7 +214,7 @@ impl Terminal
     {
         print!("{}", termion::clear::CurrentLine);
     }
-    pub fn set_bg_color(color: color::Rgb) 
+    pub fn set_bg_color(color: impl color::Color) 
     {
         print!("{}", color::Bg(color));
     }
@@ -86,7 +222,7 @@ impl Terminal
     {
         print!("{}", color::Bg(color::Reset));
     }
-    pub fn set_fg_color(color: color::Rgb) 
+    pub fn set_fg_color(color: impl color::Color) 
     {
         print!("{}", color::Fg(color));
     }
@@ -94,4 +230,33 @@ impl Terminal
     {
         print!("{}", color::Fg(color::Reset));
     }
-}
\ No newline at end of file
+}
+//when the table comes in on stdin or goes out on stdout, the keyboard and screen are reached through
+///dev/tty instead, which takes the place of whichever of the two is piped. Returns the piped stdout,
+//for the table to be written to at the end
+pub fn attach_tty() -> Result<Option<File>, io::Error>
+{
+    let stdin_piped = !termion::is_tty(&io::stdin());
+    let stdout_piped = !termion::is_tty(&io::stdout());
+    if !stdin_piped && !stdout_piped
+    {
+        return Ok(None);
+    }
+    let tty = termion::get_tty()?;
+    let check = |result: libc::c_int| if result < 0 { Err(io::Error::last_os_error()) } else { Ok(result) };
+    let mut piped = None;
+    //SAFETY: dup and dup2 only hand out and replace descriptors; the duplicate is owned by the File
+    unsafe
+    {
+        if stdout_piped
+        {
+            piped = Some(File::from_raw_fd(check(libc::dup(libc::STDOUT_FILENO))?));
+            check(libc::dup2(tty.as_raw_fd(), libc::STDOUT_FILENO))?;
+        }
+        if stdin_piped
+        {
+            check(libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO))?;
+        }
+    }
+    Ok(piped)
+}
@@ -0,0 +1,155 @@
+use crate::config::Colors;
+
+use std::env;
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use termion::color::{self, AnsiValue, Rgb};
+
+//how many colours the terminal can show
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Depth {
+    TrueColor,
+    Palette256,
+    Basic16,
+}
+
+impl Depth {
+    //truecolor when $COLORTERM says so, 256 colours when $TERM does, and otherwise the 16 every
+    //terminal has
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default().to_lowercase();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Palette256
+        } else {
+            Self::Basic16
+        }
+    }
+
+    //`truecolor`, `256` or `16` as written in the config; None for anything else, `auto` included
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "truecolor" | "24bit" => Some(Self::TrueColor),
+            "256" => Some(Self::Palette256),
+            "16" => Some(Self::Basic16),
+            _ => None,
+        }
+    }
+}
+
+//the depth every Shade is drawn at, set once the config is read
+static DEPTH: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_depth(depth: Depth) {
+    DEPTH.store(depth as u8, Ordering::Relaxed);
+}
+
+fn depth() -> Depth {
+    match DEPTH.load(Ordering::Relaxed) {
+        0 => Depth::TrueColor,
+        1 => Depth::Palette256,
+        _ => Depth::Basic16,
+    }
+}
+
+//a colour of the theme, drawn as the nearest one the terminal can show
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shade(pub Rgb);
+
+impl color::Color for Shade {
+    fn write_fg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match depth() {
+            Depth::TrueColor => self.0.write_fg(f),
+            Depth::Palette256 => AnsiValue(nearest_256(self.0)).write_fg(f),
+            Depth::Basic16 => match nearest_16(self.0) {
+                n if n < 8 => write!(f, "\x1b[{}m", 30 + n),
+                n => write!(f, "\x1b[{}m", 90 + n - 8),
+            },
+        }
+    }
+
+    fn write_bg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match depth() {
+            Depth::TrueColor => self.0.write_bg(f),
+            Depth::Palette256 => AnsiValue(nearest_256(self.0)).write_bg(f),
+            Depth::Basic16 => match nearest_16(self.0) {
+                n if n < 8 => write!(f, "\x1b[{}m", 40 + n),
+                n => write!(f, "\x1b[{}m", 100 + n - 8),
+            },
+        }
+    }
+}
+
+fn distance(a: Rgb, b: Rgb) -> u32 {
+    let square = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2) as u32;
+    square(a.0, b.0) + square(a.1, b.1) + square(a.2, b.2)
+}
+
+//the levels each channel of the 6x6x6 colour cube takes, from 16 to 231
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+//the closest of the 256 colours past the first 16: a corner of the colour cube or a step of the
+//grey ramp from 232 to 255
+fn nearest_256(rgb: Rgb) -> u8 {
+    let level = |channel: u8| (0..6).min_by_key(|&i| (i32::from(CUBE[i]) - i32::from(channel)).abs()).unwrap_or(0);
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = Rgb(CUBE[r], CUBE[g], CUBE[b]);
+    let average = (u32::from(rgb.0) + u32::from(rgb.1) + u32::from(rgb.2)) / 3;
+    let step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let grey = 8 + 10 * step;
+    if distance(rgb, Rgb(grey, grey, grey)) < distance(rgb, cube) {
+        232 + step
+    } else {
+        16 + 36 * r as u8 + 6 * g as u8 + b as u8
+    }
+}
+
+//the 16 basic colours as xterm shows them
+const BASIC: [Rgb; 16] = [
+    Rgb(0, 0, 0), Rgb(205, 0, 0), Rgb(0, 205, 0), Rgb(205, 205, 0),
+    Rgb(0, 0, 238), Rgb(205, 0, 205), Rgb(0, 205, 205), Rgb(229, 229, 229),
+    Rgb(127, 127, 127), Rgb(255, 0, 0), Rgb(0, 255, 0), Rgb(255, 255, 0),
+    Rgb(92, 92, 255), Rgb(255, 0, 255), Rgb(0, 255, 255), Rgb(255, 255, 255),
+];
+
+fn nearest_16(rgb: Rgb) -> u8 {
+    (0..16).min_by_key(|&i| distance(rgb, BASIC[i])).unwrap_or(0) as u8
+}
+
+pub const NAMES: &[&str] = &["light", "dark", "solarized"];
+
+//a built-in theme by name, which the config's [colors] can change colours of
+pub fn named(name: &str) -> Option<Colors> {
+    match name.trim().to_lowercase().as_str() {
+        "light" => Some(light()),
+        "dark" => Some(theme([
+            (208, 208, 208), (48, 48, 48), (0, 0, 0), (215, 175, 0), (95, 175, 95), (215, 135, 0),
+            (255, 95, 95), (255, 95, 95), (255, 95, 95), (135, 135, 215), (95, 175, 255), (0, 0, 0), (95, 175, 255),
+            (68, 68, 68),
+        ])),
+        "solarized" => Some(theme([
+            (147, 161, 161), (7, 54, 66), (0, 43, 54), (181, 137, 0), (133, 153, 0), (203, 75, 22),
+            (220, 50, 47), (220, 50, 47), (220, 50, 47), (108, 113, 196), (38, 139, 210), (0, 43, 54), (38, 139, 210),
+            (10, 62, 76),
+        ])),
+        _ => None,
+    }
+}
+
+//the theme used when the config names none
+pub fn light() -> Colors {
+    theme([
+        (63, 63, 63), (239, 239, 239), (0, 0, 0), (255, 215, 95), (175, 255, 175), (255, 175, 95),
+        (215, 0, 0), (215, 0, 0), (215, 0, 0), (215, 215, 255), (0, 95, 175), (255, 255, 255), (0, 95, 215),
+        (232, 238, 246),
+    ])
+}
+
+//colours in the order Colors lists them
+fn theme(colors: [(u8, u8, u8); 14]) -> Colors {
+    let [status_fg, status_bg, match_fg, match_bg, added_bg, changed_bg, removed_fg, note_fg, invalid_fg, duplicate_bg, locked_fg, cursor_fg, cursor_bg, crosshair_bg] =
+        colors.map(|(r, g, b)| Shade(Rgb(r, g, b)));
+    Colors { status_fg, status_bg, match_fg, match_bg, added_bg, changed_bg, removed_fg, note_fg, invalid_fg, duplicate_bg, locked_fg, cursor_fg, cursor_bg, crosshair_bg }
+}
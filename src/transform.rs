@@ -0,0 +1,144 @@
+use crate::regex::Regex;
+
+//a rewrite applied to each value of a column or selection
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transform {
+    //every match of the pattern, with $1 and the like standing for its groups
+    Replace(Regex, String),
+    Trim,
+    Upper,
+    Lower,
+    Prefix(String),
+    Suffix(String),
+    //takes text off the start and end as often as it is there
+    Strip(String),
+}
+
+impl Transform {
+    //`s/PATTERN/REPLACEMENT/` (any character can stand in for the /), `trim`, `upper`, `lower`,
+    //`prefix TEXT`, `suffix TEXT` or `strip TEXT`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim_start();
+        if let Some(rest) = text.strip_prefix('s') {
+            if let Some(separator) = rest.chars().next().filter(|c| !c.is_alphanumeric() && !c.is_whitespace()) {
+                return Self::replacement(&rest[separator.len_utf8()..], separator);
+            }
+        }
+        let (word, argument) = text.split_once(' ').unwrap_or((text, ""));
+        let needs_text = |make: fn(String) -> Self| {
+            if argument.is_empty() {
+                Err(format!("{} needs some text", word))
+            } else {
+                Ok(make(argument.to_string()))
+            }
+        };
+        match word.to_lowercase().as_str() {
+            "trim" => Ok(Self::Trim),
+            "upper" => Ok(Self::Upper),
+            "lower" => Ok(Self::Lower),
+            "prefix" => needs_text(Self::Prefix),
+            "suffix" => needs_text(Self::Suffix),
+            "strip" => needs_text(Self::Strip),
+            _ => Err(String::from("expected s/PATTERN/REPLACEMENT/, trim, upper, lower, prefix TEXT, suffix TEXT or strip TEXT")),
+        }
+    }
+
+    //PATTERN/REPLACEMENT/, where a backslash before the separator makes it part of the text
+    fn replacement(text: &str, separator: char) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut part = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == separator {
+                parts.push(std::mem::take(&mut part));
+                continue;
+            }
+            if c == '\\' {
+                match chars.next() {
+                    Some(next) if next == separator => part.push(next),
+                    Some(next) => {
+                        part.push(c);
+                        part.push(next);
+                    }
+                    None => part.push(c),
+                }
+                continue;
+            }
+            part.push(c);
+        }
+        parts.push(part);
+        let usage = || format!("expected s{0}PATTERN{0}REPLACEMENT{0}", separator);
+        let (pattern, replacement) = match &parts[..] {
+            [pattern, replacement] => (pattern, replacement),
+            [pattern, replacement, end] if end.is_empty() => (pattern, replacement),
+            _ => return Err(usage()),
+        };
+        if pattern.is_empty() {
+            return Err(usage());
+        }
+        Ok(Self::Replace(Regex::new(pattern)?, replacement.clone()))
+    }
+
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            Self::Replace(pattern, replacement) => pattern.replace_all(value, replacement),
+            Self::Trim => value.trim().to_string(),
+            Self::Upper => value.to_uppercase(),
+            Self::Lower => value.to_lowercase(),
+            Self::Prefix(text) => format!("{}{}", text, value),
+            Self::Suffix(text) => format!("{}{}", value, text),
+            Self::Strip(text) => {
+                let mut value = value;
+                while let Some(rest) = value.strip_prefix(text.as_str()) {
+                    value = rest;
+                }
+                while let Some(rest) = value.strip_suffix(text.as_str()) {
+                    value = rest;
+                }
+                value.to_string()
+            }
+        }
+    }
+}
+
+//how a value is cut into pieces when a column is split in several
+#[derive(Debug, Clone, PartialEq)]
+pub enum Split {
+    //at each place the text appears
+    On(String),
+    //into pieces this many characters long, with whatever is left over as one more
+    Widths(Vec<usize>),
+}
+
+impl Split {
+    //`space`, `tab`, widths like `3,2,4`, or any other text to split on
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "space" => return Ok(Self::On(String::from(" "))),
+            "tab" => return Ok(Self::On(String::from("\t"))),
+            _ => (),
+        }
+        let widths: Option<Vec<usize>> = text.split(',').map(|width| width.trim().parse().ok().filter(|&n| n > 0)).collect();
+        match widths {
+            Some(widths) => Ok(Self::Widths(widths)),
+            None if text.is_empty() => Err(String::from("expected text to split on or widths like 3,2,4")),
+            None => Ok(Self::On(text.to_string())),
+        }
+    }
+
+    //the pieces of a value, each trimmed of the spaces around it
+    pub fn pieces(&self, value: &str) -> Vec<String> {
+        match self {
+            Self::On(separator) => value.split(separator.as_str()).map(|piece| piece.trim().to_string()).collect(),
+            Self::Widths(widths) => {
+                let mut chars = value.chars();
+                let mut pieces: Vec<String> = widths.iter().map(|&width| chars.by_ref().take(width).collect()).collect();
+                let rest: String = chars.collect();
+                if !rest.is_empty() {
+                    pieces.push(rest);
+                }
+                pieces.into_iter().map(|piece| piece.trim().to_string()).collect()
+            }
+        }
+    }
+}
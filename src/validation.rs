@@ -0,0 +1,55 @@
+use crate::regex::Regex;
+
+//what the values down a column have to look like. Empty cells always pass, so a rule only judges
+//what has been filled in
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    Numeric,
+    Matches(Regex),
+    OneOf(Vec<String>),
+}
+
+impl Rule {
+    //`numeric`, `matches PATTERN` (a regular expression the whole value must match) or
+    //`one of a, b, c`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let (word, rest) = text.split_once(' ').unwrap_or((text, ""));
+        match word.to_lowercase().as_str() {
+            "numeric" | "number" if rest.is_empty() => Ok(Self::Numeric),
+            "matches" if !rest.trim().is_empty() => Regex::new(rest.trim()).map(Self::Matches),
+            "one" if rest.starts_with("of ") => {
+                let allowed: Vec<String> = rest[3..].split(',')
+                    .map(|value| value.trim().to_string())
+                    .filter(|value| !value.is_empty())
+                    .collect();
+                if allowed.is_empty() {
+                    return Err(String::from("one of needs some values"));
+                }
+                Ok(Self::OneOf(allowed))
+            }
+            _ => Err(String::from("expected numeric, matches PATTERN or one of A, B, C")),
+        }
+    }
+
+    pub fn allows(&self, value: &str) -> bool {
+        let value = value.trim();
+        if value.is_empty() {
+            return true;
+        }
+        match self {
+            Self::Numeric => value.parse::<f64>().is_ok_and(f64::is_finite),
+            Self::Matches(pattern) => pattern.is_full_match(value),
+            Self::OneOf(allowed) => allowed.iter().any(|allowed| allowed == value),
+        }
+    }
+
+    //the rule written out the way `parse` reads it
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Numeric => String::from("numeric"),
+            Self::Matches(pattern) => format!("matches {}", pattern.as_str()),
+            Self::OneOf(allowed) => format!("one of {}", allowed.join(", ")),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::fs;
+use std::time::SystemTime;
+
+//notices a file being changed on disk by something else, e.g. a script writing it again. With the
+//`watch` feature the OS says when the file's directory changes (the directory, since files are often
+//written again by renaming a new one over them); without it, the file is looked at each time. Either
+//way it only counts as changed when its modification time or size differ from what was last settled
+pub struct Watch {
+    pub file_name: String,
+    stamp: Option<(SystemTime, u64)>,
+    //a new stamp seen once, which has to be seen again before it counts, so a file part way
+    //through being written isn't taken as the change
+    moving: Option<(SystemTime, u64)>,
+    #[cfg(feature = "watch")]
+    events: Option<Events>,
+}
+
+#[cfg(feature = "watch")]
+struct Events {
+    //dropping the watcher stops it, so it is kept for as long as the Watch is
+    _watcher: notify::RecommendedWatcher,
+    receiver: std::sync::mpsc::Receiver<()>,
+}
+
+impl Watch {
+    pub fn new(file_name: &str) -> Self {
+        Self {
+            file_name: file_name.to_string(),
+            stamp: stamp(file_name),
+            moving: None,
+            #[cfg(feature = "watch")]
+            events: Events::new(file_name),
+        }
+    }
+
+    //whether the file changed since it was last settled, settling it again when it has
+    pub fn changed(&mut self) -> bool {
+        #[cfg(feature = "watch")]
+        if let Some(events) = &self.events {
+            //nothing happened in the directory, so there's no need to look at the file
+            if events.receiver.try_iter().count() == 0 && self.moving.is_none() {
+                return false;
+            }
+        }
+        let now = stamp(&self.file_name);
+        if now.is_none() || now == self.stamp {
+            self.moving = None;
+            return false;
+        }
+        if now != self.moving {
+            self.moving = now;
+            return false;
+        }
+        self.settle();
+        true
+    }
+
+    //takes the file as it is now as seen, e.g. after saving it ourselves
+    pub fn settle(&mut self) {
+        self.stamp = stamp(&self.file_name);
+        self.moving = None;
+    }
+}
+
+//a file gone for the moment, as it is while being written again, is not a change yet
+fn stamp(file_name: &str) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(file_name).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(feature = "watch")]
+impl Events {
+    //None when the OS won't watch the directory, which leaves looking at the file each time
+    fn new(file_name: &str) -> Option<Self> {
+        use notify::{RecursiveMode, Watcher};
+        use std::path::Path;
+
+        let path = fs::canonicalize(file_name).ok()?;
+        let dir = path.parent()?.to_path_buf();
+        let name = path.file_name()?.to_os_string();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let ours = event.is_ok_and(|event| event.paths.iter().any(|path| path.file_name() == Some(&name)));
+            if ours {
+                sender.send(()).ok();
+            }
+        }).ok()?;
+        watcher.watch(Path::new(&dir), RecursiveMode::NonRecursive).ok()?;
+        Some(Self { _watcher: watcher, receiver })
+    }
+}
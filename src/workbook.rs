@@ -0,0 +1,338 @@
+#[cfg(any(feature = "xlsx", feature = "ods"))]
+use crate::formula;
+#[cfg(any(feature = "xlsx", feature = "ods"))]
+use crate::dates::DateTime;
+use crate::table::Progress;
+
+use std::io::{Error, ErrorKind};
+
+//one worksheet of a workbook, as rows of plain cell text (formulas keep their leading '=')
+#[derive(Clone, Debug)]
+pub struct Sheet {
+    pub name: String,
+    pub rows: Vec<Vec<String>>,
+    //notes on cells by (x, y), which .xlsx files keep as comments
+    pub notes: Vec<(usize, usize, String)>,
+}
+
+pub fn is_xlsx(filename: &str) -> bool {
+    filename.ends_with(".xlsx")
+}
+
+pub fn is_ods(filename: &str) -> bool {
+    filename.ends_with(".ods")
+}
+
+pub fn is_workbook(filename: &str) -> bool {
+    is_xlsx(filename) || is_ods(filename)
+}
+
+//reads every sheet of a workbook, keeping formulas rather than their cached results
+pub fn read_sheets(filename: &str) -> Result<Vec<Sheet>, Error> {
+    read_sheets_with(filename, &Progress::default())
+}
+
+//like read_sheets, counting rows as they are read. A cancelled load stops with ErrorKind::Interrupted
+#[cfg(any(feature = "xlsx", feature = "ods"))]
+pub fn read_sheets_with(filename: &str, progress: &Progress) -> Result<Vec<Sheet>, Error> {
+    use calamine::{open_workbook_auto, Data, Reader};
+
+    let to_io = |e: calamine::Error| Error::new(ErrorKind::InvalidData, e.to_string());
+    let mut workbook = open_workbook_auto(filename).map_err(to_io)?;
+    let mut sheets = Vec::new();
+    for name in workbook.sheet_names() {
+        let values = workbook.worksheet_range(&name).map_err(to_io)?;
+        let formulas = workbook.worksheet_formula(&name).map_err(to_io)?;
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        //cell positions are kept absolute so that references like A1 still point at the same cell
+        let mut put = |row: usize, col: usize, text: String| {
+            if rows.len() <= row {
+                rows.resize(row + 1, Vec::new());
+            }
+            if rows[row].len() <= col {
+                rows[row].resize(col + 1, String::new());
+            }
+            rows[row][col] = text;
+        };
+        if let Some((top, left)) = values.start() {
+            let mut last_row = None;
+            for (row, col, value) in values.used_cells() {
+                if last_row != Some(row) {
+                    last_row = Some(row);
+                    progress.add_row();
+                    if progress.is_cancelled() {
+                        return Err(Error::new(ErrorKind::Interrupted, "loading was cancelled"));
+                    }
+                }
+                let text = match value {
+                    Data::Empty => continue,
+                    Data::DateTime(date) => excel_date(date),
+                    value => value.to_string(),
+                };
+                put(top as usize + row, left as usize + col, text);
+            }
+        }
+        if let Some((top, left)) = formulas.start() {
+            for (row, col, formula) in formulas.used_cells() {
+                if formula.starts_with("of:") {
+                    put(top as usize + row, left as usize + col, from_odf_formula(formula));
+                } else if !formula.is_empty() {
+                    put(top as usize + row, left as usize + col, format!("={}", formula));
+                }
+            }
+        }
+        if rows.is_empty() {
+            rows.push(vec![String::new()]);
+        }
+        sheets.push(Sheet { name, rows, notes: Vec::new() });
+    }
+    Ok(sheets)
+}
+
+//Excel keeps dates as the number of days since 1900, formatted to look like dates; they are read as
+//ISO 8601 (2024-01-31, or 2024-01-31T09:30:00 with a time) and durations as hours:minutes:seconds
+#[cfg(any(feature = "xlsx", feature = "ods"))]
+fn excel_date(date: &calamine::ExcelDateTime) -> String {
+    if date.is_duration() {
+        let seconds = (date.as_f64() * 86_400.0).round() as i64;
+        return format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60);
+    }
+    //shown like 2024-01-31 09:30:00
+    match date.as_datetime().and_then(|datetime| DateTime::parse_iso(&datetime.to_string())) {
+        Some(datetime) if datetime.time == Some((0, 0, 0)) => datetime.format("%Y-%m-%d"),
+        Some(datetime) => datetime.format("%Y-%m-%dT%H:%M:%S"),
+        None => date.as_f64().to_string(),
+    }
+}
+
+//the reverse of to_odf_formula, e.g. `of:=SUM([.A1:.A3];2)` becomes `=SUM(A1:A3,2)`
+#[cfg(any(feature = "xlsx", feature = "ods"))]
+fn from_odf_formula(odf: &str) -> String {
+    odf.trim_start_matches("of:")
+        .replace("[.", "")
+        .replace(":.", ":")
+        .replace(']', "")
+        .replace(';', ",")
+}
+
+#[cfg(not(any(feature = "xlsx", feature = "ods")))]
+pub fn read_sheets_with(_filename: &str, _progress: &Progress) -> Result<Vec<Sheet>, Error> {
+    Err(Error::new(ErrorKind::Unsupported, "clicsv was built without xlsx or ods support"))
+}
+
+//writes sheets to an .xlsx file. Numbers are stored as numbers and '=' cells as formulas
+#[cfg(feature = "xlsx")]
+pub fn write_xlsx(filename: &str, sheets: &[Sheet]) -> Result<(), Error> {
+    use rust_xlsxwriter::{ExcelDateTime, Format, Note, Workbook, XlsxError};
+
+    let to_io = |e: XlsxError| Error::new(ErrorKind::InvalidData, e.to_string());
+    let mut workbook = Workbook::new();
+    for sheet in sheets {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(&sheet.name).map_err(to_io)?;
+        for (row, values) in sheet.rows.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                let (row, col) = (row as u32, col as u16);
+                if value.is_empty() {
+                    continue;
+                }
+                if formula::is_formula(value) {
+                    worksheet.write_formula(row, col, value.trim()).map_err(to_io)?;
+                } else if let Some(date) = DateTime::parse_iso(value) {
+                    //dates are stored as Excel dates, shown the way they were written
+                    let (hour, minute, second) = date.time.unwrap_or((0, 0, 0));
+                    let datetime = ExcelDateTime::from_ymd(date.year as u16, date.month as u8, date.day as u8)
+                        .and_then(|datetime| datetime.and_hms(hour as u16, minute as u8, second))
+                        .map_err(to_io)?;
+                    let format = Format::new().set_num_format(if date.time.is_some() { "yyyy-mm-dd hh:mm:ss" } else { "yyyy-mm-dd" });
+                    worksheet.write_datetime_with_format(row, col, &datetime, &format).map_err(to_io)?;
+                } else if let Some(number) = plain_number(value) {
+                    worksheet.write_number(row, col, number).map_err(to_io)?;
+                } else {
+                    worksheet.write_string(row, col, value).map_err(to_io)?;
+                }
+            }
+        }
+        for (x, y, text) in &sheet.notes {
+            worksheet.insert_note((y - 1) as u32, (x - 1) as u16, &Note::new(text).set_author("clicsv")).map_err(to_io)?;
+        }
+    }
+    workbook.save(filename).map_err(to_io)
+}
+
+#[cfg(not(feature = "xlsx"))]
+pub fn write_xlsx(_filename: &str, _sheets: &[Sheet]) -> Result<(), Error> {
+    Err(Error::new(ErrorKind::Unsupported, "clicsv was built without xlsx support"))
+}
+
+//writes sheets to an OpenDocument spreadsheet: a zip holding the mimetype, a manifest and content.xml
+#[cfg(feature = "ods")]
+pub fn write_ods(filename: &str, sheets: &[Sheet]) -> Result<(), Error> {
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::CompressionMethod;
+
+    let to_io = |e: zip::result::ZipError| Error::new(ErrorKind::InvalidData, e.to_string());
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(filename)?);
+    //the mimetype has to come first and uncompressed so tools can sniff it
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("mimetype", stored).map_err(to_io)?;
+    zip.write_all(b"application/vnd.oasis.opendocument.spreadsheet")?;
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file("META-INF/manifest.xml", deflated).map_err(to_io)?;
+    zip.write_all(ODS_MANIFEST.as_bytes())?;
+    zip.start_file("content.xml", deflated).map_err(to_io)?;
+    zip.write_all(ods_content(sheets).as_bytes())?;
+    zip.finish().map_err(to_io)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "ods"))]
+pub fn write_ods(_filename: &str, _sheets: &[Sheet]) -> Result<(), Error> {
+    Err(Error::new(ErrorKind::Unsupported, "clicsv was built without ods support"))
+}
+
+#[cfg(feature = "ods")]
+const ODS_MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">
+ <manifest:file-entry manifest:full-path="/" manifest:version="1.2" manifest:media-type="application/vnd.oasis.opendocument.spreadsheet"/>
+ <manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+</manifest:manifest>
+"#;
+
+#[cfg(feature = "ods")]
+fn ods_content(sheets: &[Sheet]) -> String {
+    let mut xml = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        r#"<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0""#,
+        r#" xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0""#,
+        r#" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0""#,
+        r#" xmlns:of="urn:oasis:names:tc:opendocument:xmlns:of:1.2" office:version="1.2">"#,
+        "<office:body><office:spreadsheet>",
+    ));
+    for sheet in sheets {
+        xml.push_str(&format!(r#"<table:table table:name="{}">"#, escape_xml(&sheet.name)));
+        for row in &sheet.rows {
+            xml.push_str("<table:table-row>");
+            for value in row {
+                xml.push_str(&ods_cell(value));
+            }
+            xml.push_str("</table:table-row>");
+        }
+        xml.push_str("</table:table>");
+    }
+    xml.push_str("</office:spreadsheet></office:body></office:document-content>");
+    xml
+}
+
+#[cfg(feature = "ods")]
+fn ods_cell(value: &str) -> String {
+    if value.is_empty() {
+        return String::from("<table:table-cell/>");
+    }
+    if formula::is_formula(value) {
+        return format!(r#"<table:table-cell table:formula="{}"/>"#, escape_xml(&to_odf_formula(value)));
+    }
+    let paragraphs: String = value.lines()
+        .map(|line| format!("<text:p>{}</text:p>", escape_xml(line)))
+        .collect();
+    if let Some(date) = DateTime::parse_iso(value) {
+        let iso = if date.time.is_some() { date.format("%Y-%m-%dT%H:%M:%S") } else { date.format("%Y-%m-%d") };
+        return format!(r#"<table:table-cell office:value-type="date" office:date-value="{}">{}</table:table-cell>"#, iso, paragraphs);
+    }
+    match plain_number(value) {
+        Some(number) => format!(
+            r#"<table:table-cell office:value-type="float" office:value="{}">{}</table:table-cell>"#,
+            number, paragraphs
+        ),
+        None => format!(r#"<table:table-cell office:value-type="string">{}</table:table-cell>"#, paragraphs),
+    }
+}
+
+//the number a cell holds when it is written the way a spreadsheet would show that number again:
+//digits with at most one decimal point, after an optional minus sign. Text that only parses as a
+//number, like 007, +1, 1e3, nan or inf, is kept as text, as are numbers with more digits than a
+//spreadsheet keeps, like long ids
+#[cfg(any(feature = "xlsx", feature = "ods"))]
+fn plain_number(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let unsigned = value.strip_prefix('-').unwrap_or(value);
+    let (whole, fraction) = match unsigned.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (unsigned, None),
+    };
+    let digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !digits(whole) || (whole.len() > 1 && whole.starts_with('0')) || fraction.is_some_and(|fraction| !digits(fraction)) {
+        return None;
+    }
+    let significant = whole.trim_start_matches('0').len() + fraction.map_or(0, str::len);
+    if significant > 15 {
+        return None;
+    }
+    value.parse::<f64>().ok().filter(|number| number.is_finite())
+}
+
+#[cfg(feature = "ods")]
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+//OpenFormula wraps references in brackets with a leading '.' ([.A1], [.A1:.B2]) and separates arguments with ';'
+#[cfg(feature = "ods")]
+fn to_odf_formula(value: &str) -> String {
+    let chars: Vec<char> = value.trim().chars().collect();
+    let word_end = |start: usize| {
+        let mut end = start;
+        while end < chars.len() && chars[end].is_ascii_alphanumeric() {
+            end += 1;
+        }
+        end
+    };
+    let mut odf = String::from("of:");
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        if !c.is_ascii_alphanumeric() {
+            odf.push(if c == ',' { ';' } else { c });
+            pos += 1;
+            continue;
+        }
+        let end = word_end(pos);
+        let first: String = chars[pos..end].iter().collect();
+        pos = end;
+        if formula::parse_reference(&first).is_none() {
+            odf.push_str(&first);
+            continue;
+        }
+        if chars.get(pos) == Some(&':') {
+            let end = word_end(pos + 1);
+            let second: String = chars[pos + 1..end].iter().collect();
+            if formula::parse_reference(&second).is_some() {
+                odf.push_str(&format!("[.{}:.{}]", first, second));
+                pos = end;
+                continue;
+            }
+        }
+        odf.push_str(&format!("[.{}]", first));
+    }
+    odf
+}
+
+#[cfg(all(test, any(feature = "xlsx", feature = "ods")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_plain_decimals_are_numbers() {
+        for (value, number) in [("0", 0.0), ("42", 42.0), ("-3.25", -3.25), ("0.5", 0.5), (" 12 ", 12.0)] {
+            assert_eq!(plain_number(value), Some(number), "{:?}", value);
+        }
+        for value in ["007", "+1", "1e3", "nan", "Nan", "NaN", "inf", "-Infinity", ".5", "5.", "1.2.3", "-", "12345678901234567890"] {
+            assert_eq!(plain_number(value), None, "{:?}", value);
+        }
+    }
+}